@@ -0,0 +1,12 @@
+#!/bin/sh
+# Replaces arc56.schema.json with the schema published alongside ARC-56, unmodified.
+# Usage: fetch.sh [commit], the latest commit of the ARCs repository by default.
+set -eu
+
+repo=https://github.com/algorandfoundation/ARCs
+commit=${1:-$(git ls-remote "$repo" HEAD | cut -f1)}
+dir=$(dirname "$0")
+
+curl -fsSL "https://raw.githubusercontent.com/algorandfoundation/ARCs/$commit/assets/arc-0056/schema.json" \
+    -o "$dir/arc56.schema.json"
+echo "vendored assets/arc-0056/schema.json at $commit, record the commit in $dir/README.md"
@@ -0,0 +1,15 @@
+use thiserror::Error;
+
+use super::AbiType;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum AbiError {
+    #[error("Value does not have ARC-4 type {0}")]
+    TypeMismatch(AbiType),
+    #[error("Value is out of the range of ARC-4 type {0}")]
+    OutOfRange(AbiType),
+    #[error("Encoding of {0} takes {1} bytes, got {2}")]
+    LengthMismatch(AbiType, u64, u64),
+    #[error("Invalid encoding of {0}")]
+    InvalidEncoding(AbiType),
+}
@@ -0,0 +1,349 @@
+use super::{layout, AbiError, AbiType, Slot};
+
+// A value of an ARC-4 type, for encoding arguments and decoding return values and events
+#[derive(Debug, Clone, PartialEq)]
+pub enum AbiValue {
+    // uintN, and ufixedNxM by its numerator, as big-endian bytes without leading zeros
+    Uint(Vec<u8>),
+    Bool(bool),
+    Byte(u8),
+    Address([u8; 32]),
+    String(String),
+    // static and dynamic arrays
+    Array(Vec<AbiValue>),
+    Tuple(Vec<AbiValue>),
+}
+
+impl AbiValue {
+    pub fn uint(v: u128) -> AbiValue {
+        AbiValue::Uint(trim(&v.to_be_bytes()).to_vec())
+    }
+}
+
+// a big-endian number without its leading zeros
+fn trim(bytes: &[u8]) -> &[u8] {
+    let zeros = bytes.iter().take_while(|b| **b == 0).count();
+    &bytes[zeros..]
+}
+
+// a uint16 length followed by `body`
+fn with_length(t: &AbiType, length: usize, body: Vec<u8>) -> Result<Vec<u8>, AbiError> {
+    let length = u16::try_from(length).map_err(|_| AbiError::OutOfRange(t.clone()))?;
+    let mut encoded = length.to_be_bytes().to_vec();
+    encoded.extend(body);
+    Ok(encoded)
+}
+
+fn encode_tuple(t: &AbiType, types: &[AbiType], values: &[AbiValue]) -> Result<Vec<u8>, AbiError> {
+    let (slots, head_size) = layout(types);
+    let mut head = vec![0; head_size as usize];
+    let mut tail = vec![];
+    for ((t, value), slot) in types.iter().zip(values).zip(slots) {
+        let encoded = encode(t, value)?;
+        match slot {
+            Slot::Bit(i) => head[i as usize / 8] |= encoded[0] >> (i % 8),
+            Slot::Static(offset, size) => {
+                head[offset as usize..(offset + size) as usize].copy_from_slice(&encoded)
+            }
+            Slot::Offset(offset) => {
+                let at = u16::try_from(head.len() + tail.len())
+                    .map_err(|_| AbiError::OutOfRange(t.clone()))?;
+                head[offset as usize..offset as usize + 2].copy_from_slice(&at.to_be_bytes());
+                tail.extend(encoded);
+            }
+        }
+    }
+    if head.len() + tail.len() > u16::MAX as usize + 1 {
+        return Err(AbiError::OutOfRange(t.clone()));
+    }
+    head.extend(tail);
+    Ok(head)
+}
+
+pub fn encode(t: &AbiType, value: &AbiValue) -> Result<Vec<u8>, AbiError> {
+    Ok(match (t, value) {
+        (AbiType::Uint(bits) | AbiType::Ufixed(bits, _), AbiValue::Uint(v)) => {
+            let size = *bits as usize / 8;
+            let v = trim(v);
+            if v.len() > size {
+                return Err(AbiError::OutOfRange(t.clone()));
+            }
+            let mut encoded = vec![0; size - v.len()];
+            encoded.extend(v);
+            encoded
+        }
+        (AbiType::Bool, AbiValue::Bool(b)) => vec![if *b { 0x80 } else { 0 }],
+        (AbiType::Byte, AbiValue::Byte(b)) => vec![*b],
+        (AbiType::Address, AbiValue::Address(a)) => a.to_vec(),
+        (AbiType::String, AbiValue::String(s)) => with_length(t, s.len(), s.as_bytes().to_vec())?,
+        (AbiType::StaticArray(element, n), AbiValue::Array(values)) if values.len() == *n => {
+            encode_tuple(t, &vec![(**element).clone(); *n], values)?
+        }
+        (AbiType::DynamicArray(element), AbiValue::Array(values)) => {
+            let types = vec![(**element).clone(); values.len()];
+            with_length(t, values.len(), encode_tuple(t, &types, values)?)?
+        }
+        (AbiType::Tuple(types), AbiValue::Tuple(values)) if types.len() == values.len() => {
+            encode_tuple(t, types, values)?
+        }
+        _ => return Err(AbiError::TypeMismatch(t.clone())),
+    })
+}
+
+// the uint16 length prefix and what follows it
+fn split_length<'a>(t: &AbiType, bytes: &'a [u8]) -> Result<(usize, &'a [u8]), AbiError> {
+    match bytes {
+        [a, b, rest @ ..] => Ok((u16::from_be_bytes([*a, *b]) as usize, rest)),
+        _ => Err(AbiError::InvalidEncoding(t.clone())),
+    }
+}
+
+fn decode_tuple(t: &AbiType, types: &[AbiType], bytes: &[u8]) -> Result<Vec<AbiValue>, AbiError> {
+    let invalid = || AbiError::InvalidEncoding(t.clone());
+    let (slots, head_size) = layout(types);
+    let head_size = head_size as usize;
+    if bytes.len() < head_size {
+        return Err(invalid());
+    }
+    let offsets = slots
+        .iter()
+        .filter_map(|slot| match slot {
+            Slot::Offset(o) => Some(u16::from_be_bytes([
+                bytes[*o as usize],
+                bytes[*o as usize + 1],
+            ])),
+            _ => None,
+        })
+        .map(usize::from)
+        .chain([bytes.len()])
+        .collect::<Vec<_>>();
+    // dynamic values follow the head and each other without gaps
+    if offsets[0] != head_size || offsets.windows(2).any(|w| w[0] > w[1]) {
+        return Err(invalid());
+    }
+    let mut dynamic = offsets.windows(2);
+    types
+        .iter()
+        .zip(slots)
+        .map(|(t, slot)| match slot {
+            Slot::Bit(i) => Ok(AbiValue::Bool(
+                bytes[i as usize / 8] & (0x80 >> (i % 8)) != 0,
+            )),
+            Slot::Static(offset, size) => {
+                decode(t, &bytes[offset as usize..(offset + size) as usize])
+            }
+            Slot::Offset(_) => {
+                let range = dynamic.next().unwrap();
+                decode(t, &bytes[range[0]..range[1]])
+            }
+        })
+        .collect()
+}
+
+pub fn decode(t: &AbiType, bytes: &[u8]) -> Result<AbiValue, AbiError> {
+    let invalid = || AbiError::InvalidEncoding(t.clone());
+    if let Some(size) = t.static_size() {
+        if bytes.len() as u64 != size {
+            return Err(AbiError::LengthMismatch(
+                t.clone(),
+                size,
+                bytes.len() as u64,
+            ));
+        }
+    }
+    Ok(match t {
+        AbiType::Uint(_) | AbiType::Ufixed(..) => AbiValue::Uint(trim(bytes).to_vec()),
+        AbiType::Bool => match bytes[0] {
+            0x80 => AbiValue::Bool(true),
+            0 => AbiValue::Bool(false),
+            _ => return Err(invalid()),
+        },
+        AbiType::Byte => AbiValue::Byte(bytes[0]),
+        AbiType::Address => AbiValue::Address(bytes.try_into().unwrap()),
+        AbiType::String => {
+            let (length, body) = split_length(t, bytes)?;
+            if body.len() != length {
+                return Err(AbiError::LengthMismatch(
+                    t.clone(),
+                    length as u64 + 2,
+                    bytes.len() as u64,
+                ));
+            }
+            AbiValue::String(String::from_utf8(body.to_vec()).map_err(|_| invalid())?)
+        }
+        AbiType::StaticArray(..) | AbiType::Tuple(_) => {
+            let values = decode_tuple(t, &t.elements().unwrap(), bytes)?;
+            match t {
+                AbiType::Tuple(_) => AbiValue::Tuple(values),
+                _ => AbiValue::Array(values),
+            }
+        }
+        AbiType::DynamicArray(element) => {
+            let (length, body) = split_length(t, bytes)?;
+            AbiValue::Array(decode_tuple(t, &vec![(**element).clone(); length], body)?)
+        }
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::abi::{AbiError, AbiType};
+
+    use super::{decode, encode, AbiValue};
+
+    fn hex(s: &str) -> Vec<u8> {
+        let s = s.replace(' ', "");
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    // (type, value, encoding) following the encoding rules of the ARC-4 specification
+    pub(crate) fn vectors() -> Vec<(AbiType, AbiValue, Vec<u8>)> {
+        use AbiValue::*;
+        let uint = AbiValue::uint;
+        let s = |s: &str| String(s.to_string());
+        [
+            ("uint64", uint(1), "0000000000000001"),
+            ("uint8", uint(255), "ff"),
+            ("uint16", uint(256), "0100"),
+            (
+                "uint256",
+                uint(1),
+                "0000000000000000000000000000000000000000000000000000000000000001",
+            ),
+            // above what a u128 holds
+            ("uint256", Uint(hex(&"ff".repeat(32))), &"ff".repeat(32)),
+            (
+                "uint512",
+                Uint(hex(&format!("01{}", "00".repeat(20)))),
+                &format!("{}01{}", "00".repeat(43), "00".repeat(20)),
+            ),
+            ("ufixed64x2", uint(12345), "0000000000003039"),
+            ("bool", Bool(true), "80"),
+            ("bool", Bool(false), "00"),
+            ("byte", Byte(7), "07"),
+            ("string", s("abc"), "0003 616263"),
+            ("string", s(""), "0000"),
+            ("byte[]", Array(vec![Byte(1), Byte(2)]), "0002 0102"),
+            ("uint16[2]", Array(vec![uint(1), uint(2)]), "0001 0002"),
+            (
+                "bool[3]",
+                Array(vec![Bool(true), Bool(false), Bool(true)]),
+                "a0",
+            ),
+            ("bool[]", Array(vec![Bool(true); 9]), "0009 ff80"),
+            (
+                "(bool,bool,uint8,bool)",
+                Tuple(vec![Bool(true), Bool(true), uint(7), Bool(true)]),
+                "c0 07 80",
+            ),
+            (
+                "(string,bool)",
+                Tuple(vec![s("hi"), Bool(true)]),
+                "0003 80 0002 6869",
+            ),
+            (
+                "string[]",
+                Array(vec![s("a"), s("bc")]),
+                "0002 0004 0007 0001 61 0002 6263",
+            ),
+            (
+                "(uint64,address,byte[])",
+                Tuple(vec![
+                    uint(1),
+                    Address([0xaa; 32]),
+                    Array(vec![Byte(1), Byte(2)]),
+                ]),
+                &format!("0000000000000001 {} 002a 0002 0102", "aa".repeat(32)),
+            ),
+            (
+                "(uint16,(string,bool),bool[2])",
+                Tuple(vec![
+                    uint(3),
+                    Tuple(vec![s("x"), Bool(false)]),
+                    Array(vec![Bool(false), Bool(true)]),
+                ]),
+                "0003 0005 40 0003 00 0001 78",
+            ),
+            ("()", Tuple(vec![]), ""),
+            // the worked examples of the reference ABI implementations
+            ("string", s("asdf"), "0004 61736466"),
+            (
+                "bool[5]",
+                Array([true, false, false, true, true].map(Bool).to_vec()),
+                "98",
+            ),
+            (
+                "bool[]",
+                Array(
+                    [
+                        false, true, false, true, false, false, true, false, true, false, true,
+                    ]
+                    .map(Bool)
+                    .to_vec(),
+                ),
+                "000b 52 a0",
+            ),
+            (
+                "(string,bool,bool,bool,bool,string)",
+                Tuple(vec![
+                    s("AB"),
+                    Bool(true),
+                    Bool(false),
+                    Bool(true),
+                    Bool(false),
+                    s("DE"),
+                ]),
+                "0005 a0 0009 0002 4142 0002 4445",
+            ),
+            (
+                "uint64[]",
+                Array(vec![uint(1), uint(2), uint(3)]),
+                "0003 0000000000000001 0000000000000002 0000000000000003",
+            ),
+        ]
+        .into_iter()
+        .map(|(t, value, encoded)| (t.parse().unwrap(), value, hex(encoded)))
+        .collect()
+    }
+
+    #[test]
+    fn test_vectors() {
+        for (t, value, encoded) in vectors() {
+            assert_eq!(encode(&t, &value).unwrap(), encoded, "encoding {t}");
+            assert_eq!(decode(&t, &encoded).unwrap(), value, "decoding {t}");
+        }
+    }
+
+    #[test]
+    fn test_invalid() {
+        let t = |s: &str| s.parse::<AbiType>().unwrap();
+        assert_eq!(
+            encode(&t("uint8"), &AbiValue::uint(256)),
+            Err(AbiError::OutOfRange(t("uint8")))
+        );
+        assert_eq!(
+            encode(&t("uint128"), &AbiValue::Uint(vec![1; 17])),
+            Err(AbiError::OutOfRange(t("uint128")))
+        );
+        assert_eq!(
+            encode(&t("uint8[2]"), &AbiValue::Array(vec![AbiValue::uint(1)])),
+            Err(AbiError::TypeMismatch(t("uint8[2]")))
+        );
+        assert_eq!(
+            decode(&t("uint64"), &[1]),
+            Err(AbiError::LengthMismatch(t("uint64"), 8, 1))
+        );
+        assert_eq!(
+            decode(&t("bool"), &[1]),
+            Err(AbiError::InvalidEncoding(t("bool")))
+        );
+        assert!(decode(&t("string"), &hex("0003 6162")).is_err());
+        // the offset points into the head
+        assert!(decode(&t("(string,bool)"), &hex("0002 80 0002 6869")).is_err());
+        // the array claims more elements than there are
+        assert!(decode(&t("uint16[]"), &hex("0002 0001")).is_err());
+    }
+}
@@ -0,0 +1,339 @@
+use std::{fmt::Display, str::FromStr};
+
+use sha2::{Digest, Sha512_256};
+
+use crate::typing::TypePrimitive;
+
+mod abi_error;
+pub use abi_error::AbiError;
+pub(crate) mod codec;
+pub use codec::{decode, encode, AbiValue};
+
+// An ARC-4 type as it appears in method signatures
+#[derive(Debug, Clone, PartialEq)]
+pub enum AbiType {
+    // uintN, N a multiple of 8 up to 512
+    Uint(u16),
+    // ufixedNxM, N as for uintN and M decimal places up to 160
+    Ufixed(u16, u8),
+    Bool,
+    Byte,
+    Address,
+    String,
+    // T[N]
+    StaticArray(Box<AbiType>, usize),
+    // T[], byte[] among them
+    DynamicArray(Box<AbiType>),
+    // (T1,T2,...)
+    Tuple(Vec<AbiType>),
+}
+
+// Where an element of a tuple sits in the head of its encoding
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Slot {
+    // a static value at this byte offset and of this many bytes
+    Static(u64, u64),
+    // a bool at this bit index, consecutive bools share bytes
+    Bit(u64),
+    // a two byte offset at this byte offset, the value itself is in the tail
+    Offset(u64),
+}
+
+// the first 4 bytes of the SHA-512/256 hash of a method or event signature
+pub fn selector(signature: &str) -> [u8; 4] {
+    let hash = Sha512_256::digest(signature.as_bytes());
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+// the slots of a tuple with elements of the given types and the size of its head
+pub fn layout(types: &[AbiType]) -> (Vec<Slot>, u64) {
+    let mut slots = vec![];
+    let mut size = 0;
+    // the bit index of the last bool if it may be followed by another
+    let mut run = None;
+    for t in types {
+        let slot = match (t, run) {
+            (AbiType::Bool, Some(bit)) if (bit + 1) % 8 != 0 => Slot::Bit(bit + 1),
+            (AbiType::Bool, _) => {
+                size += 1;
+                Slot::Bit((size - 1) * 8)
+            }
+            _ => match t.static_size() {
+                Some(n) => {
+                    size += n;
+                    Slot::Static(size - n, n)
+                }
+                None => {
+                    size += 2;
+                    Slot::Offset(size - 2)
+                }
+            },
+        };
+        run = match slot {
+            Slot::Bit(bit) => Some(bit),
+            _ => None,
+        };
+        slots.push(slot);
+    }
+    (slots, size)
+}
+
+impl AbiType {
+    // byte[]
+    pub fn bytes() -> Self {
+        AbiType::DynamicArray(Box::new(AbiType::Byte))
+    }
+
+    // the type values of this ABI type have once decoded, `None` for compound types which stay
+    // encoded
+    pub fn value_type(&self) -> Option<TypePrimitive> {
+        match self {
+            AbiType::Uint(bits) | AbiType::Ufixed(bits, _) if *bits > 64 => {
+                Some(TypePrimitive::BigUInt)
+            }
+            AbiType::Uint(_) | AbiType::Ufixed(..) | AbiType::Bool | AbiType::Byte => {
+                Some(TypePrimitive::UInt64)
+            }
+            AbiType::Address | AbiType::String => Some(TypePrimitive::Byteslice),
+            AbiType::DynamicArray(t) if **t == AbiType::Byte => Some(TypePrimitive::Byteslice),
+            AbiType::StaticArray(..) | AbiType::DynamicArray(_) | AbiType::Tuple(_) => None,
+        }
+    }
+
+    // the length of the encoding, `None` for dynamic types
+    pub fn static_size(&self) -> Option<u64> {
+        match self {
+            AbiType::Uint(bits) | AbiType::Ufixed(bits, _) => Some(*bits as u64 / 8),
+            AbiType::Bool | AbiType::Byte => Some(1),
+            AbiType::Address => Some(32),
+            AbiType::String | AbiType::DynamicArray(_) => None,
+            AbiType::StaticArray(..) | AbiType::Tuple(_) => {
+                let elements = self.elements()?;
+                if elements.iter().any(AbiType::is_dynamic) {
+                    return None;
+                }
+                Some(layout(&elements).1)
+            }
+        }
+    }
+
+    pub fn is_dynamic(&self) -> bool {
+        self.static_size().is_none()
+    }
+
+    // the element types of tuples and static arrays
+    pub fn elements(&self) -> Option<Vec<AbiType>> {
+        match self {
+            AbiType::Tuple(types) => Some(types.clone()),
+            AbiType::StaticArray(t, n) => Some(vec![(**t).clone(); *n]),
+            _ => None,
+        }
+    }
+}
+
+impl Display for AbiType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AbiType::Uint(bits) => write!(f, "uint{bits}"),
+            AbiType::Ufixed(bits, precision) => write!(f, "ufixed{bits}x{precision}"),
+            AbiType::Bool => write!(f, "bool"),
+            AbiType::Byte => write!(f, "byte"),
+            AbiType::Address => write!(f, "address"),
+            AbiType::String => write!(f, "string"),
+            AbiType::StaticArray(t, n) => write!(f, "{t}[{n}]"),
+            AbiType::DynamicArray(t) => write!(f, "{t}[]"),
+            AbiType::Tuple(types) => write!(
+                f,
+                "({})",
+                types
+                    .iter()
+                    .map(AbiType::to_string)
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+        }
+    }
+}
+
+fn parse_bits(s: &str) -> Result<u16, ()> {
+    let bits = s.parse().map_err(|_| ())?;
+    if bits == 0 || bits > 512 || bits % 8 != 0 {
+        return Err(());
+    }
+    Ok(bits)
+}
+
+// splits on the commas outside of parentheses
+fn split_elements(s: &str) -> Result<Vec<&str>, ()> {
+    let mut elements = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => return Err(()),
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                elements.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if depth != 0 {
+        return Err(());
+    }
+    elements.push(&s[start..]);
+    Ok(elements)
+}
+
+impl FromStr for AbiType {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(rest) = s.strip_suffix(']') {
+            let (element, length) = rest.rsplit_once('[').ok_or(())?;
+            let element = Box::new(element.parse()?);
+            return Ok(match length {
+                "" => AbiType::DynamicArray(element),
+                _ if length.starts_with('+') => return Err(()),
+                _ => AbiType::StaticArray(element, length.parse().map_err(|_| ())?),
+            });
+        }
+        if let Some(inner) = s.strip_prefix('(').and_then(|s| s.strip_suffix(')')) {
+            if inner.is_empty() {
+                return Ok(AbiType::Tuple(vec![]));
+            }
+            return split_elements(inner)?
+                .into_iter()
+                .map(str::parse)
+                .collect::<Result<_, _>>()
+                .map(AbiType::Tuple);
+        }
+        Ok(match s {
+            "bool" => AbiType::Bool,
+            "byte" => AbiType::Byte,
+            "address" => AbiType::Address,
+            "string" => AbiType::String,
+            _ => {
+                if let Some(rest) = s.strip_prefix("ufixed") {
+                    let (bits, precision) = rest.split_once('x').ok_or(())?;
+                    let precision = precision.parse().map_err(|_| ())?;
+                    if precision == 0 || precision > 160 {
+                        return Err(());
+                    }
+                    AbiType::Ufixed(parse_bits(bits)?, precision)
+                } else {
+                    AbiType::Uint(parse_bits(s.strip_prefix("uint").ok_or(())?)?)
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::typing::TypePrimitive;
+
+    use super::{layout, selector, AbiType, Slot};
+
+    #[test]
+    fn test_selector() {
+        // from the ARC-4 specification
+        assert_eq!(
+            selector("add(uint64,uint64)uint128"),
+            [0x8a, 0xa3, 0xb6, 0x1f]
+        );
+    }
+
+    #[test]
+    fn test_types() {
+        for s in [
+            "uint8",
+            "uint64",
+            "uint512",
+            "ufixed64x2",
+            "bool",
+            "byte",
+            "address",
+            "byte[]",
+            "string",
+            "uint8[4]",
+            "bool[]",
+            "string[2][]",
+            "(uint64,address,byte[])",
+            "((bool,string),uint16[3])[]",
+            "()",
+        ] {
+            assert_eq!(s.parse::<AbiType>().unwrap().to_string(), s);
+        }
+        for s in [
+            "uint0",
+            "uint7",
+            "uint520",
+            "int64",
+            "bytes",
+            "ufixed64",
+            "ufixed64x0",
+            "ufixed64x161",
+            "uint8[-1]",
+            "uint8[+1]",
+            "(uint8,)",
+            "(uint8",
+            "uint8)",
+        ] {
+            assert!(s.parse::<AbiType>().is_err(), "{s}");
+        }
+        assert_eq!("byte[]".parse::<AbiType>().unwrap(), AbiType::bytes());
+        assert_eq!(
+            AbiType::Uint(128).value_type(),
+            Some(TypePrimitive::BigUInt)
+        );
+        assert_eq!(AbiType::Bool.value_type(), Some(TypePrimitive::UInt64));
+        assert_eq!(
+            AbiType::bytes().value_type(),
+            Some(TypePrimitive::Byteslice)
+        );
+        assert_eq!(AbiType::Tuple(vec![]).value_type(), None);
+        assert_eq!(AbiType::String.static_size(), None);
+    }
+
+    #[test]
+    fn test_sizes() {
+        let size = |s: &str| s.parse::<AbiType>().unwrap().static_size();
+        assert_eq!(size("bool[8]"), Some(1));
+        assert_eq!(size("bool[9]"), Some(2));
+        assert_eq!(size("uint16[3]"), Some(6));
+        assert_eq!(size("(bool,bool,uint8,bool)"), Some(3));
+        assert_eq!(size("(uint64,address)"), Some(40));
+        assert_eq!(size("(uint64,byte[])"), None);
+        assert_eq!(size("string[2]"), None);
+    }
+
+    #[test]
+    fn test_layout() {
+        let types = "(bool,bool,string,bool,uint16)"
+            .parse::<AbiType>()
+            .unwrap()
+            .elements()
+            .unwrap();
+        assert_eq!(
+            layout(&types),
+            (
+                vec![
+                    Slot::Bit(0),
+                    Slot::Bit(1),
+                    Slot::Offset(1),
+                    Slot::Bit(24),
+                    Slot::Static(4, 2)
+                ],
+                6
+            )
+        );
+        // a ninth bool starts a new byte
+        let (slots, size) = layout(&vec![AbiType::Bool; 9]);
+        assert_eq!(slots[7], Slot::Bit(7));
+        assert_eq!(slots[8], Slot::Bit(8));
+        assert_eq!(size, 2);
+    }
+}
@@ -0,0 +1,537 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::{
+    abi::AbiType, assembly::constant_blocks, contract::CompiledContract,
+    expression::constant::OnComplete, program::CompiledProgram, typing::TypePrimitive,
+};
+
+// An ARC-56 application specification, serialized with `serde_json`
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppSpec {
+    pub arcs: Vec<u32>,
+    pub name: String,
+    pub structs: BTreeMap<String, Vec<StructField>>,
+    pub methods: Vec<MethodSpec>,
+    pub state: StateSpec,
+    pub bare_actions: Actions,
+    pub source_info: Programs<ProgramSourceInfo>,
+    // base64 encoded TEAL
+    pub source: Programs<String>,
+    // base64 encoded bytecode
+    pub byte_code: Programs<String>,
+    pub events: Vec<EventSpec>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub template_variables: BTreeMap<String, TemplateVariable>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TemplateVariable {
+    #[serde(rename = "type")]
+    pub value_type: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StructField {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub field_type: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Arg {
+    #[serde(rename = "type")]
+    pub arg_type: String,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Returns {
+    #[serde(rename = "type")]
+    pub return_type: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MethodSpec {
+    pub name: String,
+    pub args: Vec<Arg>,
+    pub returns: Returns,
+    pub actions: Actions,
+}
+
+// on-completion actions by the names ARC-56 gives them
+#[derive(Debug, Serialize)]
+pub struct Actions {
+    pub create: Vec<&'static str>,
+    pub call: Vec<&'static str>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Schema {
+    pub ints: usize,
+    pub bytes: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StateSchema {
+    pub global: Schema,
+    pub local: Schema,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageKey {
+    pub key_type: &'static str,
+    pub value_type: &'static str,
+    // base64 encoded
+    pub key: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Storage<T> {
+    pub global: BTreeMap<String, T>,
+    pub local: BTreeMap<String, T>,
+    #[serde(rename = "box")]
+    pub boxes: BTreeMap<String, T>,
+}
+
+// maps are declared by their key and value types, the contract has none
+#[derive(Debug, Serialize)]
+pub struct StorageMap {}
+
+#[derive(Debug, Serialize)]
+pub struct StateSpec {
+    pub schema: StateSchema,
+    pub keys: Storage<StorageKey>,
+    pub maps: Storage<StorageMap>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Programs<T> {
+    pub approval: T,
+    pub clear: T,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceInfo {
+    pub pc: Vec<usize>,
+    // 1-based line of the TEAL source
+    pub teal: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_message: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProgramSourceInfo {
+    pub source_info: Vec<SourceInfo>,
+    // how pcs relate to the bytecode, "none" for absolute pcs and "cblocks" for pcs that
+    // leave out the constant blocks, which change size when templates are substituted
+    pub pc_offset_method: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EventSpec {
+    pub name: String,
+    pub args: Vec<Arg>,
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub(crate) fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            encoded.push(if i <= chunk.len() {
+                BASE64[(n >> (18 - 6 * i)) as usize & 63] as char
+            } else {
+                '='
+            });
+        }
+    }
+    encoded
+}
+
+fn action_name(on_complete: &OnComplete) -> Option<&'static str> {
+    match on_complete {
+        OnComplete::NoOp => Some("NoOp"),
+        OnComplete::OptIn => Some("OptIn"),
+        OnComplete::CloseOut => Some("CloseOut"),
+        OnComplete::UpdateApplication => Some("UpdateApplication"),
+        OnComplete::DeleteApplication => Some("DeleteApplication"),
+        // runs the clear program instead
+        OnComplete::ClearState => None,
+    }
+}
+
+// applications can only be created with these
+const CREATE_ACTIONS: [&str; 3] = ["NoOp", "OptIn", "DeleteApplication"];
+
+fn actions(on_completes: &[OnComplete]) -> Actions {
+    let call = on_completes
+        .iter()
+        .filter_map(action_name)
+        .collect::<Vec<_>>();
+    Actions {
+        create: call
+            .iter()
+            .copied()
+            .filter(|action| CREATE_ACTIONS.contains(action))
+            .collect(),
+        call,
+    }
+}
+
+fn avm_type(t: &TypePrimitive) -> &'static str {
+    match t {
+        TypePrimitive::UInt64 => "AVMUint64",
+        _ => "AVMBytes",
+    }
+}
+
+fn schema(state: &[(String, TypePrimitive)]) -> Schema {
+    let ints = state
+        .iter()
+        .filter(|(_, t)| t == &TypePrimitive::UInt64)
+        .count();
+    Schema {
+        ints,
+        bytes: state.len() - ints,
+    }
+}
+
+fn keys(state: &[(String, TypePrimitive)]) -> BTreeMap<String, StorageKey> {
+    state
+        .iter()
+        .map(|(key, t)| {
+            (
+                key.clone(),
+                StorageKey {
+                    key_type: "AVMString",
+                    value_type: avm_type(t),
+                    key: base64(key.as_bytes()),
+                },
+            )
+        })
+        .collect()
+}
+
+fn source_info(program: &CompiledProgram) -> ProgramSourceInfo {
+    let (offset, pc_offset_method) = if program.templates().is_empty() {
+        (0, "none")
+    } else {
+        let blocks = constant_blocks(&program.ints, &program.bytes);
+        (blocks.iter().map(|(_, block)| block.len()).sum(), "cblocks")
+    };
+    ProgramSourceInfo {
+        source_info: program
+            .lines
+            .iter()
+            .map(|(pc, line)| SourceInfo {
+                pc: vec![*pc - offset],
+                teal: *line,
+                error_message: program.messages.get(pc).cloned(),
+            })
+            .collect(),
+        pc_offset_method,
+    }
+}
+
+impl CompiledContract {
+    pub fn app_spec(&self, name: &str) -> AppSpec {
+        let methods = self
+            .methods
+            .iter()
+            .map(|method| MethodSpec {
+                name: method.name.clone(),
+                args: method
+                    .args
+                    .iter()
+                    .map(|(name, t)| Arg {
+                        arg_type: t.to_string(),
+                        name: name.clone(),
+                    })
+                    .collect(),
+                returns: Returns {
+                    return_type: method
+                        .returns
+                        .as_ref()
+                        .map_or("void".to_string(), AbiType::to_string),
+                },
+                actions: actions(&method.actions),
+            })
+            .collect();
+        let events = self
+            .events
+            .iter()
+            .map(|event| EventSpec {
+                name: event.name.clone(),
+                args: event
+                    .fields()
+                    .iter()
+                    .map(|(name, t)| Arg {
+                        arg_type: t.to_string(),
+                        name: name.clone(),
+                    })
+                    .collect(),
+            })
+            .collect();
+        AppSpec {
+            arcs: vec![4, 56],
+            name: name.to_string(),
+            structs: BTreeMap::new(),
+            methods,
+            state: StateSpec {
+                schema: StateSchema {
+                    global: schema(&self.global_state),
+                    local: schema(&self.local_state),
+                },
+                keys: Storage {
+                    global: keys(&self.global_state),
+                    local: keys(&self.local_state),
+                    boxes: keys(&self.box_state),
+                },
+                maps: Storage {
+                    global: BTreeMap::new(),
+                    local: BTreeMap::new(),
+                    boxes: BTreeMap::new(),
+                },
+            },
+            bare_actions: actions(&self.bare_actions),
+            source_info: Programs {
+                approval: source_info(&self.approval),
+                clear: source_info(&self.clear),
+            },
+            source: Programs {
+                approval: base64(self.approval.teal.as_bytes()),
+                clear: base64(self.clear.teal.as_bytes()),
+            },
+            byte_code: Programs {
+                approval: base64(&self.approval.bytecode),
+                clear: base64(&self.clear.bytecode),
+            },
+            events,
+            template_variables: self
+                .templates
+                .iter()
+                .map(|(name, t)| {
+                    let value_type = avm_type(t);
+                    (name.clone(), TemplateVariable { value_type })
+                })
+                .collect(),
+        }
+    }
+}
+
+impl AppSpec {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::Value;
+
+    use crate::{
+        abi::AbiType,
+        apply, binop,
+        box_schema::BoxSchema,
+        contract::Contract,
+        event::Event,
+        expression::{
+            apply::Apply,
+            assert::Assert,
+            binary::Binary,
+            constant::OnComplete,
+            primitive::Primitive,
+            router::{Method, Router},
+            seq::Seq,
+            template::Template,
+            txn::Txn,
+            var::{RVal, Var},
+            Expr,
+        },
+        int,
+        program::Program,
+        seq,
+        struct_def::StructDef,
+        typing::TypePrimitive,
+    };
+
+    use super::{base64, AppSpec};
+
+    // a transcription of ARC-56 until the published schema is vendored, see schema/README.md
+    const SCHEMA: &str = include_str!("../schema/arc56.schema.json");
+
+    fn var(name: &str) -> Expr {
+        Expr::RVal(RVal(Var::Bind(name.to_string())))
+    }
+
+    fn contract() -> Contract<'static> {
+        let add = Method {
+            name: "add".to_string(),
+            args: vec![
+                ("a".to_string(), AbiType::Uint(64)),
+                ("b".to_string(), AbiType::Uint(64)),
+            ],
+            returns: Some(AbiType::Uint(64)),
+            actions: vec![OnComplete::NoOp],
+            body: binop!((var("a")) + (var("b"))),
+        };
+        let bare = seq!(
+            apply!(
+                @fn Expr::Assert(Assert(Some("only on create".to_string())));
+                @arg binop!((Expr::Txn(Txn::ApplicationID)) == (int!(0)))
+            );
+            int!(1);
+        );
+        Contract {
+            schema_global: StructDef {
+                fields: HashMap::from([
+                    ("counter", TypePrimitive::UInt64),
+                    ("owner", TypePrimitive::Byteslice),
+                ]),
+            },
+            schema_local: StructDef {
+                fields: HashMap::from([("balance", TypePrimitive::UInt64)]),
+            },
+            schema_box: BoxSchema::default(),
+            events: vec![
+                Event::new("Added", vec![("sum".to_string(), TypePrimitive::UInt64)]).unwrap(),
+            ],
+            templates: vec![],
+            txn_approval: Program {
+                version: 8,
+                body: Expr::Router(Box::new(Router {
+                    methods: vec![add],
+                    bare: Some(bare),
+                    bare_actions: vec![OnComplete::NoOp],
+                })),
+                budget: None,
+            },
+            txn_clear: Program::default(),
+        }
+    }
+
+    #[test]
+    fn test_base64() {
+        // from RFC 4648
+        for (decoded, encoded) in [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ] {
+            assert_eq!(base64(decoded.as_bytes()), encoded);
+        }
+    }
+
+    fn validator() -> jsonschema::Validator {
+        let schema: Value = serde_json::from_str(SCHEMA).unwrap();
+        jsonschema::validator_for(&schema).unwrap()
+    }
+
+    // the spec as JSON, checked against the transcribed ARC-56 schema
+    fn validated(spec: &AppSpec) -> Value {
+        let json: Value = serde_json::from_str(&spec.to_json()).unwrap();
+        let errors = validator()
+            .iter_errors(&json)
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>();
+        assert!(errors.is_empty(), "{errors:?}");
+        json
+    }
+
+    #[test]
+    fn test_app_spec() {
+        let spec = contract().compile().unwrap().app_spec("Calculator");
+        let json = validated(&spec);
+
+        assert_eq!(json["name"], "Calculator");
+        assert_eq!(json["methods"][0]["name"], "add");
+        assert_eq!(json["methods"][0]["args"][1]["type"], "uint64");
+        assert_eq!(json["methods"][0]["returns"]["type"], "uint64");
+        assert_eq!(json["bareActions"]["call"][0], "NoOp");
+        assert_eq!(json["state"]["schema"]["global"]["ints"], 1);
+        assert_eq!(json["state"]["schema"]["global"]["bytes"], 1);
+        assert_eq!(json["state"]["schema"]["local"]["ints"], 1);
+        assert_eq!(
+            json["state"]["keys"]["global"]["counter"]["key"],
+            base64(b"counter")
+        );
+        assert_eq!(json["events"][0]["args"][0]["name"], "sum");
+        // the failure message is attached to the pc of its assert
+        let source_info = json["sourceInfo"]["approval"]["sourceInfo"]
+            .as_array()
+            .unwrap();
+        assert_eq!(
+            source_info
+                .iter()
+                .filter(|info| info["errorMessage"] == "only on create")
+                .count(),
+            1
+        );
+
+        let mut missing_methods = json.clone();
+        missing_methods.as_object_mut().unwrap().remove("methods");
+        assert!(!validator().is_valid(&missing_methods));
+    }
+
+    #[test]
+    fn test_actions() {
+        let mut contract = contract();
+        if let Expr::Router(router) = &mut contract.txn_approval.body {
+            router.methods[0].actions = vec![OnComplete::NoOp, OnComplete::DeleteApplication];
+            router.bare_actions = vec![OnComplete::OptIn];
+        }
+        let spec = contract.compile().unwrap().app_spec("Calculator");
+        assert_eq!(
+            spec.methods[0].actions.call,
+            vec!["NoOp", "DeleteApplication"]
+        );
+        assert_eq!(spec.bare_actions.call, vec!["OptIn"]);
+        assert_eq!(spec.bare_actions.create, vec!["OptIn"]);
+
+        // testing for an action in the program does not allow it
+        contract.txn_approval.body = apply!(
+            @fn Expr::Assert(Assert(None));
+            @arg apply!(
+                @fn Expr::Binary(Binary::NotEquals);
+                @arg Expr::OnComplete(OnComplete::DeleteApplication);
+                @arg Expr::Txn(Txn::OnCompletion)
+            )
+        );
+        let spec = contract.compile().unwrap().app_spec("Plain");
+        assert_eq!(spec.bare_actions.call, vec!["NoOp"]);
+        assert!(spec.methods.is_empty());
+    }
+
+    #[test]
+    fn test_templates() {
+        let mut contract = contract();
+        contract.templates = vec![("FEE".to_string(), TypePrimitive::UInt64)];
+        contract.txn_approval.body =
+            binop!((Expr::Txn(Txn::Fee)) <= (Expr::Template(Template("FEE".to_string()))));
+        let spec = contract.compile().unwrap().app_spec("Fees");
+        let json = validated(&spec);
+        assert_eq!(json["templateVariables"]["FEE"]["type"], "AVMUint64");
+        // pcs leave out the intcblock holding the placeholder
+        let approval = &json["sourceInfo"]["approval"];
+        assert_eq!(approval["pcOffsetMethod"], "cblocks");
+        assert_eq!(approval["sourceInfo"][0]["pc"][0], 1);
+        assert_eq!(json["sourceInfo"]["clear"]["pcOffsetMethod"], "none");
+    }
+}
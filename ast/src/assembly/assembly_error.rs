@@ -0,0 +1,25 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum AssemblyError {
+    #[error("Missing #pragma version")]
+    MissingVersion,
+    #[error("Line {0}: unknown opcode {1}")]
+    UnknownOpcode(usize, String),
+    #[error("Line {0}: {1} requires version {2}, program declares version {3}")]
+    UnsupportedOpcode(usize, String, u64, u64),
+    #[error("Line {0}: {1} expects {2} immediate argument(s)")]
+    ImmediateCount(usize, String, usize),
+    #[error("Line {0}: invalid immediate argument {1}")]
+    InvalidImmediate(usize, String),
+    #[error("Line {0}: unknown field {1}")]
+    UnknownField(usize, String),
+    #[error("Line {0}: unknown label {1}")]
+    UnknownLabel(usize, String),
+    #[error("Line {0}: duplicate label {1}")]
+    DuplicateLabel(usize, String),
+    #[error("Line {0}: branch to {1} is out of range")]
+    BranchOutOfRange(usize, String),
+    #[error("Line {0}: too many constants")]
+    TooManyConstants(usize),
+}
@@ -0,0 +1,610 @@
+// A small interpreter for the TEAL the compiler emits, enough to run generated code in tests.
+// Transaction, global and account fields it does not model read as zero.
+use std::collections::HashMap;
+
+use sha2::{Digest, Sha256, Sha512_256};
+
+use super::{parse_bytes, parse_uint, tokenize};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Uint(u64),
+    Bytes(Vec<u8>),
+}
+
+// a subroutine call, returning to `ret` with its frame starting at `fp` once `proto` ran
+#[derive(Debug)]
+struct Frame {
+    ret: usize,
+    fp: usize,
+    args: usize,
+    returns: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct Eval {
+    pub app_args: Vec<Vec<u8>>,
+    pub on_completion: u64,
+    pub stack: Vec<Value>,
+    pub logs: Vec<Vec<u8>>,
+    scratch: HashMap<u64, Value>,
+    globals: HashMap<Vec<u8>, Value>,
+    // local state of every account in one place
+    locals: HashMap<Vec<u8>, Value>,
+    boxes: HashMap<Vec<u8>, Vec<u8>>,
+    frames: Vec<Frame>,
+}
+
+impl Eval {
+    fn pop(&mut self) -> Result<Value, String> {
+        self.stack
+            .pop()
+            .ok_or_else(|| "stack underflow".to_string())
+    }
+
+    fn pop_uint(&mut self) -> Result<u64, String> {
+        match self.pop()? {
+            Value::Uint(v) => Ok(v),
+            v => Err(format!("expected uint, got {v:?}")),
+        }
+    }
+
+    fn pop_bytes(&mut self) -> Result<Vec<u8>, String> {
+        match self.pop()? {
+            Value::Bytes(v) => Ok(v),
+            v => Err(format!("expected bytes, got {v:?}")),
+        }
+    }
+
+    fn slice(bytes: &[u8], start: u64, end: u64) -> Result<Vec<u8>, String> {
+        if start > end || end > bytes.len() as u64 {
+            return Err(format!("{start}..{end} out of bounds of {}", bytes.len()));
+        }
+        Ok(bytes[start as usize..end as usize].to_vec())
+    }
+
+    fn replace(mut a: Vec<u8>, start: u64, b: &[u8]) -> Result<Vec<u8>, String> {
+        let end = start + b.len() as u64;
+        Self::slice(&a, start, end)?;
+        a[start as usize..end as usize].copy_from_slice(b);
+        Ok(a)
+    }
+
+    fn uint_at(a: &[u8], start: u64, size: u64) -> Result<u64, String> {
+        let v = Self::slice(a, start, start + size)?;
+        Ok(v.iter().fold(0, |acc, b| (acc << 8) | *b as u64))
+    }
+
+    // the index of frame slot `i` on the stack
+    fn frame_slot(&self, i: &str) -> Result<usize, String> {
+        let frame = self.frames.last().ok_or("no frame")?;
+        let i = i.parse::<i64>().map_err(|e| e.to_string())?;
+        let slot = frame.fp as i64 + i;
+        if slot < 0 || slot as usize >= self.stack.len() {
+            return Err(format!("frame slot {i} out of bounds"));
+        }
+        Ok(slot as usize)
+    }
+
+    // a field the interpreter does not model, e.g. `txn Fee`
+    fn zero(&mut self) {
+        self.stack.push(Value::Uint(0));
+    }
+
+    // runs `source` until it returns or falls off the end
+    pub fn run(&mut self, source: &str) -> Result<(), String> {
+        let lines = source
+            .lines()
+            .map(|line| tokenize(line).0)
+            .filter(|tokens| !tokens.is_empty() && !tokens[0].starts_with('#'))
+            .collect::<Vec<_>>();
+        let labels = lines
+            .iter()
+            .enumerate()
+            .filter_map(|(i, tokens)| tokens[0].strip_suffix(':').map(|label| (label, i)))
+            .collect::<HashMap<_, _>>();
+        let jump = |label: &str| {
+            labels
+                .get(label)
+                .copied()
+                .ok_or(format!("no label {label}"))
+        };
+        let mut pc = 0;
+        while let Some(tokens) = lines.get(pc) {
+            pc += 1;
+            let uint = |i: usize| parse_uint(pc, tokens[i]).map_err(|e| e.to_string());
+            match tokens[0] {
+                label if label.ends_with(':') => {}
+                "int" | "pushint" => self.stack.push(Value::Uint(uint(1)?)),
+                "byte" | "pushbytes" => self.stack.push(Value::Bytes(
+                    parse_bytes(pc, tokens[1]).map_err(|e| e.to_string())?,
+                )),
+                "load" => {
+                    let value = self.scratch.get(&uint(1)?).cloned();
+                    self.stack.push(value.unwrap_or(Value::Uint(0)));
+                }
+                "store" => {
+                    let value = self.pop()?;
+                    self.scratch.insert(uint(1)?, value);
+                }
+                "txn" if tokens[1] == "NumAppArgs" => {
+                    self.stack.push(Value::Uint(self.app_args.len() as u64))
+                }
+                "txn" if tokens[1] == "OnCompletion" => {
+                    self.stack.push(Value::Uint(self.on_completion))
+                }
+                "txna" if tokens[1] == "ApplicationArgs" => {
+                    let arg = self.app_args.get(uint(2)? as usize).ok_or("no such arg")?;
+                    self.stack.push(Value::Bytes(arg.clone()));
+                }
+                "txn" | "txna" | "global" | "gtxn" | "gtxna" | "itxn" | "itxna" => self.zero(),
+                "arg" => self.stack.push(Value::Bytes(vec![])),
+                "txnas" | "gtxns" | "gtxnsa" | "gtxnas" | "itxnas" | "balance" | "min_balance" => {
+                    self.pop()?;
+                    self.zero();
+                }
+                "gtxnsas" => {
+                    self.pop()?;
+                    self.pop()?;
+                    self.zero();
+                }
+                "asset_params_get" | "app_params_get" | "acct_params_get" => {
+                    self.pop()?;
+                    self.zero();
+                    self.zero();
+                }
+                "asset_holding_get" => {
+                    self.pop()?;
+                    self.pop()?;
+                    self.zero();
+                    self.zero();
+                }
+                "itxn_begin" | "itxn_next" | "itxn_submit" => {}
+                "itxn_field" => {
+                    self.pop()?;
+                }
+                "app_global_get" => {
+                    let key = self.pop_bytes()?;
+                    let value = self.globals.get(&key).cloned();
+                    self.stack.push(value.unwrap_or(Value::Uint(0)));
+                }
+                "app_global_get_ex" => {
+                    let key = self.pop_bytes()?;
+                    self.pop()?;
+                    let value = self.globals.get(&key).cloned();
+                    let exists = value.is_some() as u64;
+                    self.stack.push(value.unwrap_or(Value::Uint(0)));
+                    self.stack.push(Value::Uint(exists));
+                }
+                "app_global_put" => {
+                    let value = self.pop()?;
+                    let key = self.pop_bytes()?;
+                    self.globals.insert(key, value);
+                }
+                "app_global_del" => {
+                    let key = self.pop_bytes()?;
+                    self.globals.remove(&key);
+                }
+                "app_local_get" => {
+                    let key = self.pop_bytes()?;
+                    self.pop()?;
+                    let value = self.locals.get(&key).cloned();
+                    self.stack.push(value.unwrap_or(Value::Uint(0)));
+                }
+                "app_local_get_ex" => {
+                    let key = self.pop_bytes()?;
+                    self.pop()?;
+                    self.pop()?;
+                    let value = self.locals.get(&key).cloned();
+                    let exists = value.is_some() as u64;
+                    self.stack.push(value.unwrap_or(Value::Uint(0)));
+                    self.stack.push(Value::Uint(exists));
+                }
+                "app_local_put" => {
+                    let value = self.pop()?;
+                    let key = self.pop_bytes()?;
+                    self.pop()?;
+                    self.locals.insert(key, value);
+                }
+                "app_local_del" => {
+                    let key = self.pop_bytes()?;
+                    self.pop()?;
+                    self.locals.remove(&key);
+                }
+                "box_create" => {
+                    let size = self.pop_uint()?;
+                    let name = self.pop_bytes()?;
+                    let created = !self.boxes.contains_key(&name);
+                    if created {
+                        self.boxes.insert(name, vec![0; size as usize]);
+                    }
+                    self.stack.push(Value::Uint(created as u64));
+                }
+                "box_put" => {
+                    let value = self.pop_bytes()?;
+                    let name = self.pop_bytes()?;
+                    self.boxes.insert(name, value);
+                }
+                "box_get" | "box_len" => {
+                    let name = self.pop_bytes()?;
+                    let value = self.boxes.get(&name).cloned();
+                    let exists = value.is_some() as u64;
+                    let value = value.unwrap_or_default();
+                    self.stack.push(match tokens[0] {
+                        "box_get" => Value::Bytes(value),
+                        _ => Value::Uint(value.len() as u64),
+                    });
+                    self.stack.push(Value::Uint(exists));
+                }
+                "box_del" => {
+                    let name = self.pop_bytes()?;
+                    let deleted = self.boxes.remove(&name).is_some();
+                    self.stack.push(Value::Uint(deleted as u64));
+                }
+                "box_extract" => {
+                    let length = self.pop_uint()?;
+                    let start = self.pop_uint()?;
+                    let name = self.pop_bytes()?;
+                    let value = self.boxes.get(&name).ok_or("no such box")?;
+                    let extracted = Self::slice(value, start, start + length)?;
+                    self.stack.push(Value::Bytes(extracted));
+                }
+                "box_replace" => {
+                    let bytes = self.pop_bytes()?;
+                    let start = self.pop_uint()?;
+                    let name = self.pop_bytes()?;
+                    let value = self.boxes.remove(&name).ok_or("no such box")?;
+                    self.boxes
+                        .insert(name, Self::replace(value, start, &bytes)?);
+                }
+                "itob" => {
+                    let v = self.pop_uint()?;
+                    self.stack.push(Value::Bytes(v.to_be_bytes().to_vec()));
+                }
+                "btoi" => {
+                    let v = self.pop_bytes()?;
+                    if v.len() > 8 {
+                        return Err("btoi overflow".to_string());
+                    }
+                    let v = v.iter().fold(0, |acc, b| (acc << 8) | *b as u64);
+                    self.stack.push(Value::Uint(v));
+                }
+                "len" => {
+                    let v = self.pop_bytes()?;
+                    self.stack.push(Value::Uint(v.len() as u64));
+                }
+                "bzero" => {
+                    let n = self.pop_uint()?;
+                    self.stack.push(Value::Bytes(vec![0; n as usize]));
+                }
+                "sha256" | "sha512_256" => {
+                    let v = self.pop_bytes()?;
+                    let hash = match tokens[0] {
+                        "sha256" => Sha256::digest(&v).to_vec(),
+                        _ => Sha512_256::digest(&v).to_vec(),
+                    };
+                    self.stack.push(Value::Bytes(hash));
+                }
+                "bitlen" => {
+                    let bits = match self.pop()? {
+                        Value::Uint(v) => 64 - v.leading_zeros() as u64,
+                        Value::Bytes(a) => match a.iter().position(|b| *b != 0) {
+                            Some(i) => (a.len() - i) as u64 * 8 - a[i].leading_zeros() as u64,
+                            None => 0,
+                        },
+                    };
+                    self.stack.push(Value::Uint(bits));
+                }
+                "concat" => {
+                    let b = self.pop_bytes()?;
+                    let mut a = self.pop_bytes()?;
+                    a.extend(b);
+                    self.stack.push(Value::Bytes(a));
+                }
+                "b|" => {
+                    let mut b = self.pop_bytes()?;
+                    let mut a = self.pop_bytes()?;
+                    if a.len() < b.len() {
+                        std::mem::swap(&mut a, &mut b);
+                    }
+                    let offset = a.len() - b.len();
+                    for (i, byte) in b.iter().enumerate() {
+                        a[offset + i] |= byte;
+                    }
+                    self.stack.push(Value::Bytes(a));
+                }
+                "extract" => {
+                    let (start, length) = (uint(1)?, uint(2)?);
+                    let a = self.pop_bytes()?;
+                    let end = if length == 0 {
+                        a.len() as u64
+                    } else {
+                        start + length
+                    };
+                    self.stack.push(Value::Bytes(Self::slice(&a, start, end)?));
+                }
+                "substring" => {
+                    let (start, end) = (uint(1)?, uint(2)?);
+                    let a = self.pop_bytes()?;
+                    self.stack.push(Value::Bytes(Self::slice(&a, start, end)?));
+                }
+                "extract3" => {
+                    let length = self.pop_uint()?;
+                    let start = self.pop_uint()?;
+                    let a = self.pop_bytes()?;
+                    self.stack
+                        .push(Value::Bytes(Self::slice(&a, start, start + length)?));
+                }
+                "substring3" => {
+                    let end = self.pop_uint()?;
+                    let start = self.pop_uint()?;
+                    let a = self.pop_bytes()?;
+                    self.stack.push(Value::Bytes(Self::slice(&a, start, end)?));
+                }
+                "replace2" => {
+                    let b = self.pop_bytes()?;
+                    let a = self.pop_bytes()?;
+                    self.stack
+                        .push(Value::Bytes(Self::replace(a, uint(1)?, &b)?));
+                }
+                "replace3" => {
+                    let b = self.pop_bytes()?;
+                    let start = self.pop_uint()?;
+                    let a = self.pop_bytes()?;
+                    self.stack.push(Value::Bytes(Self::replace(a, start, &b)?));
+                }
+                op @ ("extract_uint16" | "extract_uint32" | "extract_uint64") => {
+                    let start = self.pop_uint()?;
+                    let a = self.pop_bytes()?;
+                    let size = match op {
+                        "extract_uint16" => 2,
+                        "extract_uint32" => 4,
+                        _ => 8,
+                    };
+                    self.stack
+                        .push(Value::Uint(Self::uint_at(&a, start, size)?));
+                }
+                "getbyte" => {
+                    let i = self.pop_uint()?;
+                    let a = self.pop_bytes()?;
+                    self.stack.push(Value::Uint(Self::uint_at(&a, i, 1)?));
+                }
+                "setbyte" => {
+                    let v = self.pop_uint()?;
+                    let i = self.pop_uint()?;
+                    let a = self.pop_bytes()?;
+                    let byte = u8::try_from(v).map_err(|e| e.to_string())?;
+                    self.stack.push(Value::Bytes(Self::replace(a, i, &[byte])?));
+                }
+                "getbit" => {
+                    let i = self.pop_uint()?;
+                    let bit = match self.pop()? {
+                        Value::Uint(v) if i < 64 => (v >> i) & 1,
+                        Value::Bytes(a) if i < a.len() as u64 * 8 => {
+                            (a[i as usize / 8] >> (7 - i % 8)) as u64 & 1
+                        }
+                        _ => return Err("getbit out of bounds".to_string()),
+                    };
+                    self.stack.push(Value::Uint(bit));
+                }
+                "setbit" => {
+                    let bit = self.pop_uint()?;
+                    let i = self.pop_uint()?;
+                    if bit > 1 {
+                        return Err("setbit with a value above 1".to_string());
+                    }
+                    let value = match self.pop()? {
+                        Value::Uint(v) if i < 64 => Value::Uint(v & !(1 << i) | bit << i),
+                        Value::Bytes(mut a) if i < a.len() as u64 * 8 => {
+                            let mask = 0x80 >> (i % 8);
+                            let byte = &mut a[i as usize / 8];
+                            *byte = if bit == 1 {
+                                *byte | mask
+                            } else {
+                                *byte & !mask
+                            };
+                            Value::Bytes(a)
+                        }
+                        _ => return Err("setbit out of bounds".to_string()),
+                    };
+                    self.stack.push(value);
+                }
+                "select" => {
+                    let c = self.pop_uint()?;
+                    let b = self.pop()?;
+                    let a = self.pop()?;
+                    self.stack.push(if c != 0 { b } else { a });
+                }
+                "dup" => {
+                    let v = self.stack.last().cloned().ok_or("stack underflow")?;
+                    self.stack.push(v);
+                }
+                "dupn" => {
+                    let v = self.stack.last().cloned().ok_or("stack underflow")?;
+                    for _ in 0..uint(1)? {
+                        self.stack.push(v.clone());
+                    }
+                }
+                "pop" => {
+                    self.pop()?;
+                }
+                "swap" => {
+                    let b = self.pop()?;
+                    let a = self.pop()?;
+                    self.stack.extend([b, a]);
+                }
+                "dig" | "uncover" | "cover" => {
+                    let n = uint(1)? as usize;
+                    let len = self.stack.len();
+                    if n >= len {
+                        return Err("stack underflow".to_string());
+                    }
+                    match tokens[0] {
+                        "dig" => self.stack.push(self.stack[len - 1 - n].clone()),
+                        "uncover" => {
+                            let v = self.stack.remove(len - 1 - n);
+                            self.stack.push(v);
+                        }
+                        _ => {
+                            let v = self.pop()?;
+                            self.stack.insert(len - 1 - n, v);
+                        }
+                    }
+                }
+                op @ ("+" | "-" | "*" | "/" | "%" | "<" | ">" | "<=" | ">=" | "==" | "!="
+                | "&&" | "||" | "&" | "|" | "^" | "exp" | "shl" | "shr") => {
+                    let (b, a) = (self.pop()?, self.pop()?);
+                    let value = match (op, a, b) {
+                        ("==", a, b) => (a == b) as u64,
+                        ("!=", a, b) => (a != b) as u64,
+                        (op, Value::Uint(a), Value::Uint(b)) => match op {
+                            "+" => a.checked_add(b).ok_or("+ overflow")?,
+                            "-" => a.checked_sub(b).ok_or("- underflow")?,
+                            "*" => a.checked_mul(b).ok_or("* overflow")?,
+                            "/" => a.checked_div(b).ok_or("/ by zero")?,
+                            "%" => a.checked_rem(b).ok_or("% by zero")?,
+                            "<" => (a < b) as u64,
+                            ">" => (a > b) as u64,
+                            "<=" => (a <= b) as u64,
+                            ">=" => (a >= b) as u64,
+                            "&&" => (a != 0 && b != 0) as u64,
+                            "||" => (a != 0 || b != 0) as u64,
+                            "&" => a & b,
+                            "|" => a | b,
+                            "^" => a ^ b,
+                            "exp" => u32::try_from(b)
+                                .ok()
+                                .and_then(|b| a.checked_pow(b))
+                                .ok_or("exp overflow")?,
+                            "shl" if b < 64 => a << b,
+                            "shr" if b < 64 => a >> b,
+                            _ => return Err(format!("{op} by {b}")),
+                        },
+                        _ => return Err(format!("{op} on bytes")),
+                    };
+                    self.stack.push(Value::Uint(value));
+                }
+                op @ ("mulw" | "addw" | "expw") => {
+                    let (b, a) = (self.pop_uint()? as u128, self.pop_uint()? as u128);
+                    let wide = match op {
+                        "mulw" => a * b,
+                        "addw" => a + b,
+                        _ => u32::try_from(b)
+                            .ok()
+                            .and_then(|b| a.checked_pow(b))
+                            .ok_or("expw overflow")?,
+                    };
+                    self.stack.push(Value::Uint((wide >> 64) as u64));
+                    self.stack.push(Value::Uint(wide as u64));
+                }
+                "divmodw" => {
+                    let divisor = self.pop_uint()? as u128 | (self.pop_uint()? as u128) << 64;
+                    let dividend = self.pop_uint()? as u128 | (self.pop_uint()? as u128) << 64;
+                    if divisor == 0 {
+                        return Err("divmodw by zero".to_string());
+                    }
+                    let (quotient, remainder) = (dividend / divisor, dividend % divisor);
+                    for wide in [quotient, remainder] {
+                        self.stack.push(Value::Uint((wide >> 64) as u64));
+                        self.stack.push(Value::Uint(wide as u64));
+                    }
+                }
+                "!" => {
+                    let v = self.pop_uint()?;
+                    self.stack.push(Value::Uint((v == 0) as u64));
+                }
+                "~" => {
+                    let v = self.pop_uint()?;
+                    self.stack.push(Value::Uint(!v));
+                }
+                "sqrt" => {
+                    let v = self.pop_uint()?;
+                    let mut root = (v as f64).sqrt() as u64;
+                    while root.checked_mul(root).is_none_or(|square| square > v) {
+                        root -= 1;
+                    }
+                    while (root + 1)
+                        .checked_mul(root + 1)
+                        .is_some_and(|square| square <= v)
+                    {
+                        root += 1;
+                    }
+                    self.stack.push(Value::Uint(root));
+                }
+                "assert" => {
+                    if self.pop_uint()? == 0 {
+                        return Err(format!("assert failed at line {pc}"));
+                    }
+                }
+                "err" => return Err(format!("err at line {pc}")),
+                "log" => {
+                    let v = self.pop_bytes()?;
+                    self.logs.push(v);
+                }
+                "b" => pc = jump(tokens[1])?,
+                "bz" | "bnz" => {
+                    let v = self.pop_uint()?;
+                    if (v == 0) == (tokens[0] == "bz") {
+                        pc = jump(tokens[1])?;
+                    }
+                }
+                "match" => {
+                    let n = tokens.len() - 1;
+                    let target = self.pop()?;
+                    let len = self.stack.len();
+                    if n > len {
+                        return Err("stack underflow".to_string());
+                    }
+                    let candidates = self.stack.split_off(len - n);
+                    if let Some(i) = candidates.iter().position(|c| *c == target) {
+                        pc = jump(tokens[i + 1])?;
+                    }
+                }
+                "callsub" => {
+                    self.frames.push(Frame {
+                        ret: pc,
+                        fp: self.stack.len(),
+                        args: 0,
+                        returns: 0,
+                    });
+                    pc = jump(tokens[1])?;
+                }
+                "proto" => {
+                    let frame = self.frames.last_mut().ok_or("proto outside a call")?;
+                    frame.args = uint(1)? as usize;
+                    frame.returns = uint(2)? as usize;
+                    if frame.args > frame.fp {
+                        return Err("stack underflow".to_string());
+                    }
+                }
+                "frame_dig" => {
+                    let slot = self.frame_slot(tokens[1])?;
+                    self.stack.push(self.stack[slot].clone());
+                }
+                "frame_bury" => {
+                    let v = self.pop()?;
+                    let slot = self.frame_slot(tokens[1])?;
+                    self.stack[slot] = v;
+                }
+                "retsub" => {
+                    let frame = self.frames.pop().ok_or("retsub outside a call")?;
+                    let len = self.stack.len();
+                    if frame.returns > len || frame.args > frame.fp {
+                        return Err("stack underflow".to_string());
+                    }
+                    let results = self.stack.split_off(len - frame.returns);
+                    self.stack.truncate(frame.fp - frame.args);
+                    self.stack.extend(results);
+                    pc = frame.ret;
+                }
+                "return" => {
+                    let v = self.pop_uint()?;
+                    self.stack = vec![Value::Uint(v)];
+                    return Ok(());
+                }
+                op => return Err(format!("unsupported op {op}")),
+            }
+        }
+        Ok(())
+    }
+}
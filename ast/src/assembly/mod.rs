@@ -0,0 +1,580 @@
+use std::collections::{BTreeMap, HashMap};
+
+mod assembly_error;
+pub use assembly_error::AssemblyError;
+#[cfg(test)]
+pub(crate) mod eval;
+pub mod opcode;
+
+use opcode::{lookup, Immediate, NAMED_INTS};
+
+// A constant block entry, template placeholders get their values at deploy time
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant<T> {
+    Value(T),
+    Template(String),
+}
+
+impl<T> Constant<T> {
+    pub fn template(&self) -> Option<&str> {
+        match self {
+            Constant::Template(t) => Some(t),
+            Constant::Value(_) => None,
+        }
+    }
+}
+
+impl<T: Clone + Default> Constant<T> {
+    // the value encoded into the block, a zero value for placeholders
+    fn encoded(&self) -> T {
+        match self {
+            Constant::Value(value) => value.clone(),
+            Constant::Template(_) => T::default(),
+        }
+    }
+}
+
+pub struct Assembled {
+    pub version: u64,
+    pub bytecode: Vec<u8>,
+    // the entries of the intcblock and bytecblock at the start of `bytecode`
+    pub ints: Vec<Constant<u64>>,
+    pub bytes: Vec<Constant<Vec<u8>>>,
+    // bytes emitted per source mnemonic (constant blocks included), largest first
+    pub contributions: Vec<(String, usize)>,
+    // trailing comments of instructions by their pc, e.g. the reasons asserts fail for
+    pub comments: BTreeMap<usize, String>,
+    // the source line of the instruction at each pc
+    pub lines: BTreeMap<usize, usize>,
+}
+
+struct Instruction<'a> {
+    line: usize,
+    name: &'a str,
+    args: Vec<&'a str>,
+    comment: Option<&'a str>,
+}
+
+enum Item<'a> {
+    Label(usize, &'a str),
+    Instruction(Instruction<'a>),
+}
+
+struct Encoded {
+    bytes: Vec<u8>,
+    // (offset of the two byte slot within `bytes`, target label)
+    fixups: Vec<(usize, String)>,
+}
+
+// the tokens of a line and its comment, if any
+pub(crate) fn tokenize(line: &str) -> (Vec<&str>, Option<&str>) {
+    let mut tokens = vec![];
+    let mut start = None;
+    let mut end = line.len();
+    let mut comment = None;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+        } else if line[i..].starts_with("//") {
+            end = i;
+            comment = Some(line[i + 2..].trim()).filter(|c| !c.is_empty());
+            break;
+        } else if c.is_whitespace() {
+            if let Some(s) = start.take() {
+                tokens.push(&line[s..i]);
+            }
+        } else {
+            start.get_or_insert(i);
+            in_string = c == '"';
+        }
+    }
+    if let Some(s) = start {
+        tokens.push(&line[s..end]);
+    }
+    (tokens, comment)
+}
+
+pub(crate) fn parse_uint(line: usize, s: &str) -> Result<u64, AssemblyError> {
+    if let Some((_, value)) = NAMED_INTS.iter().find(|(name, _)| *name == s) {
+        return Ok(*value);
+    }
+    let parsed = match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    parsed.map_err(|_| AssemblyError::InvalidImmediate(line, s.to_string()))
+}
+
+pub(crate) fn parse_bytes(line: usize, s: &str) -> Result<Vec<u8>, AssemblyError> {
+    let invalid = || AssemblyError::InvalidImmediate(line, s.to_string());
+    if let Some(hex) = s.strip_prefix("0x") {
+        if hex.len() % 2 != 0 {
+            return Err(invalid());
+        }
+        return (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid()))
+            .collect();
+    }
+    let inner = s
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .ok_or_else(invalid)?;
+    let mut bytes = vec![];
+    let mut chars = inner.bytes();
+    while let Some(c) = chars.next() {
+        if c != b'\\' {
+            bytes.push(c);
+            continue;
+        }
+        bytes.push(match chars.next().ok_or_else(invalid)? {
+            b'n' => b'\n',
+            b't' => b'\t',
+            b'r' => b'\r',
+            b'0' => 0,
+            b'x' => {
+                let hex = [
+                    chars.next().ok_or_else(invalid)?,
+                    chars.next().ok_or_else(invalid)?,
+                ];
+                let hex = std::str::from_utf8(&hex).map_err(|_| invalid())?;
+                u8::from_str_radix(hex, 16).map_err(|_| invalid())?
+            }
+            c @ (b'\\' | b'"' | b'\'') => c,
+            _ => return Err(invalid()),
+        });
+    }
+    Ok(bytes)
+}
+
+pub(crate) fn is_template(s: &str) -> bool {
+    s.starts_with("TMPL_")
+}
+
+fn int_constant(line: usize, s: &str) -> Result<Constant<u64>, AssemblyError> {
+    if is_template(s) {
+        return Ok(Constant::Template(s.to_string()));
+    }
+    parse_uint(line, s).map(Constant::Value)
+}
+
+fn bytes_constant(line: usize, s: &str) -> Result<Constant<Vec<u8>>, AssemblyError> {
+    if is_template(s) {
+        return Ok(Constant::Template(s.to_string()));
+    }
+    parse_bytes(line, s).map(Constant::Value)
+}
+
+fn write_varuint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn write_bytes(out: &mut Vec<u8>, value: &[u8]) {
+    write_varuint(out, value.len() as u64);
+    out.extend_from_slice(value);
+}
+
+// Constants used more than once go into a constant block, most used first. Without
+// pushint/pushbytes (pre v3) every constant has to live in the block, and templates always do
+// so that substituting them only rewrites the blocks.
+fn constant_block<T: PartialEq + Clone>(
+    uses: &[Constant<T>],
+    push_available: bool,
+) -> Vec<Constant<T>> {
+    let mut counts: Vec<(Constant<T>, usize)> = vec![];
+    for value in uses {
+        match counts.iter_mut().find(|(v, _)| v == value) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((value.clone(), 1)),
+        }
+    }
+    counts.sort_by(|(_, a), (_, b)| b.cmp(a));
+    counts
+        .into_iter()
+        .filter(|(value, count)| {
+            !push_available || *count > 1 || matches!(value, Constant::Template(_))
+        })
+        .map(|(value, _)| value)
+        .collect()
+}
+
+// `intc`/`bytec` are directly followed by their `_0` to `_3` shorthands
+fn encode_constant(
+    line: usize,
+    index: usize,
+    name: &str,
+    out: &mut Vec<u8>,
+) -> Result<(), AssemblyError> {
+    let base = lookup(name).unwrap().code;
+    if index < 4 {
+        out.push(base + 1 + index as u8);
+    } else {
+        out.push(base);
+        out.push(u8::try_from(index).map_err(|_| AssemblyError::TooManyConstants(line))?);
+    }
+    Ok(())
+}
+
+// The intcblock and bytecblock, each with its mnemonic, leaving out empty ones
+pub(crate) fn constant_blocks(
+    ints: &[Constant<u64>],
+    bytes: &[Constant<Vec<u8>>],
+) -> Vec<(&'static str, Vec<u8>)> {
+    let mut blocks = vec![];
+    if !ints.is_empty() {
+        let mut block = vec![lookup("intcblock").unwrap().code];
+        write_varuint(&mut block, ints.len() as u64);
+        for value in ints {
+            write_varuint(&mut block, value.encoded());
+        }
+        blocks.push(("intcblock", block));
+    }
+    if !bytes.is_empty() {
+        let mut block = vec![lookup("bytecblock").unwrap().code];
+        write_varuint(&mut block, bytes.len() as u64);
+        for value in bytes {
+            write_bytes(&mut block, &value.encoded());
+        }
+        blocks.push(("bytecblock", block));
+    }
+    blocks
+}
+
+fn encode(
+    instruction: &Instruction,
+    version: u64,
+    ints: &[Constant<u64>],
+    bytes: &[Constant<Vec<u8>>],
+) -> Result<Encoded, AssemblyError> {
+    let Instruction {
+        line, name, args, ..
+    } = instruction;
+    let line = *line;
+    let mut out = vec![];
+    let mut fixups = vec![];
+
+    match *name {
+        "int" | "byte" => {
+            if args.len() != 1 {
+                return Err(AssemblyError::ImmediateCount(line, name.to_string(), 1));
+            }
+            // templates are always found in the blocks
+            if *name == "int" {
+                let value = int_constant(line, args[0])?;
+                match ints.iter().position(|v| *v == value) {
+                    Some(i) => encode_constant(line, i, "intc", &mut out)?,
+                    None => {
+                        out.push(lookup("pushint").unwrap().code);
+                        write_varuint(&mut out, value.encoded());
+                    }
+                }
+            } else {
+                let value = bytes_constant(line, args[0])?;
+                match bytes.iter().position(|v| *v == value) {
+                    Some(i) => encode_constant(line, i, "bytec", &mut out)?,
+                    None => {
+                        out.push(lookup("pushbytes").unwrap().code);
+                        write_bytes(&mut out, &value.encoded());
+                    }
+                }
+            }
+            return Ok(Encoded { bytes: out, fixups });
+        }
+        _ => {}
+    }
+
+    let spec = lookup(name).ok_or_else(|| AssemblyError::UnknownOpcode(line, name.to_string()))?;
+    if spec.version > version {
+        return Err(AssemblyError::UnsupportedOpcode(
+            line,
+            name.to_string(),
+            spec.version,
+            version,
+        ));
+    }
+    out.push(spec.code);
+
+    let variadic = matches!(
+        spec.immediates.last(),
+        Some(Immediate::Labels | Immediate::VarUInts | Immediate::Bytess)
+    );
+    if !variadic && args.len() != spec.immediates.len() {
+        return Err(AssemblyError::ImmediateCount(
+            line,
+            name.to_string(),
+            spec.immediates.len(),
+        ));
+    }
+
+    let invalid = |arg: &str| AssemblyError::InvalidImmediate(line, arg.to_string());
+    for (i, immediate) in spec.immediates.iter().enumerate() {
+        let rest = args.get(i..).unwrap_or(&[]);
+        match immediate {
+            Immediate::UInt8 => out.push(rest[0].parse::<u8>().map_err(|_| invalid(rest[0]))?),
+            Immediate::Int8 => out.push(rest[0].parse::<i8>().map_err(|_| invalid(rest[0]))? as u8),
+            Immediate::Field(table) => out.push(
+                table
+                    .iter()
+                    .position(|f| *f == rest[0])
+                    .ok_or_else(|| AssemblyError::UnknownField(line, rest[0].to_string()))?
+                    as u8,
+            ),
+            Immediate::Label => {
+                fixups.push((out.len(), rest[0].to_string()));
+                out.extend_from_slice(&[0, 0]);
+            }
+            Immediate::Labels => {
+                out.push(u8::try_from(rest.len()).map_err(|_| invalid(name))?);
+                for label in rest {
+                    fixups.push((out.len(), label.to_string()));
+                    out.extend_from_slice(&[0, 0]);
+                }
+            }
+            Immediate::VarUInt => write_varuint(&mut out, parse_uint(line, rest[0])?),
+            Immediate::Bytes => write_bytes(&mut out, &parse_bytes(line, rest[0])?),
+            Immediate::VarUInts => {
+                write_varuint(&mut out, rest.len() as u64);
+                for arg in rest {
+                    write_varuint(&mut out, parse_uint(line, arg)?);
+                }
+            }
+            Immediate::Bytess => {
+                write_varuint(&mut out, rest.len() as u64);
+                for arg in rest {
+                    write_bytes(&mut out, &parse_bytes(line, arg)?);
+                }
+            }
+        }
+    }
+
+    Ok(Encoded { bytes: out, fixups })
+}
+
+// The bytecode size of a piece of a program, its constants pushed and its branches left
+// unresolved, so it only compares against code with the same constants and branches
+pub(crate) fn size(teal: &str, version: u64) -> Result<usize, AssemblyError> {
+    let mut size = 0;
+    for (i, text) in teal.lines().enumerate() {
+        let (tokens, comment) = tokenize(text);
+        match &tokens[..] {
+            [] => {}
+            [label] if label.ends_with(':') => {}
+            [name, args @ ..] => {
+                let instruction = Instruction {
+                    line: i + 1,
+                    name,
+                    args: args.to_vec(),
+                    comment,
+                };
+                size += encode(&instruction, version, &[], &[])?.bytes.len();
+            }
+        }
+    }
+    Ok(size)
+}
+
+pub fn assemble(source: &str) -> Result<Assembled, AssemblyError> {
+    let mut version = None;
+    let mut items = vec![];
+
+    for (i, text) in source.lines().enumerate() {
+        let line = i + 1;
+        let (tokens, comment) = tokenize(text);
+        match &tokens[..] {
+            [] => {}
+            ["#pragma", "version", v] => {
+                version = Some(
+                    v.parse::<u64>()
+                        .map_err(|_| AssemblyError::InvalidImmediate(line, v.to_string()))?,
+                )
+            }
+            [label] if label.ends_with(':') => {
+                items.push(Item::Label(line, label.trim_end_matches(':')))
+            }
+            [name, args @ ..] => items.push(Item::Instruction(Instruction {
+                line,
+                name,
+                args: args.to_vec(),
+                comment,
+            })),
+        }
+    }
+    let version = version.ok_or(AssemblyError::MissingVersion)?;
+
+    let mut int_uses = vec![];
+    let mut byte_uses = vec![];
+    for item in &items {
+        if let Item::Instruction(Instruction {
+            line, name, args, ..
+        }) = item
+        {
+            match (*name, &args[..]) {
+                ("int", [arg]) => int_uses.push(int_constant(*line, arg)?),
+                ("byte", [arg]) => byte_uses.push(bytes_constant(*line, arg)?),
+                _ => {}
+            }
+        }
+    }
+    let ints = constant_block(&int_uses, version >= 3);
+    let bytes = constant_block(&byte_uses, version >= 3);
+
+    let mut bytecode = vec![];
+    write_varuint(&mut bytecode, version);
+    let mut contributions: HashMap<String, usize> = HashMap::new();
+
+    for (name, block) in constant_blocks(&ints, &bytes) {
+        contributions.insert(name.to_string(), block.len());
+        bytecode.extend(block);
+    }
+
+    let mut comments = BTreeMap::new();
+    let mut lines = BTreeMap::new();
+    let mut labels = HashMap::new();
+    // (line, absolute slot offset, pc the branch is relative to, target label)
+    let mut fixups = vec![];
+    for item in &items {
+        match item {
+            Item::Label(line, label) => {
+                if labels.insert(*label, bytecode.len()).is_some() {
+                    return Err(AssemblyError::DuplicateLabel(*line, label.to_string()));
+                }
+            }
+            Item::Instruction(instruction) => {
+                let encoded = encode(instruction, version, &ints, &bytes)?;
+                let start = bytecode.len();
+                let end = start + encoded.bytes.len();
+                lines.insert(start, instruction.line);
+                if let Some(comment) = instruction.comment {
+                    comments.insert(start, comment.to_string());
+                }
+                for (offset, label) in encoded.fixups {
+                    fixups.push((instruction.line, start + offset, end, label));
+                }
+                bytecode.extend(encoded.bytes);
+                *contributions
+                    .entry(instruction.name.to_string())
+                    .or_default() += end - start;
+            }
+        }
+    }
+
+    for (line, slot, base, label) in fixups {
+        let target = *labels
+            .get(label.as_str())
+            .ok_or_else(|| AssemblyError::UnknownLabel(line, label.clone()))?;
+        let offset = i16::try_from(target as i64 - base as i64)
+            .map_err(|_| AssemblyError::BranchOutOfRange(line, label.clone()))?;
+        bytecode[slot..slot + 2].copy_from_slice(&offset.to_be_bytes());
+    }
+
+    let mut contributions = contributions.into_iter().collect::<Vec<_>>();
+    contributions.sort_by(|(a_name, a), (b_name, b)| b.cmp(a).then(a_name.cmp(b_name)));
+
+    Ok(Assembled {
+        version,
+        bytecode,
+        ints,
+        bytes,
+        contributions,
+        comments,
+        lines,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{assemble, tokenize, Constant};
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize("byte \"a // b\" // comment"),
+            (vec!["byte", "\"a // b\""], Some("comment"))
+        );
+        assert_eq!(tokenize("label: "), (vec!["label:"], None));
+        assert_eq!(tokenize("int 1//x"), (vec!["int", "1"], Some("x")));
+    }
+
+    #[test]
+    fn test_constants() {
+        let assembled =
+            assemble("#pragma version 5\nint 1\nint 1\nint 7\nbyte \"a\"\nbyte \"a\"\nbyte 0x00ff")
+                .unwrap();
+        assert_eq!(
+            assembled.bytecode,
+            vec![
+                0x05, // version
+                0x20, 0x01, 0x01, // intcblock 1
+                0x26, 0x01, 0x01, b'a', // bytecblock "a"
+                0x22, 0x22, // intc_0 intc_0
+                0x81, 0x07, // pushint 7
+                0x28, 0x28, // bytec_0 bytec_0
+                0x80, 0x02, 0x00, 0xff, // pushbytes 0x00ff
+            ]
+        );
+    }
+
+    #[test]
+    fn test_constants_v2() {
+        let assembled = assemble("#pragma version 2\nint 1\nint NoOp\nbyte \"a\"").unwrap();
+        assert_eq!(
+            assembled.bytecode,
+            vec![0x02, 0x20, 0x02, 0x01, 0x00, 0x26, 0x01, 0x01, b'a', 0x22, 0x23, 0x28]
+        );
+    }
+
+    #[test]
+    fn test_templates() {
+        // placeholders always go into the blocks, even when used once
+        let assembled =
+            assemble("#pragma version 8\nint TMPL_FEE\nint 1\nbyte TMPL_OWNER\nbyte TMPL_OWNER")
+                .unwrap();
+        assert_eq!(
+            assembled.bytecode,
+            vec![0x08, 0x20, 0x01, 0x00, 0x26, 0x01, 0x00, 0x22, 0x81, 0x01, 0x28, 0x28]
+        );
+        assert_eq!(
+            assembled.ints,
+            vec![Constant::Template("TMPL_FEE".to_string())]
+        );
+        assert_eq!(
+            assembled.bytes,
+            vec![Constant::Template("TMPL_OWNER".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_branches() {
+        let assembled =
+            assemble("#pragma version 5\nstart:\nint 1\nbnz end\nb start\nend:\ntxn Sender\nlen")
+                .unwrap();
+        assert_eq!(
+            assembled.bytecode,
+            vec![0x05, 0x81, 0x01, 0x40, 0x00, 0x03, 0x42, 0xff, 0xf8, 0x31, 0x00, 0x15]
+        );
+        assert_eq!(
+            assembled.lines.into_iter().collect::<Vec<_>>(),
+            vec![(1, 3), (3, 4), (6, 5), (9, 7), (11, 8)]
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(assemble("int 1").is_err());
+        assert!(assemble("#pragma version 5\nfoo").is_err());
+        assert!(assemble("#pragma version 5\nb nowhere").is_err());
+        assert!(assemble("#pragma version 5\ntxn Nothing").is_err());
+        assert!(assemble("#pragma version 5\nbox_len").is_err());
+    }
+}
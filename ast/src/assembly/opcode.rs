@@ -0,0 +1,412 @@
+/// Kinds of immediate arguments following an opcode in the bytecode
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Immediate {
+    /// Single unsigned byte
+    UInt8,
+    /// Single signed byte
+    Int8,
+    /// Single byte naming an entry in a field table
+    Field(&'static [&'static str]),
+    /// Two byte signed branch offset
+    Label,
+    /// Byte count followed by that many two byte branch offsets
+    Labels,
+    /// Variable length unsigned integer
+    VarUInt,
+    /// Length prefixed byteslice
+    Bytes,
+    /// Count followed by that many variable length unsigned integers
+    VarUInts,
+    /// Count followed by that many length prefixed byteslices
+    Bytess,
+}
+
+use Immediate::*;
+
+#[derive(Debug)]
+pub struct OpSpec {
+    pub name: &'static str,
+    pub code: u8,
+    pub immediates: &'static [Immediate],
+    pub version: u64,
+    pub cost: u64,
+}
+
+pub const TXN_FIELDS: &[&str] = &[
+    "Sender",
+    "Fee",
+    "FirstValid",
+    "FirstValidTime",
+    "LastValid",
+    "Note",
+    "Lease",
+    "Receiver",
+    "Amount",
+    "CloseRemainderTo",
+    "VotePK",
+    "SelectionPK",
+    "VoteFirst",
+    "VoteLast",
+    "VoteKeyDilution",
+    "Type",
+    "TypeEnum",
+    "XferAsset",
+    "AssetAmount",
+    "AssetSender",
+    "AssetReceiver",
+    "AssetCloseTo",
+    "GroupIndex",
+    "TxID",
+    "ApplicationID",
+    "OnCompletion",
+    "ApplicationArgs",
+    "NumAppArgs",
+    "Accounts",
+    "NumAccounts",
+    "ApprovalProgram",
+    "ClearStateProgram",
+    "RekeyTo",
+    "ConfigAsset",
+    "ConfigAssetTotal",
+    "ConfigAssetDecimals",
+    "ConfigAssetDefaultFrozen",
+    "ConfigAssetUnitName",
+    "ConfigAssetName",
+    "ConfigAssetURL",
+    "ConfigAssetMetadataHash",
+    "ConfigAssetManager",
+    "ConfigAssetReserve",
+    "ConfigAssetFreeze",
+    "ConfigAssetClawback",
+    "FreezeAsset",
+    "FreezeAssetAccount",
+    "FreezeAssetFrozen",
+    "Assets",
+    "NumAssets",
+    "Applications",
+    "NumApplications",
+    "GlobalNumUint",
+    "GlobalNumByteSlice",
+    "LocalNumUint",
+    "LocalNumByteSlice",
+    "ExtraProgramPages",
+    "Nonparticipation",
+    "Logs",
+    "NumLogs",
+    "CreatedAssetID",
+    "CreatedApplicationID",
+    "LastLog",
+    "StateProofPK",
+    "ApprovalProgramPages",
+    "NumApprovalProgramPages",
+    "ClearStateProgramPages",
+    "NumClearStateProgramPages",
+];
+
+pub const GLOBAL_FIELDS: &[&str] = &[
+    "MinTxnFee",
+    "MinBalance",
+    "MaxTxnLife",
+    "ZeroAddress",
+    "GroupSize",
+    "LogicSigVersion",
+    "Round",
+    "LatestTimestamp",
+    "CurrentApplicationID",
+    "CreatorAddress",
+    "CurrentApplicationAddress",
+    "GroupID",
+    "OpcodeBudget",
+    "CallerApplicationID",
+    "CallerApplicationAddress",
+];
+
+pub const ASSET_HOLDING_FIELDS: &[&str] = &["AssetBalance", "AssetFrozen"];
+
+pub const ASSET_PARAMS_FIELDS: &[&str] = &[
+    "AssetTotal",
+    "AssetDecimals",
+    "AssetDefaultFrozen",
+    "AssetUnitName",
+    "AssetName",
+    "AssetURL",
+    "AssetMetadataHash",
+    "AssetManager",
+    "AssetReserve",
+    "AssetFreeze",
+    "AssetClawback",
+    "AssetCreator",
+];
+
+pub const APP_PARAMS_FIELDS: &[&str] = &[
+    "AppApprovalProgram",
+    "AppClearStateProgram",
+    "AppGlobalNumUint",
+    "AppGlobalNumByteSlice",
+    "AppLocalNumUint",
+    "AppLocalNumByteSlice",
+    "AppExtraProgramPages",
+    "AppCreator",
+    "AppAddress",
+];
+
+pub const ACCT_PARAMS_FIELDS: &[&str] = &[
+    "AcctBalance",
+    "AcctMinBalance",
+    "AcctAuthAddr",
+    "AcctTotalNumUint",
+    "AcctTotalNumByteSlice",
+    "AcctTotalExtraAppPages",
+    "AcctTotalAppsCreated",
+    "AcctTotalAppsOptedIn",
+    "AcctTotalAssetsCreated",
+    "AcctTotalAssets",
+    "AcctTotalBoxes",
+    "AcctTotalBoxBytes",
+];
+
+pub const ECDSA_CURVES: &[&str] = &["Secp256k1", "Secp256r1"];
+
+pub const BASE64_ENCODINGS: &[&str] = &["URLEncoding", "StdEncoding"];
+
+pub const JSON_REF_TYPES: &[&str] = &["JSONString", "JSONUint64", "JSONObject"];
+
+pub const VRF_STANDARDS: &[&str] = &["VrfAlgorand"];
+
+pub const BLOCK_FIELDS: &[&str] = &["BlkSeed", "BlkTimestamp"];
+
+/// Named integer constants accepted by the `int` pseudo-op
+pub const NAMED_INTS: &[(&str, u64)] = &[
+    ("NoOp", 0),
+    ("OptIn", 1),
+    ("CloseOut", 2),
+    ("ClearState", 3),
+    ("UpdateApplication", 4),
+    ("DeleteApplication", 5),
+    ("unknown", 0),
+    ("pay", 1),
+    ("keyreg", 2),
+    ("acfg", 3),
+    ("axfer", 4),
+    ("afrz", 5),
+    ("appl", 6),
+];
+
+macro_rules! op {
+    ($name:expr, $code:expr, $version:expr) => {
+        op!($name, $code, $version, [], 1)
+    };
+    ($name:expr, $code:expr, $version:expr, [$($imm:expr),*]) => {
+        op!($name, $code, $version, [$($imm),*], 1)
+    };
+    ($name:expr, $code:expr, $version:expr, [$($imm:expr),*], $cost:expr) => {
+        OpSpec {
+            name: $name,
+            code: $code,
+            immediates: &[$($imm),*],
+            version: $version,
+            cost: $cost,
+        }
+    };
+}
+
+pub const OPCODES: &[OpSpec] = &[
+    op!("err", 0x00, 1),
+    op!("sha256", 0x01, 1, [], 35),
+    op!("keccak256", 0x02, 1, [], 130),
+    op!("sha512_256", 0x03, 1, [], 45),
+    op!("ed25519verify", 0x04, 1, [], 1900),
+    op!("ecdsa_verify", 0x05, 5, [Field(ECDSA_CURVES)], 1700),
+    op!("ecdsa_pk_decompress", 0x06, 5, [Field(ECDSA_CURVES)], 650),
+    op!("ecdsa_pk_recover", 0x07, 5, [Field(ECDSA_CURVES)], 2000),
+    op!("+", 0x08, 1),
+    op!("-", 0x09, 1),
+    op!("/", 0x0a, 1),
+    op!("*", 0x0b, 1),
+    op!("<", 0x0c, 1),
+    op!(">", 0x0d, 1),
+    op!("<=", 0x0e, 1),
+    op!(">=", 0x0f, 1),
+    op!("&&", 0x10, 1),
+    op!("||", 0x11, 1),
+    op!("==", 0x12, 1),
+    op!("!=", 0x13, 1),
+    op!("!", 0x14, 1),
+    op!("len", 0x15, 1),
+    op!("itob", 0x16, 1),
+    op!("btoi", 0x17, 1),
+    op!("%", 0x18, 1),
+    op!("|", 0x19, 1),
+    op!("&", 0x1a, 1),
+    op!("^", 0x1b, 1),
+    op!("~", 0x1c, 1),
+    op!("mulw", 0x1d, 1),
+    op!("addw", 0x1e, 2),
+    op!("divmodw", 0x1f, 4, [], 20),
+    op!("intcblock", 0x20, 1, [VarUInts]),
+    op!("intc", 0x21, 1, [UInt8]),
+    op!("intc_0", 0x22, 1),
+    op!("intc_1", 0x23, 1),
+    op!("intc_2", 0x24, 1),
+    op!("intc_3", 0x25, 1),
+    op!("bytecblock", 0x26, 1, [Bytess]),
+    op!("bytec", 0x27, 1, [UInt8]),
+    op!("bytec_0", 0x28, 1),
+    op!("bytec_1", 0x29, 1),
+    op!("bytec_2", 0x2a, 1),
+    op!("bytec_3", 0x2b, 1),
+    op!("arg", 0x2c, 1, [UInt8]),
+    op!("arg_0", 0x2d, 1),
+    op!("arg_1", 0x2e, 1),
+    op!("arg_2", 0x2f, 1),
+    op!("arg_3", 0x30, 1),
+    op!("txn", 0x31, 1, [Field(TXN_FIELDS)]),
+    op!("global", 0x32, 1, [Field(GLOBAL_FIELDS)]),
+    op!("gtxn", 0x33, 1, [UInt8, Field(TXN_FIELDS)]),
+    op!("load", 0x34, 1, [UInt8]),
+    op!("store", 0x35, 1, [UInt8]),
+    op!("txna", 0x36, 2, [Field(TXN_FIELDS), UInt8]),
+    op!("gtxna", 0x37, 2, [UInt8, Field(TXN_FIELDS), UInt8]),
+    op!("gtxns", 0x38, 3, [Field(TXN_FIELDS)]),
+    op!("gtxnsa", 0x39, 3, [Field(TXN_FIELDS), UInt8]),
+    op!("gload", 0x3a, 4, [UInt8, UInt8]),
+    op!("gloads", 0x3b, 4, [UInt8]),
+    op!("gaid", 0x3c, 4, [UInt8]),
+    op!("gaids", 0x3d, 4),
+    op!("loads", 0x3e, 5),
+    op!("stores", 0x3f, 5),
+    op!("bnz", 0x40, 1, [Label]),
+    op!("bz", 0x41, 2, [Label]),
+    op!("b", 0x42, 2, [Label]),
+    op!("return", 0x43, 2),
+    op!("assert", 0x44, 3),
+    op!("bury", 0x45, 8, [UInt8]),
+    op!("popn", 0x46, 8, [UInt8]),
+    op!("dupn", 0x47, 8, [UInt8]),
+    op!("pop", 0x48, 1),
+    op!("dup", 0x49, 1),
+    op!("dup2", 0x4a, 2),
+    op!("dig", 0x4b, 3, [UInt8]),
+    op!("swap", 0x4c, 3),
+    op!("select", 0x4d, 3),
+    op!("cover", 0x4e, 5, [UInt8]),
+    op!("uncover", 0x4f, 5, [UInt8]),
+    op!("concat", 0x50, 2),
+    op!("substring", 0x51, 2, [UInt8, UInt8]),
+    op!("substring3", 0x52, 2),
+    op!("getbit", 0x53, 3),
+    op!("setbit", 0x54, 3),
+    op!("getbyte", 0x55, 3),
+    op!("setbyte", 0x56, 3),
+    op!("extract", 0x57, 5, [UInt8, UInt8]),
+    op!("extract3", 0x58, 5),
+    op!("extract_uint16", 0x59, 5),
+    op!("extract_uint32", 0x5a, 5),
+    op!("extract_uint64", 0x5b, 5),
+    op!("replace2", 0x5c, 7, [UInt8]),
+    op!("replace3", 0x5d, 7),
+    op!("base64_decode", 0x5e, 7, [Field(BASE64_ENCODINGS)]),
+    op!("json_ref", 0x5f, 7, [Field(JSON_REF_TYPES)], 25),
+    op!("balance", 0x60, 2),
+    op!("app_opted_in", 0x61, 2),
+    op!("app_local_get", 0x62, 2),
+    op!("app_local_get_ex", 0x63, 2),
+    op!("app_global_get", 0x64, 2),
+    op!("app_global_get_ex", 0x65, 2),
+    op!("app_local_put", 0x66, 2),
+    op!("app_global_put", 0x67, 2),
+    op!("app_local_del", 0x68, 2),
+    op!("app_global_del", 0x69, 2),
+    op!("asset_holding_get", 0x70, 2, [Field(ASSET_HOLDING_FIELDS)]),
+    op!("asset_params_get", 0x71, 2, [Field(ASSET_PARAMS_FIELDS)]),
+    op!("app_params_get", 0x72, 5, [Field(APP_PARAMS_FIELDS)]),
+    op!("acct_params_get", 0x73, 6, [Field(ACCT_PARAMS_FIELDS)]),
+    op!("min_balance", 0x78, 3),
+    op!("pushbytes", 0x80, 3, [Bytes]),
+    op!("pushint", 0x81, 3, [VarUInt]),
+    op!("pushbytess", 0x82, 8, [Bytess]),
+    op!("pushints", 0x83, 8, [VarUInts]),
+    op!("ed25519verify_bare", 0x84, 7, [], 1900),
+    op!("callsub", 0x88, 4, [Label]),
+    op!("retsub", 0x89, 4),
+    op!("proto", 0x8a, 8, [UInt8, UInt8]),
+    op!("frame_dig", 0x8b, 8, [Int8]),
+    op!("frame_bury", 0x8c, 8, [Int8]),
+    op!("switch", 0x8d, 8, [Labels]),
+    op!("match", 0x8e, 8, [Labels]),
+    op!("shl", 0x90, 4),
+    op!("shr", 0x91, 4),
+    op!("sqrt", 0x92, 4, [], 4),
+    op!("bitlen", 0x93, 4),
+    op!("exp", 0x94, 4),
+    op!("expw", 0x95, 4, [], 10),
+    op!("bsqrt", 0x96, 6, [], 40),
+    op!("divw", 0x97, 6),
+    op!("sha3_256", 0x98, 7, [], 130),
+    op!("b+", 0xa0, 4, [], 10),
+    op!("b-", 0xa1, 4, [], 10),
+    op!("b/", 0xa2, 4, [], 20),
+    op!("b*", 0xa3, 4, [], 20),
+    op!("b<", 0xa4, 4),
+    op!("b>", 0xa5, 4),
+    op!("b<=", 0xa6, 4),
+    op!("b>=", 0xa7, 4),
+    op!("b==", 0xa8, 4),
+    op!("b!=", 0xa9, 4),
+    op!("b%", 0xaa, 4, [], 20),
+    op!("b|", 0xab, 4, [], 6),
+    op!("b&", 0xac, 4, [], 6),
+    op!("b^", 0xad, 4, [], 6),
+    op!("b~", 0xae, 4, [], 4),
+    op!("bzero", 0xaf, 4),
+    op!("log", 0xb0, 5),
+    op!("itxn_begin", 0xb1, 5),
+    op!("itxn_field", 0xb2, 5, [Field(TXN_FIELDS)]),
+    op!("itxn_submit", 0xb3, 5),
+    op!("itxn", 0xb4, 5, [Field(TXN_FIELDS)]),
+    op!("itxna", 0xb5, 5, [Field(TXN_FIELDS), UInt8]),
+    op!("itxn_next", 0xb6, 6),
+    op!("gitxn", 0xb7, 6, [UInt8, Field(TXN_FIELDS)]),
+    op!("gitxna", 0xb8, 6, [UInt8, Field(TXN_FIELDS), UInt8]),
+    op!("box_create", 0xb9, 8),
+    op!("box_extract", 0xba, 8),
+    op!("box_replace", 0xbb, 8),
+    op!("box_del", 0xbc, 8),
+    op!("box_len", 0xbd, 8),
+    op!("box_get", 0xbe, 8),
+    op!("box_put", 0xbf, 8),
+    op!("txnas", 0xc0, 5, [Field(TXN_FIELDS)]),
+    op!("gtxnas", 0xc1, 5, [UInt8, Field(TXN_FIELDS)]),
+    op!("gtxnsas", 0xc2, 5, [Field(TXN_FIELDS)]),
+    op!("args", 0xc3, 5),
+    op!("gloadss", 0xc4, 6),
+    op!("itxnas", 0xc5, 6, [Field(TXN_FIELDS)]),
+    op!("gitxnas", 0xc6, 6, [UInt8, Field(TXN_FIELDS)]),
+    op!("vrf_verify", 0xd0, 7, [Field(VRF_STANDARDS)], 5700),
+    op!("block", 0xd1, 7, [Field(BLOCK_FIELDS)]),
+];
+
+pub fn lookup(name: &str) -> Option<&'static OpSpec> {
+    OPCODES.iter().find(|spec| spec.name == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lookup, OPCODES};
+
+    #[test]
+    fn unique_codes() {
+        for (i, a) in OPCODES.iter().enumerate() {
+            for b in &OPCODES[i + 1..] {
+                assert_ne!(a.code, b.code, "{} and {}", a.name, b.name);
+                assert_ne!(a.name, b.name);
+            }
+        }
+    }
+
+    #[test]
+    fn test_lookup() {
+        assert_eq!(lookup("app_global_put").unwrap().code, 0x67);
+        assert!(lookup("app_global_set").is_none());
+    }
+}
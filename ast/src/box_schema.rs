@@ -0,0 +1,217 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{
+    expression::{box_storage::BoxOp, Expr},
+    typing::TypePrimitive,
+};
+
+// minimum balance the application account needs for each box, plus the per byte cost of its
+// name and contents
+pub const BOX_FLAT_MIN_BALANCE: u64 = 2500;
+pub const BOX_BYTE_MIN_BALANCE: u64 = 400;
+// each box reference in a transaction grants this many bytes of box I/O
+pub const BOX_IO_BUDGET: u64 = 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoxDef {
+    pub value_type: TypePrimitive,
+    pub size: u64,
+}
+
+#[derive(Default)]
+pub struct BoxSchema<'a> {
+    pub boxes: HashMap<&'a str, BoxDef>,
+}
+
+// What one path through a program needs from the transaction calling it
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoxUsage {
+    // boxes touched on the path with the number of references each one needs
+    pub references: Vec<(String, u64)>,
+    // minimum balance for the boxes the path may create
+    pub min_balance: u64,
+}
+
+// box name to whether the path may create it
+type BoxPath = BTreeMap<String, bool>;
+
+fn merge(a: &BoxPath, b: &BoxPath) -> BoxPath {
+    let mut merged = a.clone();
+    for (name, creates) in b {
+        *merged.entry(name.clone()).or_default() |= creates;
+    }
+    merged
+}
+
+fn product(a: Vec<BoxPath>, b: Vec<BoxPath>) -> Vec<BoxPath> {
+    let mut paths: Vec<BoxPath> = vec![];
+    for a in &a {
+        for b in &b {
+            let path = merge(a, b);
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+    }
+    paths
+}
+
+fn union(mut a: Vec<BoxPath>, b: Vec<BoxPath>) -> Vec<BoxPath> {
+    for path in b {
+        if !a.contains(&path) {
+            a.push(path);
+        }
+    }
+    a
+}
+
+// the distinct sets of boxes each path through the expression touches
+fn box_paths(expr: &Expr) -> Vec<BoxPath> {
+    match expr {
+        Expr::BoxStorage(storage) => vec![BoxPath::from([(
+            storage.name.clone(),
+            matches!(storage.op, BoxOp::Create | BoxOp::Put),
+        )])],
+        Expr::If(if_else) => union(box_paths(&if_else.0), box_paths(&if_else.1)),
+        Expr::Router(router) => box_paths(&router.dispatch()),
+        Expr::Cond(cond) => {
+            let tested = box_paths(&cond.0);
+            let otherwise = match &cond.2 {
+                Some(next) => box_paths(&Expr::Cond(next.clone())),
+                None => vec![BoxPath::new()],
+            };
+            union(
+                product(tested.clone(), box_paths(&cond.1)),
+                product(tested, otherwise),
+            )
+        }
+        _ => expr
+            .children()
+            .into_iter()
+            .fold(vec![BoxPath::new()], |paths, child| {
+                product(paths, box_paths(child))
+            }),
+    }
+}
+
+impl<'a> BoxSchema<'a> {
+    pub fn get(&self, name: &str) -> Option<&BoxDef> {
+        self.boxes.get(name)
+    }
+
+    pub fn min_balance(&self, name: &str) -> Option<u64> {
+        self.get(name)
+            .map(|def| BOX_FLAT_MIN_BALANCE + BOX_BYTE_MIN_BALANCE * (name.len() as u64 + def.size))
+    }
+
+    // Reports the box references and minimum balance for every distinct path through `expr`
+    pub fn usage(&self, expr: &Expr) -> Vec<BoxUsage> {
+        box_paths(expr)
+            .into_iter()
+            .filter(|path| !path.is_empty())
+            .map(|path| BoxUsage {
+                references: path
+                    .keys()
+                    .map(|name| {
+                        let size = self.get(name).map_or(0, |def| def.size);
+                        (name.clone(), size.div_ceil(BOX_IO_BUDGET).max(1))
+                    })
+                    .collect(),
+                min_balance: path
+                    .iter()
+                    .filter(|(_, creates)| **creates)
+                    .filter_map(|(name, _)| self.min_balance(name))
+                    .sum(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{
+        apply, binop, bytes,
+        expression::{
+            apply::Apply,
+            binary::Binary,
+            box_storage::{BoxOp, BoxStorage},
+            if_else::If,
+            primitive::Primitive,
+            seq::Seq,
+            txn::Txn,
+            Expr,
+        },
+        int, seq,
+        typing::TypePrimitive,
+    };
+
+    use super::{BoxDef, BoxSchema, BoxUsage};
+
+    fn storage(name: &str, op: BoxOp) -> Expr {
+        Expr::BoxStorage(BoxStorage {
+            name: name.to_string(),
+            op,
+        })
+    }
+
+    fn schema() -> BoxSchema<'static> {
+        BoxSchema {
+            boxes: HashMap::from([
+                (
+                    "counter",
+                    BoxDef {
+                        value_type: TypePrimitive::UInt64,
+                        size: 8,
+                    },
+                ),
+                (
+                    "blob",
+                    BoxDef {
+                        value_type: TypePrimitive::Byteslice,
+                        size: 3000,
+                    },
+                ),
+            ]),
+        }
+    }
+
+    #[test]
+    fn test_min_balance() {
+        assert_eq!(schema().min_balance("counter"), Some(2500 + 400 * (7 + 8)));
+        assert_eq!(schema().min_balance("missing"), None);
+    }
+
+    #[test]
+    fn test_usage() {
+        // if (Txn.NumAppArgs > 0) { blob = "..." } else { counter } ; counter = 1
+        let body = seq!(
+            apply!(
+                @fn Expr::If(Box::new(If(
+                    apply!(@fn storage("blob", BoxOp::Put); @arg bytes!(vec![0; 3000])),
+                    storage("counter", BoxOp::Get),
+                )));
+                @arg binop!((Expr::Txn(Txn::NumAppArgs)) > (int!(0)))
+            );
+            apply!(@fn storage("counter", BoxOp::Put); @arg int!(1));
+        );
+        let usage = schema().usage(&body);
+        let counter = 2500 + 400 * (7 + 8);
+        let blob = 2500 + 400 * (4 + 3000);
+        assert_eq!(
+            usage,
+            vec![
+                BoxUsage {
+                    references: vec![("blob".to_string(), 3), ("counter".to_string(), 1)],
+                    min_balance: blob + counter,
+                },
+                BoxUsage {
+                    references: vec![("counter".to_string(), 1)],
+                    min_balance: counter,
+                },
+            ]
+        );
+        assert!(schema().usage(&int!(1)).is_empty());
+    }
+}
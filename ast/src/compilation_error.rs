@@ -1,4 +1,6 @@
-use crate::{context::CompilationBinding, typing::TypeError};
+use crate::{
+    abi::AbiType, assembly::AssemblyError, context::CompilationBinding, typing::TypeError,
+};
 use std::string::FromUtf8Error;
 
 use thiserror::Error;
@@ -11,8 +13,57 @@ pub enum CompilationError {
     BytesStringParse(#[from] FromUtf8Error),
     #[error("Out of scratch space")]
     OutOfScratchSpace,
+    #[error("Out of frame space, functions have at most 128 locals")]
+    OutOfFrameSpace,
+    #[error("{0} is a variable of an enclosing function")]
+    CapturedVariable(String),
+    #[error("{0} is a function, not a value")]
+    NotAValue(String),
+    #[error(
+        "Function {0} calls itself, recursion requires version 8, program declares version {1}"
+    )]
+    RecursiveFunction(String, u64),
     #[error("Missing stack")]
     MissingStack,
     #[error("Attempt to assign to constant expression: {0:?}")]
     ConstantAssignment(CompilationBinding),
+    #[error("Invalid byte width {0}, must be between 1 and 64")]
+    InvalidByteWidth(u8),
+    #[error("{0} requires version {1}, program declares version {2}")]
+    UnsupportedOpcode(String, u64, u64),
+    #[error("Box {0} is not declared in the box schema")]
+    UnknownBox(String),
+    #[error("Box {0} holds {1} bytes, value has {2}")]
+    BoxSizeMismatch(String, u64, u64),
+    #[error("Access to box {0} reaches byte {1}, box holds {2}")]
+    BoxOutOfBounds(String, u64, u64),
+    #[error("Group index {0} is out of range, groups hold at most 16 transactions")]
+    GroupIndexOutOfRange(u64),
+    #[error("Event {0} is not declared")]
+    UnknownEvent(String),
+    #[error("Template variable {0} is not declared")]
+    UnknownTemplate(String),
+    #[error("Program has a loop without a bound, its cost cannot be checked against the budget")]
+    UnboundedCost,
+    #[error("Program may cost {0}, budget is {1}")]
+    BudgetExceeded(u64, u64),
+    #[error("Method {0} has the selector of another method")]
+    DuplicateSelector(String),
+    #[error("Cannot {0} values of ARC-4 type {1}")]
+    UnsupportedAbiOperation(String, AbiType),
+    #[error("Assembly failed: {0}")]
+    Assembly(#[from] AssemblyError),
+    #[error(
+        "Programs take {0} bytes but at most {1} fit, largest contributors: {}",
+        format_contributors(.2)
+    )]
+    ProgramTooLarge(usize, usize, Vec<(String, usize)>),
+}
+
+fn format_contributors(contributors: &[(String, usize)]) -> String {
+    contributors
+        .iter()
+        .map(|(name, size)| format!("{name} ({size} bytes)"))
+        .collect::<Vec<_>>()
+        .join(", ")
 }
@@ -1,6 +1,18 @@
-use std::rc::Rc;
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
 
-use crate::typing::TypeEnum;
+use crate::{
+    assembly::opcode::lookup,
+    box_schema::BoxSchema,
+    compilation_error::CompilationError,
+    cost::Region,
+    event::Event,
+    expression::var::Var,
+    typing::{TypeEnum, TypeError, TypePrimitive},
+    MAX_TEAL_VERSION,
+};
 
 #[derive(Clone)]
 pub struct Scope<'a, K: PartialEq, V> {
@@ -39,23 +51,208 @@ impl<'a, K: PartialEq, V> Scope<'a, K, V> {
     }
 }
 
+// What runs a program, which decides the ops it may use
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Mode {
+    #[default]
+    Application,
+    // a logic signature approving transactions
+    Signature,
+}
+
 #[derive(Default)]
 pub struct TypeContext<'a> {
     pub bind_scope: Rc<Scope<'a, String, TypeEnum>>,
     pub global_scope: Rc<Scope<'a, String, TypeEnum>>,
     pub local_scope: Rc<Scope<'a, String, TypeEnum>>,
+    pub boxes: Option<&'a BoxSchema<'a>>,
+    pub events: &'a [Event],
+    // declared template variables and their types
+    pub templates: &'a [(String, TypePrimitive)],
+    pub mode: Mode,
+}
+
+impl<'a> TypeContext<'a> {
+    // fails for ops logic signatures cannot use
+    pub fn require_application(&self, op: &str) -> Result<(), TypeError> {
+        match self.mode {
+            Mode::Application => Ok(()),
+            Mode::Signature => Err(TypeError::ApplicationOnly(op.to_string())),
+        }
+    }
 }
 
-#[derive(Default)]
 pub struct CompilationContext<'a> {
     pub scope: Scope<'a, String, CompilationBinding>,
     pub scratch_id: u8,
+    pub version: u64,
+    pub boxes: Option<&'a BoxSchema<'a>>,
+    pub events: &'a [Event],
+    pub templates: &'a [(String, TypePrimitive)],
+    // the function being compiled, `None` at the top level of the program
+    pub function: Option<FunctionContext<'a>>,
+    // the loops and functions compiled so far, for estimating the cost of the program
+    pub regions: Option<&'a RefCell<Vec<Region>>>,
+}
+
+// A function body being compiled. From version 8 its variables live in its frame, which
+// keeps recursive calls apart; before that they take scratch slots like everything else.
+#[derive(Clone, Copy)]
+pub struct FunctionContext<'a> {
+    // the label id of the function, telling its frame apart from those of enclosing functions
+    pub id: usize,
+    pub frame: bool,
+    // the next free frame local
+    pub next_local: u8,
+    // the most variables in use at once: frame locals reserved on entry, or the scratch slots
+    // the function takes
+    pub used: &'a Cell<u8>,
+}
+
+// stands in for a load of a stack binding until its depth is known
+pub(crate) fn stack_placeholder(id: usize) -> String {
+    format!("%stack {id}")
+}
+
+// frame_dig and frame_bury take a signed byte
+const MAX_FRAME_LOCALS: usize = i8::MAX as usize + 1;
+
+impl<'a> CompilationContext<'a> {
+    // fails for compiled ops the declared version does not have
+    pub(crate) fn require_ops(&self, ops: &[impl AsRef<str>]) -> Result<(), CompilationError> {
+        for op in ops {
+            let op = op.as_ref();
+            let name = op.split_whitespace().next().unwrap_or(op);
+            if let Some(spec) = lookup(name).filter(|spec| spec.version > self.version) {
+                return Err(CompilationError::UnsupportedOpcode(
+                    name.to_string(),
+                    spec.version,
+                    self.version,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn record(&self, region: Region) {
+        if let Some(regions) = self.regions {
+            regions.borrow_mut().push(region);
+        }
+    }
+
+    // Reserves `n` variables for the scope of the returned context: frame locals inside
+    // functions compiled with frames, consecutive scratch slots otherwise
+    pub fn allocate(
+        &self,
+        n: usize,
+    ) -> Result<(Vec<CompilationBinding>, CompilationContext<'a>), CompilationError> {
+        if let Some(function @ FunctionContext { frame: true, .. }) = self.function {
+            let first = function.next_local as usize;
+            if first + n > MAX_FRAME_LOCALS {
+                return Err(CompilationError::OutOfFrameSpace);
+            }
+            let next = (first + n) as u8;
+            function.used.set(function.used.get().max(next));
+            let bindings = (first..first + n)
+                .map(|i| CompilationBinding::FrameVar(function.id, i as i8))
+                .collect();
+            let context = CompilationContext {
+                scope: self.scope.clone(),
+                function: Some(FunctionContext {
+                    next_local: next,
+                    ..function
+                }),
+                ..*self
+            };
+            return Ok((bindings, context));
+        }
+
+        let first = self.scratch_id as usize;
+        if first + n > u8::MAX as usize {
+            return Err(CompilationError::OutOfScratchSpace);
+        }
+        let next = (first + n) as u8;
+        if let Some(function) = self.function {
+            function.used.set(function.used.get().max(next));
+        }
+        let bindings = (first..first + n)
+            .map(|i| CompilationBinding::ScratchVar(i as u8))
+            .collect();
+        let context = CompilationContext {
+            scope: self.scope.clone(),
+            scratch_id: next,
+            ..*self
+        };
+        Ok((bindings, context))
+    }
+
+    fn binding(&self, identifier: &str) -> Result<&CompilationBinding, CompilationError> {
+        let binding = self
+            .scope
+            .get(&identifier.to_string())
+            // should never happen if type checking is run before compilation
+            .ok_or_else(|| TypeError::UnboundIdentifier(Var::Bind(identifier.to_string())))?;
+        match binding {
+            CompilationBinding::FrameVar(id, _) if self.function.map(|f| f.id) != Some(*id) => {
+                Err(CompilationError::CapturedVariable(identifier.to_string()))
+            }
+            binding => Ok(binding),
+        }
+    }
+
+    // pushes the value of a bound variable
+    pub fn load(&self, identifier: &str) -> Result<String, CompilationError> {
+        Ok(match self.binding(identifier)? {
+            CompilationBinding::ScratchVar(i) => format!("load {i}"),
+            CompilationBinding::FrameVar(_, i) => format!("frame_dig {i}"),
+            CompilationBinding::Replacement(s) => s.to_string(),
+            CompilationBinding::Stack(id) => stack_placeholder(*id),
+            CompilationBinding::Subroutine { .. } => {
+                return Err(CompilationError::NotAValue(identifier.to_string()))
+            }
+        })
+    }
+
+    // pops the top of the stack into a bound variable
+    pub fn store(&self, identifier: &str) -> Result<String, CompilationError> {
+        match self.binding(identifier)? {
+            CompilationBinding::ScratchVar(i) => Ok(format!("store {i}")),
+            CompilationBinding::FrameVar(_, i) => Ok(format!("frame_bury {i}")),
+            binding => Err(CompilationError::ConstantAssignment(binding.to_owned())),
+        }
+    }
+}
+
+impl<'a> Default for CompilationContext<'a> {
+    fn default() -> Self {
+        Self {
+            scope: Scope::default(),
+            scratch_id: 0,
+            version: MAX_TEAL_VERSION,
+            boxes: None,
+            events: &[],
+            templates: &[],
+            function: None,
+            regions: None,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum CompilationBinding {
     ScratchVar(u8),
+    // a variable in the frame of the function with the label id, arguments below the frame
+    // pointer and locals above it
+    FrameVar(usize, i8),
     Replacement(String),
+    // a value kept on the stack, loads are placeholders resolved once the depth is known
+    Stack(usize),
+    // a user function; `enclosing` while compiling its own body, where calling it recurses
+    Subroutine {
+        id: usize,
+        arity: usize,
+        enclosing: bool,
+    },
 }
 
 #[cfg(test)]
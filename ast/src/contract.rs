@@ -1,8 +1,328 @@
-use crate::{program::Program, struct_def::StructDef};
+use std::collections::HashMap;
+
+use crate::{
+    box_schema::{BoxSchema, BoxUsage},
+    compilation_error::CompilationError,
+    event::Event,
+    expression::{constant::OnComplete, router::Method, template::TemplateValue, Expr},
+    program::{CompiledProgram, Program},
+    struct_def::StructDef,
+    typing::{TypeError, TypePrimitive},
+};
+
+pub const PROGRAM_PAGE_SIZE: usize = 2048;
+pub const MAX_EXTRA_PROGRAM_PAGES: usize = 3;
 
 pub struct Contract<'a> {
     pub schema_global: StructDef<'a>,
     pub schema_local: StructDef<'a>,
+    pub schema_box: BoxSchema<'a>,
+    pub events: Vec<Event>,
+    // template variables filled in at deploy time, with their types
+    pub templates: Vec<(String, TypePrimitive)>,
     pub txn_approval: Program,
     pub txn_clear: Program,
 }
+
+pub struct CompiledContract {
+    pub approval: CompiledProgram,
+    pub clear: CompiledProgram,
+    pub extra_pages: usize,
+    // box references and minimum balance for each path through the approval program
+    pub box_usage: Vec<BoxUsage>,
+    // the events the contract may log, for indexers to decode them
+    pub events: Vec<Event>,
+    // the ARC-4 methods of the approval program
+    pub methods: Vec<Method>,
+    // the on-completion actions the router lets bare calls use
+    pub bare_actions: Vec<OnComplete>,
+    // state keys and their types, sorted by key
+    pub global_state: Vec<(String, TypePrimitive)>,
+    pub local_state: Vec<(String, TypePrimitive)>,
+    pub box_state: Vec<(String, TypePrimitive)>,
+    // the declared template variables, see `substitute`
+    pub templates: Vec<(String, TypePrimitive)>,
+}
+
+fn sorted_state<'a>(
+    fields: impl Iterator<Item = (&'a &'a str, &'a TypePrimitive)>,
+) -> Vec<(String, TypePrimitive)> {
+    let mut state = fields
+        .map(|(key, t)| (key.to_string(), t.clone()))
+        .collect::<Vec<_>>();
+    state.sort_by(|(a, _), (b, _)| a.cmp(b));
+    state
+}
+
+// approval and clear share the pages, each extra page adds another 2048 bytes
+fn extra_pages(
+    approval: &CompiledProgram,
+    clear: &CompiledProgram,
+) -> Result<usize, CompilationError> {
+    let size = approval.size() + clear.size();
+    let extra_pages = size.div_ceil(PROGRAM_PAGE_SIZE).saturating_sub(1);
+    if extra_pages > MAX_EXTRA_PROGRAM_PAGES {
+        let mut contributors = [("approval", approval), ("clear", clear)]
+            .iter()
+            .flat_map(|(name, program)| {
+                program
+                    .contributions
+                    .iter()
+                    .map(move |(op, size)| (format!("{name} {op}"), *size))
+            })
+            .collect::<Vec<_>>();
+        contributors.sort_by(|(_, a), (_, b)| b.cmp(a));
+        contributors.truncate(5);
+        return Err(CompilationError::ProgramTooLarge(
+            size,
+            PROGRAM_PAGE_SIZE * (1 + MAX_EXTRA_PROGRAM_PAGES),
+            contributors,
+        ));
+    }
+    Ok(extra_pages)
+}
+
+impl CompiledContract {
+    // Fills in the template variables of both programs. Every value must belong to a
+    // declared variable, every placeholder needs a value of the declared type.
+    pub fn substitute(
+        mut self,
+        values: &HashMap<String, TemplateValue>,
+    ) -> Result<CompiledContract, CompilationError> {
+        if let Some(name) = values
+            .keys()
+            .find(|name| !self.templates.iter().any(|(declared, _)| declared == *name))
+        {
+            return Err(TypeError::UnboundTemplate(name.clone()).into());
+        }
+        self.approval = self.approval.substitute(values)?;
+        self.clear = self.clear.substitute(values)?;
+        self.extra_pages = extra_pages(&self.approval, &self.clear)?;
+        Ok(self)
+    }
+}
+
+impl<'a> Contract<'a> {
+    pub fn compile(&self) -> Result<CompiledContract, CompilationError> {
+        let boxes = Some(&self.schema_box);
+        let approval = self
+            .txn_approval
+            .assemble_with(boxes, &self.events, &self.templates)?;
+        let clear = self
+            .txn_clear
+            .assemble_with(boxes, &self.events, &self.templates)?;
+        let extra_pages = extra_pages(&approval, &clear)?;
+
+        // the actions the router's guards let through, a program without a router is meant
+        // for plain application calls
+        let (methods, bare_actions) = match &self.txn_approval.body {
+            Expr::Router(router) => (
+                router.methods.clone(),
+                match router.bare {
+                    Some(_) => router.bare_actions.clone(),
+                    None => vec![],
+                },
+            ),
+            _ => (vec![], vec![OnComplete::NoOp]),
+        };
+
+        Ok(CompiledContract {
+            approval,
+            clear,
+            extra_pages,
+            box_usage: self.schema_box.usage(&self.txn_approval.body),
+            events: self.events.clone(),
+            methods,
+            bare_actions,
+            global_state: sorted_state(self.schema_global.fields.iter()),
+            local_state: sorted_state(self.schema_local.fields.iter()),
+            box_state: sorted_state(
+                self.schema_box
+                    .boxes
+                    .iter()
+                    .map(|(name, def)| (name, &def.value_type)),
+            ),
+            templates: self.templates.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{
+        apply, binop,
+        box_schema::{BoxDef, BoxSchema, BoxUsage},
+        bytes,
+        compilation_error::CompilationError,
+        expression::{
+            apply::Apply,
+            binary::Binary,
+            box_storage::{BoxOp, BoxStorage},
+            primitive::Primitive,
+            seq::Seq,
+            template::{Template, TemplateValue},
+            txn::Txn,
+            Expr,
+        },
+        int,
+        program::Program,
+        seq,
+        struct_def::StructDef,
+        typing::{TypeError, TypePrimitive},
+    };
+
+    use super::Contract;
+
+    fn contract_with_approval(body: Expr) -> Contract<'static> {
+        Contract {
+            schema_global: StructDef::default(),
+            schema_local: StructDef::default(),
+            schema_box: BoxSchema::default(),
+            events: vec![],
+            templates: vec![],
+            txn_approval: Program {
+                version: 5,
+                body,
+                budget: None,
+            },
+            txn_clear: Program::default(),
+        }
+    }
+
+    #[test]
+    fn test_sizes() {
+        let compiled = contract_with_approval(int!(1)).compile().unwrap();
+        assert_eq!(compiled.approval.bytecode, vec![0x05, 0x81, 0x01]);
+        assert_eq!(compiled.clear.bytecode, vec![0x08, 0x81, 0x00]);
+        assert_eq!(compiled.extra_pages, 0);
+    }
+
+    #[test]
+    fn test_extra_pages() {
+        // pushbytes with a 3000 byte constant needs 3 + 3000 bytes of code
+        let compiled = contract_with_approval(bytes!(vec![b'a'; 3000]))
+            .compile()
+            .unwrap();
+        assert_eq!(compiled.approval.size(), 3004);
+        assert_eq!(compiled.extra_pages, 1);
+    }
+
+    #[test]
+    fn test_too_large() {
+        let body = seq!(
+            bytes!(vec![b'a'; 4000]);
+            bytes!(vec![b'b'; 3000]);
+            bytes!(vec![b'c'; 2000]);
+            int!(1);
+        );
+        match contract_with_approval(body).compile() {
+            Err(CompilationError::ProgramTooLarge(size, max, contributors)) => {
+                assert_eq!(max, 8192);
+                assert!(size > max);
+                assert_eq!(contributors[0].0, "approval byte");
+            }
+            _ => panic!("expected ProgramTooLarge"),
+        }
+    }
+
+    #[test]
+    fn test_boxes() {
+        let mut contract = contract_with_approval(seq!(
+            apply!(@fn Expr::BoxStorage(BoxStorage {
+                name: "owner".to_string(),
+                op: BoxOp::Put,
+            }); @arg Expr::Txn(Txn::Sender));
+            int!(1);
+        ));
+        contract.schema_box = BoxSchema {
+            boxes: HashMap::from([(
+                "owner",
+                BoxDef {
+                    value_type: TypePrimitive::Byteslice,
+                    size: 32,
+                },
+            )]),
+        };
+        // boxes need version 8
+        assert!(contract.compile().is_err());
+        contract.txn_approval.version = 8;
+        let compiled = contract.compile().unwrap();
+        assert_eq!(
+            compiled.box_usage,
+            vec![BoxUsage {
+                references: vec![("owner".to_string(), 1)],
+                min_balance: 2500 + 400 * (5 + 32),
+            }]
+        );
+        assert!(compiled.approval.teal.contains("box_put"));
+    }
+
+    #[test]
+    fn test_templates() {
+        let mut contract = contract_with_approval(binop!(
+            (binop!((Expr::Txn(Txn::Fee)) <= (Expr::Template(Template("FEE".to_string())))))
+                && (binop!(
+                    (Expr::Txn(Txn::Sender)) == (Expr::Template(Template("CREATOR".to_string())))
+                ))
+        ));
+        contract.templates = vec![
+            ("FEE".to_string(), TypePrimitive::UInt64),
+            ("CREATOR".to_string(), TypePrimitive::Byteslice),
+        ];
+        let compiled = contract.compile().unwrap();
+        // placeholders sit in the constant blocks as zero values
+        assert_eq!(
+            compiled.approval.bytecode,
+            [
+                0x05, 0x20, 0x01, 0x00, 0x26, 0x01, 0x00, // blocks
+                0x31, 0x01, 0x22, 0x0e, 0x31, 0x00, 0x28, 0x12, 0x10,
+            ]
+        );
+        assert_eq!(compiled.approval.templates(), vec!["FEE", "CREATOR"]);
+
+        let values = |fee: TemplateValue| {
+            HashMap::from([
+                ("FEE".to_string(), fee),
+                ("CREATOR".to_string(), TemplateValue::Bytes(vec![0xaa; 32])),
+            ])
+        };
+        assert!(matches!(
+            compiled.approval.substitute(&values(TemplateValue::Bytes(vec![]))),
+            Err(TypeError::TemplateTypeMismatch(name, TypePrimitive::UInt64)) if name == "FEE"
+        ));
+        assert!(matches!(
+            compiled.approval.substitute(&HashMap::new()),
+            Err(TypeError::MissingTemplateValue(name)) if name == "FEE"
+        ));
+        let mut unknown = values(TemplateValue::UInt64(1000));
+        unknown.insert("ASSET".to_string(), TemplateValue::UInt64(1));
+
+        let compiled = match compiled.substitute(&unknown) {
+            Err(CompilationError::TypeCheck(TypeError::UnboundTemplate(name))) => {
+                assert_eq!(name, "ASSET");
+                contract.compile().unwrap()
+            }
+            _ => panic!("expected UnboundTemplate"),
+        };
+        let substituted = compiled
+            .substitute(&values(TemplateValue::UInt64(1000)))
+            .unwrap();
+        let mut expected = vec![0x05, 0x20, 0x01, 0xe8, 0x07, 0x26, 0x01, 0x20];
+        expected.extend([0xaa; 32]);
+        expected.extend([0x31, 0x01, 0x22, 0x0e, 0x31, 0x00, 0x28, 0x12, 0x10]);
+        assert_eq!(substituted.approval.bytecode, expected);
+        assert!(substituted.approval.templates().is_empty());
+        assert!(substituted.approval.teal.contains("int 1000\n"));
+        assert!(substituted
+            .approval
+            .teal
+            .contains(&format!("byte 0x{}\n", "aa".repeat(32))));
+        // the instructions moved with the grown blocks
+        assert_eq!(
+            substituted.approval.lines.first_key_value(),
+            Some((&40, &2))
+        );
+    }
+}
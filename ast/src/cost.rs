@@ -0,0 +1,262 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::assembly::opcode::lookup;
+
+// The opcode budget of a single application call, calls in a group pool their budgets
+pub const APP_CALL_BUDGET: u64 = 700;
+
+// A loop or function body of a compiled program, recorded by the compiler under the labels
+// around it
+#[derive(Debug, Clone, PartialEq)]
+pub enum Region {
+    // runs at most `bound` times, its test up to the `bz` to `end` once more
+    Loop {
+        start: String,
+        end: String,
+        bound: Option<u64>,
+    },
+    Function {
+        start: String,
+        end: String,
+    },
+}
+
+impl Region {
+    fn labels(&self) -> (&str, &str) {
+        match self {
+            Region::Loop { start, end, .. } | Region::Function { start, end } => (start, end),
+        }
+    }
+}
+
+// A loop being walked, or the code around loops. Its test runs with one multiplier, its body
+// with the other once the test branched out to `end`.
+struct Level<'a> {
+    start: &'a str,
+    multiplier: u64,
+    body: Option<(&'a str, u64)>,
+}
+
+// Upper bound on the opcode cost of a compiled program: every instruction is counted as if
+// it runs, instructions inside a loop once per iteration of its bound and the loop's test once
+// more, function bodies once per call. Returns `None` when the program has a loop without a
+// bound, a recursive function, or a backward branch or a call the regions do not explain.
+pub fn estimate(teal: &str, regions: &[Region]) -> Option<u64> {
+    // function bodies by label, the program around them under the empty label
+    let mut costs: Regions = HashMap::new();
+    let outer = || Level {
+        start: "",
+        multiplier: 1,
+        body: None,
+    };
+    let mut open = vec![("", vec![outer()])];
+    let mut labels = HashSet::new();
+    for line in teal.lines() {
+        let mut tokens = line.split_whitespace();
+        let Some(first) = tokens.next() else {
+            continue;
+        };
+        if let Some(label) = first.strip_suffix(':') {
+            labels.insert(label);
+            let region = regions.iter().find(|region| {
+                let (start, end) = region.labels();
+                start == label || end == label
+            });
+            let (_, levels) = open.last_mut().unwrap();
+            match region {
+                Some(Region::Loop { start, end, bound }) if start == label => {
+                    let multiplier = levels.last().unwrap().multiplier;
+                    levels.push(Level {
+                        start,
+                        multiplier: multiplier * (bound.as_ref()? + 1),
+                        body: Some((end, multiplier * bound.as_ref()?)),
+                    });
+                }
+                Some(Region::Loop { .. }) if levels.len() > 1 => {
+                    levels.pop();
+                }
+                Some(Region::Function { start, .. }) if start == label => {
+                    open.push((label, vec![outer()]));
+                }
+                Some(Region::Function { .. }) if open.len() > 1 => {
+                    open.pop();
+                }
+                _ => {}
+            }
+            continue;
+        }
+        if first.starts_with("#pragma") || first.starts_with("//") {
+            continue;
+        }
+        let (label, levels) = open.last_mut().unwrap();
+        let (cost, calls) = costs.entry(label).or_default();
+        let target = tokens.next();
+        match (first, target) {
+            ("callsub", Some(target)) => {
+                let is_function = regions.iter().any(
+                    |region| matches!(region, Region::Function { start, .. } if start == target),
+                );
+                if !is_function {
+                    return None;
+                }
+                calls.push((target, levels.last().unwrap().multiplier));
+            }
+            // only the loops being walked branch back
+            ("b" | "bz" | "bnz", Some(target))
+                if labels.contains(target) && !levels.iter().any(|level| level.start == target) =>
+            {
+                return None;
+            }
+            _ => {}
+        }
+        let level = levels.last_mut().unwrap();
+        // constants are assembled into intc/bytec/push ops, all of which cost 1
+        let op_cost = lookup(first).map_or(1, |spec| spec.cost);
+        *cost += op_cost * level.multiplier;
+        // the test ends with the branch out of the loop
+        if let (Some((end, multiplier)), "bz") = (level.body, first) {
+            if target == Some(end) {
+                level.multiplier = multiplier;
+                level.body = None;
+            }
+        }
+    }
+    region_cost("", &costs, &mut vec![])
+}
+
+// the cost of each region apart from calls, and the calls it makes with their multipliers
+type Regions<'a> = HashMap<&'a str, (u64, Vec<(&'a str, u64)>)>;
+
+fn region_cost<'a>(
+    label: &'a str,
+    regions: &Regions<'a>,
+    calling: &mut Vec<&'a str>,
+) -> Option<u64> {
+    if calling.contains(&label) {
+        return None;
+    }
+    let Some((cost, calls)) = regions.get(label) else {
+        return Some(0);
+    };
+    calling.push(label);
+    let mut total = *cost;
+    for (callee, multiplier) in calls {
+        total += region_cost(callee, regions, calling)? * multiplier;
+    }
+    calling.pop();
+    Some(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        compilation_error::CompilationError,
+        expression::{loops::Loop, primitive::Primitive, Expr},
+        int,
+        program::Program,
+    };
+
+    use super::{estimate, Region};
+
+    fn looping(bound: Option<u64>) -> Program {
+        Program {
+            version: 8,
+            body: Expr::Loop(Box::new(Loop::While {
+                test: int!(1),
+                body: Expr::Primitive(Primitive::Void),
+                bound,
+            })),
+            budget: Some(700),
+        }
+    }
+
+    fn loop_region(id: usize, bound: Option<u64>) -> Region {
+        Region::Loop {
+            start: format!("loop{id}"),
+            end: format!("endloop{id}"),
+            bound,
+        }
+    }
+
+    #[test]
+    fn test_estimate() {
+        assert_eq!(estimate("#pragma version 8\nint 1\nint 2\n+", &[]), Some(3));
+        assert_eq!(estimate("byte \"a\"\nsha256", &[]), Some(36));
+        let nested = [
+            "loop1:",
+            "int 1",
+            "bz endloop1",
+            "loop2:",
+            "int 1",
+            "bz endloop2",
+            "b loop2",
+            "endloop2:",
+            "b loop1",
+            "endloop1:",
+            "int 1",
+        ]
+        .join("\n");
+        let regions = [loop_region(1, Some(10)), loop_region(2, Some(3))];
+        // each test once more than its body
+        let inner = 10 * (4 * 2 + 3);
+        assert_eq!(estimate(&nested, &regions), Some(11 * 2 + inner + 10 + 1));
+        assert_eq!(
+            estimate(&nested, &[loop_region(1, Some(10)), loop_region(2, None)]),
+            None
+        );
+        // labels alone do not make a loop
+        assert_eq!(estimate(&nested, &[]), None);
+        assert_eq!(estimate(&nested, &[loop_region(2, Some(3))]), None);
+    }
+
+    #[test]
+    fn test_functions() {
+        let function = [
+            "b endfn_f_1",
+            "fn_f_1:",
+            "int 1",
+            "retsub",
+            "endfn_f_1:",
+            "loop2:",
+            "int 1",
+            "bz endloop2",
+            "callsub fn_f_1",
+            "b loop2",
+            "endloop2:",
+        ]
+        .join("\n");
+        let f = Region::Function {
+            start: "fn_f_1".to_string(),
+            end: "endfn_f_1".to_string(),
+        };
+        let regions = [f.clone(), loop_region(2, Some(3))];
+        // the branch, the test four times, then the call, the body and the branch back three
+        // times
+        assert_eq!(
+            estimate(&function, &regions),
+            Some(1 + 4 * 2 + 3 * (1 + 2 + 1))
+        );
+        assert_eq!(estimate(&function, &[loop_region(2, Some(3))]), None);
+        let recursive = "b endfn_f_1\nfn_f_1:\ncallsub fn_f_1\nretsub\nendfn_f_1:\ncallsub fn_f_1";
+        assert_eq!(estimate(recursive, &[f]), None);
+    }
+
+    #[test]
+    fn test_budget() {
+        // the test runs once more than the backward branch
+        assert_eq!(looping(Some(100)).assemble().unwrap().cost, Some(302));
+        assert!(matches!(
+            looping(Some(1000)).assemble(),
+            Err(CompilationError::BudgetExceeded(3002, 700))
+        ));
+        assert!(matches!(
+            looping(None).assemble(),
+            Err(CompilationError::UnboundedCost)
+        ));
+        let unchecked = Program {
+            budget: None,
+            ..looping(None)
+        };
+        assert_eq!(unchecked.assemble().unwrap().cost, None);
+    }
+}
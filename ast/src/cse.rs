@@ -0,0 +1,443 @@
+use crate::{
+    effects::{effects, Effects, Storage},
+    expression::{
+        binary::Binary,
+        bind::Bind,
+        crypto::Crypto,
+        loops::Loop,
+        primitive::Primitive,
+        query::Query,
+        var::{RVal, Var},
+        Expr,
+    },
+};
+
+// prefix of the variables the pass introduces, which no identifier of the program can start with
+const PREFIX: &str = "%cse";
+
+// How a child is evaluated when its parent is
+#[derive(Clone, Copy, PartialEq)]
+enum Edge {
+    // always, in the same scope
+    Same,
+    // always, under new bindings
+    Scope,
+    // only on some paths
+    Conditional,
+    // only when a function is called, the function cannot see variables bound around it
+    Isolated,
+}
+
+// parallel to `Expr::children`, which lists children in the order they are evaluated
+fn edges(expr: &Expr) -> Vec<Edge> {
+    use Edge::*;
+    let children = expr.children();
+    let mut edges = match expr {
+        // `a && b` may skip `b`, which is the argument applied first
+        Expr::Apply(apply) if matches!(apply.0, Expr::Binary(Binary::And | Binary::Or)) => {
+            vec![Conditional, Same]
+        }
+        Expr::Bind(bind) => match bind.as_ref() {
+            Bind::Const { .. } => vec![Scope],
+            _ => vec![Same, Scope],
+        },
+        Expr::Cond(_) => (0..children.len())
+            .map(|i| if i == 0 { Same } else { Conditional })
+            .collect(),
+        Expr::Function(_) => vec![Isolated, Scope],
+        Expr::Loop(l) => match l.as_ref() {
+            Loop::While { .. } => vec![Same, Conditional],
+            Loop::For { .. } => vec![Same, Same, Conditional],
+        },
+        Expr::If(_) | Expr::Router(_) => vec![Conditional; children.len()],
+        _ => vec![Same; children.len()],
+    };
+    // what follows a return or an assertion may never run
+    if let Some(i) =
+        (0..children.len()).find(|i| edges[*i] != Isolated && effects(children[*i]).halts())
+    {
+        for edge in &mut edges[i + 1..] {
+            if *edge != Isolated {
+                *edge = Conditional;
+            }
+        }
+    }
+    edges
+}
+
+// the identifiers bound by `expr` itself
+fn binders(expr: &Expr, found: &mut Vec<String>) {
+    match expr {
+        Expr::Bind(bind) => match bind.as_ref() {
+            Bind::Let { identifier, .. } | Bind::Const { identifier, .. } => {
+                found.push(identifier.clone())
+            }
+            Bind::Destructure { identifiers, .. } => found.extend(identifiers.iter().cloned()),
+        },
+        Expr::Loop(l) => {
+            if let Loop::For { identifier, .. } = l.as_ref() {
+                found.push(identifier.clone());
+            }
+        }
+        Expr::Router(router) => found.extend(
+            router
+                .methods
+                .iter()
+                .flat_map(|method| method.args.iter().map(|(name, _)| name.clone())),
+        ),
+        _ => {}
+    }
+}
+
+// the function applied by a chain of applications
+fn head(expr: &Expr) -> &Expr {
+    match expr {
+        Expr::Apply(apply) => head(&apply.0),
+        _ => expr,
+    }
+}
+
+// ops a reuse of the value saves, a state read takes two
+fn cost(expr: &Expr) -> usize {
+    match expr {
+        Expr::Apply(apply) => cost(&apply.0) + cost(&apply.1),
+        Expr::RVal(RVal(Var::Global(_) | Var::Local(_))) => 2,
+        _ => 1,
+    }
+}
+
+// An expression that only reads state, pushes a single value and costs more than reading a
+// variable. It may fail on some inputs, evaluating it once where it was evaluated anyway fails
+// all the same.
+fn is_candidate(expr: &Expr) -> bool {
+    let single_value = !matches!(
+        head(expr),
+        Expr::Wide(_)
+            | Expr::Crypto(Crypto::EcdsaPkRecover)
+            | Expr::Query(
+                Query::AssetHolding(_)
+                    | Query::AssetParams(_)
+                    | Query::AppParams(_)
+                    | Query::AcctParams(_)
+            )
+    );
+    single_value && cost(expr) > 1 && effects(expr).reads_or_fails()
+}
+
+struct Occurrences<'a> {
+    expr: &'a Expr,
+    count: usize,
+    // evaluated on every path through the region, so evaluating it up front adds no failure
+    anchored: bool,
+}
+
+#[derive(Default)]
+struct Region<'a> {
+    occurrences: Vec<Occurrences<'a>>,
+    // of the whole region, including the functions it calls
+    effects: Effects,
+    binders: Vec<String>,
+}
+
+impl<'a> Region<'a> {
+    fn visit(&mut self, expr: &'a Expr, unconditional: bool, applied: bool) {
+        binders(expr, &mut self.binders);
+        // a function being applied is not a value of its own
+        if !applied && is_candidate(expr) {
+            match self.occurrences.iter_mut().find(|o| o.expr == expr) {
+                Some(o) => {
+                    o.count += 1;
+                    o.anchored |= unconditional;
+                }
+                None => self.occurrences.push(Occurrences {
+                    expr,
+                    count: 1,
+                    anchored: unconditional,
+                }),
+            }
+        }
+        let is_apply = matches!(expr, Expr::Apply(_));
+        for (i, (child, edge)) in expr.children().into_iter().zip(edges(expr)).enumerate() {
+            if edge != Edge::Isolated {
+                let unconditional = unconditional && edge != Edge::Conditional;
+                self.visit(child, unconditional, is_apply && i == 1);
+            }
+        }
+    }
+
+    // the most costly subexpression worth binding once for the whole region
+    fn best(&self) -> Option<Expr> {
+        self.occurrences
+            .iter()
+            .filter(|o| o.count > 1 && o.anchored)
+            // binding costs a store and a load per use
+            .filter(|o| o.count * cost(o.expr) > cost(o.expr) + 1 + o.count)
+            .filter(|o| {
+                !effects(o.expr).reads().any(|storage| {
+                    self.effects.may_write(storage)
+                        || matches!(storage, Storage::Variable(i) if self.binders.contains(i))
+                })
+            })
+            .fold(None, |best: Option<&Occurrences>, o| match best {
+                Some(b) if (cost(b.expr), b.count) >= (cost(o.expr), o.count) => Some(b),
+                _ => Some(o),
+            })
+            .map(|o| o.expr.clone())
+    }
+}
+
+fn replace(expr: &mut Expr, target: &Expr, identifier: &str) {
+    if expr == target {
+        *expr = Expr::RVal(RVal(Var::Bind(identifier.to_string())));
+        return;
+    }
+    let edges = edges(expr);
+    for (child, edge) in expr.children_mut().into_iter().zip(edges) {
+        if edge != Edge::Isolated {
+            replace(child, target, identifier);
+        }
+    }
+}
+
+// binds repeated subexpressions of the region once, then moves on to the regions inside it
+fn eliminate_region(expr: &mut Expr, next_id: &mut usize) {
+    loop {
+        let mut region = Region {
+            effects: effects(expr),
+            ..Default::default()
+        };
+        region.visit(expr, true, false);
+        let Some(target) = region.best() else {
+            break;
+        };
+        let identifier = format!("{PREFIX}{next_id}");
+        *next_id += 1;
+        let mut body = std::mem::replace(expr, Expr::Primitive(Primitive::Void));
+        replace(&mut body, &target, &identifier);
+        *expr = Expr::Bind(Box::new(Bind::Let {
+            identifier,
+            value: target,
+            body,
+        }));
+    }
+    eliminate_within(expr, next_id);
+}
+
+fn eliminate_within(expr: &mut Expr, next_id: &mut usize) {
+    let edges = edges(expr);
+    for (child, edge) in expr.children_mut().into_iter().zip(edges) {
+        match edge {
+            Edge::Same => eliminate_within(child, next_id),
+            _ => eliminate_region(child, next_id),
+        }
+    }
+}
+
+// Common subexpression elimination: a pure subexpression repeated within a region of the
+// program, evaluated on every path through it, is evaluated once and bound to a variable.
+// State reads are only shared if nothing in the region writes the state.
+pub fn eliminate(expr: &Expr) -> Expr {
+    let mut expr = expr.clone();
+    eliminate_region(&mut expr, &mut 0);
+    expr
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        apply,
+        assembly::eval::{Eval, Value},
+        assign, bind_let, binop,
+        expression::{
+            apply::Apply,
+            assert::Assert,
+            binary::Binary,
+            bind::Bind,
+            function::Function,
+            if_else::If,
+            primitive::Primitive,
+            ret::Ret,
+            seq::Seq,
+            txn::Txn,
+            var::{LVal, RVal, Var},
+            Expr,
+        },
+        int,
+        program::Program,
+        r#if, seq,
+        typing::TypePrimitive,
+        val,
+    };
+
+    use super::eliminate;
+
+    fn doubled() -> Expr {
+        binop!((val!(@global counter)) * (int!(2)))
+    }
+
+    fn cse(i: usize) -> Expr {
+        Expr::RVal(RVal(Var::Bind(format!("%cse{i}"))))
+    }
+
+    #[test]
+    fn test_eliminate() {
+        let e = binop!((doubled()) + (doubled()));
+        assert_eq!(
+            eliminate(&e),
+            Expr::Bind(Box::new(Bind::Let {
+                identifier: "%cse0".to_string(),
+                value: doubled(),
+                body: binop!((cse(0)) + (cse(0))),
+            }))
+        );
+
+        let compiled = Program {
+            version: 8,
+            body: e,
+            budget: None,
+        }
+        .compile()
+        .unwrap();
+        assert_eq!(compiled.matches("app_global_get").count(), 1);
+
+        // single reads are cheaper than a variable
+        let e = binop!((val!(@global counter)) + (val!(@global counter)));
+        assert_eq!(eliminate(&e), e);
+    }
+
+    #[test]
+    fn test_writes() {
+        // a write to the same key keeps both reads
+        let e = seq!(
+            assign!(@global counter = doubled());
+            binop!((doubled()) > (int!(10)));
+        );
+        assert_eq!(eliminate(&e), e);
+
+        // other keys do not matter
+        let e = seq!(
+            assign!(@global other = doubled());
+            binop!((doubled()) > (int!(10)));
+        );
+        assert!(matches!(eliminate(&e), Expr::Bind(_)));
+    }
+
+    #[test]
+    fn test_branches() {
+        // only on some paths, binding it up front would evaluate it on the others too
+        let e = r#if!(
+            (val!(@global flag))
+            @then doubled();
+            @else binop!((doubled()) + (int!(1)))
+        );
+        assert_eq!(eliminate(&e), e);
+
+        // evaluated by the test anyway
+        let e = r#if!(
+            (binop!((doubled()) > (int!(10))))
+            @then doubled();
+            @else int!(0)
+        );
+        assert!(matches!(eliminate(&e), Expr::Bind(_)));
+
+        // repeated within a branch, bound there
+        let e = r#if!(
+            (val!(@global flag))
+            @then binop!((doubled()) + (doubled()));
+            @else int!(0)
+        );
+        let Expr::Apply(apply) = eliminate(&e) else {
+            panic!()
+        };
+        let Expr::If(if_else) = &apply.0 else {
+            panic!()
+        };
+        assert!(matches!(if_else.0, Expr::Bind(_)));
+    }
+
+    #[test]
+    fn test_halts() {
+        // assert(Txn.NumAppArgs); 100 / Txn.NumAppArgs * 3 + 100 / Txn.NumAppArgs * 3, the
+        // division is bound after the assertion
+        let scaled = || binop!((binop!((int!(100)) / (Expr::Txn(Txn::NumAppArgs)))) * (int!(3)));
+        let asserted = apply!(
+            @fn Expr::Assert(Assert(Some("no args".to_string())));
+            @arg Expr::Txn(Txn::NumAppArgs)
+        );
+        let e = seq!(asserted.clone(); binop!((scaled()) + (scaled())););
+        let Expr::Seq(seq) = eliminate(&e) else {
+            panic!()
+        };
+        assert_eq!(seq.0, asserted);
+        assert!(matches!(seq.1, Some(Expr::Bind(_))));
+
+        // let a = if (Txn.NumAppArgs == 0) { return 1 } else {}; ..., bound inside the let
+        let approve_without_args = r#if!(
+            (binop!((Expr::Txn(Txn::NumAppArgs)) == (int!(0))))
+            @then Expr::Ret(Ret::Approve);
+            @else Expr::Primitive(Primitive::Void)
+        );
+        let e = bind_let!(a = approve_without_args.clone(); binop!((scaled()) + (scaled())));
+        let Expr::Bind(bind) = eliminate(&e) else {
+            panic!()
+        };
+        let Bind::Let {
+            identifier,
+            value,
+            body,
+        } = *bind
+        else {
+            panic!()
+        };
+        assert_eq!(identifier, "a");
+        assert_eq!(value, approve_without_args);
+        assert!(matches!(body, Expr::Bind(_)));
+        // a call without args approves before dividing by zero
+        let compiled = Program {
+            version: 8,
+            body: e,
+            budget: None,
+        }
+        .compile()
+        .unwrap();
+        let mut eval = Eval::default();
+        eval.run(&compiled).unwrap();
+        assert_eq!(eval.stack, vec![Value::Uint(1)]);
+
+        // the same for an argument evaluated after one that may halt, bound after it
+        let e = binop!((approve_without_args.clone()) + (binop!((scaled()) + (scaled()))));
+        let Expr::Apply(apply) = eliminate(&e) else {
+            panic!()
+        };
+        assert_eq!(apply.1, approve_without_args);
+        assert!(matches!(apply.0, Expr::Bind(_)));
+    }
+
+    #[test]
+    fn test_scopes() {
+        // let x = 1; x * 3 / 2 + x * 3 / 2, bound inside the let
+        let scaled = || binop!((binop!((val!(@scratch x)) * (int!(3)))) / (int!(2)));
+        let e = bind_let!(x = int!(1); binop!((scaled()) + (scaled())));
+        let Expr::Bind(bind) = eliminate(&e) else {
+            panic!()
+        };
+        let Bind::Let {
+            identifier, body, ..
+        } = *bind
+        else {
+            panic!()
+        };
+        assert_eq!(identifier, "x");
+        assert!(matches!(body, Expr::Bind(_)));
+
+        // a function body only sees its own variables
+        let e = Expr::Function(Box::new(Function {
+            name: "f".to_string(),
+            params: vec![],
+            returns: TypePrimitive::UInt64,
+            def: doubled(),
+            body: doubled(),
+        }));
+        assert_eq!(eliminate(&e), e);
+    }
+}
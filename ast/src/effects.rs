@@ -0,0 +1,630 @@
+use std::{collections::BTreeSet, fmt};
+
+use crate::expression::{
+    arc4::Arc4,
+    binary::Binary,
+    bind::Bind,
+    box_storage::{BoxOp, BoxStorage},
+    byte_math::ByteMath,
+    bytes::Bytes,
+    cond::Cond,
+    global::Global,
+    itxn::Itxn,
+    loops::Loop,
+    unwrap::Unwrap,
+    var::{LVal, RVal, Var},
+    wide::Wide,
+    Expr,
+};
+
+// State an expression may read or write: application state by key, the ledger, the remaining
+// opcode budget or a variable of the program
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Storage {
+    Global(String),
+    Local(String),
+    Box(String),
+    // balances, holdings and parameters of accounts, assets and applications, and the results
+    // of the last inner transactions
+    Ledger,
+    // every op spends some of it
+    Budget,
+    Variable(String),
+}
+
+impl fmt::Display for Storage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Storage::Global(key) => write!(f, "global.{key}"),
+            Storage::Local(key) => write!(f, "local.{key}"),
+            Storage::Box(name) => write!(f, "box.{name}"),
+            Storage::Ledger => write!(f, "ledger"),
+            Storage::Budget => write!(f, "opcode budget"),
+            Storage::Variable(identifier) => write!(f, "var.{identifier}"),
+        }
+    }
+}
+
+// Something an expression does besides computing its value
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Effect {
+    Read(Storage),
+    Write(Storage),
+    Log,
+    InnerTxn,
+    // may end the program: a return, an approval or rejection, or an assertion
+    Halt,
+    // may fail on some inputs, e.g. a division by zero, an overflow or an index out of range
+    Fail,
+    // anything at all, e.g. a call to a function defined outside the analysed expression
+    Any,
+}
+
+impl fmt::Display for Effect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Effect::Read(storage) => write!(f, "reads {storage}"),
+            Effect::Write(storage) => write!(f, "writes {storage}"),
+            Effect::Log => write!(f, "logs"),
+            Effect::InnerTxn => write!(f, "submits inner transactions"),
+            Effect::Halt => write!(f, "halts"),
+            Effect::Fail => write!(f, "may fail"),
+            Effect::Any => write!(f, "may do anything"),
+        }
+    }
+}
+
+// The effects an expression may have when evaluated, on any path through it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Effects(BTreeSet<Effect>);
+
+impl Effects {
+    pub fn is_pure(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn contains(&self, effect: &Effect) -> bool {
+        self.0.contains(effect)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Effect> {
+        self.0.iter()
+    }
+
+    pub fn reads(&self) -> impl Iterator<Item = &Storage> {
+        self.0.iter().filter_map(|effect| match effect {
+            Effect::Read(storage) => Some(storage),
+            _ => None,
+        })
+    }
+
+    pub fn writes(&self) -> impl Iterator<Item = &Storage> {
+        self.0.iter().filter_map(|effect| match effect {
+            Effect::Write(storage) => Some(storage),
+            _ => None,
+        })
+    }
+
+    // Whether the value of `storage` may differ before and after evaluating the expression
+    pub fn may_write(&self, storage: &Storage) -> bool {
+        *storage == Storage::Budget
+            || self.contains(&Effect::Write(storage.clone()))
+            || self.contains(&Effect::Any)
+    }
+
+    // The effects below also hold for `Any`, the ones above list what is known
+    pub fn logs(&self) -> bool {
+        self.contains(&Effect::Log) || self.contains(&Effect::Any)
+    }
+
+    pub fn submits_inner(&self) -> bool {
+        self.contains(&Effect::InnerTxn) || self.contains(&Effect::Any)
+    }
+
+    pub fn halts(&self) -> bool {
+        self.contains(&Effect::Halt) || self.contains(&Effect::Any)
+    }
+
+    pub fn fails(&self) -> bool {
+        self.contains(&Effect::Fail) || self.contains(&Effect::Any)
+    }
+
+    // Whether it only reads state, so evaluating it more or less often cannot be observed
+    pub fn is_read_only(&self) -> bool {
+        self.iter().all(|effect| matches!(effect, Effect::Read(_)))
+    }
+
+    // Whether it at most reads state or fails, so evaluating it once where it was evaluated
+    // several times in a row cannot be observed
+    pub fn reads_or_fails(&self) -> bool {
+        self.iter()
+            .all(|effect| matches!(effect, Effect::Read(_) | Effect::Fail))
+    }
+
+    // Whether evaluating the two in the other order may change the outcome: one writes state
+    // the other touches, both do something observable in order, or one may fail where the
+    // other may end the program first. A failure rejects the call, undoing everything else.
+    pub fn interferes(&self, other: &Effects) -> bool {
+        let touches = |effects: &Effects, storage: &Storage| {
+            effects.contains(&Effect::Read(storage.clone())) || effects.may_write(storage)
+        };
+        let ordered =
+            |effects: &Effects| effects.logs() || effects.submits_inner() || effects.halts();
+        let anything = |a: &Effects, b: &Effects| a.contains(&Effect::Any) && !b.is_pure();
+        self.writes().any(|storage| touches(other, storage))
+            || other.writes().any(|storage| touches(self, storage))
+            || self.contains(&Effect::Read(Storage::Budget))
+            || other.contains(&Effect::Read(Storage::Budget))
+            || (ordered(self) && ordered(other))
+            || (self.fails() && other.halts())
+            || (other.fails() && self.halts())
+            || anything(self, other)
+            || anything(other, self)
+    }
+
+    fn insert(&mut self, effect: Effect) {
+        self.0.insert(effect);
+    }
+
+    fn extend(&mut self, other: Effects) {
+        self.0.extend(other.0);
+    }
+
+    // leaves out the variables bound around the expression, which cannot be seen outside it
+    fn unbind(mut self, bound: impl Fn(&str) -> bool) -> Effects {
+        self.0.retain(|effect| match effect {
+            Effect::Read(Storage::Variable(identifier))
+            | Effect::Write(Storage::Variable(identifier)) => !bound(identifier),
+            _ => true,
+        });
+        self
+    }
+}
+
+impl fmt::Display for Effects {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_pure() {
+            return write!(f, "pure");
+        }
+        let effects = self.iter().map(Effect::to_string).collect::<Vec<_>>();
+        write!(f, "{}", effects.join(", "))
+    }
+}
+
+// ops that reject some of their inputs
+fn fails(expr: &Expr) -> bool {
+    match expr {
+        Expr::Binary(op) => matches!(
+            op,
+            Binary::Add
+                | Binary::Sub
+                | Binary::Mul
+                | Binary::Div
+                | Binary::Mod
+                | Binary::ShiftLeft
+                | Binary::ShiftRight
+                | Binary::Exp
+        ),
+        // byte math takes at most 64 bytes
+        Expr::ByteMath(op) => *op != ByteMath::FromUInt64,
+        Expr::Bytes(op) => *op != Bytes::Len,
+        Expr::Wide(op) => matches!(op, Wide::ExpW | Wide::DivModW),
+        Expr::Arc4(op) => !matches!(op, Arc4::ToBytes(_) | Arc4::Pack(_) | Arc4::PackArray(..)),
+        Expr::Txn(field) | Expr::Itxn(Itxn(field)) => field.is_array(),
+        Expr::Gtxn(_) => true,
+        Expr::BoxStorage(BoxStorage { op, .. }) => {
+            matches!(op, BoxOp::Put | BoxOp::Replace | BoxOp::Extract)
+        }
+        _ => false,
+    }
+}
+
+// the effects `expr` has by itself, apart from its children
+fn own_effects(expr: &Expr) -> Effects {
+    let mut effects = Effects::default();
+    if fails(expr) {
+        effects.insert(Effect::Fail);
+    }
+    match expr {
+        Expr::RVal(RVal(Var::Global(key))) => {
+            effects.insert(Effect::Read(Storage::Global(key.clone())))
+        }
+        Expr::RVal(RVal(Var::Local(key))) => {
+            effects.insert(Effect::Read(Storage::Local(key.clone())))
+        }
+        Expr::LVal(LVal(Var::Global(key))) => {
+            effects.insert(Effect::Write(Storage::Global(key.clone())))
+        }
+        Expr::LVal(LVal(Var::Local(key))) => {
+            effects.insert(Effect::Write(Storage::Local(key.clone())))
+        }
+        Expr::RVal(RVal(Var::Bind(identifier))) => {
+            effects.insert(Effect::Read(Storage::Variable(identifier.clone())))
+        }
+        Expr::LVal(LVal(Var::Bind(identifier))) => {
+            effects.insert(Effect::Write(Storage::Variable(identifier.clone())))
+        }
+        Expr::Query(_) | Expr::Itxn(_) => effects.insert(Effect::Read(Storage::Ledger)),
+        Expr::Global(Global::OpcodeBudget) => effects.insert(Effect::Read(Storage::Budget)),
+        Expr::BoxStorage(BoxStorage { name, op }) => {
+            let storage = Storage::Box(name.clone());
+            match op {
+                BoxOp::Get | BoxOp::Extract | BoxOp::Len => effects.insert(Effect::Read(storage)),
+                BoxOp::Create | BoxOp::Put | BoxOp::Replace | BoxOp::Delete => {
+                    effects.insert(Effect::Write(storage))
+                }
+            }
+        }
+        Expr::Log(_) => effects.insert(Effect::Log),
+        Expr::InnerTxn(_) => {
+            effects.insert(Effect::InnerTxn);
+            effects.insert(Effect::Write(Storage::Ledger));
+        }
+        Expr::Ret(_) | Expr::Assert(_) | Expr::Unwrap(Unwrap::Assert) => {
+            effects.insert(Effect::Halt)
+        }
+        Expr::Router(router) => {
+            // methods return their values through the log, then the call is approved
+            if router.methods.iter().any(|method| method.returns.is_some()) {
+                effects.insert(Effect::Log);
+            }
+            effects.insert(Effect::Halt);
+        }
+        _ => {}
+    }
+    effects
+}
+
+struct Analysis<'a> {
+    // the effects of calling each user function in scope, the innermost last
+    functions: Vec<(&'a str, Effects)>,
+    // entries of the report, `None` while a definition is iterated to its fixed point
+    report: Option<Vec<(String, Effects)>>,
+    conds: usize,
+}
+
+impl<'a> Analysis<'a> {
+    fn record(&mut self, label: impl FnOnce() -> String) -> Option<usize> {
+        let report = self.report.as_mut()?;
+        report.push((label(), Effects::default()));
+        Some(report.len() - 1)
+    }
+
+    fn fill(&mut self, entry: Option<usize>, effects: &Effects) {
+        if let (Some(report), Some(i)) = (self.report.as_mut(), entry) {
+            report[i].1 = effects.clone();
+        }
+    }
+
+    fn silently(&mut self, expr: &'a Expr) -> Effects {
+        let report = self.report.take();
+        let effects = self.effects(expr);
+        self.report = report;
+        effects
+    }
+
+    fn effects(&mut self, expr: &'a Expr) -> Effects {
+        match expr {
+            Expr::Function(function) => {
+                // a call may reach further calls of the function, so the definition is analysed
+                // until its effects stop growing
+                // its variables are its own
+                let mut def = Effects::default();
+                loop {
+                    self.functions.push((&function.name, def.clone()));
+                    let next = self.silently(&function.def).unbind(|_| true);
+                    self.functions.pop();
+                    if next == def {
+                        break;
+                    }
+                    def = next;
+                }
+                self.functions.push((&function.name, def.clone()));
+                // once more for the entries of the report inside the definition
+                if let Some(entry) = self.record(|| format!("fn {}", function.name)) {
+                    self.effects(&function.def);
+                    self.fill(Some(entry), &def);
+                }
+                let body = self.effects(&function.body);
+                self.functions.pop();
+                body
+            }
+            Expr::Call(call) => self
+                .functions
+                .iter()
+                .rev()
+                .find(|(name, _)| *name == call.0)
+                .map(|(_, effects)| effects.clone())
+                .unwrap_or_else(|| Effects(BTreeSet::from([Effect::Any]))),
+            Expr::Bind(bind) => {
+                let (identifiers, value, body) = match bind.as_ref() {
+                    Bind::Let {
+                        identifier,
+                        value,
+                        body,
+                    } => (vec![identifier], self.effects(value), body),
+                    Bind::Const {
+                        identifier, body, ..
+                    } => (vec![identifier], Effects::default(), body),
+                    Bind::Destructure {
+                        identifiers,
+                        value,
+                        body,
+                    } => (identifiers.iter().collect(), self.effects(value), body),
+                };
+                let mut effects = value;
+                effects.extend(
+                    self.effects(body)
+                        .unbind(|identifier| identifiers.iter().any(|i| *i == identifier)),
+                );
+                effects
+            }
+            Expr::Loop(l) => match l.as_ref() {
+                Loop::For {
+                    identifier,
+                    start,
+                    end,
+                    body,
+                    ..
+                } => {
+                    let mut effects = self.effects(start);
+                    effects.extend(self.effects(end));
+                    effects.extend(self.effects(body).unbind(|i| i == identifier));
+                    effects
+                }
+                Loop::While { .. } => self.each_effects(expr),
+            },
+            Expr::Cond(cond) => {
+                let mut effects = Effects::default();
+                let mut id = None;
+                let mut arm = Some(cond.as_ref());
+                let mut i = 0;
+                while let Some(Cond(test, body, rest)) = arm {
+                    if self.report.is_some() && id.is_none() {
+                        self.conds += 1;
+                        id = Some(self.conds);
+                    }
+                    i += 1;
+                    // an arm is reached through its test
+                    let entry = self.record(|| format!("cond {} arm {i}", id.unwrap_or_default()));
+                    let mut arm_effects = self.effects(test);
+                    arm_effects.extend(self.effects(body));
+                    self.fill(entry, &arm_effects);
+                    effects.extend(arm_effects);
+                    arm = rest.as_deref();
+                }
+                effects
+            }
+            Expr::Router(router) => {
+                let mut effects = own_effects(expr);
+                for method in &router.methods {
+                    let entry = self.record(|| format!("method {}", method.name));
+                    let method_effects = self
+                        .effects(&method.body)
+                        .unbind(|identifier| method.args.iter().any(|(arg, _)| arg == identifier));
+                    self.fill(entry, &method_effects);
+                    effects.extend(method_effects);
+                }
+                if let Some(bare) = &router.bare {
+                    effects.extend(self.effects(bare));
+                }
+                effects
+            }
+            _ => self.each_effects(expr),
+        }
+    }
+
+    // the effects of `expr` itself and of each of its children
+    fn each_effects(&mut self, expr: &'a Expr) -> Effects {
+        let mut effects = own_effects(expr);
+        for child in expr.children() {
+            effects.extend(self.effects(child));
+        }
+        effects
+    }
+}
+
+// The effects evaluating `expr` may have. A call has the effects of the function's definition.
+pub fn effects(expr: &Expr) -> Effects {
+    Analysis {
+        functions: vec![],
+        report: None,
+        conds: 0,
+    }
+    .effects(expr)
+}
+
+// The effects of each user function, router method and `cond` arm in `expr`, in the order they
+// appear. Conds are numbered from 1 and their arms include the test that selects them.
+pub fn report(expr: &Expr) -> Vec<(String, Effects)> {
+    let mut analysis = Analysis {
+        functions: vec![],
+        report: Some(vec![]),
+        conds: 0,
+    };
+    analysis.effects(expr);
+    analysis.report.unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        apply, assign, bind_let, binop,
+        expression::{
+            apply::Apply,
+            assert::Assert,
+            binary::Binary,
+            bind::Bind,
+            box_storage::{BoxOp, BoxStorage},
+            cond::Cond,
+            function::{Call, Function},
+            global::Global,
+            if_else::If,
+            itxn::InnerTxn,
+            log::Log,
+            primitive::Primitive,
+            query::Query,
+            ret::Ret,
+            seq::Seq,
+            txn::Txn,
+            var::{LVal, RVal, Var},
+            Expr,
+        },
+        int, r#if,
+        typing::TypePrimitive,
+        val,
+    };
+
+    use super::{effects, report, Effect, Storage};
+
+    fn call(name: &str, arg: Expr) -> Expr {
+        apply!(@fn Expr::Call(Call(name.to_string())); @arg arg)
+    }
+
+    fn log(value: Expr) -> Expr {
+        apply!(@fn Expr::Log(Log::Raw); @arg value)
+    }
+
+    #[test]
+    fn test_effects() {
+        assert!(effects(&binop!((int!(1)) == (int!(2)))).is_pure());
+        // a variable is only seen where it is bound
+        let assigned = assign!(@scratch x = int!(1));
+        assert_eq!(effects(&assigned).to_string(), "writes var.x");
+        assert!(effects(&bind_let!(x = int!(0); assigned)).is_pure());
+
+        let e = assign!(@global counter = binop!((val!(@global counter)) + (int!(1))));
+        let found = effects(&e);
+        assert_eq!(
+            found.reads().collect::<Vec<_>>(),
+            vec![&Storage::Global("counter".to_string())]
+        );
+        assert_eq!(
+            found.writes().collect::<Vec<_>>(),
+            vec![&Storage::Global("counter".to_string())]
+        );
+        assert!(!found.halts() && !found.logs() && found.fails());
+        assert_eq!(
+            found.to_string(),
+            "reads global.counter, writes global.counter, may fail"
+        );
+
+        let boxed = Expr::Seq(Box::new(Seq(
+            apply!(
+                @fn Expr::BoxStorage(BoxStorage {
+                    name: "data".to_string(),
+                    op: BoxOp::Put,
+                });
+                @arg Expr::BoxStorage(BoxStorage {
+                    name: "other".to_string(),
+                    op: BoxOp::Get,
+                })
+            ),
+            Some(Expr::Ret(Ret::Approve)),
+        )));
+        let found = effects(&boxed);
+        assert!(found.contains(&Effect::Write(Storage::Box("data".to_string()))));
+        assert!(found.contains(&Effect::Read(Storage::Box("other".to_string()))));
+        assert!(found.halts());
+
+        let asserted = apply!(@fn Expr::Assert(Assert(None)); @arg int!(1));
+        assert!(effects(&asserted).halts());
+        assert!(effects(&log(int!(1))).interferes(&effects(&asserted)));
+        assert!(!effects(&log(int!(1))).interferes(&effects(&val!(@global counter))));
+        assert!(effects(&e).interferes(&effects(&val!(@global counter))));
+    }
+
+    #[test]
+    fn test_implicit() {
+        // a division may fail where an earlier return would have approved
+        let divided = binop!((int!(1)) / (Expr::Txn(Txn::NumAppArgs)));
+        let found = effects(&divided);
+        assert!(found.fails() && !found.halts() && !found.is_read_only());
+        assert!(found.interferes(&effects(&Expr::Ret(Ret::Approve))));
+        // a failure undoes a log
+        assert!(!found.interferes(&effects(&log(int!(1)))));
+
+        let balance = apply!(@fn Expr::Query(Query::Balance); @arg Expr::Txn(Txn::Sender));
+        let found = effects(&balance);
+        assert_eq!(found.to_string(), "reads ledger");
+        assert!(found.is_read_only());
+        let paid = effects(&Expr::InnerTxn(Box::new(InnerTxn(vec![vec![(
+            Txn::Amount,
+            int!(1),
+        )]]))));
+        assert!(paid.may_write(&Storage::Ledger) && paid.interferes(&found));
+
+        let budget = effects(&Expr::Global(Global::OpcodeBudget));
+        assert!(budget.interferes(&effects(&binop!((int!(1)) == (int!(2))))));
+
+        // nothing is known of a function defined elsewhere
+        let unknown = effects(&call("g", int!(1)));
+        assert_eq!(unknown.to_string(), "may do anything");
+        assert!(unknown.halts() && unknown.may_write(&Storage::Global("x".to_string())));
+        assert!(unknown.interferes(&effects(&val!(@global x))));
+        assert!(!unknown.interferes(&effects(&int!(1))));
+    }
+
+    #[test]
+    fn test_functions() {
+        // fn f(x) { if x { log(x); f(x - 1) } else { 0 } }; f(3)
+        let def = r#if!(
+            (val!(@scratch x))
+            @then Expr::Seq(Box::new(Seq(
+                log(val!(@scratch x)),
+                Some(call("f", binop!((val!(@scratch x)) - (int!(1))))),
+            )));
+            @else int!(0)
+        );
+        let e = Expr::Function(Box::new(Function {
+            name: "f".to_string(),
+            params: vec![("x".to_string(), None)],
+            returns: TypePrimitive::UInt64,
+            def,
+            body: call("f", int!(3)),
+        }));
+        assert!(effects(&e).logs());
+        let entries = report(&e);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].0, "fn f");
+        assert_eq!(entries[0].1.to_string(), "logs, may fail");
+
+        // an unused function has no effect where it is defined
+        let unused = Expr::Function(Box::new(Function {
+            body: int!(1),
+            ..match e {
+                Expr::Function(f) => *f,
+                _ => unreachable!(),
+            }
+        }));
+        assert!(effects(&unused).is_pure());
+    }
+
+    #[test]
+    fn test_report() {
+        // cond { global x == 1 => assert(1), log(1) => 0, 1 => cond { 1 => 1 } }
+        let inner = Expr::Cond(Box::new(Cond(int!(1), int!(1), None)));
+        let e = Expr::Cond(Box::new(Cond(
+            binop!((val!(@global x)) == (int!(1))),
+            apply!(@fn Expr::Assert(Assert(None)); @arg int!(1)),
+            Some(Box::new(Cond(
+                log(int!(1)),
+                int!(0),
+                Some(Box::new(Cond(int!(1), inner, None))),
+            ))),
+        )));
+        let entries = report(&e)
+            .into_iter()
+            .map(|(label, effects)| format!("{label}: {effects}"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            vec![
+                "cond 1 arm 1: reads global.x, halts",
+                "cond 1 arm 2: logs",
+                "cond 1 arm 3: pure",
+                "cond 2 arm 1: pure",
+            ]
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use crate::{
+    abi::{self, AbiType},
+    typing::{TypeError, TypePrimitive},
+};
+
+// An ARC-28 event, logged as its selector followed by its ARC-4 encoded fields
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub name: String,
+    fields: Vec<(String, AbiType)>,
+}
+
+// the ARC-4 type a field is encoded as, biguints are padded to 64 bytes
+fn arc4_type(t: &TypePrimitive) -> Option<AbiType> {
+    match t {
+        TypePrimitive::UInt64 => Some(AbiType::Uint(64)),
+        TypePrimitive::Byteslice => Some(AbiType::bytes()),
+        TypePrimitive::BigUInt => Some(AbiType::Uint(512)),
+        _ => None,
+    }
+}
+
+impl Event {
+    // fails on fields of a type without an ARC-4 encoding
+    pub fn new(name: &str, fields: Vec<(String, TypePrimitive)>) -> Result<Self, TypeError> {
+        let fields = fields
+            .into_iter()
+            .map(|(field, t)| match arc4_type(&t) {
+                Some(abi_type) => Ok((field, abi_type)),
+                None => Err(TypeError::UnsupportedEventField(name.to_string(), field, t)),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Event {
+            name: name.to_string(),
+            fields,
+        })
+    }
+
+    pub fn fields(&self) -> &[(String, AbiType)] {
+        &self.fields
+    }
+
+    // e.g. `Transfer(byte[],uint64)`
+    pub fn signature(&self) -> String {
+        let types = self
+            .fields
+            .iter()
+            .map(|(_, t)| t.to_string())
+            .collect::<Vec<_>>();
+        format!("{}({})", self.name, types.join(","))
+    }
+
+    pub fn selector(&self) -> [u8; 4] {
+        abi::selector(&self.signature())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::typing::{TypeError, TypePrimitive};
+
+    use super::Event;
+
+    #[test]
+    fn test_selector() {
+        let event = Event::new(
+            "Swapped",
+            vec![
+                ("a".to_string(), TypePrimitive::UInt64),
+                ("b".to_string(), TypePrimitive::UInt64),
+            ],
+        )
+        .unwrap();
+        assert_eq!(event.signature(), "Swapped(uint64,uint64)");
+        // from the ARC-28 specification
+        assert_eq!(event.selector(), [0x1c, 0xcb, 0xd9, 0x25]);
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let event = Event::new(
+            "Cleared",
+            vec![
+                ("at".to_string(), TypePrimitive::UInt64),
+                ("nothing".to_string(), TypePrimitive::Void),
+            ],
+        );
+        assert!(matches!(
+            event,
+            Err(TypeError::UnsupportedEventField(event, field, TypePrimitive::Void))
+                if event == "Cleared" && field == "nothing"
+        ));
+    }
+}
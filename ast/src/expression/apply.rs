@@ -27,6 +27,14 @@ impl Expression for Apply {
         context: &CompilationContext,
         prepared_stack: &mut Vec<String>,
     ) -> Result<String, CompilationError> {
+        if let Expr::Apply(inner) = &self.0 {
+            if let Expr::Binary(binary) = &inner.0 {
+                if let Some(compiled) = binary.compile_short_circuit(&self.1, &inner.1, context)? {
+                    return Ok(compiled);
+                }
+            }
+        }
+
         let arg = self.1.compile(context, prepared_stack)?;
         prepared_stack.push(arg);
         let f = self.0.compile(context, prepared_stack)?;
@@ -0,0 +1,610 @@
+use crate::{
+    abi::{layout, AbiType, Slot},
+    compilation_error::CompilationError,
+    context::{CompilationContext, TypeContext},
+    label::create_label_id,
+    typing::{TypeEnum, TypeError, TypePrimitive},
+    OP_SEPARATOR,
+};
+
+use super::Expression;
+
+const ARC4_VERSION: u64 = 5;
+
+// Converts between values and their ARC-4 encoding, and builds and takes apart encodings of
+// tuples and arrays
+#[derive(Debug, Clone, PartialEq)]
+pub enum Arc4 {
+    // (value) -> encoding, for types with a value
+    Encode(AbiType),
+    // (encoding) -> value, for types with a value
+    Decode(AbiType),
+    // (bytes) -> encoding, checking the length where it follows from the type
+    FromBytes(AbiType),
+    // (encoding) -> bytes
+    ToBytes(AbiType),
+    // (element encodings...) -> encoding of a tuple or static array
+    Pack(AbiType),
+    // (element encodings...) -> encoding of a dynamic array of this many elements
+    PackArray(AbiType, usize),
+    // (encoding) -> encoding of the element at a constant index of a tuple or static array
+    Field(AbiType, usize),
+    // (encoding, index) -> encoding of the element at the index of an array, failing when out
+    // of bounds
+    Element(AbiType),
+    // (encoding) -> number of elements of an array or bytes of a string
+    Length(AbiType),
+}
+
+fn ops(ops: &[&str]) -> Vec<String> {
+    ops.iter().map(|op| op.to_string()).collect()
+}
+
+fn unsupported(operation: &str, t: &AbiType) -> CompilationError {
+    CompilationError::UnsupportedAbiOperation(operation.to_string(), t.clone())
+}
+
+// stack: [head, tail, value] -> [head ++ value, tail]
+fn append_head() -> Vec<String> {
+    ops(&["uncover 2", "swap", "concat", "swap"])
+}
+
+// stack: [0 or 1] -> [encoding of the bool]
+fn encode_bool() -> Vec<String> {
+    ops(&["byte 0x00", "int 0", "uncover 2", "setbit"])
+}
+
+// stack: [bytes] -> [`size` bytes from `offset`]
+fn extract(offset: u64, size: u64) -> Vec<String> {
+    if offset <= 255 && (1..=255).contains(&size) {
+        vec![format!("extract {offset} {size}")]
+    } else {
+        vec![
+            format!("int {offset}"),
+            format!("int {size}"),
+            "extract3".to_string(),
+        ]
+    }
+}
+
+pub(crate) fn encode_ops(t: &AbiType) -> Result<Vec<String>, CompilationError> {
+    Ok(match t {
+        AbiType::Uint(64) | AbiType::Ufixed(64, _) => ops(&["itob"]),
+        AbiType::Uint(bits) | AbiType::Ufixed(bits, _) if *bits < 64 => {
+            let size = bits / 8;
+            vec![
+                "dup".to_string(),
+                format!("int {}", 1u64 << bits),
+                "<".to_string(),
+                "assert".to_string(),
+                "itob".to_string(),
+                format!("extract {} {size}", 8 - size),
+            ]
+        }
+        // big-endian, padded to the full width
+        AbiType::Uint(bits) | AbiType::Ufixed(bits, _) => {
+            let size = bits / 8;
+            vec![
+                "dup".to_string(),
+                "len".to_string(),
+                format!("int {size}"),
+                "<=".to_string(),
+                "assert".to_string(),
+                format!("int {size}"),
+                "bzero".to_string(),
+                "b|".to_string(),
+            ]
+        }
+        AbiType::Bool => encode_bool(),
+        AbiType::Byte => ops(&["dup", "int 256", "<", "assert", "itob", "extract 7 1"]),
+        AbiType::Address => ops(&["dup", "len", "int 32", "==", "assert"]),
+        // prefixed with the length as a uint16
+        AbiType::String => ops(&["dup", "len", "itob", "extract 6 2", "swap", "concat"]),
+        AbiType::DynamicArray(element) if **element == AbiType::Byte => {
+            encode_ops(&AbiType::String)?
+        }
+        _ => return Err(unsupported("encode", t)),
+    })
+}
+
+fn decode_ops(t: &AbiType) -> Result<Vec<String>, CompilationError> {
+    Ok(match t {
+        AbiType::Uint(bits) | AbiType::Ufixed(bits, _) if *bits <= 64 => ops(&["btoi"]),
+        AbiType::Uint(_) | AbiType::Ufixed(..) | AbiType::Address => vec![],
+        AbiType::Bool => ops(&["int 0", "getbit"]),
+        AbiType::Byte => ops(&["btoi"]),
+        AbiType::String => ops(&["extract 2 0"]),
+        AbiType::DynamicArray(element) if **element == AbiType::Byte => ops(&["extract 2 0"]),
+        _ => return Err(unsupported("decode", t)),
+    })
+}
+
+// checks what the type tells about the length of the encoding on top of the stack
+fn from_bytes_ops(t: &AbiType) -> Vec<String> {
+    let element_size = match t {
+        AbiType::String => Some(1),
+        AbiType::DynamicArray(element) => element.static_size(),
+        _ => None,
+    };
+    match (t.static_size(), element_size) {
+        (Some(size), _) => vec![
+            "dup".to_string(),
+            "len".to_string(),
+            format!("int {size}"),
+            "==".to_string(),
+            "assert".to_string(),
+        ],
+        // the length prefix, then as many elements as it says
+        (None, Some(size)) => {
+            let mut checked = ops(&["dup", "len", "dig 1", "int 0", "extract_uint16"]);
+            if t == &AbiType::DynamicArray(Box::new(AbiType::Bool)) {
+                checked.extend(ops(&["int 7", "+", "int 8", "/"]));
+            } else if size != 1 {
+                checked.extend([format!("int {size}"), "*".to_string()]);
+            }
+            checked.extend(ops(&["int 2", "+", "==", "assert"]));
+            checked
+        }
+        // element accesses check their offsets
+        (None, None) => vec![],
+    }
+}
+
+// the encoding of a tuple of elements of the given types, computed by `values`
+pub(crate) fn pack_ops(types: &[AbiType], values: Vec<String>) -> Vec<String> {
+    let (slots, head_size) = layout(types);
+    let dynamic = slots.iter().any(|slot| matches!(slot, Slot::Offset(_)));
+    let mut packed = vec![];
+    if dynamic {
+        // the head and the tail, dynamic values are appended to the tail
+        packed.extend(ops(&["byte \"\"", "byte \"\""]));
+    }
+    // whether the top of the stack holds a part of the head not added to it yet
+    let mut pending = false;
+    let mut started = false;
+    let mut commit = |packed: &mut Vec<String>, pending: &mut bool| {
+        if *pending {
+            if dynamic {
+                packed.extend(append_head());
+            } else if started {
+                packed.push("concat".to_string());
+            }
+            started = true;
+        }
+        *pending = false;
+    };
+    for (slot, value) in slots.into_iter().zip(values) {
+        match slot {
+            // packed into the byte of the previous bool
+            Slot::Bit(bit) if bit % 8 != 0 => {
+                packed.push(format!("int {}", bit % 8));
+                packed.push(value);
+                packed.extend(ops(&["int 0", "getbit", "setbit"]));
+            }
+            Slot::Bit(_) | Slot::Static(..) => {
+                commit(&mut packed, &mut pending);
+                packed.push(value);
+                pending = true;
+            }
+            // the offset is where the value starts, after the head and the tail so far
+            Slot::Offset(_) => {
+                commit(&mut packed, &mut pending);
+                packed.extend(ops(&["dup", "len"]));
+                packed.extend([format!("int {head_size}"), "+".to_string()]);
+                packed.extend(ops(&["itob", "extract 6 2"]));
+                packed.extend(append_head());
+                packed.push(value);
+                packed.push("concat".to_string());
+            }
+        }
+    }
+    commit(&mut packed, &mut pending);
+    if dynamic {
+        packed.push("concat".to_string());
+    } else if !started {
+        packed.push("byte \"\"".to_string());
+    }
+    packed
+}
+
+// stack: [encoding of a tuple] -> [encoding of its element at `index`]
+fn field_ops(types: &[AbiType], index: usize) -> Vec<String> {
+    let (slots, _) = layout(types);
+    match slots[index] {
+        Slot::Bit(bit) => {
+            let mut field = vec![format!("int {bit}"), "getbit".to_string()];
+            field.extend(encode_bool());
+            field
+        }
+        Slot::Static(offset, size) => extract(offset, size),
+        // the value ends where the next dynamic value starts, or with the tuple
+        Slot::Offset(offset) => {
+            let mut field = vec![
+                "dup".to_string(),
+                format!("int {offset}"),
+                "extract_uint16".to_string(),
+            ];
+            match slots[index + 1..].iter().find_map(|slot| match slot {
+                Slot::Offset(next) => Some(next),
+                _ => None,
+            }) {
+                Some(next) => field.extend([
+                    "dig 1".to_string(),
+                    format!("int {next}"),
+                    "extract_uint16".to_string(),
+                ]),
+                None => field.extend(ops(&["dig 1", "len"])),
+            }
+            field.push("substring3".to_string());
+            field
+        }
+    }
+}
+
+// stack: [encoding of an array, index] -> [encoding of the element at the index]
+fn element_ops(t: &AbiType) -> Result<Vec<String>, CompilationError> {
+    // dynamic arrays start with their length
+    let (element, n, base) = match t {
+        AbiType::StaticArray(element, n) => (element, Some(*n), 0),
+        AbiType::DynamicArray(element) => (element, None, 2),
+        _ => return Err(unsupported("index", t)),
+    };
+    // the number of elements, the array being `depth` deep in the stack
+    let length = |depth: usize| match n {
+        Some(n) => vec![format!("int {n}")],
+        None => vec![
+            format!("dig {depth}"),
+            "int 0".to_string(),
+            "extract_uint16".to_string(),
+        ],
+    };
+    let plus_base = |base: u64| match base {
+        0 => vec![],
+        _ => vec![format!("int {base}"), "+".to_string()],
+    };
+    let mut element_ops = ops(&["dup"]);
+    element_ops.extend(length(2));
+    element_ops.extend(ops(&["<", "assert"]));
+    match element.static_size() {
+        _ if **element == AbiType::Bool => {
+            element_ops.extend(plus_base(base * 8));
+            element_ops.push("getbit".to_string());
+            element_ops.extend(encode_bool());
+        }
+        Some(size) => {
+            element_ops.extend([format!("int {size}"), "*".to_string()]);
+            element_ops.extend(plus_base(base));
+            element_ops.extend([format!("int {size}"), "extract3".to_string()]);
+        }
+        // the head holds offsets, the element ends where the next one starts or with the array
+        None => {
+            let id = create_label_id();
+            // stack: [array, _, index] -> [array, _, start of the element]
+            let start = || {
+                let mut start = ops(&["int 2", "*"]);
+                start.extend(plus_base(base));
+                start.extend(ops(&["dig 2", "swap", "extract_uint16"]));
+                start.extend(plus_base(base));
+                start
+            };
+            element_ops.push("dup".to_string());
+            element_ops.extend(start());
+            element_ops.extend(ops(&["swap", "int 1", "+", "dup"]));
+            element_ops.extend(length(3));
+            element_ops.push("<".to_string());
+            element_ops.push(format!("bz arc4last{id}"));
+            element_ops.extend(start());
+            element_ops.push(format!("b arc4end{id}"));
+            element_ops.push(format!("arc4last{id}:"));
+            element_ops.extend(ops(&["pop", "dig 1", "len"]));
+            element_ops.push(format!("arc4end{id}:"));
+            element_ops.push("substring3".to_string());
+        }
+    }
+    Ok(element_ops)
+}
+
+fn length_ops(t: &AbiType) -> Result<Vec<String>, CompilationError> {
+    match t {
+        AbiType::String | AbiType::DynamicArray(_) => Ok(ops(&["int 0", "extract_uint16"])),
+        AbiType::StaticArray(_, n) => Ok(vec!["pop".to_string(), format!("int {n}")]),
+        _ => Err(unsupported("take the length of", t)),
+    }
+}
+
+impl Arc4 {
+    // the types of the arguments and of the result
+    fn signature(&self) -> Result<(Vec<TypeEnum>, TypeEnum), TypeError> {
+        let unsupported = |operation: &str, t: &AbiType| {
+            TypeError::UnsupportedAbiOperation(operation.to_string(), t.clone())
+        };
+        let encoded = |t: &AbiType| TypeEnum::Arc4(t.clone());
+        let bytes = TypeEnum::Simple(TypePrimitive::Byteslice);
+        let uint = TypeEnum::Simple(TypePrimitive::UInt64);
+        Ok(match self {
+            Arc4::Encode(t) => {
+                let value = t.value_type().ok_or_else(|| unsupported("encode", t))?;
+                (vec![TypeEnum::Simple(value)], encoded(t))
+            }
+            Arc4::Decode(t) => {
+                let value = t.value_type().ok_or_else(|| unsupported("decode", t))?;
+                (vec![encoded(t)], TypeEnum::Simple(value))
+            }
+            Arc4::FromBytes(t) => (vec![bytes], encoded(t)),
+            Arc4::ToBytes(t) => (vec![encoded(t)], bytes),
+            Arc4::Pack(t) => {
+                let elements = t.elements().ok_or_else(|| unsupported("pack", t))?;
+                (elements.iter().map(encoded).collect(), encoded(t))
+            }
+            Arc4::PackArray(element, n) => (
+                vec![encoded(element); *n],
+                TypeEnum::Arc4(AbiType::DynamicArray(Box::new(element.clone()))),
+            ),
+            Arc4::Field(t, index) => {
+                let elements = t.elements().ok_or_else(|| unsupported("index", t))?;
+                let element = elements
+                    .get(*index)
+                    .ok_or_else(|| TypeError::AbiIndexOutOfRange(t.clone(), *index))?;
+                (vec![encoded(t)], encoded(element))
+            }
+            Arc4::Element(t) => match t {
+                AbiType::StaticArray(element, _) | AbiType::DynamicArray(element) => {
+                    (vec![encoded(t), uint], encoded(element))
+                }
+                _ => return Err(unsupported("index", t)),
+            },
+            Arc4::Length(t) => match t {
+                AbiType::String | AbiType::StaticArray(..) | AbiType::DynamicArray(_) => {
+                    (vec![encoded(t)], uint)
+                }
+                _ => return Err(unsupported("take the length of", t)),
+            },
+        })
+    }
+}
+
+impl Expression for Arc4 {
+    fn resolve(&self, _: &TypeContext) -> Result<TypeEnum, TypeError> {
+        let (args, result) = self.signature()?;
+        Ok(args.into_iter().rev().fold(result, |result, arg| {
+            TypeEnum::Arrow(Box::new(arg), Box::new(result))
+        }))
+    }
+
+    fn compile(
+        &self,
+        context: &CompilationContext,
+        prepared_stack: &mut Vec<String>,
+    ) -> Result<String, CompilationError> {
+        if context.version < ARC4_VERSION {
+            return Err(CompilationError::UnsupportedOpcode(
+                "ARC-4 encoding".to_string(),
+                ARC4_VERSION,
+                context.version,
+            ));
+        }
+        let arity = match self {
+            Arc4::Pack(t) => t.elements().ok_or_else(|| unsupported("pack", t))?.len(),
+            Arc4::PackArray(_, n) => *n,
+            Arc4::Element(_) => 2,
+            _ => 1,
+        };
+        let mut args = vec![];
+        for _ in 0..arity {
+            args.push(prepared_stack.pop().ok_or(CompilationError::MissingStack)?);
+        }
+        let pieces = match self {
+            Arc4::Pack(t) => pack_ops(&t.elements().unwrap(), args),
+            Arc4::PackArray(element, n) => {
+                let mut pieces = pack_ops(&vec![element.clone(); *n], args);
+                pieces.push(format!("byte 0x{n:04x}"));
+                pieces.extend(ops(&["swap", "concat"]));
+                pieces
+            }
+            _ => {
+                let mut pieces = args;
+                pieces.extend(match self {
+                    Arc4::Encode(t) => encode_ops(t)?,
+                    Arc4::Decode(t) => decode_ops(t)?,
+                    Arc4::FromBytes(t) => from_bytes_ops(t),
+                    Arc4::ToBytes(_) => vec![],
+                    Arc4::Field(t, index) => {
+                        let elements = t.elements().ok_or_else(|| unsupported("index", t))?;
+                        if *index >= elements.len() {
+                            return Err(unsupported("index", t));
+                        }
+                        field_ops(&elements, *index)
+                    }
+                    Arc4::Element(t) => element_ops(t)?,
+                    Arc4::Length(t) => length_ops(t)?,
+                    Arc4::Pack(_) | Arc4::PackArray(..) => unreachable!(),
+                });
+                pieces
+            }
+        };
+        Ok(pieces.join(OP_SEPARATOR))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        abi::{codec::tests::vectors, encode, AbiType, AbiValue},
+        apply,
+        assembly::eval::{Eval, Value},
+        bytes,
+        context::TypeContext,
+        expression::{apply::Apply, byte_math::ByteMath, primitive::Primitive, Expr, Expression},
+        int,
+        typing::TypeEnum,
+    };
+
+    use super::Arc4;
+
+    fn arc4(op: Arc4) -> Expr {
+        Expr::Arc4(op)
+    }
+
+    fn from_bytes(t: &AbiType, encoded: Vec<u8>) -> Expr {
+        apply!(@fn arc4(Arc4::FromBytes(t.clone())); @arg bytes!(encoded))
+    }
+
+    // the value the expression leaves on the stack
+    fn run(e: &Expr) -> Result<Value, String> {
+        e.resolve(&TypeContext::default())
+            .map_err(|e| e.to_string())?;
+        let mut eval = Eval::default();
+        eval.run(&e.compile_raw().map_err(|e| e.to_string())?)?;
+        eval.stack.pop().ok_or_else(|| "empty stack".to_string())
+    }
+
+    #[test]
+    fn test_encode() {
+        let encode = |t: AbiType, value: Expr| {
+            let e = apply!(@fn arc4(Arc4::Encode(t.clone())); @arg value);
+            assert_eq!(
+                e.resolve(&TypeContext::default()).unwrap(),
+                TypeEnum::Arc4(t)
+            );
+            e.compile_raw().unwrap()
+        };
+        assert_eq!(encode(AbiType::Uint(64), int!(1)), "int 1\nitob");
+        assert_eq!(
+            encode(AbiType::Uint(16), int!(1)),
+            "int 1\ndup\nint 65536\n<\nassert\nitob\nextract 6 2"
+        );
+        assert_eq!(
+            encode(AbiType::String, bytes!(b"hi".to_vec())),
+            "byte \"hi\"\ndup\nlen\nitob\nextract 6 2\nswap\nconcat"
+        );
+        assert_eq!(
+            encode(AbiType::Bool, int!(1)),
+            "int 1\nbyte 0x00\nint 0\nuncover 2\nsetbit"
+        );
+        let run_encode =
+            |t: AbiType, value: Expr| run(&apply!(@fn arc4(Arc4::Encode(t)); @arg value));
+        assert_eq!(
+            run_encode(AbiType::Uint(16), int!(258)),
+            Ok(Value::Bytes(vec![1, 2]))
+        );
+        assert!(run_encode(AbiType::Uint(16), int!(65536)).is_err());
+        assert!(run_encode(AbiType::Byte, int!(256)).is_err());
+        assert!(run_encode(AbiType::Bool, int!(2)).is_err());
+        assert!(run_encode(AbiType::Address, bytes!(vec![0; 31])).is_err());
+        assert_eq!(
+            run_encode(
+                AbiType::Uint(128),
+                apply!(@fn Expr::ByteMath(ByteMath::FromBytes); @arg bytes!(vec![1, 2]))
+            ),
+            Ok(Value::Bytes([vec![0; 14], vec![1, 2]].concat()))
+        );
+        // compound types have no value to encode
+        let e = apply!(@fn arc4(Arc4::Encode(AbiType::Tuple(vec![]))); @arg int!(1));
+        assert!(e.resolve(&TypeContext::default()).is_err());
+    }
+
+    #[test]
+    fn test_decode() {
+        let decode = |t: AbiType, encoded: Vec<u8>| {
+            run(&apply!(@fn arc4(Arc4::Decode(t.clone())); @arg from_bytes(&t, encoded)))
+        };
+        assert_eq!(
+            decode(AbiType::String, b"\x00\x01a".to_vec()),
+            Ok(Value::Bytes(b"a".to_vec()))
+        );
+        assert_eq!(decode(AbiType::Uint(16), vec![1, 2]), Ok(Value::Uint(258)));
+        assert_eq!(decode(AbiType::Bool, vec![0x80]), Ok(Value::Uint(1)));
+        // lengths not matching the type fail
+        assert!(decode(AbiType::Uint(16), vec![1, 2, 3]).is_err());
+        assert!(decode(AbiType::String, b"\x00\x02a".to_vec()).is_err());
+        // encodings only decode as their own type
+        let e = apply!(@fn arc4(Arc4::Decode(AbiType::Uint(64))); @arg int!(1));
+        assert!(e.resolve(&TypeContext::default()).is_err());
+        let e = apply!(
+            @fn arc4(Arc4::Decode(AbiType::Uint(64)));
+            @arg from_bytes(&AbiType::Uint(32), vec![0; 4])
+        );
+        assert!(e.resolve(&TypeContext::default()).is_err());
+    }
+
+    #[test]
+    fn test_vectors() {
+        for (t, value, encoded) in vectors() {
+            let encoding = from_bytes(&t, encoded.clone());
+            assert_eq!(run(&encoding), Ok(Value::Bytes(encoded.clone())), "{t}");
+
+            let (types, values, pack) = match (&t, &value) {
+                (AbiType::Tuple(_) | AbiType::StaticArray(..), AbiValue::Tuple(values))
+                | (AbiType::Tuple(_) | AbiType::StaticArray(..), AbiValue::Array(values)) => {
+                    (t.elements().unwrap(), values, Arc4::Pack(t.clone()))
+                }
+                (AbiType::DynamicArray(element), AbiValue::Array(values)) => (
+                    vec![(**element).clone(); values.len()],
+                    values,
+                    Arc4::PackArray((**element).clone(), values.len()),
+                ),
+                _ => continue,
+            };
+            let elements = types
+                .iter()
+                .zip(values)
+                .map(|(t, value)| encode(t, value).unwrap())
+                .collect::<Vec<_>>();
+
+            // packing the elements gives the encoding
+            let packed = types.iter().zip(&elements).fold(
+                arc4(pack),
+                |packed, (t, element)| apply!(@fn packed; @arg from_bytes(t, element.clone())),
+            );
+            assert_eq!(
+                run(&packed),
+                Ok(Value::Bytes(encoded.clone())),
+                "packing {t}"
+            );
+
+            // and each element can be taken back out
+            for (i, element) in elements.iter().enumerate() {
+                let access = match &t {
+                    AbiType::Tuple(_) => {
+                        apply!(@fn arc4(Arc4::Field(t.clone(), i)); @arg encoding.clone())
+                    }
+                    _ => apply!(
+                        @fn arc4(Arc4::Element(t.clone()));
+                        @arg encoding.clone();
+                        @arg int!(i as u64)
+                    ),
+                };
+                assert_eq!(
+                    run(&access),
+                    Ok(Value::Bytes(element.clone())),
+                    "element {i} of {t}"
+                );
+            }
+            if !matches!(t, AbiType::Tuple(_)) {
+                let length = apply!(@fn arc4(Arc4::Length(t.clone())); @arg encoding.clone());
+                assert_eq!(run(&length), Ok(Value::Uint(elements.len() as u64)));
+                let out_of_bounds = apply!(
+                    @fn arc4(Arc4::Element(t.clone()));
+                    @arg encoding.clone();
+                    @arg int!(elements.len() as u64)
+                );
+                assert!(run(&out_of_bounds).is_err(), "index out of {t}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid() {
+        let t: AbiType = "(uint64,address,byte[])".parse().unwrap();
+        let field = |i| apply!(@fn arc4(Arc4::Field(t.clone(), i)); @arg from_bytes(&t, vec![]));
+        assert!(field(2).resolve(&TypeContext::default()).is_ok());
+        assert!(field(3).resolve(&TypeContext::default()).is_err());
+        // static sizes are checked
+        let t = AbiType::StaticArray(Box::new(AbiType::Uint(16)), 2);
+        assert!(run(&from_bytes(&t, vec![0; 3])).is_err());
+        // offsets past the end fail on access
+        let t: AbiType = "(string,bool)".parse().unwrap();
+        let e = apply!(@fn arc4(Arc4::Field(t.clone(), 0)); @arg from_bytes(&t, vec![0, 9, 0x80]));
+        assert!(run(&e).is_err());
+    }
+}
@@ -0,0 +1,101 @@
+use crate::{
+    compilation_error::CompilationError,
+    context::{CompilationContext, Mode, TypeContext},
+    typing::{TypeEnum, TypeError, TypePrimitive},
+    OP_SEPARATOR,
+};
+
+use super::{primitive::uint64_literal, Expression};
+
+const ARGS_VERSION: u64 = 5;
+
+// The argument at an index among those the logic signature was signed with
+#[derive(Debug, Clone, PartialEq)]
+pub struct Arg;
+
+impl Expression for Arg {
+    fn resolve(&self, context: &TypeContext) -> Result<TypeEnum, TypeError> {
+        if context.mode != Mode::Signature {
+            return Err(TypeError::SignatureOnly("arg".to_string()));
+        }
+        Ok(TypeEnum::Arrow(
+            Box::new(TypeEnum::Simple(TypePrimitive::UInt64)),
+            Box::new(TypeEnum::Simple(TypePrimitive::Byteslice)),
+        ))
+    }
+
+    fn compile(
+        &self,
+        context: &CompilationContext,
+        prepared_stack: &mut Vec<String>,
+    ) -> Result<String, CompilationError> {
+        let index = prepared_stack.pop().ok_or(CompilationError::MissingStack)?;
+        if let Some(i) = uint64_literal(&index).filter(|i| *i <= u8::MAX as u64) {
+            return Ok(format!("arg {i}"));
+        }
+        if context.version < ARGS_VERSION {
+            return Err(CompilationError::UnsupportedOpcode(
+                "args".to_string(),
+                ARGS_VERSION,
+                context.version,
+            ));
+        }
+        Ok([index, "args".to_string()].join(OP_SEPARATOR))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        apply,
+        compilation_error::CompilationError,
+        context::{CompilationContext, Mode, TypeContext},
+        expression::{apply::Apply, primitive::Primitive, txn::Txn, Expr, Expression},
+        int,
+        typing::{TypeEnum, TypeError, TypePrimitive},
+    };
+
+    use super::Arg;
+
+    #[test]
+    fn test_arg() {
+        let signature = TypeContext {
+            mode: Mode::Signature,
+            ..Default::default()
+        };
+        let arg = apply!(@fn Expr::Arg(Arg); @arg int!(2));
+        assert!(matches!(
+            arg.resolve(&signature),
+            Ok(TypeEnum::Simple(TypePrimitive::Byteslice))
+        ));
+        assert!(matches!(
+            arg.resolve(&TypeContext::default()),
+            Err(TypeError::SignatureOnly(op)) if op == "arg"
+        ));
+        assert_eq!(
+            arg.compile(&CompilationContext::default(), &mut vec![])
+                .unwrap(),
+            "arg 2"
+        );
+    }
+
+    #[test]
+    fn test_args() {
+        let arg = apply!(@fn Expr::Arg(Arg); @arg Expr::Txn(Txn::GroupIndex));
+        assert_eq!(
+            arg.compile(&CompilationContext::default(), &mut vec![])
+                .unwrap(),
+            "txn GroupIndex\nargs"
+        );
+        assert!(matches!(
+            arg.compile(
+                &CompilationContext {
+                    version: 4,
+                    ..Default::default()
+                },
+                &mut vec![]
+            ),
+            Err(CompilationError::UnsupportedOpcode(op, 5, 4)) if op == "args"
+        ));
+    }
+}
@@ -0,0 +1,102 @@
+use crate::{
+    compilation_error::CompilationError,
+    context::{CompilationContext, TypeContext},
+    typing::{TypeEnum, TypeError, TypePrimitive},
+    OP_SEPARATOR,
+};
+
+use super::Expression;
+
+const ASSERT_VERSION: u64 = 3;
+
+// (condition), fails the program when the condition is zero. The message ends up in the
+// assembled program's comments under the pc of the `assert`, so a failing pc can be
+// explained.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assert(pub Option<String>);
+
+impl Expression for Assert {
+    fn resolve(&self, _: &TypeContext) -> Result<TypeEnum, TypeError> {
+        Ok(TypeEnum::Arrow(
+            Box::new(TypeEnum::Simple(TypePrimitive::UInt64)),
+            Box::new(TypeEnum::Simple(TypePrimitive::Void)),
+        ))
+    }
+
+    fn compile(
+        &self,
+        context: &CompilationContext,
+        prepared_stack: &mut Vec<String>,
+    ) -> Result<String, CompilationError> {
+        if context.version < ASSERT_VERSION {
+            return Err(CompilationError::UnsupportedOpcode(
+                "assert".to_string(),
+                ASSERT_VERSION,
+                context.version,
+            ));
+        }
+        let condition = prepared_stack.pop().ok_or(CompilationError::MissingStack)?;
+        let assert = match &self.0 {
+            // comments end at the line
+            Some(message) => format!("assert // {}", message.replace(['\r', '\n'], " ")),
+            None => "assert".to_string(),
+        };
+        Ok([condition, assert].join(OP_SEPARATOR))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        apply,
+        assembly::assemble,
+        binop, bytes,
+        context::TypeContext,
+        expression::seq::Seq,
+        expression::{
+            apply::Apply, binary::Binary, primitive::Primitive, txn::Txn, Expr, Expression,
+        },
+        int,
+        program::Program,
+        seq,
+    };
+
+    use super::Assert;
+
+    fn assert(message: &str, condition: Expr) -> Expr {
+        apply!(@fn Expr::Assert(Assert(Some(message.to_string()))); @arg condition)
+    }
+
+    #[test]
+    fn test() {
+        let e = assert("fee too low", binop!((Expr::Txn(Txn::Fee)) >= (int!(1000))));
+        e.resolve(&TypeContext::default()).unwrap();
+        assert_eq!(
+            e.compile_raw().unwrap(),
+            "txn Fee\nint 1000\n>=\nassert // fee too low"
+        );
+        assert!(assert("", bytes!(b"no".to_vec()))
+            .resolve(&TypeContext::default())
+            .is_err());
+    }
+
+    #[test]
+    fn test_messages() {
+        let program = Program {
+            version: 8,
+            body: seq!(
+                assert("first", int!(1));
+                assert("second\nline", int!(2));
+                int!(1);
+            ),
+            budget: None,
+        };
+        let compiled = program.assemble().unwrap();
+        // version and `intcblock 1` take 4 bytes, then `intc_0` and `pushint 2`
+        assert_eq!(
+            compiled.messages.into_iter().collect::<Vec<_>>(),
+            vec![(5, "first".to_string()), (8, "second line".to_string())]
+        );
+        assert!(assemble("#pragma version 2\nint 1\nassert").is_err());
+    }
+}
@@ -1,11 +1,28 @@
 use crate::{
     compilation_error::CompilationError,
     context::{CompilationContext, TypeContext},
+    effects::effects,
+    label::create_label_id,
     typing::{TypeEnum, TypeError, TypePrimitive, TypeVar},
     OP_SEPARATOR,
 };
 
-use super::Expression;
+use super::{Expr, Expression};
+
+// Upper bound on the size of an operand that is evaluated unconditionally rather than
+// branched around, a short-circuit costs about four extra ops. Only operands that cannot fail
+// qualify, `x != 0 && 10 / x > 1` must not divide when `x` is 0.
+const CHEAP_OPERAND_SIZE: usize = 4;
+
+fn is_cheap(expr: &Expr) -> bool {
+    fn size(expr: &Expr) -> usize {
+        match expr {
+            Expr::Apply(apply) => size(&apply.0) + size(&apply.1),
+            _ => 1,
+        }
+    }
+    size(expr) <= CHEAP_OPERAND_SIZE && effects(expr).is_read_only()
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Binary {
@@ -15,6 +32,19 @@ pub enum Binary {
     GreaterThanEquals,
     LessThan,
     LessThanEquals,
+    And,
+    Or,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    BitOr,
+    BitAnd,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+    Exp,
 }
 
 fn op(l: String, s: &str, r: String) -> Result<String, CompilationError> {
@@ -24,6 +54,41 @@ fn op(l: String, s: &str, r: String) -> Result<String, CompilationError> {
     ))
 }
 
+impl Binary {
+    // `lhs && rhs` and `lhs || rhs` only evaluate `rhs` when `lhs` doesn't already decide the
+    // result, unless both operands are pure and cheap enough to evaluate with `&&`/`||`
+    pub(crate) fn compile_short_circuit(
+        &self,
+        lhs: &Expr,
+        rhs: &Expr,
+        context: &CompilationContext,
+    ) -> Result<Option<String>, CompilationError> {
+        let (branch, label_id) = match self {
+            Binary::And => ("bz", format!("and{}", create_label_id())),
+            Binary::Or => ("bnz", format!("or{}", create_label_id())),
+            _ => return Ok(None),
+        };
+        if is_cheap(lhs) && is_cheap(rhs) {
+            return Ok(None);
+        }
+
+        // the deciding `lhs` is left on the stack, both paths are normalised to 0 or 1
+        Ok(Some(
+            [
+                lhs.compile(context, &mut vec![])?,
+                "dup".to_string(),
+                format!("{branch} {label_id}"),
+                "pop".to_string(),
+                rhs.compile(context, &mut vec![])?,
+                format!("{label_id}:"),
+                "!".to_string(),
+                "!".to_string(),
+            ]
+            .join(OP_SEPARATOR),
+        ))
+    }
+}
+
 impl Expression for Binary {
     fn resolve(&self, _: &TypeContext) -> Result<TypeEnum, TypeError> {
         Ok(match self {
@@ -32,7 +97,20 @@ impl Expression for Binary {
             Binary::GreaterThan
             | Binary::GreaterThanEquals
             | Binary::LessThan
-            | Binary::LessThanEquals => TypeEnum::Arrow(
+            | Binary::LessThanEquals
+            | Binary::And
+            | Binary::Or
+            | Binary::Add
+            | Binary::Sub
+            | Binary::Mul
+            | Binary::Div
+            | Binary::Mod
+            | Binary::BitOr
+            | Binary::BitAnd
+            | Binary::BitXor
+            | Binary::ShiftLeft
+            | Binary::ShiftRight
+            | Binary::Exp => TypeEnum::Arrow(
                 Box::new(TypeEnum::Simple(TypePrimitive::UInt64)),
                 Box::new(TypeEnum::Arrow(
                     Box::new(TypeEnum::Simple(TypePrimitive::UInt64)),
@@ -68,6 +146,139 @@ impl Expression for Binary {
             Binary::GreaterThanEquals => op(a, ">=", b),
             Binary::LessThan => op(a, "<", b),
             Binary::LessThanEquals => op(a, "<=", b),
+            Binary::And => op(a, "&&", b),
+            Binary::Or => op(a, "||", b),
+            // overflow, underflow and division by zero fail the program, as in the AVM
+            Binary::Add => op(a, "+", b),
+            Binary::Sub => op(a, "-", b),
+            Binary::Mul => op(a, "*", b),
+            Binary::Div => op(a, "/", b),
+            Binary::Mod => op(a, "%", b),
+            Binary::BitOr => op(a, "|", b),
+            Binary::BitAnd => op(a, "&", b),
+            Binary::BitXor => op(a, "^", b),
+            Binary::ShiftLeft => op(a, "shl", b),
+            Binary::ShiftRight => op(a, "shr", b),
+            Binary::Exp => op(a, "exp", b),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        apply,
+        assembly::assemble,
+        binop,
+        context::TypeContext,
+        expression::{
+            apply::Apply,
+            binary::Binary,
+            primitive::Primitive,
+            txn::Txn,
+            var::{LVal, RVal, Var},
+            Expr, Expression,
+        },
+        int,
+        typing::{TypeEnum, TypePrimitive},
+        val,
+    };
+
+    #[test]
+    fn test_logical_types() {
+        let e = binop!((int!(1)) && (binop!((int!(2)) > (int!(1)))));
+        assert_eq!(
+            e.resolve(&TypeContext::default()).unwrap(),
+            TypeEnum::Simple(TypePrimitive::UInt64)
+        );
+        assert_eq!(
+            Expr::Binary(Binary::Or)
+                .resolve(&TypeContext::default())
+                .unwrap()
+                .to_string(),
+            "int -> int -> int"
+        );
+        assert!(
+            binop!((int!(1)) || (Expr::Primitive(Primitive::Byteslice(vec![]))))
+                .resolve(&TypeContext::default())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_plain() {
+        let e = binop!((int!(1)) && (binop!((int!(2)) > (int!(1)))));
+        assert_eq!(e.compile_raw().unwrap(), "int 1\nint 2\nint 1\n>\n&&");
+        let e = binop!((int!(0)) || (int!(1)));
+        assert_eq!(e.compile_raw().unwrap(), "int 0\nint 1\n||");
+    }
+
+    #[test]
+    fn test_short_circuit() {
+        // the right hand side writes state, so it must be skipped when the left decides
+        let write = apply!(
+            @fn Expr::LVal(LVal(Var::Global("x".to_string())));
+            @arg int!(1);
+        );
+        let compiled = binop!((val!(@global flag)) && (write.clone()))
+            .compile_raw()
+            .unwrap();
+        let lines = compiled.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[..4],
+            ["byte \"flag\"", "app_global_get", "dup", lines[3]]
+        );
+        assert!(lines[3].starts_with("bz and"));
+        assert_eq!(lines[4], "pop");
+        assert_eq!(lines[lines.len() - 2..], ["!", "!"]);
+
+        let compiled = binop!((int!(1)) || (write)).compile_raw().unwrap();
+        assert!(compiled.lines().nth(2).unwrap().starts_with("bnz or"));
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let ops = [
+            (Binary::Add, "+"),
+            (Binary::Sub, "-"),
+            (Binary::Mul, "*"),
+            (Binary::Div, "/"),
+            (Binary::Mod, "%"),
+            (Binary::BitOr, "|"),
+            (Binary::BitAnd, "&"),
+            (Binary::BitXor, "^"),
+            (Binary::ShiftLeft, "shl"),
+            (Binary::ShiftRight, "shr"),
+            (Binary::Exp, "exp"),
+        ];
+        for (binary, opcode) in ops {
+            let e = apply!(@fn Expr::Binary(binary); @arg int!(4); @arg int!(12));
+            assert_eq!(
+                e.resolve(&TypeContext::default()).unwrap(),
+                TypeEnum::Simple(TypePrimitive::UInt64)
+            );
+            assert_eq!(e.compile_raw().unwrap(), format!("int 12\nint 4\n{opcode}"));
+            assemble(&format!("#pragma version 8\n{}", e.compile_raw().unwrap())).unwrap();
+        }
+        assert!(binop!((int!(12)) - (Expr::Primitive(Primitive::from("4"))))
+            .resolve(&TypeContext::default())
+            .is_err());
+    }
+
+    #[test]
+    fn test_short_circuit_costly() {
+        let costly = binop!((binop!((int!(1)) == (int!(2)))) && (binop!((int!(3)) == (int!(4)))));
+        let compiled = binop!((int!(1)) || (costly)).compile_raw().unwrap();
+        assert!(compiled.lines().nth(2).unwrap().starts_with("bnz or"));
+    }
+
+    #[test]
+    fn test_short_circuit_failing() {
+        // x != 0 && 10 / x > 1, dividing by a zero x would fail the program
+        let x = || Expr::Txn(Txn::NumAppArgs);
+        let nonzero = apply!(@fn Expr::Binary(Binary::NotEquals); @arg int!(0); @arg x());
+        let divided = binop!((binop!((int!(10)) / (x()))) > (int!(1)));
+        let compiled = binop!((nonzero) && (divided)).compile_raw().unwrap();
+        assert!(compiled.lines().nth(4).unwrap().starts_with("bz and"));
+    }
+}
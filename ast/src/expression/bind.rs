@@ -1,13 +1,24 @@
 use std::rc::Rc;
 
 use crate::{
+    assembly::size,
     compilation_error::CompilationError,
-    context::{CompilationBinding, CompilationContext, TypeContext},
-    typing::{TypeEnum, TypeError},
+    context::{stack_placeholder, CompilationBinding, CompilationContext, TypeContext},
+    cost::estimate,
+    label::create_label_id,
+    stack::effect,
+    typing::{TypeEnum, TypeError, TypeVar},
     OP_SEPARATOR,
 };
 
-use super::{primitive::Primitive, Expr, Expression};
+use super::{
+    primitive::Primitive,
+    var::{LVal, RVal, Var},
+    Expr, Expression,
+};
+
+// uncover
+const STACK_VERSION: u64 = 5;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Bind {
@@ -21,13 +32,188 @@ pub enum Bind {
         value: Primitive,
         body: Expr,
     },
+    // binds each value of a tuple, e.g. the (high, low) words of `mulw`
+    Destructure {
+        identifiers: Vec<String>,
+        value: Expr,
+        body: Expr,
+    },
+}
+
+pub(super) fn resolve_destructured(
+    identifiers: &[String],
+    types: &[TypeEnum],
+    body: &Expr,
+    context: &TypeContext,
+) -> Result<TypeEnum, TypeError> {
+    match (identifiers.split_first(), types.split_first()) {
+        (Some((identifier, identifiers)), Some((value_type, types))) => {
+            let context = TypeContext {
+                bind_scope: Rc::new(
+                    context
+                        .bind_scope
+                        .add(identifier.to_string(), value_type.clone()),
+                ),
+                global_scope: Rc::clone(&context.global_scope),
+                local_scope: Rc::clone(&context.local_scope),
+                boxes: context.boxes,
+                events: context.events,
+                templates: context.templates,
+                mode: context.mode,
+            };
+            resolve_destructured(identifiers, types, body, &context)
+        }
+        _ => body.resolve(context),
+    }
+}
+
+// binds each identifier to the variable allocated for it
+pub(super) fn compile_destructured(
+    identifiers: &[String],
+    bindings: &[CompilationBinding],
+    body: &Expr,
+    context: &CompilationContext,
+) -> Result<String, CompilationError> {
+    match (identifiers.split_first(), bindings.split_first()) {
+        (Some((identifier, identifiers)), Some((binding, bindings))) => {
+            let context = CompilationContext {
+                scope: context.scope.add(identifier.to_string(), binding.clone()),
+                ..*context
+            };
+            compile_destructured(identifiers, bindings, body, &context)
+        }
+        _ => body.compile(context, &mut vec![]),
+    }
+}
+
+// Whether a `let` could keep its value on the stack instead of in a variable: it is never
+// assigned and all uses come before any branch, so their depth follows from the ops in
+// between. Without uses a `store` costs the same as the `pop` the stack would need.
+fn stack_candidate(identifier: &str, body: &Expr) -> bool {
+    fn walk(identifier: &str, expr: &Expr, branched: &mut bool, uses: &mut usize) -> bool {
+        match expr {
+            Expr::LVal(LVal(Var::Bind(i))) if i == identifier => return false,
+            Expr::RVal(RVal(Var::Bind(i))) if i == identifier => {
+                if *branched {
+                    return false;
+                }
+                *uses += 1;
+            }
+            Expr::If(_)
+            | Expr::Cond(_)
+            | Expr::Loop(_)
+            | Expr::Function(_)
+            | Expr::Call(_)
+            | Expr::Ret(_)
+            | Expr::Router(_) => *branched = true,
+            _ => {}
+        }
+        expr.children()
+            .into_iter()
+            .all(|child| walk(identifier, child, branched, uses))
+    }
+    let mut uses = 0;
+    walk(identifier, body, &mut false, &mut uses) && uses > 0
+}
+
+// Whether the stack form of a `let` body is cheaper than its form with the store and loads of
+// a variable: it must cost fewer opcodes, or as many in fewer bytes.
+fn prefer_stack(stack: &str, variable: &str, context: &CompilationContext) -> bool {
+    let regions = context
+        .regions
+        .map(|regions| regions.borrow().clone())
+        .unwrap_or_default();
+    match (estimate(stack, &regions), estimate(variable, &regions)) {
+        (Some(stack), Some(variable)) if stack != variable => stack < variable,
+        _ => match (
+            size(stack, context.version),
+            size(variable, context.version),
+        ) {
+            (Ok(stack), Ok(variable)) => stack < variable,
+            _ => false,
+        },
+    }
+}
+
+// Replaces the loads of a stack binding in its compiled body, which runs with the value on
+// top of the stack. Earlier loads copy the value, the last one takes it off the stack so
+// nothing is left to clean up. `None` when a depth cannot be followed.
+fn resolve_stack(id: usize, body: &str) -> Option<String> {
+    let placeholder = stack_placeholder(id);
+    let lines = body.lines().collect::<Vec<_>>();
+    let last = lines.iter().rposition(|line| *line == placeholder)?;
+    // values above the binding
+    let mut depth = 0;
+    let mut resolved = vec![];
+    for (i, line) in lines.iter().enumerate() {
+        if i > last {
+            resolved.push(line.to_string());
+        } else if i == last {
+            match depth {
+                0 => {}
+                1 => resolved.push("swap".to_string()),
+                _ => resolved.push(format!("uncover {depth}")),
+            }
+        } else if *line == placeholder {
+            resolved.push(match depth {
+                0 => "dup".to_string(),
+                _ => format!("dig {depth}"),
+            });
+            depth += 1;
+        } else {
+            // loads of enclosing stack bindings push a copy
+            let (pops, pushes) = match line.starts_with("%stack ") {
+                true => (0, 1),
+                false => effect(line)?,
+            };
+            if pops > depth {
+                return None;
+            }
+            depth = depth - pops + pushes;
+            resolved.push(line.to_string());
+        }
+    }
+    Some(peephole(resolved).join(OP_SEPARATOR))
+}
+
+// Drops the moves a resolved binding leaves without effect: a `swap` right after a `dup`
+// swaps two equal values and two `swap`s in a row cancel out
+fn peephole(lines: Vec<String>) -> Vec<String> {
+    let mut kept: Vec<String> = vec![];
+    for line in lines {
+        match (kept.last().map(String::as_str), line.as_str()) {
+            (Some("dup"), "swap") => {}
+            (Some("swap"), "swap") => {
+                kept.pop();
+            }
+            _ => kept.push(line),
+        }
+    }
+    kept
 }
 
 impl Expression for Bind {
     fn resolve(&self, context: &TypeContext) -> Result<TypeEnum, TypeError> {
+        if let Bind::Destructure {
+            identifiers,
+            value,
+            body,
+        } = self
+        {
+            let types = identifiers
+                .iter()
+                .map(|_| TypeEnum::Var(TypeVar::new()))
+                .collect::<Vec<_>>();
+            value
+                .resolve(context)?
+                .unify(&mut TypeEnum::Tuple(types.clone()))?;
+            return resolve_destructured(identifiers, &types, body, context);
+        }
+
         let value_type = match self {
             Bind::Let { value, .. } => value.resolve(context),
             Bind::Const { value, .. } => value.resolve(context),
+            Bind::Destructure { .. } => unreachable!(),
         }?;
 
         match self {
@@ -41,9 +227,14 @@ impl Expression for Bind {
                     bind_scope: Rc::new(context.bind_scope.add(identifier.to_string(), value_type)),
                     global_scope: Rc::clone(&context.global_scope),
                     local_scope: Rc::clone(&context.local_scope),
+                    boxes: context.boxes,
+                    events: context.events,
+                    templates: context.templates,
+                    mode: context.mode,
                 };
                 body.resolve(&context)
             }
+            Bind::Destructure { .. } => unreachable!(),
         }
     }
 
@@ -74,24 +265,68 @@ impl Expression for Bind {
                 body,
             } => {
                 let value_compiled = value.compile(context, &mut Vec::new())?;
-                let scratch_id = context.scratch_id;
-                let next_scratch_id = if context.scratch_id < u8::MAX {
-                    context.scratch_id + 1
+                let stack = if context.version >= STACK_VERSION && stack_candidate(identifier, body)
+                {
+                    let id = create_label_id();
+                    let stack_context = CompilationContext {
+                        scope: context
+                            .scope
+                            .add(identifier.to_string(), CompilationBinding::Stack(id)),
+                        ..*context
+                    };
+                    resolve_stack(id, &body.compile(&stack_context, &mut vec![])?)
                 } else {
-                    return Err(CompilationError::OutOfScratchSpace);
+                    None
                 };
-                let context = CompilationContext {
-                    scope: context.scope.add(
-                        identifier.to_string(),
-                        CompilationBinding::ScratchVar(scratch_id),
-                    ),
-                    scratch_id: next_scratch_id,
+                // the variables the enclosing function reserves, in case the stack form wins
+                let used = context.function.map(|function| function.used.get());
+                let variable = context.allocate(1).and_then(|(mut bindings, allocated)| {
+                    let context = CompilationContext {
+                        scope: allocated
+                            .scope
+                            .add(identifier.to_string(), bindings.remove(0)),
+                        ..allocated
+                    };
+                    let store = context.store(identifier)?;
+                    let body_compiled = body.compile(&context, &mut vec![])?;
+                    Ok([store, body_compiled].join(OP_SEPARATOR))
+                });
+                let body_compiled = match (stack, variable) {
+                    (Some(stack), Ok(variable)) if !prefer_stack(&stack, &variable, context) => {
+                        variable
+                    }
+                    (Some(stack), _) => {
+                        if let (Some(function), Some(used)) = (context.function, used) {
+                            function.used.set(used);
+                        }
+                        stack
+                    }
+                    (None, variable) => variable?,
                 };
-                let body_compiled = body.compile(&context, &mut vec![])?;
-                Ok(
-                    vec![value_compiled, format!("store {scratch_id}"), body_compiled]
-                        .join(OP_SEPARATOR),
-                )
+                Ok([value_compiled, body_compiled].join(OP_SEPARATOR))
+            }
+            Bind::Destructure {
+                identifiers,
+                value,
+                body,
+            } => {
+                let (bindings, allocated) = context.allocate(identifiers.len())?;
+                // the last value is on top of the stack, so it is stored first
+                let mut pieces = vec![value.compile(context, &mut Vec::new())?];
+                for binding in bindings.iter().rev() {
+                    pieces.push(match binding {
+                        CompilationBinding::FrameVar(_, i) => format!("frame_bury {i}"),
+                        CompilationBinding::ScratchVar(i) => format!("store {i}"),
+                        _ => unreachable!(),
+                    });
+                }
+                pieces.push(compile_destructured(
+                    identifiers,
+                    &bindings,
+                    body,
+                    &allocated,
+                )?);
+                Ok(pieces.join(OP_SEPARATOR))
             }
         }
     }
@@ -99,20 +334,41 @@ impl Expression for Bind {
 
 #[cfg(test)]
 mod tests {
+    use std::cell::Cell;
+
     use super::*;
     use crate::{
-        context::TypeContext,
+        apply, assign, bind_let, binop,
+        expression::{if_else::If, seq::Seq},
+        int, r#if, seq, val,
+    };
+    use crate::{
+        assembly::eval::Eval,
+        context::{FunctionContext, TypeContext},
         expression::{
             apply::Apply,
             binary::Binary,
             primitive::Primitive,
             var::{RVal, Var},
+            wide::Wide,
             Expression,
         },
+        typing::TypePrimitive,
     };
 
     use super::Bind;
 
+    fn compiled(e: &Expr, version: u64) -> String {
+        e.compile(
+            &CompilationContext {
+                version,
+                ..Default::default()
+            },
+            &mut vec![],
+        )
+        .unwrap()
+    }
+
     #[test]
     fn test() {
         let e = Bind::Let {
@@ -129,4 +385,273 @@ mod tests {
         println!("{:?}", e.resolve(&TypeContext::default()));
         println!("{}", e.compile_raw().unwrap());
     }
+
+    #[test]
+    fn test_destructure() {
+        // let (high, low) = mulw(a, b); low
+        let e = Bind::Destructure {
+            identifiers: vec!["high".to_string(), "low".to_string()],
+            value: Expr::Apply(Box::new(Apply(
+                Expr::Apply(Box::new(Apply(
+                    Expr::Wide(Wide::MulW),
+                    Expr::Primitive(Primitive::UInt64(u64::MAX)),
+                ))),
+                Expr::Primitive(Primitive::UInt64(2)),
+            ))),
+            body: Expr::RVal(RVal(Var::Bind("low".to_string()))),
+        };
+        e.resolve(&TypeContext::default())
+            .unwrap()
+            .unify(&mut TypeEnum::Simple(TypePrimitive::UInt64))
+            .unwrap();
+        assert_eq!(
+            e.compile_raw().unwrap(),
+            "int 18446744073709551615\nint 2\nmulw\nstore 1\nstore 0\nload 1"
+        );
+    }
+
+    #[test]
+    fn test_destructure_arity() {
+        let e = Bind::Destructure {
+            identifiers: vec!["a".to_string(), "b".to_string(), "c".to_string()],
+            value: Expr::Apply(Box::new(Apply(
+                Expr::Apply(Box::new(Apply(
+                    Expr::Wide(Wide::AddW),
+                    Expr::Primitive(Primitive::UInt64(1)),
+                ))),
+                Expr::Primitive(Primitive::UInt64(2)),
+            ))),
+            body: Expr::RVal(RVal(Var::Bind("a".to_string()))),
+        };
+        assert!(e.resolve(&TypeContext::default()).is_err());
+    }
+
+    #[test]
+    fn test_stack() {
+        // let x = 5; x + x
+        let e = bind_let!(x = int!(5); binop!((val!(@scratch x)) + (val!(@scratch x))));
+        assert_eq!(compiled(&e, 5), "int 5\ndup\n+");
+        // before uncover the value goes through scratch
+        assert_eq!(compiled(&e, 4), "int 5\nstore 0\nload 0\nload 0\n+");
+
+        // let a = 1; let b = 2; a - b
+        let e = bind_let!(
+            a = int!(1);
+            bind_let!(b = int!(2); binop!((val!(@scratch a)) - (val!(@scratch b))))
+        );
+        assert_eq!(compiled(&e, 8), "int 1\nint 2\n-");
+
+        // let a = 1; let b = a; a + b + a
+        let e = bind_let!(
+            a = int!(1);
+            bind_let!(
+                b = val!(@scratch a);
+                binop!((binop!((val!(@scratch a)) + (val!(@scratch b)))) + (val!(@scratch a)))
+            )
+        );
+        assert_eq!(compiled(&e, 8), "int 1\ndup\ndig 1\nswap\n+\nswap\n+");
+    }
+
+    // an op with the values it pops pushed before it
+    const SAMPLES: &[(&str, &str)] = &[
+        ("", "int 3"),
+        ("", "byte 0x01"),
+        ("", "txn Fee"),
+        ("", "txna Accounts 0"),
+        ("", "global Round"),
+        ("", "gtxn 0 Fee"),
+        ("", "gtxna 0 Accounts 0"),
+        ("", "itxn Fee"),
+        ("", "itxna Logs 0"),
+        ("", "arg 0"),
+        ("", "load 5"),
+        ("", "frame_dig -1"),
+        ("int 3", "store 5"),
+        ("int 3", "frame_bury 0"),
+        ("int 3", "pop"),
+        ("byte 0x01", "log"),
+        ("int 1", "assert"),
+        ("int 1", "itxn_field Fee"),
+        ("byte \"k\"", "app_global_del"),
+        ("", "itxn_begin"),
+        ("", "itxn_next"),
+        ("", "itxn_submit"),
+        ("byte \"k\"\nint 3", "app_global_put"),
+        ("int 0\nbyte \"k\"", "app_local_del"),
+        ("byte \"b\"\nbyte 0x01", "box_put"),
+        ("int 0\nbyte \"k\"\nint 3", "app_local_put"),
+        (
+            "byte \"b\"\nint 4\nbox_create\npop\nbyte \"b\"\nint 1\nbyte 0x01",
+            "box_replace",
+        ),
+        ("int 0", "!"),
+        ("int 3", "~"),
+        ("byte 0x0102", "len"),
+        ("byte 0x01", "btoi"),
+        ("int 3", "itob"),
+        ("byte 0x01", "sha256"),
+        ("byte 0x01", "sha512_256"),
+        ("int 17", "sqrt"),
+        ("int 5", "bitlen"),
+        ("int 2", "bzero"),
+        ("byte \"k\"", "app_global_get"),
+        ("int 0", "balance"),
+        ("int 0", "min_balance"),
+        ("int 0", "txnas Accounts"),
+        ("int 0", "gtxns Fee"),
+        ("int 0", "gtxnsa Accounts 0"),
+        ("int 0", "gtxnas 0 Accounts"),
+        ("int 0", "itxnas Logs"),
+        ("byte 0x010203", "extract 1 1"),
+        ("byte 0x010203", "substring 0 2"),
+        ("byte \"b\"", "box_del"),
+        ("int 6\nint 3", "+"),
+        ("int 6\nint 3", "-"),
+        ("int 6\nint 3", "*"),
+        ("int 6\nint 3", "/"),
+        ("int 6\nint 4", "%"),
+        ("int 6\nint 3", "<"),
+        ("int 6\nint 3", ">"),
+        ("int 6\nint 3", "<="),
+        ("int 6\nint 3", ">="),
+        ("int 6\nint 3", "=="),
+        ("int 6\nint 3", "!="),
+        ("int 6\nint 0", "&&"),
+        ("int 6\nint 0", "||"),
+        ("int 6\nint 3", "&"),
+        ("int 6\nint 3", "|"),
+        ("int 6\nint 3", "^"),
+        ("int 2\nint 3", "exp"),
+        ("int 6\nint 3", "shl"),
+        ("int 6\nint 1", "shr"),
+        ("byte 0x01\nbyte 0x02", "concat"),
+        ("byte 0x0102\nint 1", "getbyte"),
+        ("int 5\nint 0", "getbit"),
+        ("byte 0x0001020304050607\nint 1", "extract_uint16"),
+        ("byte 0x0001020304050607\nint 1", "extract_uint32"),
+        ("byte 0x0001020304050607\nint 0", "extract_uint64"),
+        ("byte 0x010203\nbyte 0xff", "replace2 1"),
+        ("byte 0x01\nbyte 0x0200", "b|"),
+        ("int 0\nbyte \"k\"", "app_local_get"),
+        ("int 0\nint 0", "gtxnsas Accounts"),
+        ("byte \"b\"\nint 4", "box_create"),
+        ("byte 0x0102\nint 0\nint 9", "setbyte"),
+        ("int 0\nint 3\nint 1", "setbit"),
+        ("byte 0x010203\nint 1\nint 2", "extract3"),
+        ("byte 0x010203\nint 0\nint 2", "substring3"),
+        ("byte 0x010203\nint 1\nbyte 0xff", "replace3"),
+        ("int 1\nint 2\nint 0", "select"),
+        (
+            "byte \"b\"\nint 4\nbox_create\npop\nbyte \"b\"\nint 1\nint 2",
+            "box_extract",
+        ),
+        ("byte \"b\"", "box_len"),
+        ("byte \"b\"", "box_get"),
+        ("int 0", "asset_params_get AssetTotal"),
+        ("int 0", "app_params_get AppCreator"),
+        ("int 0", "acct_params_get AcctBalance"),
+        ("int 3", "dup"),
+        ("int 3\nint 4", "swap"),
+        ("int 3\nint 4", "mulw"),
+        ("int 3\nint 4", "addw"),
+        ("int 3\nint 4", "expw"),
+        ("int 0\nbyte \"k\"", "app_global_get_ex"),
+        ("int 0\nint 0", "asset_holding_get AssetBalance"),
+        ("int 0\nint 0\nbyte \"k\"", "app_local_get_ex"),
+        ("int 0\nint 7\nint 0\nint 2", "divmodw"),
+        ("int 3\nint 4", "dig 1"),
+        ("int 3\nint 4", "uncover 1"),
+        ("int 3", "dupn 2"),
+    ];
+
+    #[test]
+    fn test_stack_ops() {
+        for (name, _, _) in crate::stack::FIXED {
+            assert!(
+                SAMPLES
+                    .iter()
+                    .any(|(_, op)| op.split(' ').next() == Some(name)),
+                "{name} has no sample"
+            );
+        }
+        // the binding is used twice after each op, copied and then taken from below what
+        // the op left, so a wrong effect moves the wrong value
+        let prelude = "int 1\ncallsub f\nf:\nproto 1 0\nint 7\nint 42";
+        let placeholder = stack_placeholder(0);
+        for (setup, op) in SAMPLES {
+            let body = format!("{setup}\n{op}\n{placeholder}\n{placeholder}");
+            let body = body.trim_start();
+            let stack = resolve_stack(0, body).unwrap_or_else(|| panic!("{op} not resolved"));
+            let variable = body.replace(&placeholder, "load 99");
+            let mut stack_eval = Eval::default();
+            stack_eval.run(&format!("{prelude}\n{stack}")).unwrap();
+            let mut variable_eval = Eval::default();
+            variable_eval
+                .run(&format!("{prelude}\nstore 99\n{variable}"))
+                .unwrap();
+            assert_eq!(stack_eval.stack, variable_eval.stack, "{op}");
+        }
+    }
+
+    #[test]
+    fn test_stack_fallback() {
+        // assigned: let x = 1; x = x + 1; x
+        let e = bind_let!(
+            x = int!(1);
+            seq!(
+                assign!(@scratch x = binop!((val!(@scratch x)) + (int!(1))));
+                val!(@scratch x);
+            )
+        );
+        assert!(compiled(&e, 8).starts_with("int 1\nstore 0"));
+
+        // used after a branch: let x = 1; if x { x } else { 0 }
+        let e = bind_let!(
+            x = int!(1);
+            r#if!((val!(@scratch x)) @then val!(@scratch x); @else int!(0))
+        );
+        assert!(compiled(&e, 8).starts_with("int 1\nstore 0\nload 0\nbz"));
+
+        // unused: let x = 1; 2
+        let e = bind_let!(x = int!(1); int!(2));
+        assert_eq!(compiled(&e, 8), "int 1\nstore 0\nint 2");
+    }
+
+    #[test]
+    fn test_prefer_stack() {
+        let context = CompilationContext {
+            version: 8,
+            ..Default::default()
+        };
+        // fewer opcodes
+        assert!(prefer_stack(
+            "dup\n+",
+            "store 0\nload 0\nload 0\n+",
+            &context
+        ));
+        // as many opcodes in fewer bytes
+        assert!(prefer_stack("dup\nswap", "store 0\nload 0", &context));
+        // as many opcodes in as many bytes
+        assert!(!prefer_stack(
+            "dig 1\nuncover 2",
+            "store 0\nload 0",
+            &context
+        ));
+
+        // a function does not reserve a frame local for a binding on the stack
+        let used = Cell::new(0);
+        let context = CompilationContext {
+            version: 8,
+            function: Some(FunctionContext {
+                id: 0,
+                frame: true,
+                next_local: 0,
+                used: &used,
+            }),
+            ..Default::default()
+        };
+        let e = bind_let!(x = int!(5); binop!((val!(@scratch x)) + (val!(@scratch x))));
+        assert_eq!(e.compile(&context, &mut vec![]).unwrap(), "int 5\ndup\n+");
+        assert_eq!(used.get(), 0);
+    }
 }
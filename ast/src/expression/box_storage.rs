@@ -0,0 +1,292 @@
+use crate::{
+    assembly::parse_bytes,
+    compilation_error::CompilationError,
+    context::{CompilationContext, TypeContext},
+    typing::{TypeEnum, TypeError, TypePrimitive},
+    OP_SEPARATOR,
+};
+
+use super::{
+    primitive::{uint64_literal, Primitive},
+    Expression,
+};
+
+const BOX_VERSION: u64 = 8;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BoxOp {
+    // creates the box zero filled, resolves to whether it did not exist yet
+    Create,
+    // the value, fails when the box does not exist
+    Get,
+    // (value), creates the box when it does not exist
+    Put,
+    // (offset, length)
+    Extract,
+    // (offset, bytes)
+    Replace,
+    // resolves to whether the box existed
+    Delete,
+    // the size, fails when the box does not exist
+    Len,
+}
+
+// An operation on a box declared in the box schema
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoxStorage {
+    pub name: String,
+    pub op: BoxOp,
+}
+
+// the bytes of a compiled byteslice literal
+fn byteslice_literal(compiled: &str) -> Option<Vec<u8>> {
+    compiled
+        .strip_prefix("byte ")
+        .and_then(|s| parse_bytes(0, s).ok())
+}
+
+impl Expression for BoxStorage {
+    fn resolve(&self, context: &TypeContext) -> Result<TypeEnum, TypeError> {
+        context.require_application("box storage")?;
+        let def = context
+            .boxes
+            .and_then(|boxes| boxes.get(&self.name))
+            .ok_or_else(|| TypeError::UnboundBox(self.name.clone()))?;
+        let value = TypeEnum::Simple(def.value_type.clone());
+        let uint64 = || TypeEnum::Simple(TypePrimitive::UInt64);
+        let bytes = || TypeEnum::Simple(TypePrimitive::Byteslice);
+        let void = TypeEnum::Simple(TypePrimitive::Void);
+        let arrow = |a: TypeEnum, b: TypeEnum| TypeEnum::Arrow(Box::new(a), Box::new(b));
+        Ok(match self.op {
+            BoxOp::Create | BoxOp::Delete | BoxOp::Len => uint64(),
+            BoxOp::Get => value,
+            BoxOp::Put => arrow(value, void),
+            BoxOp::Extract => arrow(uint64(), arrow(uint64(), bytes())),
+            BoxOp::Replace => arrow(uint64(), arrow(bytes(), void)),
+        })
+    }
+
+    fn compile(
+        &self,
+        context: &CompilationContext,
+        prepared_stack: &mut Vec<String>,
+    ) -> Result<String, CompilationError> {
+        if context.version < BOX_VERSION {
+            return Err(CompilationError::UnsupportedOpcode(
+                "box".to_string(),
+                BOX_VERSION,
+                context.version,
+            ));
+        }
+        let def = context
+            .boxes
+            .and_then(|boxes| boxes.get(&self.name))
+            .ok_or_else(|| CompilationError::UnknownBox(self.name.clone()))?;
+        let is_uint64 = def.value_type == TypePrimitive::UInt64;
+        // literal offsets and lengths are checked against the declared size
+        let check_bounds =
+            |offset: &str, length: Option<u64>| match (uint64_literal(offset), length) {
+                (Some(offset), Some(length)) if offset + length > def.size => Err(
+                    CompilationError::BoxOutOfBounds(self.name.clone(), offset + length, def.size),
+                ),
+                _ => Ok(()),
+            };
+
+        let mut pieces = vec![Primitive::from(&self.name).compile(context, &mut vec![])?];
+        let mut pop = || prepared_stack.pop().ok_or(CompilationError::MissingStack);
+        match self.op {
+            BoxOp::Create => {
+                pieces.push(format!("int {}", def.size));
+                pieces.push("box_create".to_string());
+            }
+            BoxOp::Get => {
+                pieces.extend(["box_get".to_string(), "assert".to_string()]);
+                if is_uint64 {
+                    pieces.push("btoi".to_string());
+                }
+            }
+            BoxOp::Put => {
+                let value = pop()?;
+                if let Some(literal) = byteslice_literal(&value) {
+                    if literal.len() as u64 != def.size {
+                        return Err(CompilationError::BoxSizeMismatch(
+                            self.name.clone(),
+                            def.size,
+                            literal.len() as u64,
+                        ));
+                    }
+                }
+                pieces.push(value);
+                if is_uint64 {
+                    pieces.push("itob".to_string());
+                }
+                pieces.push("box_put".to_string());
+            }
+            BoxOp::Extract => {
+                let offset = pop()?;
+                let length = pop()?;
+                check_bounds(&offset, uint64_literal(&length))?;
+                pieces.extend([offset, length, "box_extract".to_string()]);
+            }
+            BoxOp::Replace => {
+                let offset = pop()?;
+                let value = pop()?;
+                check_bounds(
+                    &offset,
+                    byteslice_literal(&value).map(|literal| literal.len() as u64),
+                )?;
+                pieces.extend([offset, value, "box_replace".to_string()]);
+            }
+            BoxOp::Delete => pieces.push("box_del".to_string()),
+            BoxOp::Len => pieces.extend(["box_len".to_string(), "assert".to_string()]),
+        }
+        Ok(pieces.join(OP_SEPARATOR))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{
+        apply,
+        assembly::assemble,
+        box_schema::{BoxDef, BoxSchema},
+        bytes,
+        compilation_error::CompilationError,
+        context::{CompilationContext, TypeContext},
+        expression::{apply::Apply, primitive::Primitive, txn::Txn, Expr, Expression},
+        int,
+        typing::{TypeEnum, TypePrimitive},
+    };
+
+    use super::{BoxOp, BoxStorage};
+
+    fn storage(name: &str, op: BoxOp) -> Expr {
+        Expr::BoxStorage(BoxStorage {
+            name: name.to_string(),
+            op,
+        })
+    }
+
+    fn schema() -> BoxSchema<'static> {
+        BoxSchema {
+            boxes: HashMap::from([
+                (
+                    "counter",
+                    BoxDef {
+                        value_type: TypePrimitive::UInt64,
+                        size: 8,
+                    },
+                ),
+                (
+                    "owner",
+                    BoxDef {
+                        value_type: TypePrimitive::Byteslice,
+                        size: 32,
+                    },
+                ),
+            ]),
+        }
+    }
+
+    fn compile(e: &Expr, schema: &BoxSchema) -> Result<String, CompilationError> {
+        e.resolve(&TypeContext {
+            boxes: Some(schema),
+            ..Default::default()
+        })?;
+        let compiled = e.compile(
+            &CompilationContext {
+                boxes: Some(schema),
+                ..Default::default()
+            },
+            &mut vec![],
+        )?;
+        assemble(&format!("#pragma version 8\n{compiled}"))?;
+        Ok(compiled)
+    }
+
+    #[test]
+    fn test_uint64_box() {
+        let schema = schema();
+        let e = storage("counter", BoxOp::Get);
+        e.resolve(&TypeContext {
+            boxes: Some(&schema),
+            ..Default::default()
+        })
+        .unwrap()
+        .unify(&mut TypeEnum::Simple(TypePrimitive::UInt64))
+        .unwrap();
+        assert_eq!(
+            compile(&e, &schema).unwrap(),
+            "byte \"counter\"\nbox_get\nassert\nbtoi"
+        );
+        let e = apply!(@fn storage("counter", BoxOp::Put); @arg int!(1));
+        assert_eq!(
+            compile(&e, &schema).unwrap(),
+            "byte \"counter\"\nint 1\nitob\nbox_put"
+        );
+        assert_eq!(
+            compile(&storage("counter", BoxOp::Create), &schema).unwrap(),
+            "byte \"counter\"\nint 8\nbox_create"
+        );
+        // values must match the declared type
+        let e = apply!(@fn storage("counter", BoxOp::Put); @arg bytes!(b"one".to_vec()));
+        assert!(compile(&e, &schema).is_err());
+    }
+
+    #[test]
+    fn test_bytes_box() {
+        let schema = schema();
+        let e = apply!(@fn storage("owner", BoxOp::Put); @arg Expr::Txn(Txn::Sender));
+        assert_eq!(
+            compile(&e, &schema).unwrap(),
+            "byte \"owner\"\ntxn Sender\nbox_put"
+        );
+        let e = apply!(@fn storage("owner", BoxOp::Extract); @arg int!(0); @arg int!(8));
+        assert_eq!(
+            compile(&e, &schema).unwrap(),
+            "byte \"owner\"\nint 0\nint 8\nbox_extract"
+        );
+        let e =
+            apply!(@fn storage("owner", BoxOp::Replace); @arg int!(4); @arg bytes!(b"ab".to_vec()));
+        assert_eq!(
+            compile(&e, &schema).unwrap(),
+            "byte \"owner\"\nint 4\nbyte \"ab\"\nbox_replace"
+        );
+        assert_eq!(
+            compile(&storage("owner", BoxOp::Len), &schema).unwrap(),
+            "byte \"owner\"\nbox_len\nassert"
+        );
+        assert_eq!(
+            compile(&storage("owner", BoxOp::Delete), &schema).unwrap(),
+            "byte \"owner\"\nbox_del"
+        );
+    }
+
+    #[test]
+    fn test_size_checks() {
+        let schema = schema();
+        let e = apply!(@fn storage("owner", BoxOp::Put); @arg bytes!(b"short".to_vec()));
+        assert!(matches!(
+            compile(&e, &schema),
+            Err(CompilationError::BoxSizeMismatch(name, 32, 5)) if name == "owner"
+        ));
+        let e = apply!(@fn storage("owner", BoxOp::Extract); @arg int!(30); @arg int!(8));
+        assert!(matches!(
+            compile(&e, &schema),
+            Err(CompilationError::BoxOutOfBounds(name, 38, 32)) if name == "owner"
+        ));
+        let e = apply!(@fn storage("owner", BoxOp::Replace); @arg int!(31); @arg bytes!(b"ab".to_vec()));
+        assert!(compile(&e, &schema).is_err());
+        assert!(compile(&storage("missing", BoxOp::Get), &schema).is_err());
+        let context = CompilationContext {
+            boxes: Some(&schema),
+            version: 7,
+            ..Default::default()
+        };
+        assert!(storage("owner", BoxOp::Len)
+            .compile(&context, &mut vec![])
+            .is_err());
+    }
+}
@@ -0,0 +1,318 @@
+use crate::{
+    compilation_error::CompilationError,
+    context::{CompilationContext, TypeContext},
+    typing::{TypeEnum, TypeError, TypePrimitive},
+    OP_SEPARATOR,
+};
+
+use super::Expression;
+
+// Largest input the b-prefixed opcodes accept
+pub const MAX_BIGUINT_BYTES: u8 = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ByteMath {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Equals,
+    NotEquals,
+    GreaterThan,
+    GreaterThanEquals,
+    LessThan,
+    LessThanEquals,
+    BitOr,
+    BitAnd,
+    BitXor,
+    BitNot,
+    FromUInt64,
+    ToUInt64,
+    FromBytes,
+    // zero-padded on the left to the given width
+    ToBytes(u8),
+}
+
+fn simple(primitive: TypePrimitive) -> Box<TypeEnum> {
+    Box::new(TypeEnum::Simple(primitive))
+}
+
+impl Expression for ByteMath {
+    fn resolve(&self, _: &TypeContext) -> Result<TypeEnum, TypeError> {
+        use TypePrimitive::{BigUInt, Byteslice, UInt64};
+        Ok(match self {
+            // biguint -> biguint -> biguint
+            ByteMath::Add
+            | ByteMath::Sub
+            | ByteMath::Mul
+            | ByteMath::Div
+            | ByteMath::Mod
+            | ByteMath::BitOr
+            | ByteMath::BitAnd
+            | ByteMath::BitXor => TypeEnum::Arrow(
+                simple(BigUInt),
+                Box::new(TypeEnum::Arrow(simple(BigUInt), simple(BigUInt))),
+            ),
+            // biguint -> biguint -> uint
+            ByteMath::Equals
+            | ByteMath::NotEquals
+            | ByteMath::GreaterThan
+            | ByteMath::GreaterThanEquals
+            | ByteMath::LessThan
+            | ByteMath::LessThanEquals => TypeEnum::Arrow(
+                simple(BigUInt),
+                Box::new(TypeEnum::Arrow(simple(BigUInt), simple(UInt64))),
+            ),
+            ByteMath::BitNot => TypeEnum::Arrow(simple(BigUInt), simple(BigUInt)),
+            ByteMath::FromUInt64 => TypeEnum::Arrow(simple(UInt64), simple(BigUInt)),
+            ByteMath::ToUInt64 => TypeEnum::Arrow(simple(BigUInt), simple(UInt64)),
+            ByteMath::FromBytes => TypeEnum::Arrow(simple(Byteslice), simple(BigUInt)),
+            ByteMath::ToBytes(_) => TypeEnum::Arrow(simple(BigUInt), simple(Byteslice)),
+        })
+    }
+
+    fn compile(
+        &self,
+        context: &CompilationContext,
+        prepared_stack: &mut Vec<String>,
+    ) -> Result<String, CompilationError> {
+        let binary = match self {
+            ByteMath::Add => Some("b+"),
+            ByteMath::Sub => Some("b-"),
+            ByteMath::Mul => Some("b*"),
+            ByteMath::Div => Some("b/"),
+            ByteMath::Mod => Some("b%"),
+            ByteMath::Equals => Some("b=="),
+            ByteMath::NotEquals => Some("b!="),
+            ByteMath::GreaterThan => Some("b>"),
+            ByteMath::GreaterThanEquals => Some("b>="),
+            ByteMath::LessThan => Some("b<"),
+            ByteMath::LessThanEquals => Some("b<="),
+            ByteMath::BitOr => Some("b|"),
+            ByteMath::BitAnd => Some("b&"),
+            ByteMath::BitXor => Some("b^"),
+            _ => None,
+        };
+        if let Some(s) = binary {
+            context.require_ops(&[s])?;
+            let b = prepared_stack.pop().ok_or(CompilationError::MissingStack)?;
+            let a = prepared_stack.pop().ok_or(CompilationError::MissingStack)?;
+            return Ok([a, b, s.to_string()].join(OP_SEPARATOR));
+        }
+
+        let a = prepared_stack.pop().ok_or(CompilationError::MissingStack)?;
+        let ops: Vec<String> = match self {
+            ByteMath::BitNot => vec!["b~".into()],
+            ByteMath::FromUInt64 => vec!["itob".into()],
+            // at most 64 significant bits, padded to at least 8 bytes, last 8 bytes read
+            ByteMath::ToUInt64 => [
+                "dup",
+                "bitlen",
+                "int 64",
+                "<=",
+                "assert",
+                "int 8",
+                "bzero",
+                "b|",
+                "dup",
+                "len",
+                "int 8",
+                "-",
+                "extract_uint64",
+            ]
+            .map(String::from)
+            .to_vec(),
+            ByteMath::FromBytes => vec![
+                "dup".into(),
+                "len".into(),
+                format!("int {MAX_BIGUINT_BYTES}"),
+                "<=".into(),
+                "assert".into(),
+            ],
+            ByteMath::ToBytes(width) => {
+                if *width == 0 || *width > MAX_BIGUINT_BYTES {
+                    return Err(CompilationError::InvalidByteWidth(*width));
+                }
+                // bitwise ops keep leading zero bytes, so the significant bits are checked,
+                // then the last `width` bytes of the padded value taken
+                vec![
+                    "dup".into(),
+                    "bitlen".into(),
+                    format!("int {}", 8 * *width as u64),
+                    "<=".into(),
+                    "assert".into(),
+                    format!("int {width}"),
+                    "bzero".into(),
+                    "b|".into(),
+                    "dup".into(),
+                    "len".into(),
+                    "dup".into(),
+                    format!("int {width}"),
+                    "-".into(),
+                    "swap".into(),
+                    "substring3".into(),
+                ]
+            }
+            _ => unreachable!(),
+        };
+        context.require_ops(&ops)?;
+        Ok(std::iter::once(a)
+            .chain(ops)
+            .collect::<Vec<_>>()
+            .join(OP_SEPARATOR))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        apply,
+        assembly::{
+            assemble,
+            eval::{Eval, Value},
+        },
+        compilation_error::CompilationError,
+        context::{CompilationContext, TypeContext},
+        expression::{apply::Apply, primitive::Primitive, Expr, Expression},
+        int,
+        typing::{TypeEnum, TypePrimitive},
+    };
+
+    use super::ByteMath;
+
+    fn biguint(value: u64) -> Expr {
+        apply!(@fn Expr::ByteMath(ByteMath::FromUInt64); @arg int!(value))
+    }
+
+    #[test]
+    fn test_binary() {
+        let ops = [
+            (ByteMath::Add, "b+", TypePrimitive::BigUInt),
+            (ByteMath::Sub, "b-", TypePrimitive::BigUInt),
+            (ByteMath::Mul, "b*", TypePrimitive::BigUInt),
+            (ByteMath::Div, "b/", TypePrimitive::BigUInt),
+            (ByteMath::Mod, "b%", TypePrimitive::BigUInt),
+            (ByteMath::BitOr, "b|", TypePrimitive::BigUInt),
+            (ByteMath::BitAnd, "b&", TypePrimitive::BigUInt),
+            (ByteMath::BitXor, "b^", TypePrimitive::BigUInt),
+            (ByteMath::Equals, "b==", TypePrimitive::UInt64),
+            (ByteMath::NotEquals, "b!=", TypePrimitive::UInt64),
+            (ByteMath::GreaterThan, "b>", TypePrimitive::UInt64),
+            (ByteMath::GreaterThanEquals, "b>=", TypePrimitive::UInt64),
+            (ByteMath::LessThan, "b<", TypePrimitive::UInt64),
+            (ByteMath::LessThanEquals, "b<=", TypePrimitive::UInt64),
+        ];
+        for (byte_math, opcode, result) in ops {
+            let e = apply!(@fn Expr::ByteMath(byte_math); @arg biguint(2); @arg biguint(3));
+            assert_eq!(
+                e.resolve(&TypeContext::default()).unwrap(),
+                TypeEnum::Simple(result)
+            );
+            let compiled = e.compile_raw().unwrap();
+            assert_eq!(compiled, format!("int 3\nitob\nint 2\nitob\n{opcode}"));
+            assemble(&format!("#pragma version 8\n{compiled}")).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_explicit_conversions() {
+        // uint64 and bytes never unify with biguint implicitly
+        let e = apply!(@fn Expr::ByteMath(ByteMath::Add); @arg int!(1); @arg biguint(2));
+        assert!(e.resolve(&TypeContext::default()).is_err());
+        let e = apply!(
+            @fn Expr::ByteMath(ByteMath::Add);
+            @arg Expr::Primitive(Primitive::from("a"));
+            @arg biguint(2);
+        );
+        assert!(e.resolve(&TypeContext::default()).is_err());
+
+        let e = apply!(
+            @fn Expr::ByteMath(ByteMath::ToUInt64);
+            @arg apply!(@fn Expr::ByteMath(ByteMath::BitNot); @arg biguint(7));
+        );
+        assert_eq!(
+            e.resolve(&TypeContext::default()).unwrap(),
+            TypeEnum::Simple(TypePrimitive::UInt64)
+        );
+        assemble(&format!("#pragma version 8\n{}", e.compile_raw().unwrap())).unwrap();
+
+        let e = apply!(
+            @fn Expr::ByteMath(ByteMath::ToBytes(32));
+            @arg apply!(
+                @fn Expr::ByteMath(ByteMath::FromBytes);
+                @arg Expr::Primitive(Primitive::Byteslice(vec![1; 20]));
+            );
+        );
+        assert_eq!(
+            e.resolve(&TypeContext::default()).unwrap(),
+            TypeEnum::Simple(TypePrimitive::Byteslice)
+        );
+        let compiled = e.compile_raw().unwrap();
+        assert!(compiled.ends_with(
+            "dup\nlen\nint 64\n<=\nassert\ndup\nbitlen\nint 256\n<=\nassert\nint 32\nbzero\n\
+             b|\ndup\nlen\ndup\nint 32\n-\nswap\nsubstring3"
+        ));
+        assemble(&format!("#pragma version 8\n{compiled}")).unwrap();
+
+        let e = apply!(@fn Expr::ByteMath(ByteMath::ToBytes(65)); @arg biguint(1));
+        assert!(e.compile_raw().is_err());
+    }
+
+    #[test]
+    fn test_to_bytes() {
+        let to_bytes = |width: u8, value: Vec<u8>| {
+            let e = apply!(
+                @fn Expr::ByteMath(ByteMath::ToBytes(width));
+                @arg apply!(
+                    @fn Expr::ByteMath(ByteMath::FromBytes);
+                    @arg Expr::Primitive(Primitive::Byteslice(value));
+                );
+            );
+            let mut eval = Eval::default();
+            eval.run(&e.compile_raw().unwrap()).map(|_| eval.stack)
+        };
+        // leading zero bytes do not count against the width
+        assert_eq!(
+            to_bytes(2, vec![0, 0, 0, 0, 0, 0, 0, 3]),
+            Ok(vec![Value::Bytes(vec![0, 3])])
+        );
+        assert_eq!(
+            to_bytes(4, vec![1, 2]),
+            Ok(vec![Value::Bytes(vec![0, 0, 1, 2])])
+        );
+        assert!(to_bytes(2, vec![0, 1, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_versions() {
+        let compile_at = |e: &Expr, version: u64| {
+            e.compile(
+                &CompilationContext {
+                    version,
+                    ..Default::default()
+                },
+                &mut vec![],
+            )
+        };
+        let e = apply!(@fn Expr::ByteMath(ByteMath::Add); @arg biguint(2); @arg biguint(3));
+        assert!(matches!(
+            compile_at(&e, 3),
+            Err(CompilationError::UnsupportedOpcode(op, 4, 3)) if op == "b+"
+        ));
+        let e = apply!(@fn Expr::ByteMath(ByteMath::BitNot); @arg biguint(2));
+        assert!(matches!(
+            compile_at(&e, 3),
+            Err(CompilationError::UnsupportedOpcode(op, 4, 3)) if op == "b~"
+        ));
+        let e = apply!(@fn Expr::ByteMath(ByteMath::ToUInt64); @arg biguint(2));
+        assert!(matches!(
+            compile_at(&e, 4),
+            Err(CompilationError::UnsupportedOpcode(op, 5, 4)) if op == "extract_uint64"
+        ));
+        compile_at(&e, 5).unwrap();
+        let e = apply!(@fn Expr::ByteMath(ByteMath::ToBytes(8)); @arg biguint(2));
+        let compiled = compile_at(&e, 4).unwrap();
+        assemble(&format!("#pragma version 4\n{compiled}")).unwrap();
+    }
+}
@@ -0,0 +1,356 @@
+use crate::{
+    compilation_error::CompilationError,
+    context::{CompilationContext, TypeContext},
+    typing::{TypeEnum, TypeError, TypePrimitive, TypeVar},
+    OP_SEPARATOR,
+};
+
+use super::{primitive::uint64_literal, Expression};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Bytes {
+    Concat,
+    Len,
+    // (bytes, start, end)
+    Substring,
+    // (bytes, start, length)
+    Extract,
+    // (bytes, start), everything from start to the end
+    SliceFrom,
+    // (bytes, start)
+    ExtractUInt16,
+    ExtractUInt32,
+    ExtractUInt64,
+    // (bytes, start, replacement)
+    Replace,
+    // (bytes, index)
+    GetByte,
+    // (bytes, index, value)
+    SetByte,
+    // (bytes or uint, index)
+    GetBit,
+    // (bytes or uint, index, value)
+    SetBit,
+}
+
+fn arrows(params: Vec<TypeEnum>, result: TypeEnum) -> TypeEnum {
+    params.into_iter().rev().fold(result, |body, param| {
+        TypeEnum::Arrow(Box::new(param), Box::new(body))
+    })
+}
+
+// immediate arguments are single bytes
+fn immediate(compiled: &str) -> Option<u64> {
+    uint64_literal(compiled).filter(|v| *v <= u8::MAX as u64)
+}
+
+impl Bytes {
+    fn arity(&self) -> usize {
+        match self {
+            Bytes::Len => 1,
+            Bytes::Concat
+            | Bytes::SliceFrom
+            | Bytes::ExtractUInt16
+            | Bytes::ExtractUInt32
+            | Bytes::ExtractUInt64
+            | Bytes::GetByte
+            | Bytes::GetBit => 2,
+            Bytes::Substring | Bytes::Extract | Bytes::Replace | Bytes::SetByte | Bytes::SetBit => {
+                3
+            }
+        }
+    }
+}
+
+impl Expression for Bytes {
+    fn resolve(&self, _: &TypeContext) -> Result<TypeEnum, TypeError> {
+        let bytes = || TypeEnum::Simple(TypePrimitive::Byteslice);
+        let uint64 = || TypeEnum::Simple(TypePrimitive::UInt64);
+        Ok(match self {
+            Bytes::Concat => arrows(vec![bytes(), bytes()], bytes()),
+            Bytes::Len => arrows(vec![bytes()], uint64()),
+            Bytes::Substring | Bytes::Extract => arrows(vec![bytes(), uint64(), uint64()], bytes()),
+            Bytes::SliceFrom => arrows(vec![bytes(), uint64()], bytes()),
+            Bytes::ExtractUInt16 | Bytes::ExtractUInt32 | Bytes::ExtractUInt64 | Bytes::GetByte => {
+                arrows(vec![bytes(), uint64()], uint64())
+            }
+            Bytes::Replace => arrows(vec![bytes(), uint64(), bytes()], bytes()),
+            Bytes::SetByte => arrows(vec![bytes(), uint64(), uint64()], bytes()),
+            // getbit and setbit work on both uint64 and bytes
+            Bytes::GetBit => arrows(vec![TypeEnum::Var(TypeVar::new()), uint64()], uint64()),
+            Bytes::SetBit => {
+                let tv = TypeVar::new();
+                arrows(
+                    vec![TypeEnum::Var(tv.clone()), uint64(), uint64()],
+                    TypeEnum::Var(tv),
+                )
+            }
+        })
+    }
+
+    fn compile(
+        &self,
+        context: &CompilationContext,
+        prepared_stack: &mut Vec<String>,
+    ) -> Result<String, CompilationError> {
+        // arguments are on the prepared stack with the first one on top
+        let args = (0..self.arity())
+            .map(|_| prepared_stack.pop().ok_or(CompilationError::MissingStack))
+            .collect::<Result<Vec<_>, _>>()?;
+        let with = |args: &[String], ops: &[&str]| {
+            context.require_ops(ops)?;
+            Ok(args
+                .iter()
+                .map(String::as_str)
+                .chain(ops.iter().copied())
+                .collect::<Vec<_>>()
+                .join(OP_SEPARATOR))
+        };
+
+        match self {
+            Bytes::Concat => with(&args, &["concat"]),
+            Bytes::Len => with(&args, &["len"]),
+            Bytes::Substring => match (immediate(&args[1]), immediate(&args[2])) {
+                (Some(start), Some(end)) => {
+                    with(&args[..1], &[&format!("substring {start} {end}")])
+                }
+                _ => with(&args, &["substring3"]),
+            },
+            Bytes::Extract => match (immediate(&args[1]), immediate(&args[2])) {
+                // a zero length immediate means "to the end", so only the stack form fits
+                (Some(start), Some(length)) if length > 0 && context.version >= 5 => {
+                    with(&args[..1], &[&format!("extract {start} {length}")])
+                }
+                (Some(start), Some(length)) if context.version < 5 && start + length <= 255 => {
+                    with(
+                        &args[..1],
+                        &[&format!("substring {start} {}", start + length)],
+                    )
+                }
+                _ if context.version >= 5 => with(&args, &["extract3"]),
+                // the end is the start plus the length
+                _ => with(&args, &["dig 1", "+", "substring3"]),
+            },
+            Bytes::SliceFrom => match immediate(&args[1]) {
+                Some(start) if context.version >= 5 => {
+                    with(&args[..1], &[&format!("extract {start} 0")])
+                }
+                _ => with(&args, &["dig 1", "len", "substring3"]),
+            },
+            Bytes::ExtractUInt16 => with(&args, &["extract_uint16"]),
+            Bytes::ExtractUInt32 => with(&args, &["extract_uint32"]),
+            Bytes::ExtractUInt64 => with(&args, &["extract_uint64"]),
+            Bytes::Replace => match immediate(&args[1]) {
+                Some(start) => with(
+                    &[args[0].clone(), args[2].clone()],
+                    &[&format!("replace2 {start}")],
+                ),
+                None => with(&args, &["replace3"]),
+            },
+            Bytes::GetByte => with(&args, &["getbyte"]),
+            Bytes::SetByte => with(&args, &["setbyte"]),
+            Bytes::GetBit => with(&args, &["getbit"]),
+            Bytes::SetBit => with(&args, &["setbit"]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        apply,
+        assembly::{
+            assemble,
+            eval::{Eval, Value},
+        },
+        compilation_error::CompilationError,
+        context::{CompilationContext, TypeContext},
+        expression::{apply::Apply, primitive::Primitive, txn::Txn, Expr, Expression},
+        int,
+        typing::{TypeEnum, TypePrimitive},
+    };
+
+    use super::Bytes;
+
+    fn b(s: &str) -> Expr {
+        Expr::Primitive(Primitive::from(s))
+    }
+
+    fn compile(e: &Expr, version: u64) -> String {
+        let compiled = e
+            .compile(
+                &CompilationContext {
+                    version,
+                    ..Default::default()
+                },
+                &mut vec![],
+            )
+            .unwrap();
+        assemble(&format!("#pragma version {version}\n{compiled}")).unwrap();
+        compiled
+    }
+
+    #[test]
+    fn test_types() {
+        let cases = [
+            (
+                apply!(@fn Expr::Bytes(Bytes::Concat); @arg b("a"); @arg b("b")),
+                TypePrimitive::Byteslice,
+            ),
+            (
+                apply!(@fn Expr::Bytes(Bytes::Len); @arg b("a")),
+                TypePrimitive::UInt64,
+            ),
+            (
+                apply!(@fn Expr::Bytes(Bytes::ExtractUInt64); @arg b("a"); @arg int!(0)),
+                TypePrimitive::UInt64,
+            ),
+            (
+                apply!(@fn Expr::Bytes(Bytes::Replace); @arg b("abc"); @arg int!(1); @arg b("x")),
+                TypePrimitive::Byteslice,
+            ),
+            (
+                apply!(@fn Expr::Bytes(Bytes::GetBit); @arg int!(8); @arg int!(3)),
+                TypePrimitive::UInt64,
+            ),
+            (
+                apply!(@fn Expr::Bytes(Bytes::SetBit); @arg b("a"); @arg int!(3); @arg int!(1)),
+                TypePrimitive::Byteslice,
+            ),
+        ];
+        for (e, expected) in cases {
+            e.resolve(&TypeContext::default())
+                .unwrap()
+                .unify(&mut TypeEnum::Simple(expected))
+                .unwrap();
+        }
+        let e = apply!(@fn Expr::Bytes(Bytes::Len); @arg int!(1));
+        assert!(e.resolve(&TypeContext::default()).is_err());
+        let e = apply!(@fn Expr::Bytes(Bytes::SetBit); @arg int!(8); @arg int!(3); @arg int!(1));
+        assert!(e
+            .resolve(&TypeContext::default())
+            .unwrap()
+            .unify(&mut TypeEnum::Simple(TypePrimitive::Byteslice))
+            .is_err());
+    }
+
+    #[test]
+    fn test_static_forms() {
+        let e =
+            apply!(@fn Expr::Bytes(Bytes::Substring); @arg b("abcd"); @arg int!(1); @arg int!(3));
+        assert_eq!(compile(&e, 8), "byte \"abcd\"\nsubstring 1 3");
+        let e = apply!(@fn Expr::Bytes(Bytes::Extract); @arg b("abcd"); @arg int!(1); @arg int!(2));
+        assert_eq!(compile(&e, 8), "byte \"abcd\"\nextract 1 2");
+        assert_eq!(compile(&e, 4), "byte \"abcd\"\nsubstring 1 3");
+        let e = apply!(@fn Expr::Bytes(Bytes::SliceFrom); @arg b("abcd"); @arg int!(2));
+        assert_eq!(compile(&e, 8), "byte \"abcd\"\nextract 2 0");
+        let e = apply!(@fn Expr::Bytes(Bytes::Replace); @arg b("abcd"); @arg int!(2); @arg b("x"));
+        assert_eq!(compile(&e, 8), "byte \"abcd\"\nbyte \"x\"\nreplace2 2");
+    }
+
+    #[test]
+    fn test_dynamic_forms() {
+        let start = Expr::Txn(Txn::GroupIndex);
+        let e = apply!(@fn Expr::Bytes(Bytes::Substring); @arg b("abcd"); @arg start.clone(); @arg int!(3));
+        assert_eq!(
+            compile(&e, 8),
+            "byte \"abcd\"\ntxn GroupIndex\nint 3\nsubstring3"
+        );
+        // zero length and out of range immediates fall back to the stack form
+        let e = apply!(@fn Expr::Bytes(Bytes::Extract); @arg b("abcd"); @arg int!(1); @arg int!(0));
+        assert_eq!(compile(&e, 8), "byte \"abcd\"\nint 1\nint 0\nextract3");
+        let e =
+            apply!(@fn Expr::Bytes(Bytes::Substring); @arg b("abcd"); @arg int!(1); @arg int!(300));
+        assert_eq!(compile(&e, 8), "byte \"abcd\"\nint 1\nint 300\nsubstring3");
+        let e = apply!(@fn Expr::Bytes(Bytes::SliceFrom); @arg b("abcd"); @arg start.clone());
+        assert_eq!(
+            compile(&e, 8),
+            "byte \"abcd\"\ntxn GroupIndex\ndig 1\nlen\nsubstring3"
+        );
+        let e = apply!(@fn Expr::Bytes(Bytes::Replace); @arg b("abcd"); @arg start; @arg b("x"));
+        assert_eq!(
+            compile(&e, 8),
+            "byte \"abcd\"\ntxn GroupIndex\nbyte \"x\"\nreplace3"
+        );
+    }
+
+    #[test]
+    fn test_versions() {
+        let compile_at = |e: &Expr, version: u64| {
+            e.compile(
+                &CompilationContext {
+                    version,
+                    ..Default::default()
+                },
+                &mut vec![],
+            )
+        };
+        let start = Expr::Txn(Txn::GroupIndex);
+
+        // extract3 is lowered to substring3 before version 5
+        let e = apply!(@fn Expr::Bytes(Bytes::Extract); @arg b("abcd"); @arg start.clone(); @arg int!(2));
+        assert_eq!(
+            compile(&e, 4),
+            "byte \"abcd\"\ntxn GroupIndex\nint 2\ndig 1\n+\nsubstring3"
+        );
+        let mut eval = Eval::default();
+        eval.run(&compile(&e, 4)).unwrap();
+        assert_eq!(eval.stack, vec![Value::Bytes(b"ab".to_vec())]);
+        assert_eq!(
+            compile(&e, 6),
+            "byte \"abcd\"\ntxn GroupIndex\nint 2\nextract3"
+        );
+        let e = apply!(@fn Expr::Bytes(Bytes::SliceFrom); @arg b("abcd"); @arg int!(1));
+        assert_eq!(
+            compile(&e, 4),
+            "byte \"abcd\"\nint 1\ndig 1\nlen\nsubstring3"
+        );
+
+        // the rest is rejected
+        let e = apply!(@fn Expr::Bytes(Bytes::ExtractUInt16); @arg b("abcd"); @arg int!(1));
+        assert!(matches!(
+            compile_at(&e, 4),
+            Err(CompilationError::UnsupportedOpcode(op, 5, 4)) if op == "extract_uint16"
+        ));
+        let e = apply!(@fn Expr::Bytes(Bytes::Replace); @arg b("abcd"); @arg int!(2); @arg b("x"));
+        assert!(matches!(
+            compile_at(&e, 6),
+            Err(CompilationError::UnsupportedOpcode(op, 7, 6)) if op == "replace2"
+        ));
+        let e = apply!(@fn Expr::Bytes(Bytes::Replace); @arg b("abcd"); @arg start.clone(); @arg b("x"));
+        assert!(matches!(
+            compile_at(&e, 6),
+            Err(CompilationError::UnsupportedOpcode(op, 7, 6)) if op == "replace3"
+        ));
+        let e = apply!(@fn Expr::Bytes(Bytes::GetBit); @arg int!(8); @arg int!(3));
+        assert!(matches!(
+            compile_at(&e, 2),
+            Err(CompilationError::UnsupportedOpcode(op, 3, 2)) if op == "getbit"
+        ));
+        let e = apply!(@fn Expr::Bytes(Bytes::SliceFrom); @arg b("abcd"); @arg start);
+        assert!(matches!(
+            compile_at(&e, 2),
+            Err(CompilationError::UnsupportedOpcode(op, 3, 2)) if op == "dig"
+        ));
+    }
+
+    #[test]
+    fn test_byte_and_bit_access() {
+        let e = apply!(@fn Expr::Bytes(Bytes::GetByte); @arg b("abcd"); @arg int!(1));
+        assert_eq!(compile(&e, 8), "byte \"abcd\"\nint 1\ngetbyte");
+        let e = apply!(@fn Expr::Bytes(Bytes::SetByte); @arg b("abcd"); @arg int!(1); @arg int!(7));
+        assert_eq!(compile(&e, 8), "byte \"abcd\"\nint 1\nint 7\nsetbyte");
+        let e = apply!(@fn Expr::Bytes(Bytes::GetBit); @arg int!(8); @arg int!(3));
+        assert_eq!(compile(&e, 8), "int 8\nint 3\ngetbit");
+        let e = apply!(@fn Expr::Bytes(Bytes::SetBit); @arg int!(8); @arg int!(3); @arg int!(0));
+        assert_eq!(compile(&e, 8), "int 8\nint 3\nint 0\nsetbit");
+        let e = apply!(@fn Expr::Bytes(Bytes::ExtractUInt16); @arg b("abcd"); @arg int!(1));
+        assert_eq!(compile(&e, 8), "byte \"abcd\"\nint 1\nextract_uint16");
+        let e = apply!(@fn Expr::Bytes(Bytes::ExtractUInt32); @arg b("abcd"); @arg int!(0));
+        assert_eq!(compile(&e, 8), "byte \"abcd\"\nint 0\nextract_uint32");
+        let e = apply!(@fn Expr::Bytes(Bytes::Concat); @arg b("ab"); @arg b("cd"));
+        assert_eq!(compile(&e, 8), "byte \"ab\"\nbyte \"cd\"\nconcat");
+        let e = apply!(@fn Expr::Bytes(Bytes::Len); @arg b("ab"));
+        assert_eq!(compile(&e, 8), "byte \"ab\"\nlen");
+    }
+}
@@ -1,16 +1,132 @@
 use crate::{
     compilation_error::CompilationError,
     context::{CompilationContext, TypeContext},
+    effects::effects,
     label::create_label_id,
     typing::{TypeEnum, TypeError, TypePrimitive},
     OP_SEPARATOR,
 };
 
-use super::{Expr, Expression};
+use super::{binary::Binary, primitive::Primitive, Expr, Expression};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Cond(pub Expr, pub Expr, pub Option<Box<Cond>>);
 
+// `scrutinee == constant` (in either order) as (scrutinee, constant)
+fn dispatch_arm(test: &Expr) -> Option<(&Expr, &Expr)> {
+    let (lhs, rhs) = match test {
+        Expr::Apply(outer) => match &outer.0 {
+            Expr::Apply(inner) if inner.0 == Expr::Binary(Binary::Equals) => (&outer.1, &inner.1),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    let is_constant = |e: &Expr| matches!(e, Expr::Primitive(_) | Expr::OnComplete(_));
+    // evaluated once instead of once per arm, failing the same way the first arm would
+    let once = |e: &Expr| effects(e).reads_or_fails();
+    match (is_constant(lhs), is_constant(rhs)) {
+        (false, true) if once(lhs) => Some((lhs, rhs)),
+        (true, false) if once(rhs) => Some((rhs, lhs)),
+        _ => None,
+    }
+}
+
+fn constant_value(constant: &Expr) -> Option<u64> {
+    match constant {
+        Expr::Primitive(Primitive::UInt64(v)) => Some(*v),
+        Expr::OnComplete(on_complete) => Some(on_complete.clone() as u64),
+        _ => None,
+    }
+}
+
+impl Cond {
+    // v8+: leading arms comparing one scrutinee against constants become a single `switch`
+    // (dense small integers) or `match`, the remaining arms are the fallthrough
+    fn compile_jump_table(
+        &self,
+        context: &CompilationContext,
+    ) -> Result<Option<String>, CompilationError> {
+        let mut scrutinee = None;
+        let mut arms: Vec<(&Expr, &Expr)> = vec![];
+        let mut rest = Some(self);
+        while let Some(Cond(test, body, continuation)) = rest {
+            match dispatch_arm(test) {
+                Some((s, constant)) if scrutinee.is_none_or(|x| x == s) => {
+                    scrutinee = Some(s);
+                    // a repeated constant can never be reached in the chain either
+                    if !arms.iter().any(|(c, _)| *c == constant) {
+                        arms.push((constant, body));
+                    }
+                    rest = continuation.as_deref();
+                }
+                _ => break,
+            }
+        }
+        let scrutinee = match scrutinee {
+            Some(s) if arms.len() >= 2 => s,
+            _ => return Ok(None),
+        };
+
+        let label_id = format!("cond{}", create_label_id());
+        let arm_label = |i: usize| format!("{label_id}_{i}");
+        let default_label = format!("{label_id}_default");
+        let end_label = format!("{label_id}_end");
+
+        let mut pieces = vec![];
+        let values = arms
+            .iter()
+            .map(|(c, _)| constant_value(c))
+            .collect::<Option<Vec<u64>>>();
+        match values {
+            Some(values)
+                if values
+                    .iter()
+                    .all(|v| *v < 2 * arms.len() as u64 && *v < u8::MAX as u64) =>
+            {
+                let targets = (0..=*values.iter().max().unwrap())
+                    .map(|v| match values.iter().position(|x| *x == v) {
+                        Some(i) => arm_label(i),
+                        None => default_label.clone(),
+                    })
+                    .collect::<Vec<_>>();
+                pieces.push(scrutinee.compile(context, &mut vec![])?);
+                pieces.push(format!("switch {}", targets.join(" ")));
+                if targets.contains(&default_label) {
+                    pieces.push(format!("{default_label}:"));
+                }
+            }
+            _ => {
+                for (constant, _) in &arms {
+                    pieces.push(constant.compile(context, &mut vec![])?);
+                }
+                pieces.push(scrutinee.compile(context, &mut vec![])?);
+                pieces.push(format!(
+                    "match {}",
+                    (0..arms.len()).map(arm_label).collect::<Vec<_>>().join(" ")
+                ));
+            }
+        }
+
+        match rest {
+            Some(c) => {
+                pieces.push(c.compile(context, &mut vec![])?);
+                pieces.push(format!("b {end_label}"));
+            }
+            None => pieces.push("err".to_string()),
+        }
+        for (i, (_, body)) in arms.iter().enumerate() {
+            pieces.push(format!("{}:", arm_label(i)));
+            pieces.push(body.compile(context, &mut vec![])?);
+            if i + 1 < arms.len() {
+                pieces.push(format!("b {end_label}"));
+            }
+        }
+        pieces.push(format!("{end_label}:"));
+
+        Ok(Some(pieces.join(OP_SEPARATOR)))
+    }
+}
+
 impl Expression for Cond {
     fn resolve(&self, context: &TypeContext) -> Result<TypeEnum, TypeError> {
         let Cond(test, body, continuation) = self;
@@ -31,22 +147,34 @@ impl Expression for Cond {
         context: &CompilationContext,
         _: &mut Vec<String>,
     ) -> Result<String, CompilationError> {
+        if context.version >= 8 {
+            if let Some(compiled) = self.compile_jump_table(context)? {
+                return Ok(compiled);
+            }
+        }
+
         let label_id = format!("cond{}", create_label_id());
         let Cond(test, body, continuation) = self;
 
-        let continuation = if let Some(c) = continuation {
-            c.compile(context, &mut vec![])?
-        } else {
-            "err".to_string()
-        };
-
-        let pieces = vec![
+        let mut pieces = vec![
             test.compile(context, &mut vec![])?,
             format!("bnz {label_id}"),
-            continuation,
-            format!("{label_id}:"),
-            body.compile(context, &mut vec![])?,
         ];
+        match continuation {
+            // the continuation must not fall through into this arm's body
+            Some(c) => pieces.extend([
+                c.compile(context, &mut vec![])?,
+                format!("b {label_id}_end"),
+                format!("{label_id}:"),
+                body.compile(context, &mut vec![])?,
+                format!("{label_id}_end:"),
+            ]),
+            None => pieces.extend([
+                "err".to_string(),
+                format!("{label_id}:"),
+                body.compile(context, &mut vec![])?,
+            ]),
+        }
 
         Ok(pieces.join(OP_SEPARATOR))
     }
@@ -56,10 +184,76 @@ impl Expression for Cond {
 mod tests {
     use super::*;
     use crate::{
+        apply,
+        assembly::assemble,
+        binop, bytes,
         context::TypeContext,
-        expression::{cond::Cond, primitive::Primitive, Expression},
+        expression::{
+            apply::Apply, cond::Cond, constant::OnComplete, primitive::Primitive, txn::Txn,
+            Expression,
+        },
+        int,
     };
 
+    // label ids come from a global counter, renumber them in order of appearance
+    fn normalize_labels(teal: &str) -> String {
+        let mut ids: Vec<String> = vec![];
+        teal.lines()
+            .map(|line| {
+                line.split(' ')
+                    .map(|token| match token.strip_prefix("cond") {
+                        Some(rest) if rest.starts_with(|c: char| c.is_ascii_digit()) => {
+                            let digits = rest.len()
+                                - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+                            let id = rest[..digits].to_string();
+                            let n = ids.iter().position(|i| *i == id).unwrap_or_else(|| {
+                                ids.push(id);
+                                ids.len() - 1
+                            });
+                            format!("cond{n}{}", &rest[digits..])
+                        }
+                        _ => token.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn compile(cond: &Cond, version: u64) -> String {
+        normalize_labels(
+            &cond
+                .compile(
+                    &CompilationContext {
+                        version,
+                        ..Default::default()
+                    },
+                    &mut vec![],
+                )
+                .unwrap(),
+        )
+    }
+
+    fn on_completion_arms(tail: Option<Box<Cond>>) -> Cond {
+        let arm = |on_complete: OnComplete, body: Expr, continuation| {
+            Cond(
+                binop!((Expr::Txn(Txn::OnCompletion)) == (Expr::OnComplete(on_complete))),
+                body,
+                continuation,
+            )
+        };
+        arm(
+            OnComplete::NoOp,
+            int!(10),
+            Some(Box::new(arm(
+                OnComplete::OptIn,
+                int!(11),
+                Some(Box::new(arm(OnComplete::CloseOut, int!(12), tail))),
+            ))),
+        )
+    }
+
     #[test]
     fn test() {
         let prog = Cond(
@@ -74,4 +268,164 @@ mod tests {
         println!("{:?}", prog.resolve(&TypeContext::default()));
         println!("{}", prog.compile_raw().unwrap());
     }
+
+    #[test]
+    fn test_chain() {
+        assert_eq!(
+            compile(&on_completion_arms(None), 7),
+            [
+                "txn OnCompletion",
+                "int NoOp",
+                "==",
+                "bnz cond0",
+                "txn OnCompletion",
+                "int OptIn",
+                "==",
+                "bnz cond1",
+                "txn OnCompletion",
+                "int CloseOut",
+                "==",
+                "bnz cond2",
+                "err",
+                "cond2:",
+                "int 12",
+                "b cond1_end",
+                "cond1:",
+                "int 11",
+                "cond1_end:",
+                "b cond0_end",
+                "cond0:",
+                "int 10",
+                "cond0_end:",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_switch() {
+        assert_eq!(
+            compile(&on_completion_arms(None), 8),
+            [
+                "txn OnCompletion",
+                "switch cond0_0 cond0_1 cond0_2",
+                "err",
+                "cond0_0:",
+                "int 10",
+                "b cond0_end",
+                "cond0_1:",
+                "int 11",
+                "b cond0_end",
+                "cond0_2:",
+                "int 12",
+                "cond0_end:",
+            ]
+            .join("\n")
+        );
+        assemble(&format!(
+            "#pragma version 8\n{}",
+            compile(&on_completion_arms(None), 8)
+        ))
+        .unwrap();
+    }
+
+    #[test]
+    fn test_switch_gaps() {
+        let cond = Cond(
+            binop!((Expr::Txn(Txn::OnCompletion)) == (Expr::OnComplete(OnComplete::OptIn))),
+            int!(1),
+            Some(Box::new(Cond(
+                binop!((Expr::OnComplete(OnComplete::CloseOut)) == (Expr::Txn(Txn::OnCompletion))),
+                int!(2),
+                Some(Box::new(Cond(int!(1), int!(3), None))),
+            ))),
+        );
+        assert_eq!(
+            compile(&cond, 8),
+            [
+                "txn OnCompletion",
+                "switch cond0_default cond0_0 cond0_1",
+                "cond0_default:",
+                "int 1",
+                "bnz cond1",
+                "err",
+                "cond1:",
+                "int 3",
+                "b cond0_end",
+                "cond0_0:",
+                "int 1",
+                "b cond0_end",
+                "cond0_1:",
+                "int 2",
+                "cond0_end:",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_match() {
+        let arm = |selector: &str, body: Expr, continuation| {
+            Cond(
+                binop!((Expr::Txn(Txn::Sender)) == (bytes!(selector.into()))),
+                body,
+                continuation,
+            )
+        };
+        let cond = arm(
+            "a",
+            int!(1),
+            Some(Box::new(arm(
+                "b",
+                int!(2),
+                Some(Box::new(arm("a", int!(3), None))),
+            ))),
+        );
+        assert_eq!(
+            compile(&cond, 8),
+            [
+                "byte \"a\"",
+                "byte \"b\"",
+                "txn Sender",
+                "match cond0_0 cond0_1",
+                "err",
+                "cond0_0:",
+                "int 1",
+                "b cond0_end",
+                "cond0_1:",
+                "int 2",
+                "cond0_end:",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_match_sparse() {
+        let cond = Cond(
+            binop!((Expr::Txn(Txn::Fee)) == (int!(1000))),
+            int!(1),
+            Some(Box::new(Cond(
+                binop!((Expr::Txn(Txn::Fee)) == (int!(2000))),
+                int!(2),
+                None,
+            ))),
+        );
+        assert!(
+            compile(&cond, 8).starts_with("int 1000\nint 2000\ntxn Fee\nmatch cond0_0 cond0_1\n")
+        );
+    }
+
+    #[test]
+    fn test_mixed_scrutinees() {
+        // the leading arm tests a different scrutinee, the rest still becomes a jump table
+        let cond = Cond(
+            binop!((Expr::Txn(Txn::ApplicationID)) == (int!(0))),
+            int!(0),
+            Some(Box::new(on_completion_arms(None))),
+        );
+        assert!(compile(&cond, 8).starts_with(
+            "txn ApplicationID\nint 0\n==\nbnz cond0\ntxn OnCompletion\nswitch cond1_0"
+        ));
+    }
 }
@@ -1,3 +1,5 @@
+use strum_macros::{Display, EnumString};
+
 use crate::{
     compilation_error::CompilationError,
     context::{CompilationContext, TypeContext},
@@ -6,7 +8,7 @@ use crate::{
 
 use super::Expression;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, EnumString)]
 pub enum OnComplete {
     NoOp,
     OptIn,
@@ -30,13 +32,51 @@ impl Expression for OnComplete {
     }
 }
 
+// Values of the TypeEnum transaction field
+#[derive(Debug, Clone, PartialEq, Display, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum TxnType {
+    Unknown,
+    Pay,
+    KeyReg,
+    Acfg,
+    Axfer,
+    Afrz,
+    Appl,
+}
+
+impl Expression for TxnType {
+    fn resolve(&self, _: &TypeContext) -> Result<TypeEnum, TypeError> {
+        Ok(TypeEnum::Simple(TypePrimitive::UInt64))
+    }
+
+    fn compile(
+        &self,
+        _: &CompilationContext,
+        _: &mut Vec<String>,
+    ) -> Result<String, CompilationError> {
+        Ok(format!("int {self}"))
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::expression::{constant::OnComplete, Expression};
+    use std::str::FromStr;
+
+    use crate::expression::{
+        constant::{OnComplete, TxnType},
+        Expression,
+    };
 
     #[test]
     fn test() {
         let e = OnComplete::NoOp;
         assert_eq!(e.compile_raw().unwrap(), "int NoOp");
     }
+
+    #[test]
+    fn test_txn_type() {
+        assert_eq!(TxnType::from_str("keyreg").unwrap(), TxnType::KeyReg);
+        assert_eq!(TxnType::Axfer.compile_raw().unwrap(), "int axfer");
+    }
 }
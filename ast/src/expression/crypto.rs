@@ -0,0 +1,234 @@
+use strum_macros::{Display, EnumString};
+
+use crate::{
+    assembly::opcode::lookup,
+    compilation_error::CompilationError,
+    context::{CompilationContext, TypeContext},
+    typing::{TypeEnum, TypeError, TypePrimitive},
+    OP_SEPARATOR,
+};
+
+use super::Expression;
+
+#[derive(Debug, Clone, Copy, PartialEq, Display, EnumString)]
+pub enum Curve {
+    Secp256k1,
+    Secp256r1,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Crypto {
+    Sha256,
+    Keccak256,
+    Sha512_256,
+    Sha3_256,
+    // (data, signature, public key)
+    Ed25519Verify,
+    Ed25519VerifyBare,
+    // (data, signature r, signature s, public key x, public key y)
+    EcdsaVerify(Curve),
+    // (data, recovery id, signature r, signature s) to the (x, y) public key, secp256k1 only
+    EcdsaPkRecover,
+}
+
+impl Crypto {
+    fn opcode(&self) -> &'static str {
+        match self {
+            Crypto::Sha256 => "sha256",
+            Crypto::Keccak256 => "keccak256",
+            Crypto::Sha512_256 => "sha512_256",
+            Crypto::Sha3_256 => "sha3_256",
+            Crypto::Ed25519Verify => "ed25519verify",
+            Crypto::Ed25519VerifyBare => "ed25519verify_bare",
+            Crypto::EcdsaVerify(_) => "ecdsa_verify",
+            Crypto::EcdsaPkRecover => "ecdsa_pk_recover",
+        }
+    }
+
+    fn arity(&self) -> usize {
+        match self {
+            Crypto::Sha256 | Crypto::Keccak256 | Crypto::Sha512_256 | Crypto::Sha3_256 => 1,
+            Crypto::Ed25519Verify | Crypto::Ed25519VerifyBare => 3,
+            Crypto::EcdsaVerify(_) => 5,
+            Crypto::EcdsaPkRecover => 4,
+        }
+    }
+
+    // the first version of the AVM that supports the operation
+    pub fn min_version(&self) -> u64 {
+        match self {
+            Crypto::EcdsaVerify(Curve::Secp256r1) => 7,
+            _ => lookup(self.opcode()).unwrap().version,
+        }
+    }
+
+    // opcode budget used by the operation
+    pub fn cost(&self) -> u64 {
+        match self {
+            Crypto::EcdsaVerify(Curve::Secp256r1) => 2500,
+            _ => lookup(self.opcode()).unwrap().cost,
+        }
+    }
+}
+
+impl Expression for Crypto {
+    fn resolve(&self, _: &TypeContext) -> Result<TypeEnum, TypeError> {
+        let bytes = || TypeEnum::Simple(TypePrimitive::Byteslice);
+        let (mut params, result) = match self {
+            Crypto::Sha256 | Crypto::Keccak256 | Crypto::Sha512_256 | Crypto::Sha3_256 => {
+                (vec![bytes()], bytes())
+            }
+            Crypto::Ed25519Verify | Crypto::Ed25519VerifyBare | Crypto::EcdsaVerify(_) => (
+                (0..self.arity()).map(|_| bytes()).collect(),
+                TypeEnum::Simple(TypePrimitive::UInt64),
+            ),
+            Crypto::EcdsaPkRecover => (
+                vec![
+                    bytes(),
+                    TypeEnum::Simple(TypePrimitive::UInt64),
+                    bytes(),
+                    bytes(),
+                ],
+                TypeEnum::Tuple(vec![bytes(), bytes()]),
+            ),
+        };
+        params.reverse();
+        Ok(params.into_iter().fold(result, |body, param| {
+            TypeEnum::Arrow(Box::new(param), Box::new(body))
+        }))
+    }
+
+    fn compile(
+        &self,
+        context: &CompilationContext,
+        prepared_stack: &mut Vec<String>,
+    ) -> Result<String, CompilationError> {
+        if context.version < self.min_version() {
+            return Err(CompilationError::UnsupportedOpcode(
+                self.opcode().to_string(),
+                self.min_version(),
+                context.version,
+            ));
+        }
+        // arguments are on the prepared stack with the first one on top
+        let mut pieces = (0..self.arity())
+            .map(|_| prepared_stack.pop().ok_or(CompilationError::MissingStack))
+            .collect::<Result<Vec<_>, _>>()?;
+        pieces.push(match self {
+            Crypto::EcdsaVerify(curve) => format!("ecdsa_verify {curve}"),
+            Crypto::EcdsaPkRecover => format!("ecdsa_pk_recover {}", Curve::Secp256k1),
+            _ => self.opcode().to_string(),
+        });
+        Ok(pieces.join(OP_SEPARATOR))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        apply,
+        assembly::assemble,
+        compilation_error::CompilationError,
+        context::{CompilationContext, TypeContext},
+        expression::{apply::Apply, primitive::Primitive, Expr, Expression},
+        int,
+        typing::{TypeEnum, TypePrimitive},
+    };
+
+    use super::{Crypto, Curve};
+
+    fn b(s: &str) -> Expr {
+        Expr::Primitive(Primitive::from(s))
+    }
+
+    fn compile(e: &Expr, version: u64) -> Result<String, CompilationError> {
+        e.compile(
+            &CompilationContext {
+                version,
+                ..Default::default()
+            },
+            &mut vec![],
+        )
+    }
+
+    #[test]
+    fn test_hashes() {
+        for (hash, opcode, cost) in [
+            (Crypto::Sha256, "sha256", 35),
+            (Crypto::Keccak256, "keccak256", 130),
+            (Crypto::Sha512_256, "sha512_256", 45),
+            (Crypto::Sha3_256, "sha3_256", 130),
+        ] {
+            assert_eq!(hash.cost(), cost);
+            let e = apply!(@fn Expr::Crypto(hash); @arg b("secret"));
+            e.resolve(&TypeContext::default())
+                .unwrap()
+                .unify(&mut TypeEnum::Simple(TypePrimitive::Byteslice))
+                .unwrap();
+            let compiled = compile(&e, 8).unwrap();
+            assert_eq!(compiled, format!("byte \"secret\"\n{opcode}"));
+            assemble(&format!("#pragma version 8\n{compiled}")).unwrap();
+        }
+        let e = apply!(@fn Expr::Crypto(Crypto::Sha256); @arg int!(1));
+        assert!(e.resolve(&TypeContext::default()).is_err());
+    }
+
+    #[test]
+    fn test_signatures() {
+        let e = apply!(@fn Expr::Crypto(Crypto::Ed25519VerifyBare); @arg b("data"); @arg b("sig"); @arg b("pk"));
+        e.resolve(&TypeContext::default())
+            .unwrap()
+            .unify(&mut TypeEnum::Simple(TypePrimitive::UInt64))
+            .unwrap();
+        assert_eq!(
+            compile(&e, 7).unwrap(),
+            "byte \"data\"\nbyte \"sig\"\nbyte \"pk\"\ned25519verify_bare"
+        );
+
+        let e = apply!(
+            @fn Expr::Crypto(Crypto::EcdsaVerify(Curve::Secp256r1));
+            @arg b("data"); @arg b("r"); @arg b("s"); @arg b("x"); @arg b("y")
+        );
+        e.resolve(&TypeContext::default()).unwrap();
+        let compiled = compile(&e, 7).unwrap();
+        assert!(compiled.ends_with("byte \"y\"\necdsa_verify Secp256r1"));
+        assemble(&format!("#pragma version 7\n{compiled}")).unwrap();
+        assert_eq!(Crypto::EcdsaVerify(Curve::Secp256k1).cost(), 1700);
+        assert_eq!(Crypto::EcdsaVerify(Curve::Secp256r1).cost(), 2500);
+
+        let e = apply!(
+            @fn Expr::Crypto(Crypto::EcdsaPkRecover);
+            @arg b("data"); @arg int!(0); @arg b("r"); @arg b("s")
+        );
+        e.resolve(&TypeContext::default())
+            .unwrap()
+            .unify(&mut TypeEnum::Tuple(vec![
+                TypeEnum::Simple(TypePrimitive::Byteslice),
+                TypeEnum::Simple(TypePrimitive::Byteslice),
+            ]))
+            .unwrap();
+        assert!(compile(&e, 5)
+            .unwrap()
+            .ends_with("ecdsa_pk_recover Secp256k1"));
+    }
+
+    #[test]
+    fn test_versions() {
+        let e = apply!(@fn Expr::Crypto(Crypto::Sha3_256); @arg b("secret"));
+        assert!(matches!(
+            compile(&e, 6),
+            Err(CompilationError::UnsupportedOpcode(op, 7, 6)) if op == "sha3_256"
+        ));
+        let e = apply!(
+            @fn Expr::Crypto(Crypto::EcdsaVerify(Curve::Secp256r1));
+            @arg b("data"); @arg b("r"); @arg b("s"); @arg b("x"); @arg b("y")
+        );
+        assert!(compile(&e, 6).is_err());
+        let e = apply!(
+            @fn Expr::Crypto(Crypto::EcdsaVerify(Curve::Secp256k1));
+            @arg b("data"); @arg b("r"); @arg b("s"); @arg b("x"); @arg b("y")
+        );
+        assert!(compile(&e, 5).is_ok());
+        assert!(compile(&e, 4).is_err());
+    }
+}
@@ -0,0 +1,369 @@
+use std::cell::Cell;
+
+use crate::{
+    compilation_error::CompilationError,
+    context::{CompilationBinding, CompilationContext, FunctionContext, TypeContext},
+    cost::Region,
+    label::create_label_id,
+    typing::{TypeEnum, TypeError, TypePrimitive, TypeVar},
+    OP_SEPARATOR,
+};
+
+use super::{
+    bind::{compile_destructured, resolve_destructured},
+    var::Var,
+    Expr, Expression,
+};
+
+// callsub and retsub
+const SUBROUTINE_VERSION: u64 = 4;
+// proto, frame_dig and frame_bury
+const FRAME_VERSION: u64 = 8;
+
+// A user function, in scope for `body`. From version 8 its arguments and variables live in
+// its frame, so it may call itself; before that they take scratch slots.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub name: String,
+    // untyped parameters are inferred from the definition
+    pub params: Vec<(String, Option<TypePrimitive>)>,
+    pub returns: TypePrimitive,
+    pub def: Expr,
+    pub body: Expr,
+}
+
+// A call of a user function, applied to its arguments
+#[derive(Debug, Clone, PartialEq)]
+pub struct Call(pub String);
+
+pub(crate) fn function_label(name: &str, id: usize) -> String {
+    format!("fn_{name}_{id}")
+}
+
+impl Function {
+    fn param_types(&self) -> Vec<TypeEnum> {
+        self.params
+            .iter()
+            .map(|(_, t)| match t {
+                Some(t) => TypeEnum::Simple(t.clone()),
+                None => TypeEnum::Var(TypeVar::new()),
+            })
+            .collect()
+    }
+
+    fn binding(&self, id: usize, enclosing: bool) -> CompilationBinding {
+        CompilationBinding::Subroutine {
+            id,
+            arity: self.params.len(),
+            enclosing,
+        }
+    }
+}
+
+impl Expression for Function {
+    fn resolve(&self, context: &TypeContext) -> Result<TypeEnum, TypeError> {
+        let param_types = self.param_types();
+        let function_type = param_types
+            .iter()
+            .rev()
+            .fold(TypeEnum::Simple(self.returns.clone()), |returns, param| {
+                TypeEnum::Arrow(Box::new(param.clone()), Box::new(returns))
+            });
+
+        // the function is in scope in its own definition, so that it can call itself
+        let mut identifiers = vec![self.name.clone()];
+        identifiers.extend(self.params.iter().map(|(name, _)| name.clone()));
+        let mut types = vec![function_type.clone()];
+        types.extend(param_types);
+        resolve_destructured(&identifiers, &types, &self.def, context)?
+            .unify(&mut TypeEnum::Simple(self.returns.clone()))?;
+
+        resolve_destructured(
+            std::slice::from_ref(&self.name),
+            &[function_type],
+            &self.body,
+            context,
+        )
+    }
+
+    fn compile(
+        &self,
+        context: &CompilationContext,
+        _: &mut Vec<String>,
+    ) -> Result<String, CompilationError> {
+        if context.version < SUBROUTINE_VERSION {
+            return Err(CompilationError::UnsupportedOpcode(
+                "callsub".to_string(),
+                SUBROUTINE_VERSION,
+                context.version,
+            ));
+        }
+        let id = create_label_id();
+        let label = function_label(&self.name, id);
+        context.record(Region::Function {
+            start: label.clone(),
+            end: format!("end{label}"),
+        });
+        let frame = context.version >= FRAME_VERSION;
+        let arity = self.params.len();
+        let returns = u8::from(self.returns != TypePrimitive::Void);
+        let identifiers = self
+            .params
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+
+        let used = Cell::new(if frame { 0 } else { context.scratch_id });
+        let def_context = CompilationContext {
+            scope: context.scope.add(self.name.clone(), self.binding(id, true)),
+            function: Some(FunctionContext {
+                id,
+                frame,
+                next_local: 0,
+                used: &used,
+            }),
+            ..*context
+        };
+
+        let mut pieces = vec![format!("b end{label}"), format!("{label}:")];
+        if frame {
+            if arity > i8::MAX as usize {
+                return Err(CompilationError::OutOfFrameSpace);
+            }
+            // arguments sit below the frame pointer, the last one right under it
+            let bindings = (0..arity)
+                .map(|i| CompilationBinding::FrameVar(id, i as i8 - arity as i8))
+                .collect::<Vec<_>>();
+            let def = compile_destructured(&identifiers, &bindings, &self.def, &def_context)?;
+            pieces.push(format!("proto {arity} {returns}"));
+            // reserve the locals the definition uses
+            match used.get() {
+                0 => {}
+                1 => pieces.push("int 0".to_string()),
+                n => pieces.extend(["int 0".to_string(), format!("dupn {}", n - 1)]),
+            }
+            pieces.push(def);
+        } else {
+            let (bindings, allocated) = def_context.allocate(arity)?;
+            // the last argument is on top of the stack, so it is stored first
+            for binding in bindings.iter().rev() {
+                if let CompilationBinding::ScratchVar(i) = binding {
+                    pieces.push(format!("store {i}"));
+                }
+            }
+            pieces.push(compile_destructured(
+                &identifiers,
+                &bindings,
+                &self.def,
+                &allocated,
+            )?);
+            // slots the function takes must outlive every call of it
+            if let Some(enclosing) = context.function.filter(|f| !f.frame) {
+                enclosing.used.set(enclosing.used.get().max(used.get()));
+            }
+        }
+        pieces.extend(["retsub".to_string(), format!("end{label}:")]);
+
+        let body_context = CompilationContext {
+            scope: context
+                .scope
+                .add(self.name.clone(), self.binding(id, false)),
+            scratch_id: if frame {
+                context.scratch_id
+            } else {
+                used.get()
+            },
+            ..*context
+        };
+        pieces.push(self.body.compile(&body_context, &mut vec![])?);
+        Ok(pieces.join(OP_SEPARATOR))
+    }
+}
+
+impl Expression for Call {
+    fn resolve(&self, context: &TypeContext) -> Result<TypeEnum, TypeError> {
+        context
+            .bind_scope
+            .get(&self.0)
+            .cloned()
+            .ok_or_else(|| TypeError::UnboundIdentifier(Var::Bind(self.0.clone())))
+    }
+
+    fn compile(
+        &self,
+        context: &CompilationContext,
+        prepared_stack: &mut Vec<String>,
+    ) -> Result<String, CompilationError> {
+        let Some(&CompilationBinding::Subroutine {
+            id,
+            arity,
+            enclosing,
+        }) = context.scope.get(&self.0)
+        else {
+            // should never happen if type checking is run before compilation
+            return Err(TypeError::UnboundIdentifier(Var::Bind(self.0.clone())).into());
+        };
+        if enclosing && context.version < FRAME_VERSION {
+            return Err(CompilationError::RecursiveFunction(
+                self.0.clone(),
+                context.version,
+            ));
+        }
+        let mut pieces = (0..arity)
+            .map(|_| prepared_stack.pop().ok_or(CompilationError::MissingStack))
+            .collect::<Result<Vec<_>, _>>()?;
+        pieces.push(format!("callsub {}", function_label(&self.0, id)));
+        Ok(pieces.join(OP_SEPARATOR))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        apply, bind_let, binop,
+        compilation_error::CompilationError,
+        context::TypeContext,
+        expression::{
+            apply::Apply,
+            binary::Binary,
+            bind::Bind,
+            if_else::If,
+            primitive::Primitive,
+            var::{RVal, Var},
+            Expr, Expression,
+        },
+        int,
+        program::Program,
+        r#if,
+        typing::{TypeEnum, TypePrimitive},
+        val,
+    };
+
+    use super::{Call, Function};
+
+    fn call(name: &str, args: Vec<Expr>) -> Expr {
+        args.into_iter().fold(
+            Expr::Call(Call(name.to_string())),
+            |f, arg| apply!(@fn f; @arg arg),
+        )
+    }
+
+    // fn fact(n: uint64) -> uint64 { let m = n - 1; if n < 2 { 1 } else { n * fact(m) } }
+    fn factorial(body: Expr) -> Expr {
+        Expr::Function(Box::new(Function {
+            name: "fact".to_string(),
+            params: vec![("n".to_string(), Some(TypePrimitive::UInt64))],
+            returns: TypePrimitive::UInt64,
+            def: bind_let!(
+                m = binop!((val!(@scratch n)) - (int!(1)));
+                r#if!(
+                    (binop!((val!(@scratch n)) < (int!(2))))
+                    @then int!(1);
+                    @else binop!((val!(@scratch n)) * (call("fact", vec![val!(@scratch m)])))
+                )
+            ),
+            body,
+        }))
+    }
+
+    fn program(version: u64, body: Expr) -> Program {
+        Program {
+            version,
+            body,
+            budget: None,
+        }
+    }
+
+    #[test]
+    fn test_frames() {
+        let e = factorial(call("fact", vec![int!(5)]));
+        e.resolve(&TypeContext::default())
+            .unwrap()
+            .unify(&mut TypeEnum::Simple(TypePrimitive::UInt64))
+            .unwrap();
+        let compiled = program(8, e).assemble().unwrap();
+        for line in [
+            "proto 1 1\nint 0\nframe_dig -1\nint 1\n-\nframe_bury 0",
+            "frame_dig -1\nframe_dig 0\ncallsub fn_fact_",
+            "retsub\nendfn_fact_",
+            "int 5\ncallsub fn_fact_",
+        ] {
+            assert!(compiled.teal.contains(line), "{line}\n{}", compiled.teal);
+        }
+        // the recursion makes the cost unbounded
+        assert_eq!(compiled.cost, None);
+    }
+
+    #[test]
+    fn test_scratch() {
+        // fn double(x) { x + x }; let y = double(2); if y { y } else { 0 }
+        let e = Expr::Function(Box::new(Function {
+            name: "double".to_string(),
+            params: vec![("x".to_string(), None)],
+            returns: TypePrimitive::UInt64,
+            def: binop!((val!(@scratch x)) + (val!(@scratch x))),
+            body: bind_let!(
+                y = call("double", vec![int!(2)]);
+                r#if!((val!(@scratch y)) @then val!(@scratch y); @else int!(0))
+            ),
+        }));
+        e.resolve(&TypeContext::default()).unwrap();
+        let compiled = program(5, e).assemble().unwrap();
+        assert!(compiled.teal.contains("store 0\nload 0\nload 0\n+\nretsub"));
+        // the variables after the function do not reuse its slots
+        assert!(compiled.teal.contains("callsub fn_double_"));
+        assert!(compiled.teal.contains("store 1\nload 1\nbz"));
+        assert_eq!(compiled.cost, Some(14));
+
+        assert!(matches!(
+            program(3, factorial(int!(1))).compile(),
+            Err(CompilationError::UnsupportedOpcode(op, 4, 3)) if op == "callsub"
+        ));
+    }
+
+    #[test]
+    fn test_recursion_before_frames() {
+        let e = factorial(call("fact", vec![int!(5)]));
+        assert!(matches!(
+            program(7, e).compile(),
+            Err(CompilationError::RecursiveFunction(name, 7)) if name == "fact"
+        ));
+    }
+
+    #[test]
+    fn test_captured() {
+        // fn f(a) { fn g(b) { a + b }; g(1) }; f(2)
+        let g = Expr::Function(Box::new(Function {
+            name: "g".to_string(),
+            params: vec![("b".to_string(), None)],
+            returns: TypePrimitive::UInt64,
+            def: binop!((val!(@scratch a)) + (val!(@scratch b))),
+            body: call("g", vec![int!(1)]),
+        }));
+        let e = Expr::Function(Box::new(Function {
+            name: "f".to_string(),
+            params: vec![("a".to_string(), None)],
+            returns: TypePrimitive::UInt64,
+            def: g,
+            body: call("f", vec![int!(2)]),
+        }));
+        e.resolve(&TypeContext::default()).unwrap();
+        assert!(matches!(
+            program(8, e.clone()).compile(),
+            Err(CompilationError::CapturedVariable(name)) if name == "a"
+        ));
+        // scratch slots are shared, so before frames the inner function can read them
+        assert!(program(5, e).assemble().is_ok());
+    }
+
+    #[test]
+    fn test_types() {
+        let e = factorial(call(
+            "fact",
+            vec![Expr::Primitive(Primitive::Byteslice(vec![]))],
+        ));
+        assert!(e.resolve(&TypeContext::default()).is_err());
+        let unknown = call("missing", vec![int!(1)]);
+        assert!(unknown.resolve(&TypeContext::default()).is_err());
+    }
+}
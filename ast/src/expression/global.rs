@@ -0,0 +1,161 @@
+use strum_macros::{Display, EnumString};
+
+use crate::{
+    compilation_error::CompilationError,
+    context::{CompilationContext, TypeContext},
+    typing::{TypeEnum, TypeError, TypePrimitive},
+};
+
+use super::Expression;
+
+#[derive(Debug, Clone, PartialEq, Display, EnumString)]
+pub enum Global {
+    MinTxnFee,
+    MinBalance,
+    MaxTxnLife,
+    ZeroAddress,
+    GroupSize,
+    LogicSigVersion,
+    Round,
+    LatestTimestamp,
+    CurrentApplicationID,
+    CreatorAddress,
+    CurrentApplicationAddress,
+    GroupID,
+    OpcodeBudget,
+    CallerApplicationID,
+    CallerApplicationAddress,
+}
+
+impl Global {
+    // the first version of the AVM that has the field
+    pub fn min_version(&self) -> u64 {
+        match self {
+            Global::MinTxnFee
+            | Global::MinBalance
+            | Global::MaxTxnLife
+            | Global::ZeroAddress
+            | Global::GroupSize => 1,
+            Global::LogicSigVersion
+            | Global::Round
+            | Global::LatestTimestamp
+            | Global::CurrentApplicationID => 2,
+            Global::CreatorAddress => 3,
+            Global::CurrentApplicationAddress | Global::GroupID => 5,
+            Global::OpcodeBudget
+            | Global::CallerApplicationID
+            | Global::CallerApplicationAddress => 6,
+        }
+    }
+
+    // fields describing the application being run or the block it runs in, which signatures
+    // are checked before
+    pub fn is_application_only(&self) -> bool {
+        matches!(
+            self,
+            Global::Round
+                | Global::LatestTimestamp
+                | Global::CurrentApplicationID
+                | Global::CreatorAddress
+                | Global::CurrentApplicationAddress
+                | Global::CallerApplicationID
+                | Global::CallerApplicationAddress
+        )
+    }
+}
+
+impl Expression for Global {
+    fn resolve(&self, context: &TypeContext) -> Result<TypeEnum, TypeError> {
+        if self.is_application_only() {
+            context.require_application(&format!("global {self}"))?;
+        }
+        Ok(TypeEnum::Simple(match self {
+            Global::ZeroAddress
+            | Global::CreatorAddress
+            | Global::CurrentApplicationAddress
+            | Global::GroupID
+            | Global::CallerApplicationAddress => TypePrimitive::Byteslice,
+            _ => TypePrimitive::UInt64,
+        }))
+    }
+
+    fn compile(
+        &self,
+        context: &CompilationContext,
+        _: &mut Vec<String>,
+    ) -> Result<String, CompilationError> {
+        let op = format!("global {self}");
+        if context.version < self.min_version() {
+            return Err(CompilationError::UnsupportedOpcode(
+                op,
+                self.min_version(),
+                context.version,
+            ));
+        }
+        Ok(op)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::{
+        assembly::{assemble, opcode::GLOBAL_FIELDS},
+        context::{CompilationContext, Mode, TypeContext},
+        expression::Expression,
+        typing::{TypeEnum, TypeError, TypePrimitive},
+    };
+
+    use super::Global;
+
+    #[test]
+    fn test_fields() {
+        for field in GLOBAL_FIELDS {
+            let global = Global::from_str(field).unwrap();
+            let compiled = global.compile_raw().unwrap();
+            assert_eq!(compiled, format!("global {field}"));
+            assemble(&format!("#pragma version 8\n{compiled}")).unwrap();
+        }
+        Global::GroupID
+            .resolve(&TypeContext::default())
+            .unwrap()
+            .unify(&mut TypeEnum::Simple(TypePrimitive::Byteslice))
+            .unwrap();
+        Global::OpcodeBudget
+            .resolve(&TypeContext::default())
+            .unwrap()
+            .unify(&mut TypeEnum::Simple(TypePrimitive::UInt64))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_versions() {
+        let context = CompilationContext {
+            version: 5,
+            ..Default::default()
+        };
+        assert!(Global::GroupID.compile(&context, &mut vec![]).is_ok());
+        assert!(Global::OpcodeBudget.compile(&context, &mut vec![]).is_err());
+    }
+
+    #[test]
+    fn test_signature_mode() {
+        let signature = TypeContext {
+            mode: Mode::Signature,
+            ..Default::default()
+        };
+        for global in [
+            Global::Round,
+            Global::LatestTimestamp,
+            Global::CurrentApplicationID,
+        ] {
+            assert!(matches!(
+                global.resolve(&signature),
+                Err(TypeError::ApplicationOnly(op)) if op == format!("global {global}")
+            ));
+            assert!(global.resolve(&TypeContext::default()).is_ok());
+        }
+        assert!(Global::GroupSize.resolve(&signature).is_ok());
+    }
+}
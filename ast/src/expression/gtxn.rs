@@ -0,0 +1,172 @@
+use crate::{
+    compilation_error::CompilationError,
+    context::{CompilationContext, TypeContext},
+    typing::{TypeEnum, TypeError, TypePrimitive},
+};
+
+use super::{
+    apply::Apply,
+    binary::Binary,
+    primitive::{uint64_literal, Primitive},
+    txn::{FieldOps, Txn},
+    Expr, Expression,
+};
+
+// transactions a group holds at most
+const MAX_GROUP_SIZE: u64 = 16;
+
+const GTXN_OPS: FieldOps = FieldOps {
+    scalar: ("gtxn", 1),
+    static_array: ("gtxna", 2),
+    dynamic_array: ("gtxnas", 5),
+};
+
+const GTXNS_OPS: FieldOps = FieldOps {
+    scalar: ("gtxns", 3),
+    static_array: ("gtxnsa", 3),
+    dynamic_array: ("gtxnsas", 5),
+};
+
+// A field of another transaction in the group, applied to the group index and then, for array
+// fields, to the array index
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gtxn(pub Txn);
+
+impl Gtxn {
+    // reads `field` of the transaction `offset` positions away from the current one
+    pub fn relative(offset: i64, field: Txn) -> Expr {
+        let (op, distance) = if offset < 0 {
+            (Binary::Sub, offset.unsigned_abs())
+        } else {
+            (Binary::Add, offset as u64)
+        };
+        let group_index = Expr::Apply(Box::new(Apply(
+            Expr::Apply(Box::new(Apply(
+                Expr::Binary(op),
+                Expr::Primitive(Primitive::UInt64(distance)),
+            ))),
+            Expr::Txn(Txn::GroupIndex),
+        )));
+        Expr::Apply(Box::new(Apply(Expr::Gtxn(Gtxn(field)), group_index)))
+    }
+}
+
+impl Expression for Gtxn {
+    fn resolve(&self, _: &TypeContext) -> Result<TypeEnum, TypeError> {
+        Ok(TypeEnum::Arrow(
+            Box::new(TypeEnum::Simple(TypePrimitive::UInt64)),
+            Box::new(self.0.resolve_field()),
+        ))
+    }
+
+    fn compile(
+        &self,
+        context: &CompilationContext,
+        prepared_stack: &mut Vec<String>,
+    ) -> Result<String, CompilationError> {
+        let group_index = prepared_stack.pop().ok_or(CompilationError::MissingStack)?;
+        match uint64_literal(&group_index) {
+            Some(i) if i >= MAX_GROUP_SIZE => Err(CompilationError::GroupIndexOutOfRange(i)),
+            Some(i) => self
+                .0
+                .compile_field(context, prepared_stack, &GTXN_OPS, Some(i), vec![]),
+            None => {
+                self.0
+                    .compile_field(context, prepared_stack, &GTXNS_OPS, None, vec![group_index])
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        apply,
+        assembly::assemble,
+        compilation_error::CompilationError,
+        context::{CompilationContext, TypeContext},
+        expression::{apply::Apply, primitive::Primitive, txn::Txn, Expr, Expression},
+        int,
+        typing::{TypeEnum, TypePrimitive},
+    };
+
+    use super::Gtxn;
+
+    fn compile(e: &Expr) -> String {
+        e.resolve(&TypeContext::default()).unwrap();
+        let compiled = e.compile_raw().unwrap();
+        assemble(&format!("#pragma version 8\n{compiled}")).unwrap();
+        compiled
+    }
+
+    #[test]
+    fn test_static_group_index() {
+        let e = apply!(@fn Expr::Gtxn(Gtxn(Txn::Amount)); @arg int!(0));
+        e.resolve(&TypeContext::default())
+            .unwrap()
+            .unify(&mut TypeEnum::Simple(TypePrimitive::UInt64))
+            .unwrap();
+        assert_eq!(compile(&e), "gtxn 0 Amount");
+        let e = apply!(@fn Expr::Gtxn(Gtxn(Txn::ApplicationArgs)); @arg int!(1); @arg int!(2));
+        assert_eq!(compile(&e), "gtxna 1 ApplicationArgs 2");
+        let e = apply!(
+            @fn Expr::Gtxn(Gtxn(Txn::Accounts));
+            @arg int!(1);
+            @arg Expr::Txn(Txn::NumAccounts)
+        );
+        assert_eq!(compile(&e), "txn NumAccounts\ngtxnas 1 Accounts");
+
+        // groups hold at most 16 transactions
+        let e = apply!(@fn Expr::Gtxn(Gtxn(Txn::Amount)); @arg int!(15));
+        assert_eq!(compile(&e), "gtxn 15 Amount");
+        let e = apply!(@fn Expr::Gtxn(Gtxn(Txn::Amount)); @arg int!(16));
+        assert!(matches!(
+            e.compile_raw(),
+            Err(CompilationError::GroupIndexOutOfRange(16))
+        ));
+    }
+
+    #[test]
+    fn test_dynamic_group_index() {
+        let e = apply!(@fn Expr::Gtxn(Gtxn(Txn::Receiver)); @arg Expr::Txn(Txn::NumAppArgs));
+        e.resolve(&TypeContext::default())
+            .unwrap()
+            .unify(&mut TypeEnum::Simple(TypePrimitive::Byteslice))
+            .unwrap();
+        assert_eq!(compile(&e), "txn NumAppArgs\ngtxns Receiver");
+        let e = apply!(
+            @fn Expr::Gtxn(Gtxn(Txn::ApplicationArgs));
+            @arg Expr::Txn(Txn::NumAppArgs);
+            @arg int!(0)
+        );
+        assert_eq!(compile(&e), "txn NumAppArgs\ngtxnsa ApplicationArgs 0");
+        let e = apply!(
+            @fn Expr::Gtxn(Gtxn(Txn::ApplicationArgs));
+            @arg Expr::Txn(Txn::NumAppArgs);
+            @arg Expr::Txn(Txn::NumAccounts)
+        );
+        assert_eq!(
+            compile(&e),
+            "txn NumAppArgs\ntxn NumAccounts\ngtxnsas ApplicationArgs"
+        );
+        let context = CompilationContext {
+            version: 2,
+            ..Default::default()
+        };
+        let e = apply!(@fn Expr::Gtxn(Gtxn(Txn::Receiver)); @arg Expr::Txn(Txn::NumAppArgs));
+        assert!(e.compile(&context, &mut vec![]).is_err());
+    }
+
+    #[test]
+    fn test_relative() {
+        let e = Gtxn::relative(-1, Txn::Amount);
+        assert_eq!(compile(&e), "txn GroupIndex\nint 1\n-\ngtxns Amount");
+        let e = Gtxn::relative(2, Txn::Sender);
+        assert_eq!(compile(&e), "txn GroupIndex\nint 2\n+\ngtxns Sender");
+        assert!(Gtxn::relative(1, Txn::Accounts)
+            .resolve(&TypeContext::default())
+            .unwrap()
+            .unify(&mut TypeEnum::Simple(TypePrimitive::Byteslice))
+            .is_err());
+    }
+}
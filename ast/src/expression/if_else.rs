@@ -17,9 +17,14 @@ impl Expression for If {
         let mut true_type = true_expression.resolve(context)?;
         let mut false_type = false_expression.resolve(context)?;
         true_type.unify(&mut false_type)?;
+        // a branch that halts takes the type of the other one
+        let result_type = match true_type {
+            TypeEnum::Simple(TypePrimitive::Halt) => false_type,
+            _ => true_type,
+        };
         Ok(TypeEnum::Arrow(
             Box::new(TypeEnum::Simple(TypePrimitive::UInt64)),
-            Box::new(true_type),
+            Box::new(result_type),
         ))
     }
 
@@ -0,0 +1,217 @@
+use crate::{
+    compilation_error::CompilationError,
+    context::{CompilationContext, TypeContext},
+    typing::{TypeEnum, TypeError, TypePrimitive},
+    OP_SEPARATOR,
+};
+
+use super::{
+    txn::{FieldOps, Txn},
+    Expr, Expression,
+};
+
+const ITXN_OPS: FieldOps = FieldOps {
+    scalar: ("itxn", 5),
+    static_array: ("itxna", 5),
+    dynamic_array: ("itxnas", 6),
+};
+
+// Submits a group of inner transactions, each given by the fields it sets. Array fields such as
+// ApplicationArgs may be listed repeatedly, each entry appends an element.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InnerTxn(pub Vec<Vec<(Txn, Expr)>>);
+
+// A field of the last submitted inner transaction
+#[derive(Debug, Clone, PartialEq)]
+pub struct Itxn(pub Txn);
+
+impl Expression for InnerTxn {
+    fn resolve(&self, context: &TypeContext) -> Result<TypeEnum, TypeError> {
+        context.require_application("inner transactions")?;
+        if self.0.is_empty() || self.0.iter().any(Vec::is_empty) {
+            return Err(TypeError::EmptyInnerTxn);
+        }
+        for fields in &self.0 {
+            for (i, (field, value)) in fields.iter().enumerate() {
+                if field.settable_version().is_none() {
+                    return Err(TypeError::UnsettableField(field.to_string()));
+                }
+                if !field.is_array() && fields[..i].iter().any(|(f, _)| f == field) {
+                    return Err(TypeError::DuplicateField(field.to_string()));
+                }
+                value
+                    .resolve(context)?
+                    .unify(&mut TypeEnum::Simple(field.field_type()))?;
+            }
+        }
+        Ok(TypeEnum::Simple(TypePrimitive::Void))
+    }
+
+    fn compile(
+        &self,
+        context: &CompilationContext,
+        _: &mut Vec<String>,
+    ) -> Result<String, CompilationError> {
+        let check = |op: String, version: u64| {
+            if context.version < version {
+                Err(CompilationError::UnsupportedOpcode(
+                    op,
+                    version,
+                    context.version,
+                ))
+            } else {
+                Ok(op)
+            }
+        };
+
+        let mut pieces = vec![check("itxn_begin".to_string(), 5)?];
+        for (i, fields) in self.0.iter().enumerate() {
+            if i > 0 {
+                pieces.push(check("itxn_next".to_string(), 6)?);
+            }
+            for (field, value) in fields {
+                pieces.push(value.compile(context, &mut vec![])?);
+                pieces.push(check(
+                    format!("itxn_field {field}"),
+                    field.settable_version().unwrap_or(u64::MAX),
+                )?);
+            }
+        }
+        pieces.push("itxn_submit".to_string());
+        Ok(pieces.join(OP_SEPARATOR))
+    }
+}
+
+impl Expression for Itxn {
+    fn resolve(&self, context: &TypeContext) -> Result<TypeEnum, TypeError> {
+        context.require_application("inner transactions")?;
+        Ok(self.0.resolve_field())
+    }
+
+    fn compile(
+        &self,
+        context: &CompilationContext,
+        prepared_stack: &mut Vec<String>,
+    ) -> Result<String, CompilationError> {
+        self.0
+            .compile_field(context, prepared_stack, &ITXN_OPS, None, vec![])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        apply,
+        assembly::assemble,
+        compilation_error::CompilationError,
+        context::{CompilationContext, TypeContext},
+        expression::{
+            apply::Apply, constant::TxnType, global::Global, primitive::Primitive, txn::Txn, Expr,
+            Expression,
+        },
+        int,
+        typing::{TypeEnum, TypeError, TypePrimitive},
+    };
+
+    use super::{InnerTxn, Itxn};
+
+    fn payment(amount: u64) -> Vec<(Txn, Expr)> {
+        vec![
+            (Txn::TypeEnum, Expr::TxnType(TxnType::Pay)),
+            (Txn::Receiver, Expr::Txn(Txn::Sender)),
+            (Txn::Amount, int!(amount)),
+        ]
+    }
+
+    #[test]
+    fn test_submit() {
+        let e = InnerTxn(vec![payment(1000)]);
+        e.resolve(&TypeContext::default())
+            .unwrap()
+            .unify(&mut TypeEnum::Simple(TypePrimitive::Void))
+            .unwrap();
+        let compiled = e.compile_raw().unwrap();
+        assert_eq!(
+            compiled,
+            "itxn_begin\nint pay\nitxn_field TypeEnum\ntxn Sender\nitxn_field Receiver\n\
+             int 1000\nitxn_field Amount\nitxn_submit"
+        );
+        assemble(&format!("#pragma version 8\n{compiled}")).unwrap();
+    }
+
+    #[test]
+    fn test_group() {
+        let transfer = vec![
+            (Txn::TypeEnum, Expr::TxnType(TxnType::Axfer)),
+            (Txn::XferAsset, int!(10)),
+            (
+                Txn::AssetReceiver,
+                Expr::Global(Global::CurrentApplicationAddress),
+            ),
+            (Txn::AssetAmount, int!(0)),
+        ];
+        let e = InnerTxn(vec![payment(1000), transfer]);
+        e.resolve(&TypeContext::default()).unwrap();
+        let compiled = e.compile_raw().unwrap();
+        assert!(compiled.contains("itxn_field Amount\nitxn_next\nint axfer"));
+        assemble(&format!("#pragma version 8\n{compiled}")).unwrap();
+
+        let context = CompilationContext {
+            version: 5,
+            ..Default::default()
+        };
+        assert!(matches!(
+            e.compile(&context, &mut vec![]),
+            Err(CompilationError::UnsupportedOpcode(op, 6, 5)) if op == "itxn_next"
+        ));
+    }
+
+    #[test]
+    fn test_field_checks() {
+        let e = InnerTxn(vec![vec![(
+            Txn::Amount,
+            Expr::Primitive(Primitive::from("1")),
+        )]]);
+        assert!(e.resolve(&TypeContext::default()).is_err());
+        let e = InnerTxn(vec![vec![(
+            Txn::TxID,
+            Expr::Primitive(Primitive::from("id")),
+        )]]);
+        assert!(matches!(
+            e.resolve(&TypeContext::default()),
+            Err(TypeError::UnsettableField(f)) if f == "TxID"
+        ));
+        let e = InnerTxn(vec![vec![(Txn::Amount, int!(1)), (Txn::Amount, int!(2))]]);
+        assert!(matches!(
+            e.resolve(&TypeContext::default()),
+            Err(TypeError::DuplicateField(f)) if f == "Amount"
+        ));
+        let args = vec![
+            (Txn::ApplicationArgs, Expr::Primitive(Primitive::from("a"))),
+            (Txn::ApplicationArgs, Expr::Primitive(Primitive::from("b"))),
+        ];
+        InnerTxn(vec![args])
+            .resolve(&TypeContext::default())
+            .unwrap();
+        for e in [InnerTxn(vec![]), InnerTxn(vec![payment(1), vec![]])] {
+            assert!(matches!(
+                e.resolve(&TypeContext::default()),
+                Err(TypeError::EmptyInnerTxn)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_read() {
+        assert_eq!(
+            Itxn(Txn::CreatedAssetID).compile_raw().unwrap(),
+            "itxn CreatedAssetID"
+        );
+        let e = apply!(@fn Expr::Itxn(Itxn(Txn::Logs)); @arg int!(0));
+        e.resolve(&TypeContext::default())
+            .unwrap()
+            .unify(&mut TypeEnum::Simple(TypePrimitive::Byteslice))
+            .unwrap();
+        assert_eq!(e.compile_raw().unwrap(), "itxna Logs 0");
+    }
+}
@@ -0,0 +1,199 @@
+use crate::{
+    compilation_error::CompilationError,
+    context::{CompilationContext, TypeContext},
+    typing::{TypeEnum, TypeError, TypePrimitive},
+    OP_SEPARATOR,
+};
+
+use super::{
+    arc4::{encode_ops, pack_ops},
+    Expression,
+};
+
+const LOG_VERSION: u64 = 5;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Log {
+    // (bytes), logs the bytes as they are
+    Raw,
+    // (fields...), logs an event declared in the contract
+    Emit(String),
+}
+
+impl Expression for Log {
+    fn resolve(&self, context: &TypeContext) -> Result<TypeEnum, TypeError> {
+        context.require_application("log")?;
+        let void = TypeEnum::Simple(TypePrimitive::Void);
+        match self {
+            Log::Raw => Ok(TypeEnum::Arrow(
+                Box::new(TypeEnum::Simple(TypePrimitive::Byteslice)),
+                Box::new(void),
+            )),
+            Log::Emit(name) => {
+                let event = context
+                    .events
+                    .iter()
+                    .find(|event| &event.name == name)
+                    .ok_or_else(|| TypeError::UnboundEvent(name.clone()))?;
+                Ok(event.fields().iter().rev().fold(void, |result, (_, t)| {
+                    // fields of a type with a value take the value, others their encoding
+                    let t = t
+                        .value_type()
+                        .map_or(TypeEnum::Arc4(t.clone()), TypeEnum::Simple);
+                    TypeEnum::Arrow(Box::new(t), Box::new(result))
+                }))
+            }
+        }
+    }
+
+    fn compile(
+        &self,
+        context: &CompilationContext,
+        prepared_stack: &mut Vec<String>,
+    ) -> Result<String, CompilationError> {
+        if context.version < LOG_VERSION {
+            return Err(CompilationError::UnsupportedOpcode(
+                "log".to_string(),
+                LOG_VERSION,
+                context.version,
+            ));
+        }
+        let mut pieces = vec![];
+        match self {
+            Log::Raw => pieces.push(prepared_stack.pop().ok_or(CompilationError::MissingStack)?),
+            Log::Emit(name) => {
+                let event = context
+                    .events
+                    .iter()
+                    .find(|event| &event.name == name)
+                    .ok_or_else(|| CompilationError::UnknownEvent(name.clone()))?;
+                let selector = event
+                    .selector()
+                    .iter()
+                    .map(|b| format!("{b:02x}"))
+                    .collect::<String>();
+                pieces.push(format!("byte 0x{selector}"));
+                let mut types = vec![];
+                let mut values = vec![];
+                for (_, t) in event.fields() {
+                    let mut value =
+                        vec![prepared_stack.pop().ok_or(CompilationError::MissingStack)?];
+                    if t.value_type().is_some() {
+                        value.extend(encode_ops(t)?);
+                    }
+                    values.push(value.join(OP_SEPARATOR));
+                    types.push(t.clone());
+                }
+                if !types.is_empty() {
+                    pieces.extend(pack_ops(&types, values));
+                    pieces.push("concat".to_string());
+                }
+            }
+        }
+        pieces.push("log".to_string());
+        Ok(pieces.join(OP_SEPARATOR))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        abi::{encode, AbiValue},
+        apply,
+        assembly::eval::Eval,
+        bytes,
+        context::{CompilationContext, TypeContext},
+        event::Event,
+        expression::{apply::Apply, primitive::Primitive, Expr, Expression},
+        int,
+        typing::TypePrimitive,
+    };
+
+    use super::Log;
+
+    fn events() -> Vec<Event> {
+        vec![
+            Event::new(
+                "Swapped",
+                vec![
+                    ("a".to_string(), TypePrimitive::UInt64),
+                    ("b".to_string(), TypePrimitive::UInt64),
+                ],
+            )
+            .unwrap(),
+            Event::new(
+                "Renamed",
+                vec![
+                    ("from".to_string(), TypePrimitive::Byteslice),
+                    ("at".to_string(), TypePrimitive::UInt64),
+                    ("to".to_string(), TypePrimitive::Byteslice),
+                ],
+            )
+            .unwrap(),
+        ]
+    }
+
+    fn compile(e: &Expr, events: &[Event], version: u64) -> String {
+        e.resolve(&TypeContext {
+            events,
+            ..Default::default()
+        })
+        .unwrap();
+        e.compile(
+            &CompilationContext {
+                events,
+                version,
+                ..Default::default()
+            },
+            &mut vec![],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_log() {
+        let e = apply!(@fn Expr::Log(Log::Raw); @arg bytes!(b"hi".to_vec()));
+        assert_eq!(compile(&e, &[], 5), "byte \"hi\"\nlog");
+        let e = apply!(@fn Expr::Log(Log::Raw); @arg int!(1));
+        assert!(e.resolve(&TypeContext::default()).is_err());
+        assert!(Expr::Log(Log::Raw)
+            .compile(
+                &CompilationContext {
+                    version: 4,
+                    ..Default::default()
+                },
+                &mut vec![bytes!(b"hi".to_vec()).compile_raw().unwrap()],
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_emit() {
+        let events = events();
+        let e = apply!(@fn Expr::Log(Log::Emit("Swapped".to_string())); @arg int!(1); @arg int!(2));
+        assert_eq!(
+            compile(&e, &events, 8),
+            "byte 0x1ccbd925\nint 1\nitob\nint 2\nitob\nconcat\nconcat\nlog"
+        );
+        let e = apply!(
+            @fn Expr::Log(Log::Emit("Renamed".to_string()));
+            @arg bytes!(b"ab".to_vec());
+            @arg int!(7);
+            @arg bytes!(b"c".to_vec())
+        );
+        // the fields are logged as their ARC-4 encoding after the selector
+        let mut eval = Eval::default();
+        eval.run(&compile(&e, &events, 8)).unwrap();
+        let fields = AbiValue::Tuple(vec![
+            AbiValue::Array(vec![AbiValue::Byte(b'a'), AbiValue::Byte(b'b')]),
+            AbiValue::uint(7),
+            AbiValue::Array(vec![AbiValue::Byte(b'c')]),
+        ]);
+        let mut logged = events[1].selector().to_vec();
+        logged.extend(encode(&"(byte[],uint64,byte[])".parse().unwrap(), &fields).unwrap());
+        assert_eq!(eval.logs, vec![logged]);
+
+        let e = Expr::Log(Log::Emit("Missing".to_string()));
+        assert!(e.resolve(&TypeContext::default()).is_err());
+    }
+}
@@ -0,0 +1,275 @@
+use std::rc::Rc;
+
+use crate::{
+    compilation_error::CompilationError,
+    context::{CompilationContext, TypeContext},
+    cost::Region,
+    label::create_label_id,
+    typing::{TypeEnum, TypeError, TypePrimitive},
+    OP_SEPARATOR,
+};
+
+use super::{primitive::uint64_literal, Expr, Expression};
+
+// backward branches are only allowed from version 4
+const LOOP_VERSION: u64 = 4;
+
+// the end of a `for` range, named so that no identifier of the program can refer to it
+const LIMIT: &str = "%limit";
+
+// Loops evaluate their body for its effects, so bodies are `Void`. The bound is the maximum
+// number of iterations the cost estimator assumes, loops without one make the cost unbounded.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Loop {
+    While {
+        test: Expr,
+        body: Expr,
+        bound: Option<u64>,
+    },
+    // `identifier` goes from `start` up to but excluding `end`, which is evaluated once
+    For {
+        identifier: String,
+        start: Expr,
+        end: Expr,
+        body: Expr,
+        bound: Option<u64>,
+    },
+}
+
+fn unify_simple(expr: &Expr, t: TypePrimitive, context: &TypeContext) -> Result<(), TypeError> {
+    expr.resolve(context)?.unify(&mut TypeEnum::Simple(t))
+}
+
+// the loop header label, which tells the cost estimator how often the body runs
+fn loop_label(id: usize, bound: Option<u64>) -> String {
+    match bound {
+        Some(bound) => format!("loop{id}: // bound {bound}"),
+        None => format!("loop{id}:"),
+    }
+}
+
+impl Expression for Loop {
+    fn resolve(&self, context: &TypeContext) -> Result<TypeEnum, TypeError> {
+        match self {
+            Loop::While { test, body, .. } => {
+                unify_simple(test, TypePrimitive::UInt64, context)?;
+                unify_simple(body, TypePrimitive::Void, context)?;
+            }
+            Loop::For {
+                identifier,
+                start,
+                end,
+                body,
+                ..
+            } => {
+                unify_simple(start, TypePrimitive::UInt64, context)?;
+                unify_simple(end, TypePrimitive::UInt64, context)?;
+                let context = TypeContext {
+                    bind_scope: Rc::new(context.bind_scope.add(
+                        identifier.to_string(),
+                        TypeEnum::Simple(TypePrimitive::UInt64),
+                    )),
+                    global_scope: Rc::clone(&context.global_scope),
+                    local_scope: Rc::clone(&context.local_scope),
+                    boxes: context.boxes,
+                    events: context.events,
+                    templates: context.templates,
+                    mode: context.mode,
+                };
+                unify_simple(body, TypePrimitive::Void, &context)?;
+            }
+        }
+        Ok(TypeEnum::Simple(TypePrimitive::Void))
+    }
+
+    fn compile(
+        &self,
+        context: &CompilationContext,
+        _: &mut Vec<String>,
+    ) -> Result<String, CompilationError> {
+        if context.version < LOOP_VERSION {
+            return Err(CompilationError::UnsupportedOpcode(
+                "loop".to_string(),
+                LOOP_VERSION,
+                context.version,
+            ));
+        }
+        let id = create_label_id();
+        let end_label = format!("endloop{id}");
+        let record = |bound| {
+            context.record(Region::Loop {
+                start: format!("loop{id}"),
+                end: end_label.clone(),
+                bound,
+            })
+        };
+        match self {
+            Loop::While { test, body, bound } => {
+                record(*bound);
+                Ok([
+                    loop_label(id, *bound),
+                    test.compile(context, &mut vec![])?,
+                    format!("bz {end_label}"),
+                    body.compile(context, &mut vec![])?,
+                    format!("b loop{id}"),
+                    format!("{end_label}:"),
+                ]
+                .join(OP_SEPARATOR))
+            }
+            Loop::For {
+                identifier,
+                start,
+                end,
+                body,
+                bound,
+            } => {
+                let start_compiled = start.compile(context, &mut vec![])?;
+                let end_compiled = end.compile(context, &mut vec![])?;
+                // a range of literals bounds itself
+                let bound = bound.or(
+                    match (
+                        uint64_literal(&start_compiled),
+                        uint64_literal(&end_compiled),
+                    ) {
+                        (Some(start), Some(end)) => Some(end.saturating_sub(start)),
+                        _ => None,
+                    },
+                );
+                record(bound);
+                // the counter and the end of the range
+                let (bindings, allocated) = context.allocate(2)?;
+                let (counter, limit) = (bindings[0].clone(), bindings[1].clone());
+                let limit_context = CompilationContext {
+                    scope: allocated.scope.add(LIMIT.to_string(), limit),
+                    ..allocated
+                };
+                let body_context = CompilationContext {
+                    scope: limit_context.scope.add(identifier.to_string(), counter),
+                    ..limit_context
+                };
+                let load_counter = body_context.load(identifier)?;
+                let store_counter = body_context.store(identifier)?;
+                Ok([
+                    start_compiled,
+                    store_counter.clone(),
+                    end_compiled,
+                    body_context.store(LIMIT)?,
+                    loop_label(id, bound),
+                    load_counter.clone(),
+                    body_context.load(LIMIT)?,
+                    "<".to_string(),
+                    format!("bz {end_label}"),
+                    body.compile(&body_context, &mut vec![])?,
+                    load_counter,
+                    "int 1".to_string(),
+                    "+".to_string(),
+                    store_counter,
+                    format!("b loop{id}"),
+                    format!("{end_label}:"),
+                ]
+                .join(OP_SEPARATOR))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        apply,
+        assembly::assemble,
+        binop,
+        context::{CompilationContext, TypeContext},
+        expression::{
+            apply::Apply,
+            binary::Binary,
+            bind::Bind,
+            log::Log,
+            primitive::Primitive,
+            seq::Seq,
+            txn::Txn,
+            var::{LVal, RVal, Var},
+            Expr, Expression,
+        },
+        int, seq,
+    };
+
+    use super::Loop;
+
+    fn counter() -> Expr {
+        Expr::RVal(RVal(Var::Bind("i".to_string())))
+    }
+
+    #[test]
+    fn test_while() {
+        // let i = 0; while (i < 3) { i = i + 1; }
+        let e = Expr::Bind(Box::new(Bind::Let {
+            identifier: "i".to_string(),
+            value: int!(0),
+            body: seq!(
+                Expr::Loop(Box::new(Loop::While {
+                    test: binop!((counter()) < (int!(3))),
+                    body: apply!(
+                        @fn Expr::LVal(LVal(Var::Bind("i".to_string())));
+                        @arg binop!((counter()) + (int!(1)))
+                    ),
+                    bound: Some(3),
+                }));
+                counter();
+            ),
+        }));
+        e.resolve(&TypeContext::default()).unwrap();
+        let compiled = e.compile_raw().unwrap();
+        assert!(compiled.contains("// bound 3\nload 0\nint 3\n<\nbz endloop"));
+        assemble(&format!("#pragma version 4\n{compiled}")).unwrap();
+        assert!(e
+            .compile(
+                &CompilationContext {
+                    version: 3,
+                    ..Default::default()
+                },
+                &mut vec![]
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_for() {
+        // for i in 0..Txn.NumAppArgs { log(Txn.ApplicationArgs[i]) }
+        let e = Expr::Loop(Box::new(Loop::For {
+            identifier: "i".to_string(),
+            start: int!(0),
+            end: Expr::Txn(Txn::NumAppArgs),
+            body: apply!(
+                @fn Expr::Log(Log::Raw);
+                @arg apply!(@fn Expr::Txn(Txn::ApplicationArgs); @arg counter())
+            ),
+            bound: Some(16),
+        }));
+        e.resolve(&TypeContext::default()).unwrap();
+        let compiled = e.compile_raw().unwrap();
+        assert!(compiled.starts_with("int 0\nstore 0\ntxn NumAppArgs\nstore 1\nloop"));
+        assert!(compiled.contains("load 0\ntxnas ApplicationArgs\nlog\nload 0\nint 1\n+\nstore 0"));
+        assemble(&format!("#pragma version 8\n{compiled}")).unwrap();
+
+        // a range of literals is its own bound
+        let e = Expr::Loop(Box::new(Loop::For {
+            identifier: "i".to_string(),
+            start: int!(2),
+            end: int!(5),
+            body: Expr::Primitive(Primitive::Void),
+            bound: None,
+        }));
+        assert!(e.compile_raw().unwrap().contains("// bound 3"));
+
+        // bodies must not leave values on the stack
+        let e = Expr::Loop(Box::new(Loop::For {
+            identifier: "i".to_string(),
+            start: int!(0),
+            end: int!(3),
+            body: counter(),
+            bound: None,
+        }));
+        assert!(e.resolve(&TypeContext::default()).is_err());
+    }
+}
@@ -5,31 +5,172 @@ use crate::{
 };
 
 pub mod apply;
+pub mod arc4;
+pub mod arg;
+pub mod assert;
 pub mod binary;
 pub mod bind;
+pub mod box_storage;
+pub mod byte_math;
+pub mod bytes;
 pub mod cond;
 pub mod constant;
+pub mod crypto;
+pub mod function;
+pub mod global;
+pub mod gtxn;
 pub mod if_else;
+pub mod itxn;
+pub mod log;
+pub mod loops;
 pub mod primitive;
+pub mod query;
 pub mod ret;
+pub mod router;
 pub mod seq;
+pub mod template;
 pub mod txn;
+pub mod unary;
+pub mod unwrap;
 pub mod var;
+pub mod wide;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Expr {
     Apply(Box<apply::Apply>),
+    Arc4(arc4::Arc4),
+    Arg(arg::Arg),
+    Assert(assert::Assert),
     Binary(binary::Binary),
     Bind(Box<bind::Bind>),
+    BoxStorage(box_storage::BoxStorage),
+    ByteMath(byte_math::ByteMath),
+    Bytes(bytes::Bytes),
     Cond(Box<cond::Cond>),
+    Crypto(crypto::Crypto),
+    Function(Box<function::Function>),
+    Call(function::Call),
     OnComplete(constant::OnComplete),
+    TxnType(constant::TxnType),
+    Global(global::Global),
+    Gtxn(gtxn::Gtxn),
     If(Box<if_else::If>),
+    InnerTxn(Box<itxn::InnerTxn>),
+    Itxn(itxn::Itxn),
+    Log(log::Log),
+    Loop(Box<loops::Loop>),
     Primitive(primitive::Primitive),
+    Query(query::Query),
     Ret(ret::Ret),
+    Router(Box<router::Router>),
     Seq(Box<seq::Seq>),
+    Template(template::Template),
     Txn(txn::Txn),
+    Unary(unary::Unary),
+    Unwrap(unwrap::Unwrap),
     LVal(var::LVal),
     RVal(var::RVal),
+    Wide(wide::Wide),
+}
+
+impl Expr {
+    // the expressions directly under this one, in evaluation order
+    pub fn children(&self) -> Vec<&Expr> {
+        match self {
+            Expr::Apply(apply) => vec![&apply.1, &apply.0],
+            Expr::Bind(bind) => match bind.as_ref() {
+                bind::Bind::Let { value, body, .. }
+                | bind::Bind::Destructure { value, body, .. } => vec![value, body],
+                bind::Bind::Const { body, .. } => vec![body],
+            },
+            Expr::Cond(cond) => {
+                let mut children = vec![];
+                let mut arm = Some(cond.as_ref());
+                while let Some(cond) = arm {
+                    children.extend([&cond.0, &cond.1]);
+                    arm = cond.2.as_deref();
+                }
+                children
+            }
+            Expr::Function(function) => vec![&function.def, &function.body],
+            Expr::If(if_else) => vec![&if_else.0, &if_else.1],
+            Expr::Loop(l) => match l.as_ref() {
+                loops::Loop::While { test, body, .. } => vec![test, body],
+                loops::Loop::For {
+                    start, end, body, ..
+                } => vec![start, end, body],
+            },
+            Expr::InnerTxn(inner) => inner.0.iter().flatten().map(|(_, value)| value).collect(),
+            Expr::Ret(ret::Ret::Value(value)) => vec![value],
+            Expr::Router(router) => router
+                .methods
+                .iter()
+                .map(|method| &method.body)
+                .chain(&router.bare)
+                .collect(),
+            Expr::Seq(seq) => std::iter::once(&seq.0).chain(&seq.1).collect(),
+            _ => vec![],
+        }
+    }
+
+    // `children` for passes that rewrite the tree, in the same order
+    pub fn children_mut(&mut self) -> Vec<&mut Expr> {
+        match self {
+            Expr::Apply(apply) => {
+                let apply::Apply(f, arg) = apply.as_mut();
+                vec![arg, f]
+            }
+            Expr::Bind(bind) => match bind.as_mut() {
+                bind::Bind::Let { value, body, .. }
+                | bind::Bind::Destructure { value, body, .. } => vec![value, body],
+                bind::Bind::Const { body, .. } => vec![body],
+            },
+            Expr::Cond(cond) => {
+                let mut children = vec![];
+                let mut arm = Some(cond.as_mut());
+                while let Some(cond::Cond(test, body, rest)) = arm {
+                    children.extend([test, body]);
+                    arm = rest.as_deref_mut();
+                }
+                children
+            }
+            Expr::Function(function) => {
+                let function = function.as_mut();
+                vec![&mut function.def, &mut function.body]
+            }
+            Expr::If(if_else) => {
+                let if_else::If(then, otherwise) = if_else.as_mut();
+                vec![then, otherwise]
+            }
+            Expr::Loop(l) => match l.as_mut() {
+                loops::Loop::While { test, body, .. } => vec![test, body],
+                loops::Loop::For {
+                    start, end, body, ..
+                } => vec![start, end, body],
+            },
+            Expr::InnerTxn(inner) => inner
+                .0
+                .iter_mut()
+                .flatten()
+                .map(|(_, value)| value)
+                .collect(),
+            Expr::Ret(ret::Ret::Value(value)) => vec![value],
+            Expr::Router(router) => {
+                let router = router.as_mut();
+                router
+                    .methods
+                    .iter_mut()
+                    .map(|method| &mut method.body)
+                    .chain(router.bare.as_mut())
+                    .collect()
+            }
+            Expr::Seq(seq) => {
+                let seq::Seq(head, tail) = seq.as_mut();
+                std::iter::once(head).chain(tail.as_mut()).collect()
+            }
+            _ => vec![],
+        }
+    }
 }
 
 pub trait Expression {
@@ -49,17 +190,39 @@ impl Expression for Expr {
     fn resolve(&self, context: &TypeContext) -> Result<TypeEnum, TypeError> {
         match self {
             Expr::Apply(expr) => expr.resolve(context),
+            Expr::Arc4(expr) => expr.resolve(context),
+            Expr::Arg(expr) => expr.resolve(context),
+            Expr::Assert(expr) => expr.resolve(context),
             Expr::Binary(expr) => expr.resolve(context),
             Expr::Bind(expr) => expr.resolve(context),
+            Expr::BoxStorage(expr) => expr.resolve(context),
+            Expr::ByteMath(expr) => expr.resolve(context),
+            Expr::Bytes(expr) => expr.resolve(context),
             Expr::Cond(expr) => expr.resolve(context),
+            Expr::Global(expr) => expr.resolve(context),
+            Expr::Gtxn(expr) => expr.resolve(context),
+            Expr::Crypto(expr) => expr.resolve(context),
+            Expr::Function(expr) => expr.resolve(context),
+            Expr::Call(expr) => expr.resolve(context),
             Expr::OnComplete(expr) => expr.resolve(context),
+            Expr::TxnType(expr) => expr.resolve(context),
             Expr::If(expr) => expr.resolve(context),
+            Expr::InnerTxn(expr) => expr.resolve(context),
+            Expr::Itxn(expr) => expr.resolve(context),
+            Expr::Log(expr) => expr.resolve(context),
+            Expr::Loop(expr) => expr.resolve(context),
             Expr::Primitive(expr) => expr.resolve(context),
+            Expr::Query(expr) => expr.resolve(context),
             Expr::Ret(expr) => expr.resolve(context),
+            Expr::Router(expr) => expr.resolve(context),
             Expr::Seq(expr) => expr.resolve(context),
+            Expr::Template(expr) => expr.resolve(context),
             Expr::Txn(expr) => expr.resolve(context),
+            Expr::Unary(expr) => expr.resolve(context),
+            Expr::Unwrap(expr) => expr.resolve(context),
             Expr::LVal(expr) => expr.resolve(context),
             Expr::RVal(expr) => expr.resolve(context),
+            Expr::Wide(expr) => expr.resolve(context),
         }
     }
 
@@ -70,17 +233,39 @@ impl Expression for Expr {
     ) -> Result<String, CompilationError> {
         match self {
             Expr::Apply(expr) => expr.compile(context, prepared_stack),
+            Expr::Arc4(expr) => expr.compile(context, prepared_stack),
+            Expr::Arg(expr) => expr.compile(context, prepared_stack),
+            Expr::Assert(expr) => expr.compile(context, prepared_stack),
             Expr::Binary(expr) => expr.compile(context, prepared_stack),
             Expr::Bind(expr) => expr.compile(context, prepared_stack),
+            Expr::BoxStorage(expr) => expr.compile(context, prepared_stack),
+            Expr::ByteMath(expr) => expr.compile(context, prepared_stack),
+            Expr::Bytes(expr) => expr.compile(context, prepared_stack),
             Expr::Cond(expr) => expr.compile(context, prepared_stack),
+            Expr::Global(expr) => expr.compile(context, prepared_stack),
+            Expr::Gtxn(expr) => expr.compile(context, prepared_stack),
+            Expr::Crypto(expr) => expr.compile(context, prepared_stack),
+            Expr::Function(expr) => expr.compile(context, prepared_stack),
+            Expr::Call(expr) => expr.compile(context, prepared_stack),
             Expr::If(expr) => expr.compile(context, prepared_stack),
+            Expr::InnerTxn(expr) => expr.compile(context, prepared_stack),
+            Expr::Itxn(expr) => expr.compile(context, prepared_stack),
+            Expr::Log(expr) => expr.compile(context, prepared_stack),
+            Expr::Loop(expr) => expr.compile(context, prepared_stack),
             Expr::OnComplete(expr) => expr.compile(context, prepared_stack),
+            Expr::TxnType(expr) => expr.compile(context, prepared_stack),
             Expr::Primitive(expr) => expr.compile(context, prepared_stack),
+            Expr::Query(expr) => expr.compile(context, prepared_stack),
             Expr::Ret(expr) => expr.compile(context, prepared_stack),
+            Expr::Router(expr) => expr.compile(context, prepared_stack),
             Expr::Seq(expr) => expr.compile(context, prepared_stack),
+            Expr::Template(expr) => expr.compile(context, prepared_stack),
             Expr::Txn(expr) => expr.compile(context, prepared_stack),
+            Expr::Unary(expr) => expr.compile(context, prepared_stack),
+            Expr::Unwrap(expr) => expr.compile(context, prepared_stack),
             Expr::LVal(expr) => expr.compile(context, prepared_stack),
             Expr::RVal(expr) => expr.compile(context, prepared_stack),
+            Expr::Wide(expr) => expr.compile(context, prepared_stack),
         }
     }
 }
@@ -12,6 +12,8 @@ use super::Expression;
 pub enum Primitive {
     UInt64(u64),
     Byteslice(Vec<u8>),
+    // the value of an empty block or a missing else branch
+    Void,
 }
 
 impl From<&str> for Primitive {
@@ -39,11 +41,17 @@ impl From<u64> for Primitive {
     }
 }
 
+// The value of a compiled `int` literal, lets operations pick their immediate forms
+pub(crate) fn uint64_literal(compiled: &str) -> Option<u64> {
+    compiled.strip_prefix("int ")?.parse().ok()
+}
+
 impl Expression for Primitive {
     fn resolve(&self, _: &TypeContext) -> Result<TypeEnum, TypeError> {
         Ok(TypeEnum::Simple(match self {
             Primitive::UInt64(_) => TypePrimitive::UInt64,
             Primitive::Byteslice(_) => TypePrimitive::Byteslice,
+            Primitive::Void => TypePrimitive::Void,
         }))
     }
 
@@ -64,6 +72,7 @@ impl Expression for Primitive {
 
                 Ok(format!("byte \"{escaped}\""))
             }
+            Self::Void => Ok(String::new()),
         }
     }
 }
@@ -0,0 +1,286 @@
+use strum_macros::{Display, EnumString};
+
+use crate::{
+    compilation_error::CompilationError,
+    context::{CompilationContext, TypeContext},
+    typing::{TypeEnum, TypeError, TypePrimitive, TypeVar},
+    OP_SEPARATOR,
+};
+
+use super::Expression;
+
+#[derive(Debug, Clone, PartialEq, Display, EnumString)]
+pub enum AssetHoldingField {
+    AssetBalance,
+    AssetFrozen,
+}
+
+#[derive(Debug, Clone, PartialEq, Display, EnumString)]
+pub enum AssetParamsField {
+    AssetTotal,
+    AssetDecimals,
+    AssetDefaultFrozen,
+    AssetUnitName,
+    AssetName,
+    AssetURL,
+    AssetMetadataHash,
+    AssetManager,
+    AssetReserve,
+    AssetFreeze,
+    AssetClawback,
+    AssetCreator,
+}
+
+#[derive(Debug, Clone, PartialEq, Display, EnumString)]
+pub enum AppParamsField {
+    AppApprovalProgram,
+    AppClearStateProgram,
+    AppGlobalNumUint,
+    AppGlobalNumByteSlice,
+    AppLocalNumUint,
+    AppLocalNumByteSlice,
+    AppExtraProgramPages,
+    AppCreator,
+    AppAddress,
+}
+
+#[derive(Debug, Clone, PartialEq, Display, EnumString)]
+pub enum AcctParamsField {
+    AcctBalance,
+    AcctMinBalance,
+    AcctAuthAddr,
+    AcctTotalNumUint,
+    AcctTotalNumByteSlice,
+    AcctTotalExtraAppPages,
+    AcctTotalAppsCreated,
+    AcctTotalAppsOptedIn,
+    AcctTotalAssetsCreated,
+    AcctTotalAssets,
+    AcctTotalBoxes,
+    AcctTotalBoxBytes,
+}
+
+// Reads of ledger state about accounts, assets and applications. The `*_get` queries leave the
+// value and a flag telling whether it exists, so they resolve to an option.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    // (account, asset)
+    AssetHolding(AssetHoldingField),
+    // (asset)
+    AssetParams(AssetParamsField),
+    // (application)
+    AppParams(AppParamsField),
+    // (account)
+    AcctParams(AcctParamsField),
+    // (account), these leave a single value
+    Balance,
+    MinBalance,
+}
+
+impl Query {
+    fn opcode(&self) -> String {
+        match self {
+            Query::AssetHolding(field) => format!("asset_holding_get {field}"),
+            Query::AssetParams(field) => format!("asset_params_get {field}"),
+            Query::AppParams(field) => format!("app_params_get {field}"),
+            Query::AcctParams(field) => format!("acct_params_get {field}"),
+            Query::Balance => "balance".to_string(),
+            Query::MinBalance => "min_balance".to_string(),
+        }
+    }
+
+    fn arity(&self) -> usize {
+        match self {
+            Query::AssetHolding(_) => 2,
+            _ => 1,
+        }
+    }
+
+    // the first version of the AVM that has the opcode and field
+    pub fn min_version(&self) -> u64 {
+        match self {
+            Query::AssetHolding(_) | Query::Balance => 2,
+            Query::AssetParams(AssetParamsField::AssetCreator) => 5,
+            Query::AssetParams(_) => 2,
+            Query::AppParams(_) => 5,
+            Query::AcctParams(
+                AcctParamsField::AcctBalance
+                | AcctParamsField::AcctMinBalance
+                | AcctParamsField::AcctAuthAddr,
+            ) => 6,
+            Query::AcctParams(_) => 8,
+            Query::MinBalance => 3,
+        }
+    }
+
+    fn value_type(&self) -> TypePrimitive {
+        match self {
+            Query::AssetParams(
+                AssetParamsField::AssetUnitName
+                | AssetParamsField::AssetName
+                | AssetParamsField::AssetURL
+                | AssetParamsField::AssetMetadataHash
+                | AssetParamsField::AssetManager
+                | AssetParamsField::AssetReserve
+                | AssetParamsField::AssetFreeze
+                | AssetParamsField::AssetClawback
+                | AssetParamsField::AssetCreator,
+            )
+            | Query::AppParams(
+                AppParamsField::AppApprovalProgram
+                | AppParamsField::AppClearStateProgram
+                | AppParamsField::AppCreator
+                | AppParamsField::AppAddress,
+            )
+            | Query::AcctParams(AcctParamsField::AcctAuthAddr) => TypePrimitive::Byteslice,
+            _ => TypePrimitive::UInt64,
+        }
+    }
+}
+
+impl Expression for Query {
+    fn resolve(&self, context: &TypeContext) -> Result<TypeEnum, TypeError> {
+        context.require_application(&self.opcode())?;
+        let uint64 = || TypeEnum::Simple(TypePrimitive::UInt64);
+        // accounts are given by address or by their index in Txn.Accounts
+        let account = || TypeEnum::Var(TypeVar::new());
+        let value = TypeEnum::Simple(self.value_type());
+        let (params, result) = match self {
+            Query::AssetHolding(_) => {
+                (vec![account(), uint64()], TypeEnum::Option(Box::new(value)))
+            }
+            Query::AssetParams(_) | Query::AppParams(_) => {
+                (vec![uint64()], TypeEnum::Option(Box::new(value)))
+            }
+            Query::AcctParams(_) => (vec![account()], TypeEnum::Option(Box::new(value))),
+            Query::Balance | Query::MinBalance => (vec![account()], value),
+        };
+        Ok(params.into_iter().rev().fold(result, |body, param| {
+            TypeEnum::Arrow(Box::new(param), Box::new(body))
+        }))
+    }
+
+    fn compile(
+        &self,
+        context: &CompilationContext,
+        prepared_stack: &mut Vec<String>,
+    ) -> Result<String, CompilationError> {
+        if context.version < self.min_version() {
+            return Err(CompilationError::UnsupportedOpcode(
+                self.opcode(),
+                self.min_version(),
+                context.version,
+            ));
+        }
+        // arguments are on the prepared stack with the first one on top
+        let mut pieces = (0..self.arity())
+            .map(|_| prepared_stack.pop().ok_or(CompilationError::MissingStack))
+            .collect::<Result<Vec<_>, _>>()?;
+        pieces.push(self.opcode());
+        Ok(pieces.join(OP_SEPARATOR))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::{
+        apply,
+        assembly::{
+            assemble,
+            opcode::{
+                ACCT_PARAMS_FIELDS, APP_PARAMS_FIELDS, ASSET_HOLDING_FIELDS, ASSET_PARAMS_FIELDS,
+            },
+        },
+        context::{CompilationContext, TypeContext},
+        expression::{apply::Apply, primitive::Primitive, txn::Txn, Expr, Expression},
+        int,
+        typing::{TypeEnum, TypePrimitive},
+    };
+
+    use super::{AcctParamsField, AppParamsField, AssetHoldingField, AssetParamsField, Query};
+
+    #[test]
+    fn test_fields() {
+        let queries = ASSET_HOLDING_FIELDS
+            .iter()
+            .map(|f| Query::AssetHolding(AssetHoldingField::from_str(f).unwrap()))
+            .chain(
+                ASSET_PARAMS_FIELDS
+                    .iter()
+                    .map(|f| Query::AssetParams(AssetParamsField::from_str(f).unwrap())),
+            )
+            .chain(
+                APP_PARAMS_FIELDS
+                    .iter()
+                    .map(|f| Query::AppParams(AppParamsField::from_str(f).unwrap())),
+            )
+            .chain(
+                ACCT_PARAMS_FIELDS
+                    .iter()
+                    .map(|f| Query::AcctParams(AcctParamsField::from_str(f).unwrap())),
+            )
+            .chain([Query::Balance, Query::MinBalance]);
+        for query in queries {
+            let e = match query {
+                Query::AssetHolding(_) => {
+                    apply!(@fn Expr::Query(query); @arg Expr::Txn(Txn::Sender); @arg int!(10))
+                }
+                _ => apply!(@fn Expr::Query(query); @arg int!(0)),
+            };
+            e.resolve(&TypeContext::default()).unwrap();
+            let compiled = e.compile_raw().unwrap();
+            assemble(&format!("#pragma version 8\n{compiled}")).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_types() {
+        let e = apply!(
+            @fn Expr::Query(Query::AssetHolding(AssetHoldingField::AssetBalance));
+            @arg Expr::Txn(Txn::Sender);
+            @arg int!(10)
+        );
+        e.resolve(&TypeContext::default())
+            .unwrap()
+            .unify(&mut TypeEnum::Option(Box::new(TypeEnum::Simple(
+                TypePrimitive::UInt64,
+            ))))
+            .unwrap();
+        assert_eq!(
+            e.compile_raw().unwrap(),
+            "txn Sender\nint 10\nasset_holding_get AssetBalance"
+        );
+        // the option has to be unwrapped before use
+        assert!(e
+            .resolve(&TypeContext::default())
+            .unwrap()
+            .unify(&mut TypeEnum::Simple(TypePrimitive::UInt64))
+            .is_err());
+
+        let e = apply!(@fn Expr::Query(Query::Balance); @arg Expr::Txn(Txn::Sender));
+        e.resolve(&TypeContext::default())
+            .unwrap()
+            .unify(&mut TypeEnum::Simple(TypePrimitive::UInt64))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_versions() {
+        let context = |version| CompilationContext {
+            version,
+            ..Default::default()
+        };
+        let e = apply!(
+            @fn Expr::Query(Query::AcctParams(AcctParamsField::AcctTotalBoxes));
+            @arg Expr::Txn(Txn::Sender)
+        );
+        assert!(e.compile(&context(7), &mut vec![]).is_err());
+        let e = apply!(
+            @fn Expr::Query(Query::AcctParams(AcctParamsField::AcctBalance));
+            @arg Expr::Txn(Txn::Sender)
+        );
+        assert!(e.compile(&context(6), &mut vec![]).is_ok());
+    }
+}
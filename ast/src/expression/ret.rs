@@ -7,39 +7,50 @@ use crate::{
     OP_SEPARATOR,
 };
 
-use super::Expression;
+use super::{Expr, Expression};
 
 #[derive(Debug, Clone, PartialEq, EnumString)]
 pub enum Ret {
     Approve,
     Reject,
+    // returns a computed uint64
+    #[strum(disabled)]
+    Value(Box<Expr>),
 }
 
 impl Expression for Ret {
-    fn resolve(&self, _: &TypeContext) -> Result<TypeEnum, TypeError> {
+    fn resolve(&self, context: &TypeContext) -> Result<TypeEnum, TypeError> {
+        if let Ret::Value(value) = self {
+            value
+                .resolve(context)?
+                .unify(&mut TypeEnum::Simple(TypePrimitive::UInt64))?;
+        }
         Ok(TypeEnum::Simple(TypePrimitive::Halt))
     }
 
     fn compile(
         &self,
-        _: &CompilationContext,
+        context: &CompilationContext,
         _: &mut Vec<String>,
     ) -> Result<String, CompilationError> {
-        Ok(format!(
-            "int {value}{OP_SEPARATOR}return",
-            value = match self {
-                Ret::Approve => "1",
-                Ret::Reject => "0",
-            }
-        ))
+        let value = match self {
+            Ret::Approve => "int 1".to_string(),
+            Ret::Reject => "int 0".to_string(),
+            Ret::Value(value) => value.compile(context, &mut vec![])?,
+        };
+        Ok(format!("{value}{OP_SEPARATOR}return"))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::{
+        apply, binop, bytes,
         context::TypeContext,
-        expression::{ret::Ret, Expression},
+        expression::{
+            apply::Apply, binary::Binary, primitive::Primitive, ret::Ret, Expr, Expression,
+        },
+        int,
     };
 
     #[test]
@@ -48,4 +59,13 @@ mod tests {
         println!("{:?}", e.resolve(&TypeContext::default()));
         println!("{}", e.compile_raw().unwrap());
     }
+
+    #[test]
+    fn test_value() {
+        let e = Ret::Value(Box::new(binop!((int!(2)) > (int!(1)))));
+        e.resolve(&TypeContext::default()).unwrap();
+        assert_eq!(e.compile_raw().unwrap(), "int 2\nint 1\n>\nreturn");
+        let e = Ret::Value(Box::new(bytes!(b"no".to_vec())));
+        assert!(e.resolve(&TypeContext::default()).is_err());
+    }
 }
@@ -0,0 +1,389 @@
+use crate::{
+    abi::{self, AbiType},
+    compilation_error::CompilationError,
+    context::{CompilationContext, TypeContext},
+    typing::{TypeEnum, TypeError},
+};
+
+use super::{
+    apply::Apply, arc4::Arc4, assert::Assert, binary::Binary, bind::Bind, bytes::Bytes, cond::Cond,
+    constant::OnComplete, log::Log, primitive::Primitive, ret::Ret, seq::Seq, txn::Txn, Expr,
+    Expression,
+};
+
+fn apply(f: Expr, arg: Expr) -> Expr {
+    Expr::Apply(Box::new(Apply(f, arg)))
+}
+
+fn binary(op: Binary, lhs: Expr, rhs: Expr) -> Expr {
+    apply(apply(Expr::Binary(op), rhs), lhs)
+}
+
+// fails the call unless its on-completion action is one of `actions`, then evaluates `body`
+fn allow(actions: &[OnComplete], body: Expr) -> Expr {
+    let allowed = actions
+        .iter()
+        .map(|action| {
+            binary(
+                Binary::Equals,
+                Expr::Txn(Txn::OnCompletion),
+                Expr::OnComplete(action.clone()),
+            )
+        })
+        .reduce(|allowed, test| binary(Binary::Or, allowed, test))
+        .unwrap_or(Expr::Primitive(Primitive::UInt64(0)));
+    let check = apply(
+        Expr::Assert(Assert(Some("on completion not allowed".to_string()))),
+        allowed,
+    );
+    Expr::Seq(Box::new(Seq(check, Some(body))))
+}
+
+// ARC-4 logs return values behind this prefix
+pub const RETURN_PREFIX: [u8; 4] = [0x15, 0x1f, 0x7c, 0x75];
+// application args left after the selector, with more arguments than this the last one holds
+// the 15th and later arguments as a tuple
+pub const MAX_METHOD_ARGS: usize = 15;
+
+// An ARC-4 method, its arguments are bound by name in the body
+#[derive(Debug, Clone, PartialEq)]
+pub struct Method {
+    pub name: String,
+    pub args: Vec<(String, AbiType)>,
+    pub returns: Option<AbiType>,
+    // the on-completion actions the method may be called with, others are rejected
+    pub actions: Vec<OnComplete>,
+    pub body: Expr,
+}
+
+impl Method {
+    // e.g. `add(uint64,uint64)uint64`
+    pub fn signature(&self) -> String {
+        let args = self
+            .args
+            .iter()
+            .map(|(_, t)| t.to_string())
+            .collect::<Vec<_>>();
+        let returns = self
+            .returns
+            .as_ref()
+            .map_or("void".to_string(), AbiType::to_string);
+        format!("{}({}){returns}", self.name, args.join(","))
+    }
+
+    pub fn selector(&self) -> [u8; 4] {
+        abi::selector(&self.signature())
+    }
+
+    // decodes the arguments, runs the body, logs the return value and approves
+    fn handler(&self) -> Expr {
+        let result = match &self.returns {
+            Some(t) => {
+                let encoded = match t.value_type() {
+                    Some(_) => apply(Expr::Arc4(Arc4::Encode(t.clone())), self.body.clone()),
+                    None => self.body.clone(),
+                };
+                let encoded = apply(Expr::Arc4(Arc4::ToBytes(t.clone())), encoded);
+                let logged = apply(
+                    apply(
+                        Expr::Bytes(Bytes::Concat),
+                        Expr::Primitive(Primitive::Byteslice(RETURN_PREFIX.to_vec())),
+                    ),
+                    encoded,
+                );
+                apply(Expr::Log(Log::Raw), logged)
+            }
+            None => self.body.clone(),
+        };
+        let handled = Expr::Seq(Box::new(Seq(result, Some(Expr::Ret(Ret::Approve)))));
+        let app_arg = |i: usize| {
+            apply(
+                Expr::Txn(Txn::ApplicationArgs),
+                Expr::Primitive(Primitive::UInt64(i as u64)),
+            )
+        };
+        let packed = (self.args.len() > MAX_METHOD_ARGS).then(|| {
+            AbiType::Tuple(
+                self.args[MAX_METHOD_ARGS - 1..]
+                    .iter()
+                    .map(|(_, t)| t.clone())
+                    .collect(),
+            )
+        });
+        let decoded =
+            self.args
+                .iter()
+                .enumerate()
+                .rev()
+                .fold(handled, |body, (i, (identifier, t))| {
+                    let encoded = match &packed {
+                        Some(tuple) if i >= MAX_METHOD_ARGS - 1 => apply(
+                            Expr::Arc4(Arc4::Field(tuple.clone(), i + 1 - MAX_METHOD_ARGS)),
+                            apply(
+                                Expr::Arc4(Arc4::FromBytes(tuple.clone())),
+                                app_arg(MAX_METHOD_ARGS),
+                            ),
+                        ),
+                        _ => apply(Expr::Arc4(Arc4::FromBytes(t.clone())), app_arg(i + 1)),
+                    };
+                    // compound arguments stay encoded
+                    let value = match t.value_type() {
+                        Some(_) => apply(Expr::Arc4(Arc4::Decode(t.clone())), encoded),
+                        None => encoded,
+                    };
+                    Expr::Bind(Box::new(Bind::Let {
+                        identifier: identifier.clone(),
+                        value,
+                        body,
+                    }))
+                });
+        allow(&self.actions, decoded)
+    }
+}
+
+// Dispatches application calls to methods by the selector in the first application arg.
+// Calls without args go to `bare`, calls to unknown selectors fail, and so do calls with an
+// on-completion action their handler does not allow.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Router {
+    pub methods: Vec<Method>,
+    pub bare: Option<Expr>,
+    // the on-completion actions bare calls may use
+    pub bare_actions: Vec<OnComplete>,
+}
+
+impl Router {
+    fn check(&self) -> Result<(), CompilationError> {
+        let mut selectors = vec![];
+        for method in &self.methods {
+            let selector = method.selector();
+            if selectors.contains(&selector) {
+                return Err(CompilationError::DuplicateSelector(method.signature()));
+            }
+            selectors.push(selector);
+        }
+        Ok(())
+    }
+
+    // the `Cond` the router compiles to, its method arms compile to a single `match`
+    pub fn dispatch(&self) -> Expr {
+        let selector_arg = || {
+            apply(
+                Expr::Txn(Txn::ApplicationArgs),
+                Expr::Primitive(Primitive::UInt64(0)),
+            )
+        };
+        let methods = self.methods.iter().rev().fold(None, |next, method| {
+            Some(Box::new(Cond(
+                binary(
+                    Binary::Equals,
+                    selector_arg(),
+                    Expr::Primitive(Primitive::Byteslice(method.selector().to_vec())),
+                ),
+                method.handler(),
+                next,
+            )))
+        });
+        match (&self.bare, methods) {
+            (Some(bare), methods) => Expr::Cond(Box::new(Cond(
+                binary(
+                    Binary::Equals,
+                    Expr::Txn(Txn::NumAppArgs),
+                    Expr::Primitive(Primitive::UInt64(0)),
+                ),
+                allow(&self.bare_actions, bare.clone()),
+                methods,
+            ))),
+            (None, Some(methods)) => Expr::Cond(methods),
+            (None, None) => Expr::Primitive(Primitive::Void),
+        }
+    }
+}
+
+impl Expression for Router {
+    fn resolve(&self, context: &TypeContext) -> Result<TypeEnum, TypeError> {
+        context.require_application("ARC-4 methods")?;
+        self.dispatch().resolve(context)
+    }
+
+    fn compile(
+        &self,
+        context: &CompilationContext,
+        prepared_stack: &mut Vec<String>,
+    ) -> Result<String, CompilationError> {
+        self.check()?;
+        self.dispatch().compile(context, prepared_stack)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        abi::{
+            codec::{self, AbiValue},
+            AbiType,
+        },
+        apply,
+        assembly::{assemble, eval::Eval},
+        binop,
+        context::TypeContext,
+        expression::{
+            apply::Apply,
+            binary::Binary,
+            bytes::Bytes,
+            constant::OnComplete,
+            primitive::Primitive,
+            var::{RVal, Var},
+            Expr, Expression,
+        },
+        int,
+        program::Program,
+    };
+
+    use super::{Method, Router};
+
+    fn var(name: &str) -> Expr {
+        Expr::RVal(RVal(Var::Bind(name.to_string())))
+    }
+
+    fn add() -> Method {
+        Method {
+            name: "add".to_string(),
+            args: vec![
+                ("a".to_string(), AbiType::Uint(64)),
+                ("b".to_string(), AbiType::Uint(64)),
+            ],
+            returns: Some(AbiType::Uint(64)),
+            actions: vec![OnComplete::NoOp],
+            body: binop!((var("a")) + (var("b"))),
+        }
+    }
+
+    #[test]
+    fn test_selector() {
+        assert_eq!(add().signature(), "add(uint64,uint64)uint64");
+        assert_eq!(add().selector(), [0xfe, 0x6b, 0xdf, 0x69]);
+    }
+
+    #[test]
+    fn test_router() {
+        let router = Router {
+            methods: vec![
+                add(),
+                Method {
+                    name: "hello".to_string(),
+                    args: vec![("name".to_string(), AbiType::String)],
+                    returns: None,
+                    actions: vec![OnComplete::NoOp],
+                    body: Expr::Primitive(Primitive::Void),
+                },
+            ],
+            bare: Some(int!(1)),
+            bare_actions: vec![OnComplete::NoOp],
+        };
+        let e = Expr::Router(Box::new(router.clone()));
+        e.resolve(&TypeContext::default()).unwrap();
+        let compiled = e.compile_raw().unwrap();
+        assert!(compiled.contains("txna ApplicationArgs 0\nmatch"));
+        assert!(compiled.contains("txna ApplicationArgs 1\ndup\nlen\nint 8\n==\nassert\nbtoi"));
+        // the arguments stay on the stack until their only use
+        assert!(
+            compiled.contains("byte \"\\x15\\x1f|u\"\nuncover 2\nuncover 2\n+\nitob\nconcat\nlog")
+        );
+        assemble(&format!("#pragma version 8\n{compiled}")).unwrap();
+
+        // return values must match the declared type
+        let mut wrong = router.clone();
+        wrong.methods[0].returns = Some(AbiType::String);
+        assert!(Expr::Router(Box::new(wrong))
+            .resolve(&TypeContext::default())
+            .is_err());
+        let mut duplicate = router;
+        duplicate.methods.push(add());
+        assert!(Expr::Router(Box::new(duplicate)).compile_raw().is_err());
+    }
+
+    #[test]
+    fn test_on_completion() {
+        let program = Program {
+            version: 8,
+            body: Expr::Router(Box::new(Router {
+                methods: vec![add()],
+                bare: Some(int!(1)),
+                bare_actions: vec![OnComplete::NoOp, OnComplete::OptIn],
+            })),
+            budget: None,
+        };
+        let teal = program.compile().unwrap();
+        let run = |app_args: Vec<Vec<u8>>, on_completion: OnComplete| {
+            let mut eval = Eval::default();
+            eval.app_args = app_args;
+            eval.on_completion = on_completion as u64;
+            eval.run(&teal).map(|_| eval.logs)
+        };
+        let call = || {
+            vec![
+                add().selector().to_vec(),
+                2u64.to_be_bytes().to_vec(),
+                3u64.to_be_bytes().to_vec(),
+            ]
+        };
+
+        let logs = run(call(), OnComplete::NoOp).unwrap();
+        assert_eq!(logs[0][4..], 5u64.to_be_bytes());
+        assert!(run(vec![], OnComplete::OptIn).is_ok());
+        // nobody may update or delete the application through a method or a bare call
+        for action in [OnComplete::UpdateApplication, OnComplete::DeleteApplication] {
+            assert!(run(call(), action.clone()).is_err());
+            assert!(run(vec![], action).is_err());
+        }
+        assert!(run(call(), OnComplete::OptIn).is_err());
+    }
+
+    #[test]
+    fn test_packed_args() {
+        let mut args = (0..15)
+            .map(|i| (format!("a{i}"), AbiType::Uint(64)))
+            .collect::<Vec<_>>();
+        args.push(("s".to_string(), AbiType::String));
+        let method = Method {
+            name: "many".to_string(),
+            args,
+            returns: Some(AbiType::Uint(64)),
+            actions: vec![OnComplete::NoOp],
+            body: binop!(
+                (binop!((binop!((var("a0")) + (var("a13")))) + (var("a14"))))
+                    + (apply!(@fn Expr::Bytes(Bytes::Len); @arg var("s")))
+            ),
+        };
+        let program = Program {
+            version: 8,
+            body: Expr::Router(Box::new(Router {
+                methods: vec![method.clone()],
+                bare: None,
+                bare_actions: vec![],
+            })),
+            budget: None,
+        };
+        let teal = program.compile().unwrap();
+
+        // the first 14 arguments have their own application args, the rest share the last one
+        let mut app_args = vec![method.selector().to_vec()];
+        app_args.extend((0..14u64).map(|i| i.to_be_bytes().to_vec()));
+        let rest = AbiValue::Tuple(vec![
+            AbiValue::uint(14),
+            AbiValue::String("hey".to_string()),
+        ]);
+        app_args.push(
+            codec::encode(
+                &AbiType::Tuple(vec![AbiType::Uint(64), AbiType::String]),
+                &rest,
+            )
+            .unwrap(),
+        );
+        let mut eval = Eval::default();
+        eval.app_args = app_args;
+        eval.run(&teal).unwrap();
+        assert_eq!(eval.logs[0][4..], 30u64.to_be_bytes());
+    }
+}
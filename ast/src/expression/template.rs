@@ -0,0 +1,101 @@
+use crate::{
+    compilation_error::CompilationError,
+    context::{CompilationContext, TypeContext},
+    typing::{TypeEnum, TypeError, TypePrimitive},
+};
+
+use super::Expression;
+
+// A declared template variable, compiled to a `TMPL_` placeholder that gets its value at
+// deploy time
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template(pub String);
+
+// A value substituted for a template variable
+#[derive(Debug, Clone, PartialEq)]
+pub enum TemplateValue {
+    UInt64(u64),
+    Bytes(Vec<u8>),
+}
+
+impl Template {
+    pub fn placeholder(&self) -> String {
+        format!("TMPL_{}", self.0)
+    }
+}
+
+impl Expression for Template {
+    fn resolve(&self, context: &TypeContext) -> Result<TypeEnum, TypeError> {
+        context
+            .templates
+            .iter()
+            .find(|(name, _)| name == &self.0)
+            .map(|(_, t)| TypeEnum::Simple(t.clone()))
+            .ok_or_else(|| TypeError::UnboundTemplate(self.0.clone()))
+    }
+
+    fn compile(
+        &self,
+        context: &CompilationContext,
+        _: &mut Vec<String>,
+    ) -> Result<String, CompilationError> {
+        let (_, t) = context
+            .templates
+            .iter()
+            .find(|(name, _)| name == &self.0)
+            .ok_or_else(|| CompilationError::UnknownTemplate(self.0.clone()))?;
+        Ok(match t {
+            TypePrimitive::UInt64 => format!("int {}", self.placeholder()),
+            _ => format!("byte {}", self.placeholder()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        compilation_error::CompilationError,
+        context::{CompilationContext, TypeContext},
+        expression::Expression,
+        typing::{TypeEnum, TypeError, TypePrimitive},
+    };
+
+    use super::Template;
+
+    #[test]
+    fn test_template() {
+        let templates = [
+            ("FEE".to_string(), TypePrimitive::UInt64),
+            ("CREATOR".to_string(), TypePrimitive::Byteslice),
+        ];
+        let type_context = TypeContext {
+            templates: &templates,
+            ..Default::default()
+        };
+        let context = CompilationContext {
+            templates: &templates,
+            ..Default::default()
+        };
+        let fee = Template("FEE".to_string());
+        assert!(matches!(
+            fee.resolve(&type_context),
+            Ok(TypeEnum::Simple(TypePrimitive::UInt64))
+        ));
+        assert_eq!(fee.compile(&context, &mut vec![]).unwrap(), "int TMPL_FEE");
+        let creator = Template("CREATOR".to_string());
+        assert_eq!(
+            creator.compile(&context, &mut vec![]).unwrap(),
+            "byte TMPL_CREATOR"
+        );
+
+        let unknown = Template("ASSET".to_string());
+        assert!(matches!(
+            unknown.resolve(&type_context),
+            Err(TypeError::UnboundTemplate(name)) if name == "ASSET"
+        ));
+        assert!(matches!(
+            unknown.compile(&context, &mut vec![]),
+            Err(CompilationError::UnknownTemplate(name)) if name == "ASSET"
+        ));
+    }
+}
@@ -1,53 +1,427 @@
-use strum_macros::EnumString;
+use strum_macros::{Display, EnumString};
 
 use crate::{
     compilation_error::CompilationError,
     context::{CompilationContext, TypeContext},
     typing::{TypeEnum, TypeError, TypePrimitive},
+    OP_SEPARATOR,
 };
 
-use super::Expression;
+use super::{primitive::uint64_literal, Expression};
 
-// TODO: Incomplete
-#[derive(Debug, Clone, PartialEq, EnumString)]
+// In the order of the AVM field table
+#[derive(Debug, Clone, PartialEq, Display, EnumString)]
 pub enum Txn {
     Sender,
     Fee,
+    FirstValid,
+    FirstValidTime,
+    LastValid,
+    Note,
+    Lease,
     Receiver,
     Amount,
     CloseRemainderTo,
+    VotePK,
+    SelectionPK,
+    VoteFirst,
+    VoteLast,
+    VoteKeyDilution,
+    Type,
+    TypeEnum,
+    XferAsset,
+    AssetAmount,
+    AssetSender,
+    AssetReceiver,
+    AssetCloseTo,
     GroupIndex,
+    TxID,
     ApplicationID,
     OnCompletion,
+    ApplicationArgs,
+    NumAppArgs,
     Accounts,
     NumAccounts,
+    ApprovalProgram,
+    ClearStateProgram,
+    RekeyTo,
+    ConfigAsset,
+    ConfigAssetTotal,
+    ConfigAssetDecimals,
+    ConfigAssetDefaultFrozen,
+    ConfigAssetUnitName,
+    ConfigAssetName,
+    ConfigAssetURL,
+    ConfigAssetMetadataHash,
+    ConfigAssetManager,
+    ConfigAssetReserve,
+    ConfigAssetFreeze,
+    ConfigAssetClawback,
+    FreezeAsset,
+    FreezeAssetAccount,
+    FreezeAssetFrozen,
+    Assets,
+    NumAssets,
+    Applications,
+    NumApplications,
+    GlobalNumUint,
+    GlobalNumByteSlice,
+    LocalNumUint,
+    LocalNumByteSlice,
+    ExtraProgramPages,
+    Nonparticipation,
+    Logs,
+    NumLogs,
+    CreatedAssetID,
+    CreatedApplicationID,
+    LastLog,
+    StateProofPK,
+    ApprovalProgramPages,
+    NumApprovalProgramPages,
+    ClearStateProgramPages,
+    NumClearStateProgramPages,
 }
 
+impl Txn {
+    // array fields take an index, e.g. `txna ApplicationArgs 0`
+    pub fn is_array(&self) -> bool {
+        matches!(
+            self,
+            Txn::ApplicationArgs
+                | Txn::Accounts
+                | Txn::Assets
+                | Txn::Applications
+                | Txn::Logs
+                | Txn::ApprovalProgramPages
+                | Txn::ClearStateProgramPages
+        )
+    }
+
+    // the type of the field, or of an element for array fields
+    pub fn field_type(&self) -> TypePrimitive {
+        match self {
+            Txn::Sender
+            | Txn::Note
+            | Txn::Lease
+            | Txn::Receiver
+            | Txn::CloseRemainderTo
+            | Txn::VotePK
+            | Txn::SelectionPK
+            | Txn::Type
+            | Txn::AssetSender
+            | Txn::AssetReceiver
+            | Txn::AssetCloseTo
+            | Txn::TxID
+            | Txn::ApplicationArgs
+            | Txn::Accounts
+            | Txn::ApprovalProgram
+            | Txn::ClearStateProgram
+            | Txn::RekeyTo
+            | Txn::ConfigAssetUnitName
+            | Txn::ConfigAssetName
+            | Txn::ConfigAssetURL
+            | Txn::ConfigAssetMetadataHash
+            | Txn::ConfigAssetManager
+            | Txn::ConfigAssetReserve
+            | Txn::ConfigAssetFreeze
+            | Txn::ConfigAssetClawback
+            | Txn::FreezeAssetAccount
+            | Txn::Logs
+            | Txn::LastLog
+            | Txn::StateProofPK
+            | Txn::ApprovalProgramPages
+            | Txn::ClearStateProgramPages => TypePrimitive::Byteslice,
+            _ => TypePrimitive::UInt64,
+        }
+    }
+
+    // the first version of the AVM that has the field
+    pub fn min_version(&self) -> u64 {
+        match self {
+            Txn::FirstValidTime => 7,
+            Txn::Sender
+            | Txn::Fee
+            | Txn::FirstValid
+            | Txn::LastValid
+            | Txn::Note
+            | Txn::Lease
+            | Txn::Receiver
+            | Txn::Amount
+            | Txn::CloseRemainderTo
+            | Txn::VotePK
+            | Txn::SelectionPK
+            | Txn::VoteFirst
+            | Txn::VoteLast
+            | Txn::VoteKeyDilution
+            | Txn::Type
+            | Txn::TypeEnum
+            | Txn::XferAsset
+            | Txn::AssetAmount
+            | Txn::AssetSender
+            | Txn::AssetReceiver
+            | Txn::AssetCloseTo
+            | Txn::GroupIndex
+            | Txn::TxID => 1,
+            Txn::ApplicationID
+            | Txn::OnCompletion
+            | Txn::ApplicationArgs
+            | Txn::NumAppArgs
+            | Txn::Accounts
+            | Txn::NumAccounts
+            | Txn::ApprovalProgram
+            | Txn::ClearStateProgram
+            | Txn::RekeyTo
+            | Txn::ConfigAsset
+            | Txn::ConfigAssetTotal
+            | Txn::ConfigAssetDecimals
+            | Txn::ConfigAssetDefaultFrozen
+            | Txn::ConfigAssetUnitName
+            | Txn::ConfigAssetName
+            | Txn::ConfigAssetURL
+            | Txn::ConfigAssetMetadataHash
+            | Txn::ConfigAssetManager
+            | Txn::ConfigAssetReserve
+            | Txn::ConfigAssetFreeze
+            | Txn::ConfigAssetClawback
+            | Txn::FreezeAsset
+            | Txn::FreezeAssetAccount
+            | Txn::FreezeAssetFrozen => 2,
+            Txn::Assets
+            | Txn::NumAssets
+            | Txn::Applications
+            | Txn::NumApplications
+            | Txn::GlobalNumUint
+            | Txn::GlobalNumByteSlice
+            | Txn::LocalNumUint
+            | Txn::LocalNumByteSlice => 3,
+            Txn::ExtraProgramPages => 4,
+            Txn::Nonparticipation
+            | Txn::Logs
+            | Txn::NumLogs
+            | Txn::CreatedAssetID
+            | Txn::CreatedApplicationID => 5,
+            Txn::LastLog | Txn::StateProofPK => 6,
+            Txn::ApprovalProgramPages
+            | Txn::NumApprovalProgramPages
+            | Txn::ClearStateProgramPages
+            | Txn::NumClearStateProgramPages => 7,
+        }
+    }
+
+    // the first version of the AVM that lets inner transactions set the field
+    pub fn settable_version(&self) -> Option<u64> {
+        match self {
+            Txn::Sender
+            | Txn::Fee
+            | Txn::Receiver
+            | Txn::Amount
+            | Txn::CloseRemainderTo
+            | Txn::Type
+            | Txn::TypeEnum
+            | Txn::XferAsset
+            | Txn::AssetAmount
+            | Txn::AssetSender
+            | Txn::AssetReceiver
+            | Txn::AssetCloseTo
+            | Txn::ConfigAsset
+            | Txn::ConfigAssetTotal
+            | Txn::ConfigAssetDecimals
+            | Txn::ConfigAssetDefaultFrozen
+            | Txn::ConfigAssetUnitName
+            | Txn::ConfigAssetName
+            | Txn::ConfigAssetURL
+            | Txn::ConfigAssetMetadataHash
+            | Txn::ConfigAssetManager
+            | Txn::ConfigAssetReserve
+            | Txn::ConfigAssetFreeze
+            | Txn::ConfigAssetClawback
+            | Txn::FreezeAsset
+            | Txn::FreezeAssetAccount
+            | Txn::FreezeAssetFrozen => Some(5),
+            Txn::Note
+            | Txn::RekeyTo
+            | Txn::VotePK
+            | Txn::SelectionPK
+            | Txn::VoteFirst
+            | Txn::VoteLast
+            | Txn::VoteKeyDilution
+            | Txn::Nonparticipation
+            | Txn::ApplicationID
+            | Txn::OnCompletion
+            | Txn::ApplicationArgs
+            | Txn::Accounts
+            | Txn::Assets
+            | Txn::Applications
+            | Txn::ApprovalProgram
+            | Txn::ClearStateProgram
+            | Txn::GlobalNumUint
+            | Txn::GlobalNumByteSlice
+            | Txn::LocalNumUint
+            | Txn::LocalNumByteSlice
+            | Txn::ExtraProgramPages => Some(6),
+            Txn::StateProofPK | Txn::ApprovalProgramPages | Txn::ClearStateProgramPages => Some(7),
+            _ => None,
+        }
+    }
+
+    // array fields are functions from the index to the element
+    pub(crate) fn resolve_field(&self) -> TypeEnum {
+        let field_type = TypeEnum::Simple(self.field_type());
+        if self.is_array() {
+            TypeEnum::Arrow(
+                Box::new(TypeEnum::Simple(TypePrimitive::UInt64)),
+                Box::new(field_type),
+            )
+        } else {
+            field_type
+        }
+    }
+
+    // Compiles a read through one of the txn opcode families. `immediate` is an immediate
+    // argument before the field (the group index of `gtxn`) and `prefix` holds anything the
+    // opcodes take from the stack before the array index.
+    pub(crate) fn compile_field(
+        &self,
+        context: &CompilationContext,
+        prepared_stack: &mut Vec<String>,
+        ops: &FieldOps,
+        immediate: Option<u64>,
+        prefix: Vec<String>,
+    ) -> Result<String, CompilationError> {
+        let opcode = |(name, version): (&str, u64)| {
+            let op = match immediate {
+                Some(immediate) => format!("{name} {immediate} {self}"),
+                None => format!("{name} {self}"),
+            };
+            let version = version.max(self.min_version());
+            if context.version < version {
+                Err(CompilationError::UnsupportedOpcode(
+                    op,
+                    version,
+                    context.version,
+                ))
+            } else {
+                Ok(op)
+            }
+        };
+
+        let mut pieces = prefix;
+        if !self.is_array() {
+            pieces.push(opcode(ops.scalar)?);
+            return Ok(pieces.join(OP_SEPARATOR));
+        }
+
+        let index = prepared_stack.pop().ok_or(CompilationError::MissingStack)?;
+        match uint64_literal(&index).filter(|i| *i <= u8::MAX as u64) {
+            Some(i) => pieces.push(format!("{} {i}", opcode(ops.static_array)?)),
+            None => {
+                pieces.push(index);
+                pieces.push(opcode(ops.dynamic_array)?);
+            }
+        }
+        Ok(pieces.join(OP_SEPARATOR))
+    }
+}
+
+// The opcodes reading scalar fields, array fields at an immediate index and array fields at an
+// index from the stack, with the version each one appeared in
+pub(crate) struct FieldOps {
+    pub scalar: (&'static str, u64),
+    pub static_array: (&'static str, u64),
+    pub dynamic_array: (&'static str, u64),
+}
+
+pub(crate) const TXN_OPS: FieldOps = FieldOps {
+    scalar: ("txn", 1),
+    static_array: ("txna", 2),
+    dynamic_array: ("txnas", 5),
+};
+
 impl Expression for Txn {
     fn resolve(&self, _: &TypeContext) -> Result<TypeEnum, TypeError> {
-        Ok(TypeEnum::Simple(match self {
-            Txn::Sender | Txn::Receiver | Txn::CloseRemainderTo | Txn::Accounts => {
-                TypePrimitive::Byteslice
-            }
-            _ => TypePrimitive::UInt64,
-        }))
+        Ok(self.resolve_field())
     }
 
     fn compile(
         &self,
-        _: &CompilationContext,
-        _: &mut Vec<String>,
+        context: &CompilationContext,
+        prepared_stack: &mut Vec<String>,
     ) -> Result<String, CompilationError> {
-        Ok(format!("txn {:?}", self))
+        self.compile_field(context, prepared_stack, &TXN_OPS, None, vec![])
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::expression::txn::Txn;
+    use std::str::FromStr;
+
+    use crate::{
+        apply,
+        assembly::{assemble, opcode::TXN_FIELDS},
+        compilation_error::CompilationError,
+        context::{CompilationContext, TypeContext},
+        expression::{apply::Apply, primitive::Primitive, txn::Txn, Expr, Expression},
+        int,
+        typing::{TypeEnum, TypePrimitive},
+    };
 
     #[test]
     fn test() {
         println!("{:?}", Txn::Sender);
     }
+
+    #[test]
+    fn test_fields() {
+        for field in TXN_FIELDS {
+            let txn = Txn::from_str(field).unwrap();
+            let e = if txn.is_array() {
+                apply!(@fn Expr::Txn(txn); @arg int!(0))
+            } else {
+                Expr::Txn(txn)
+            };
+            e.resolve(&TypeContext::default()).unwrap();
+            let compiled = e.compile_raw().unwrap();
+            assemble(&format!("#pragma version 8\n{compiled}")).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_arrays() {
+        let e = apply!(@fn Expr::Txn(Txn::ApplicationArgs); @arg int!(1));
+        e.resolve(&TypeContext::default())
+            .unwrap()
+            .unify(&mut TypeEnum::Simple(TypePrimitive::Byteslice))
+            .unwrap();
+        assert_eq!(e.compile_raw().unwrap(), "txna ApplicationArgs 1");
+
+        let e = apply!(@fn Expr::Txn(Txn::Assets); @arg Expr::Txn(Txn::GroupIndex));
+        e.resolve(&TypeContext::default())
+            .unwrap()
+            .unify(&mut TypeEnum::Simple(TypePrimitive::UInt64))
+            .unwrap();
+        assert_eq!(e.compile_raw().unwrap(), "txn GroupIndex\ntxnas Assets");
+
+        // a bare array field is not a value
+        assert!(Expr::Txn(Txn::Accounts)
+            .resolve(&TypeContext::default())
+            .unwrap()
+            .unify(&mut TypeEnum::Simple(TypePrimitive::Byteslice))
+            .is_err());
+    }
+
+    #[test]
+    fn test_versions() {
+        let context = |version| CompilationContext {
+            version,
+            ..Default::default()
+        };
+        let e = apply!(@fn Expr::Txn(Txn::Accounts); @arg Expr::Txn(Txn::GroupIndex));
+        assert!(matches!(
+            e.compile(&context(4), &mut vec![]),
+            Err(CompilationError::UnsupportedOpcode(op, 5, 4)) if op == "txnas Accounts"
+        ));
+        let e = apply!(@fn Expr::Txn(Txn::Accounts); @arg int!(1));
+        assert!(e.compile(&context(2), &mut vec![]).is_ok());
+        assert!(Txn::LastLog.compile(&context(5), &mut vec![]).is_err());
+    }
 }
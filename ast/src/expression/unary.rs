@@ -0,0 +1,81 @@
+use crate::{
+    compilation_error::CompilationError,
+    context::{CompilationContext, TypeContext},
+    typing::{TypeEnum, TypeError, TypePrimitive},
+    OP_SEPARATOR,
+};
+
+use super::Expression;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Unary {
+    Not,
+    BitNot,
+    Sqrt,
+}
+
+impl Expression for Unary {
+    fn resolve(&self, _: &TypeContext) -> Result<TypeEnum, TypeError> {
+        Ok(match self {
+            // uint -> uint
+            Unary::Not | Unary::BitNot | Unary::Sqrt => TypeEnum::Arrow(
+                Box::new(TypeEnum::Simple(TypePrimitive::UInt64)),
+                Box::new(TypeEnum::Simple(TypePrimitive::UInt64)),
+            ),
+        })
+    }
+
+    fn compile(
+        &self,
+        _: &CompilationContext,
+        prepared_stack: &mut Vec<String>,
+    ) -> Result<String, CompilationError> {
+        let a = prepared_stack.pop().ok_or(CompilationError::MissingStack)?;
+        let s = match self {
+            Unary::Not => "!",
+            Unary::BitNot => "~",
+            Unary::Sqrt => "sqrt",
+        };
+        Ok(format!("{a}{OP_SEPARATOR}{s}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        apply,
+        context::TypeContext,
+        expression::{apply::Apply, primitive::Primitive, Expr, Expression},
+        int,
+        typing::{TypeEnum, TypePrimitive},
+    };
+
+    use super::Unary;
+
+    #[test]
+    fn test_not() {
+        let e = apply!(@fn Expr::Unary(Unary::Not); @arg int!(0));
+        assert_eq!(
+            e.resolve(&TypeContext::default()).unwrap(),
+            TypeEnum::Simple(TypePrimitive::UInt64)
+        );
+        assert_eq!(e.compile_raw().unwrap(), "int 0\n!");
+        assert!(
+            apply!(@fn Expr::Unary(Unary::Not); @arg Expr::Primitive(Primitive::from("x")))
+                .resolve(&TypeContext::default())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_bit_not_sqrt() {
+        let e = apply!(@fn Expr::Unary(Unary::BitNot); @arg int!(1));
+        assert_eq!(e.compile_raw().unwrap(), "int 1\n~");
+        let e = apply!(@fn Expr::Unary(Unary::Sqrt); @arg int!(16));
+        assert_eq!(
+            e.resolve(&TypeContext::default()).unwrap(),
+            TypeEnum::Simple(TypePrimitive::UInt64)
+        );
+        assert_eq!(e.compile_raw().unwrap(), "int 16\nsqrt");
+    }
+}
@@ -0,0 +1,131 @@
+use crate::{
+    compilation_error::CompilationError,
+    context::{CompilationContext, TypeContext},
+    label::create_label_id,
+    typing::{TypeEnum, TypeError, TypeVar},
+    OP_SEPARATOR,
+};
+
+use super::Expression;
+
+// Gets the value out of an option, which leaves the value under its exists flag
+#[derive(Debug, Clone, PartialEq)]
+pub enum Unwrap {
+    // fails the program when the value does not exist
+    Assert,
+    // (option, default), the default is only evaluated when the value does not exist
+    Or,
+}
+
+impl Expression for Unwrap {
+    fn resolve(&self, _: &TypeContext) -> Result<TypeEnum, TypeError> {
+        let tv = TypeVar::new();
+        let option = TypeEnum::Option(Box::new(TypeEnum::Var(tv.clone())));
+        let result = TypeEnum::Var(tv);
+        Ok(match self {
+            Unwrap::Assert => TypeEnum::Arrow(Box::new(option), Box::new(result)),
+            Unwrap::Or => TypeEnum::Arrow(
+                Box::new(option),
+                Box::new(TypeEnum::Arrow(Box::new(result.clone()), Box::new(result))),
+            ),
+        })
+    }
+
+    fn compile(
+        &self,
+        _: &CompilationContext,
+        prepared_stack: &mut Vec<String>,
+    ) -> Result<String, CompilationError> {
+        let option = prepared_stack.pop().ok_or(CompilationError::MissingStack)?;
+        Ok(match self {
+            Unwrap::Assert => [option, "assert".to_string()].join(OP_SEPARATOR),
+            Unwrap::Or => {
+                let default = prepared_stack.pop().ok_or(CompilationError::MissingStack)?;
+                let label = format!("unwrap{}", create_label_id());
+                [
+                    option,
+                    format!("bnz {label}"),
+                    "pop".to_string(),
+                    default,
+                    format!("{label}:"),
+                ]
+                .join(OP_SEPARATOR)
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        apply,
+        assembly::assemble,
+        context::TypeContext,
+        expression::{
+            apply::Apply,
+            primitive::Primitive,
+            query::{AssetHoldingField, AssetParamsField, Query},
+            txn::Txn,
+            Expr, Expression,
+        },
+        int,
+        typing::{TypeEnum, TypePrimitive},
+    };
+
+    use super::Unwrap;
+
+    fn balance() -> Expr {
+        apply!(
+            @fn Expr::Query(Query::AssetHolding(AssetHoldingField::AssetBalance));
+            @arg Expr::Txn(Txn::Sender);
+            @arg int!(10)
+        )
+    }
+
+    #[test]
+    fn test_assert() {
+        let e = apply!(@fn Expr::Unwrap(Unwrap::Assert); @arg balance());
+        e.resolve(&TypeContext::default())
+            .unwrap()
+            .unify(&mut TypeEnum::Simple(TypePrimitive::UInt64))
+            .unwrap();
+        assert_eq!(
+            e.compile_raw().unwrap(),
+            "txn Sender\nint 10\nasset_holding_get AssetBalance\nassert"
+        );
+    }
+
+    #[test]
+    fn test_or() {
+        let e = apply!(@fn Expr::Unwrap(Unwrap::Or); @arg balance(); @arg int!(0));
+        e.resolve(&TypeContext::default())
+            .unwrap()
+            .unify(&mut TypeEnum::Simple(TypePrimitive::UInt64))
+            .unwrap();
+        let compiled = e.compile_raw().unwrap();
+        let label = compiled
+            .lines()
+            .last()
+            .unwrap()
+            .trim_end_matches(':')
+            .to_string();
+        assert_eq!(
+            compiled,
+            format!(
+                "txn Sender\nint 10\nasset_holding_get AssetBalance\nbnz {label}\npop\nint 0\n{label}:"
+            )
+        );
+        assemble(&format!("#pragma version 8\n{compiled}")).unwrap();
+
+        // the default has to match the value type
+        let name = apply!(
+            @fn Expr::Query(Query::AssetParams(AssetParamsField::AssetName));
+            @arg int!(10)
+        );
+        let e = apply!(@fn Expr::Unwrap(Unwrap::Or); @arg name; @arg int!(0));
+        assert!(e.resolve(&TypeContext::default()).is_err());
+        // and only options can be unwrapped
+        let e = apply!(@fn Expr::Unwrap(Unwrap::Assert); @arg int!(0));
+        assert!(e.resolve(&TypeContext::default()).is_err());
+    }
+}
@@ -1,6 +1,6 @@
 use crate::{
     compilation_error::CompilationError,
-    context::{CompilationBinding, CompilationContext, TypeContext},
+    context::{CompilationContext, TypeContext},
     expression::{primitive::Primitive, Expression},
     typing::{TypeEnum, TypeError, TypePrimitive},
     OP_SEPARATOR,
@@ -37,19 +37,10 @@ impl Expression for LVal {
         match &self.0 {
             Var::Bind(identifier) => {
                 let what = prepared_stack.pop().ok_or(CompilationError::MissingStack)?;
-                let scratch_binding =
-                    context.scope.get(identifier).ok_or(CompilationError::from(
-                        // should never happen if type checking is run before compilation
-                        TypeError::UnboundIdentifier(self.0.clone()),
-                    ))?;
-                let scratch_id = if let CompilationBinding::ScratchVar(id) = scratch_binding {
-                    id
-                } else {
-                    return Err(CompilationError::ConstantAssignment(
-                        scratch_binding.to_owned(),
-                    ));
-                };
-                Ok(format!("{what}{OP_SEPARATOR}store {scratch_id}"))
+                Ok(format!(
+                    "{what}{OP_SEPARATOR}{}",
+                    context.store(identifier)?
+                ))
             }
             Var::Global(identifier) => {
                 let what = prepared_stack.pop().ok_or(CompilationError::MissingStack)?;
@@ -111,6 +102,7 @@ mod tests {
                     scope: Scope::default()
                         .add("key".to_string(), CompilationBinding::ScratchVar(0)),
                     scratch_id: 1,
+                    ..Default::default()
                 },
                 &mut vec![]
             )
@@ -20,8 +20,14 @@ impl Var {
     pub fn get_type<'a>(&self, context: &'a TypeContext) -> Result<&'a TypeEnum, TypeError> {
         let (identifier, scope) = match &self {
             Var::Bind(i) => (i, &context.bind_scope),
-            Var::Global(i) => (i, &context.global_scope),
-            Var::Local(i) => (i, &context.local_scope),
+            Var::Global(i) => {
+                context.require_application("global state")?;
+                (i, &context.global_scope)
+            }
+            Var::Local(i) => {
+                context.require_application("local state")?;
+                (i, &context.local_scope)
+            }
         };
 
         scope
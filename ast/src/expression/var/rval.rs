@@ -1,6 +1,6 @@
 use crate::{
     compilation_error::CompilationError,
-    context::{CompilationBinding, CompilationContext, TypeContext},
+    context::{CompilationContext, TypeContext},
     expression::{primitive::Primitive, Expression},
     typing::{TypeEnum, TypeError, TypePrimitive},
     OP_SEPARATOR,
@@ -42,17 +42,7 @@ impl Expression for RVal {
                 push_identifier = Primitive::Byteslice(identifier.as_bytes().to_vec())
                     .compile(context, &mut Vec::new())?
             )),
-            Var::Bind(identifier) => {
-                let binding = context.scope.get(&identifier).ok_or::<CompilationError>(
-                    // should never happen if type checking is run before compilation
-                    TypeError::UnboundIdentifier(self.0.clone()).into(),
-                )?;
-
-                Ok(match binding {
-                    CompilationBinding::Replacement(s) => s.to_string(),
-                    CompilationBinding::ScratchVar(i) => format!("load {i}"),
-                })
-            }
+            Var::Bind(identifier) => context.load(identifier),
         }
     }
 }
@@ -0,0 +1,120 @@
+use crate::{
+    compilation_error::CompilationError,
+    context::{CompilationContext, TypeContext},
+    typing::{TypeEnum, TypeError, TypePrimitive},
+    OP_SEPARATOR,
+};
+
+use super::Expression;
+
+// 128 bit results come back as a (high, low) pair of words
+#[derive(Debug, Clone, PartialEq)]
+pub enum Wide {
+    AddW,
+    MulW,
+    ExpW,
+    DivModW,
+}
+
+fn uint64() -> TypeEnum {
+    TypeEnum::Simple(TypePrimitive::UInt64)
+}
+
+fn arrows(params: usize, result: TypeEnum) -> TypeEnum {
+    (0..params).fold(result, |body, _| {
+        TypeEnum::Arrow(Box::new(uint64()), Box::new(body))
+    })
+}
+
+impl Wide {
+    fn arity(&self) -> usize {
+        match self {
+            Wide::AddW | Wide::MulW | Wide::ExpW => 2,
+            Wide::DivModW => 4,
+        }
+    }
+}
+
+impl Expression for Wide {
+    fn resolve(&self, _: &TypeContext) -> Result<TypeEnum, TypeError> {
+        Ok(match self {
+            // uint -> uint -> (uint, uint)
+            Wide::AddW | Wide::MulW | Wide::ExpW => {
+                arrows(2, TypeEnum::Tuple(vec![uint64(), uint64()]))
+            }
+            // (uint, uint) / (uint, uint) as quotient (uint, uint) and remainder (uint, uint)
+            Wide::DivModW => arrows(4, TypeEnum::Tuple(vec![uint64(); 4])),
+        })
+    }
+
+    fn compile(
+        &self,
+        _: &CompilationContext,
+        prepared_stack: &mut Vec<String>,
+    ) -> Result<String, CompilationError> {
+        // arguments are on the prepared stack with the first one on top
+        let mut pieces = (0..self.arity())
+            .map(|_| prepared_stack.pop().ok_or(CompilationError::MissingStack))
+            .collect::<Result<Vec<_>, _>>()?;
+        pieces.push(
+            match self {
+                Wide::AddW => "addw",
+                Wide::MulW => "mulw",
+                Wide::ExpW => "expw",
+                Wide::DivModW => "divmodw",
+            }
+            .to_string(),
+        );
+        Ok(pieces.join(OP_SEPARATOR))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        apply,
+        assembly::assemble,
+        context::TypeContext,
+        expression::{apply::Apply, primitive::Primitive, Expr, Expression},
+        int,
+    };
+
+    use super::Wide;
+
+    #[test]
+    fn test_pairs() {
+        for (wide, opcode) in [
+            (Wide::AddW, "addw"),
+            (Wide::MulW, "mulw"),
+            (Wide::ExpW, "expw"),
+        ] {
+            let e = apply!(@fn Expr::Wide(wide); @arg int!(2); @arg int!(3));
+            assert_eq!(
+                e.resolve(&TypeContext::default()).unwrap().to_string(),
+                "(int, int)"
+            );
+            let compiled = e.compile_raw().unwrap();
+            assert_eq!(compiled, format!("int 2\nint 3\n{opcode}"));
+            assemble(&format!("#pragma version 8\n{compiled}")).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_divmodw() {
+        let e = apply!(
+            @fn Expr::Wide(Wide::DivModW);
+            @arg int!(1);
+            @arg int!(2);
+            @arg int!(3);
+            @arg int!(4);
+        );
+        assert_eq!(
+            e.resolve(&TypeContext::default()).unwrap().to_string(),
+            "(int, int, int, int)"
+        );
+        assert_eq!(
+            e.compile_raw().unwrap(),
+            "int 1\nint 2\nint 3\nint 4\ndivmodw"
+        );
+    }
+}
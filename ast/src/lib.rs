@@ -1,13 +1,23 @@
-pub const MAX_TEAL_VERSION: u64 = 5;
+pub const MAX_TEAL_VERSION: u64 = 8;
 pub const OP_SEPARATOR: &'static str = "\n";
 
+pub mod abi;
+pub mod app_spec;
+pub mod assembly;
+pub mod box_schema;
 pub mod compilation_error;
 pub mod context;
 pub mod contract;
+pub mod cost;
+pub mod cse;
+pub mod effects;
+pub mod event;
 pub mod expression;
 pub mod label;
+pub mod logic_sig;
 pub mod macros;
 pub mod program;
+pub mod stack;
 pub mod struct_def;
 pub mod typing;
 
@@ -27,6 +37,7 @@ mod tests {
     fn test_seq_int_bytes() {
         let compiled = Program {
             version: 5,
+            budget: None,
             body: Expr::Seq(Box::new(Seq(
                 Expr::Primitive(Primitive::UInt64(5)),
                 Some(Expr::Primitive(Primitive::Byteslice(b"test".to_vec()))),
@@ -40,6 +51,7 @@ mod tests {
     fn test_types() {
         let program = Program {
             version: 5,
+            budget: None,
             body: Expr::Seq(Box::new(Seq(
                 Expr::Apply(Box::new(Apply(
                     Expr::Apply(Box::new(Apply(
@@ -74,6 +86,7 @@ mod tests {
     fn main_conditional() {
         let program = Program {
             version: 5,
+            budget: None,
             body: Expr::Seq(Box::new(Seq(
                 Expr::Cond(Box::new(Cond(
                     Expr::Apply(Box::new(Apply(
@@ -0,0 +1,209 @@
+use sha2::{Digest, Sha512_256};
+
+use crate::{
+    compilation_error::CompilationError,
+    context::{Mode, TypeContext},
+    expression::{primitive::Primitive, Expr, Expression},
+    program::{CompiledProgram, Program},
+    typing::TypeError,
+    MAX_TEAL_VERSION,
+};
+
+pub const LOGIC_SIG_MAX_SIZE: usize = 1000;
+
+// A program run in signature mode, approving the transactions it is attached to
+pub struct LogicSig {
+    pub version: u64,
+    pub body: Expr,
+}
+
+pub struct CompiledLogicSig {
+    pub program: CompiledProgram,
+    // the account whose transactions the program approves
+    pub address: String,
+}
+
+impl Default for LogicSig {
+    fn default() -> Self {
+        LogicSig {
+            version: MAX_TEAL_VERSION,
+            body: Expr::Primitive(Primitive::UInt64(0)),
+        }
+    }
+}
+
+impl LogicSig {
+    pub fn type_check(&self) -> Result<(), TypeError> {
+        self.body.resolve(&TypeContext {
+            mode: Mode::Signature,
+            ..Default::default()
+        })?;
+        Ok(())
+    }
+
+    pub fn compile(self) -> Result<CompiledLogicSig, CompilationError> {
+        self.type_check()?;
+        let program = Program {
+            version: self.version,
+            body: self.body,
+            budget: None,
+        }
+        .assemble()?;
+        if program.size() > LOGIC_SIG_MAX_SIZE {
+            let mut contributors = program.contributions.clone();
+            contributors.sort_by(|(_, a), (_, b)| b.cmp(a));
+            contributors.truncate(5);
+            return Err(CompilationError::ProgramTooLarge(
+                program.size(),
+                LOGIC_SIG_MAX_SIZE,
+                contributors,
+            ));
+        }
+        let address = address(&program.bytecode);
+        Ok(CompiledLogicSig { program, address })
+    }
+}
+
+// the contract account of a logic signature, the hash of its bytecode
+pub fn address(bytecode: &[u8]) -> String {
+    let mut hasher = Sha512_256::new();
+    hasher.update(b"Program");
+    hasher.update(bytecode);
+    encode_address(&hasher.finalize().into())
+}
+
+// an Algorand address, the key followed by a 4 byte checksum in unpadded base32
+pub fn encode_address(key: &[u8; 32]) -> String {
+    let checksum = Sha512_256::digest(key);
+    let mut bytes = key.to_vec();
+    bytes.extend(&checksum[checksum.len() - 4..]);
+    base32(&bytes)
+}
+
+fn base32(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+    let mut encoded = String::new();
+    let (mut buffer, mut bits) = (0u32, 0);
+    for byte in bytes {
+        buffer = (buffer << 8) | *byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(ALPHABET[(buffer >> bits) as usize & 31] as char);
+        }
+    }
+    if bits > 0 {
+        encoded.push(ALPHABET[(buffer << (5 - bits)) as usize & 31] as char);
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        apply, bytes,
+        compilation_error::CompilationError,
+        expression::{
+            apply::Apply,
+            arg::Arg,
+            binary::Binary,
+            box_storage::{BoxOp, BoxStorage},
+            global::Global,
+            log::Log,
+            primitive::Primitive,
+            var::{RVal, Var},
+            Expr,
+        },
+        int,
+        typing::TypeError,
+    };
+
+    use super::{base32, encode_address, LogicSig};
+
+    #[test]
+    fn test_base32() {
+        // RFC 4648 test vectors without padding
+        for (input, encoded) in [
+            ("", ""),
+            ("f", "MY"),
+            ("fo", "MZXQ"),
+            ("foo", "MZXW6"),
+            ("foob", "MZXW6YQ"),
+            ("fooba", "MZXW6YTB"),
+            ("foobar", "MZXW6YTBOI"),
+        ] {
+            assert_eq!(base32(input.as_bytes()), encoded);
+        }
+        assert_eq!(
+            encode_address(&[0; 32]),
+            "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAY5HFKQ"
+        );
+    }
+
+    #[test]
+    fn test_address() {
+        let compiled = LogicSig {
+            version: 1,
+            body: int!(1),
+        }
+        .compile()
+        .unwrap();
+        assert_eq!(compiled.program.bytecode, [0x01, 0x20, 0x01, 0x01, 0x22]);
+        assert_eq!(
+            compiled.address,
+            "6Z3C3LDVWGMX23BMSYMANACQOSINPFIRF77H7N3AWJZYV6OH6GWTJKVMXY"
+        );
+    }
+
+    fn arg0() -> Expr {
+        apply!(@fn Expr::Arg(Arg); @arg int!(0))
+    }
+
+    #[test]
+    fn test_arg() {
+        let compiled = LogicSig {
+            version: 5,
+            body: apply!(
+                @fn Expr::Binary(Binary::Equals);
+                @arg arg0();
+                @arg bytes!(b"secret".to_vec())
+            ),
+        }
+        .compile()
+        .unwrap();
+        assert!(compiled.program.teal.contains("arg 0"));
+    }
+
+    #[test]
+    fn test_application_only() {
+        for body in [
+            Expr::RVal(RVal(Var::Global("counter".to_string()))),
+            apply!(@fn Expr::Log(Log::Raw); @arg bytes!(b"hi".to_vec())),
+            Expr::BoxStorage(BoxStorage {
+                name: "data".to_string(),
+                op: BoxOp::Len,
+            }),
+            Expr::Global(Global::CurrentApplicationID),
+        ] {
+            let result = LogicSig { version: 8, body }.compile();
+            assert!(matches!(
+                result,
+                Err(CompilationError::TypeCheck(TypeError::ApplicationOnly(_)))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_too_large() {
+        let body = apply!(
+            @fn Expr::Binary(Binary::Equals);
+            @arg arg0();
+            @arg bytes!(vec![0; 1000])
+        );
+        let result = LogicSig { version: 8, body }.compile();
+        assert!(matches!(
+            result,
+            Err(CompilationError::ProgramTooLarge(_, 1000, _))
+        ));
+    }
+}
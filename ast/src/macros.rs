@@ -75,6 +75,27 @@ macro_rules! binop {
     (($a:expr) <= ($b:expr)) => {
         apply!(@fn Expr::Binary(Binary::LessThanEquals); @arg $b; @arg $a)
     };
+    (($a:expr) && ($b:expr)) => {
+        apply!(@fn Expr::Binary(Binary::And); @arg $b; @arg $a)
+    };
+    (($a:expr) || ($b:expr)) => {
+        apply!(@fn Expr::Binary(Binary::Or); @arg $b; @arg $a)
+    };
+    (($a:expr) + ($b:expr)) => {
+        apply!(@fn Expr::Binary(Binary::Add); @arg $b; @arg $a)
+    };
+    (($a:expr) - ($b:expr)) => {
+        apply!(@fn Expr::Binary(Binary::Sub); @arg $b; @arg $a)
+    };
+    (($a:expr) * ($b:expr)) => {
+        apply!(@fn Expr::Binary(Binary::Mul); @arg $b; @arg $a)
+    };
+    (($a:expr) / ($b:expr)) => {
+        apply!(@fn Expr::Binary(Binary::Div); @arg $b; @arg $a)
+    };
+    (($a:expr) % ($b:expr)) => {
+        apply!(@fn Expr::Binary(Binary::Mod); @arg $b; @arg $a)
+    };
 }
 
 #[macro_export]
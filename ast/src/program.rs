@@ -1,14 +1,178 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+};
+
 use crate::{
+    assembly::{assemble, constant_blocks, is_template, tokenize, Constant},
+    box_schema::BoxSchema,
     compilation_error::CompilationError,
     context::{CompilationContext, TypeContext},
-    expression::{primitive::Primitive, Expr, Expression},
-    typing::TypeError,
+    cost::{estimate, Region},
+    cse,
+    event::Event,
+    expression::{primitive::Primitive, template::TemplateValue, Expr, Expression},
+    typing::{TypeError, TypePrimitive},
     MAX_TEAL_VERSION, OP_SEPARATOR,
 };
 
+pub struct CompiledProgram {
+    pub teal: String,
+    pub bytecode: Vec<u8>,
+    pub contributions: Vec<(String, usize)>,
+    // upper bound on the opcode cost, `None` when a loop has no bound
+    pub cost: Option<u64>,
+    // assert failure messages by the pc of their `assert`
+    pub messages: BTreeMap<usize, String>,
+    // the TEAL line of the instruction at each pc
+    pub lines: BTreeMap<usize, usize>,
+    // the constant blocks, holding the template placeholders
+    pub ints: Vec<Constant<u64>>,
+    pub bytes: Vec<Constant<Vec<u8>>>,
+}
+
+// the value for a `TMPL_` placeholder, keyed by the declared name
+fn template_value<'a>(
+    placeholder: &str,
+    values: &'a HashMap<String, TemplateValue>,
+) -> Result<(&'a str, &'a TemplateValue), TypeError> {
+    let name = placeholder.trim_start_matches("TMPL_");
+    values
+        .get_key_value(name)
+        .map(|(name, value)| (name.as_str(), value))
+        .ok_or_else(|| TypeError::MissingTemplateValue(name.to_string()))
+}
+
+fn template_uint64(
+    placeholder: &str,
+    values: &HashMap<String, TemplateValue>,
+) -> Result<u64, TypeError> {
+    match template_value(placeholder, values)? {
+        (_, TemplateValue::UInt64(v)) => Ok(*v),
+        (name, _) => Err(TypeError::TemplateTypeMismatch(
+            name.to_string(),
+            TypePrimitive::UInt64,
+        )),
+    }
+}
+
+fn template_bytes(
+    placeholder: &str,
+    values: &HashMap<String, TemplateValue>,
+) -> Result<Vec<u8>, TypeError> {
+    match template_value(placeholder, values)? {
+        (_, TemplateValue::Bytes(v)) => Ok(v.clone()),
+        (name, _) => Err(TypeError::TemplateTypeMismatch(
+            name.to_string(),
+            TypePrimitive::Byteslice,
+        )),
+    }
+}
+
+impl CompiledProgram {
+    pub fn size(&self) -> usize {
+        self.bytecode.len()
+    }
+
+    // the names of the template variables still waiting for values
+    pub fn templates(&self) -> Vec<&str> {
+        let ints = self.ints.iter().filter_map(Constant::template);
+        let bytes = self.bytes.iter().filter_map(Constant::template);
+        ints.chain(bytes)
+            .map(|t| t.trim_start_matches("TMPL_"))
+            .collect()
+    }
+
+    // Fills in the template variables. Placeholders only live in the constant blocks at the
+    // start of the bytecode, so the blocks are rewritten and everything after them moves by
+    // the change in their size; branches are relative and stay valid.
+    pub fn substitute(
+        &self,
+        values: &HashMap<String, TemplateValue>,
+    ) -> Result<CompiledProgram, TypeError> {
+        let ints = self
+            .ints
+            .iter()
+            .map(|c| match c {
+                Constant::Template(t) => template_uint64(t, values).map(Constant::Value),
+                value => Ok(value.clone()),
+            })
+            .collect::<Result<Vec<_>, TypeError>>()?;
+        let bytes = self
+            .bytes
+            .iter()
+            .map(|c| match c {
+                Constant::Template(t) => template_bytes(t, values).map(Constant::Value),
+                value => Ok(value.clone()),
+            })
+            .collect::<Result<Vec<_>, TypeError>>()?;
+
+        // the version is a varuint in front of the blocks
+        let header = self.bytecode.iter().position(|b| b & 0x80 == 0).unwrap() + 1;
+        let old_blocks = constant_blocks(&self.ints, &self.bytes);
+        let old_size = old_blocks.iter().map(|(_, b)| b.len()).sum::<usize>();
+        let blocks = constant_blocks(&ints, &bytes);
+        let mut bytecode = self.bytecode[..header].to_vec();
+        for (_, block) in &blocks {
+            bytecode.extend(block);
+        }
+        let start = bytecode.len();
+        bytecode.extend(&self.bytecode[header + old_size..]);
+        let moved = |pc: &usize| pc - (header + old_size) + start;
+
+        let mut contributions = self
+            .contributions
+            .iter()
+            .map(|(name, size)| {
+                let block = blocks.iter().find(|(block, _)| block == name);
+                (name.clone(), block.map_or(*size, |(_, b)| b.len()))
+            })
+            .collect::<Vec<_>>();
+        contributions.sort_by(|(a_name, a), (b_name, b)| b.cmp(a).then(a_name.cmp(b_name)));
+
+        let teal = self
+            .teal
+            .lines()
+            .map(|line| match &tokenize(line).0[..] {
+                ["int", t] if is_template(t) => Ok(format!("int {}", template_uint64(t, values)?)),
+                ["byte", t] if is_template(t) => Ok(format!(
+                    "byte 0x{}",
+                    template_bytes(t, values)?
+                        .iter()
+                        .map(|b| format!("{b:02x}"))
+                        .collect::<String>()
+                )),
+                _ => Ok(line.to_string()),
+            })
+            .collect::<Result<Vec<_>, TypeError>>()?
+            .join(OP_SEPARATOR);
+
+        Ok(CompiledProgram {
+            teal,
+            bytecode,
+            contributions,
+            cost: self.cost,
+            messages: self
+                .messages
+                .iter()
+                .map(|(pc, message)| (moved(pc), message.clone()))
+                .collect(),
+            lines: self
+                .lines
+                .iter()
+                .map(|(pc, line)| (moved(pc), *line))
+                .collect(),
+            ints,
+            bytes,
+        })
+    }
+}
+
 pub struct Program {
     pub version: u64,
     pub body: Expr,
+    // opcode budget the program must fit in, checked against the estimated cost
+    pub budget: Option<u64>,
 }
 
 impl Default for Program {
@@ -16,6 +180,7 @@ impl Default for Program {
         Program {
             version: MAX_TEAL_VERSION,
             body: Expr::Primitive(Primitive::UInt64(0)),
+            budget: None,
         }
     }
 }
@@ -28,9 +193,76 @@ impl Program {
     }
 
     pub fn compile(&self) -> Result<String, CompilationError> {
+        self.compile_with(None, &[], &[])
+    }
+
+    // compiles with access to the boxes, events and template variables declared by the
+    // contract
+    pub fn compile_with(
+        &self,
+        boxes: Option<&BoxSchema>,
+        events: &[Event],
+        templates: &[(String, TypePrimitive)],
+    ) -> Result<String, CompilationError> {
+        self.compile_regions(boxes, events, templates)
+            .map(|(teal, _)| teal)
+    }
+
+    // the program with the loops and functions in it
+    fn compile_regions(
+        &self,
+        boxes: Option<&BoxSchema>,
+        events: &[Event],
+        templates: &[(String, TypePrimitive)],
+    ) -> Result<(String, Vec<Region>), CompilationError> {
         let version = self.version;
-        self.body
-            .compile(&CompilationContext::default(), &mut vec![])
-            .map(|compiled| format!("#pragma version {version}{OP_SEPARATOR}{compiled}"))
+        let regions = RefCell::new(vec![]);
+        let compiled = cse::eliminate(&self.body).compile(
+            &CompilationContext {
+                version,
+                boxes,
+                events,
+                templates,
+                regions: Some(&regions),
+                ..Default::default()
+            },
+            &mut vec![],
+        )?;
+        Ok((
+            format!("#pragma version {version}{OP_SEPARATOR}{compiled}"),
+            regions.into_inner(),
+        ))
+    }
+
+    pub fn assemble(&self) -> Result<CompiledProgram, CompilationError> {
+        self.assemble_with(None, &[], &[])
+    }
+
+    pub fn assemble_with(
+        &self,
+        boxes: Option<&BoxSchema>,
+        events: &[Event],
+        templates: &[(String, TypePrimitive)],
+    ) -> Result<CompiledProgram, CompilationError> {
+        let (teal, regions) = self.compile_regions(boxes, events, templates)?;
+        let assembled = assemble(&teal)?;
+        let cost = estimate(&teal, &regions);
+        match (self.budget, cost) {
+            (Some(_), None) => return Err(CompilationError::UnboundedCost),
+            (Some(budget), Some(cost)) if cost > budget => {
+                return Err(CompilationError::BudgetExceeded(cost, budget))
+            }
+            _ => {}
+        }
+        Ok(CompiledProgram {
+            teal,
+            bytecode: assembled.bytecode,
+            contributions: assembled.contributions,
+            cost,
+            messages: assembled.comments,
+            lines: assembled.lines,
+            ints: assembled.ints,
+            bytes: assembled.bytes,
+        })
     }
 }
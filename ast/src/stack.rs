@@ -0,0 +1,141 @@
+// The ops the compiler emits whose stack effect is fixed, as (op, pops, pushes). Each entry is
+// checked against the evaluator, ops left out make stack bindings fall back to variables.
+pub(crate) const FIXED: &[(&str, usize, usize)] = &[
+    ("int", 0, 1),
+    ("byte", 0, 1),
+    ("txn", 0, 1),
+    ("txna", 0, 1),
+    ("global", 0, 1),
+    ("gtxn", 0, 1),
+    ("gtxna", 0, 1),
+    ("itxn", 0, 1),
+    ("itxna", 0, 1),
+    ("arg", 0, 1),
+    ("load", 0, 1),
+    ("frame_dig", 0, 1),
+    ("store", 1, 0),
+    ("frame_bury", 1, 0),
+    ("pop", 1, 0),
+    ("log", 1, 0),
+    ("assert", 1, 0),
+    ("itxn_field", 1, 0),
+    ("app_global_del", 1, 0),
+    ("itxn_begin", 0, 0),
+    ("itxn_next", 0, 0),
+    ("itxn_submit", 0, 0),
+    ("app_global_put", 2, 0),
+    ("app_local_del", 2, 0),
+    ("box_put", 2, 0),
+    ("app_local_put", 3, 0),
+    ("box_replace", 3, 0),
+    ("!", 1, 1),
+    ("~", 1, 1),
+    ("len", 1, 1),
+    ("btoi", 1, 1),
+    ("itob", 1, 1),
+    ("sha256", 1, 1),
+    ("sha512_256", 1, 1),
+    ("sqrt", 1, 1),
+    ("bitlen", 1, 1),
+    ("bzero", 1, 1),
+    ("app_global_get", 1, 1),
+    ("balance", 1, 1),
+    ("min_balance", 1, 1),
+    ("txnas", 1, 1),
+    ("gtxns", 1, 1),
+    ("gtxnsa", 1, 1),
+    ("gtxnas", 1, 1),
+    ("itxnas", 1, 1),
+    ("extract", 1, 1),
+    ("substring", 1, 1),
+    ("box_del", 1, 1),
+    ("+", 2, 1),
+    ("-", 2, 1),
+    ("*", 2, 1),
+    ("/", 2, 1),
+    ("%", 2, 1),
+    ("<", 2, 1),
+    (">", 2, 1),
+    ("<=", 2, 1),
+    (">=", 2, 1),
+    ("==", 2, 1),
+    ("!=", 2, 1),
+    ("&&", 2, 1),
+    ("||", 2, 1),
+    ("&", 2, 1),
+    ("|", 2, 1),
+    ("^", 2, 1),
+    ("exp", 2, 1),
+    ("shl", 2, 1),
+    ("shr", 2, 1),
+    ("concat", 2, 1),
+    ("getbyte", 2, 1),
+    ("getbit", 2, 1),
+    ("extract_uint16", 2, 1),
+    ("extract_uint32", 2, 1),
+    ("extract_uint64", 2, 1),
+    ("replace2", 2, 1),
+    ("b|", 2, 1),
+    ("app_local_get", 2, 1),
+    ("gtxnsas", 2, 1),
+    ("box_create", 2, 1),
+    ("setbyte", 3, 1),
+    ("setbit", 3, 1),
+    ("extract3", 3, 1),
+    ("substring3", 3, 1),
+    ("replace3", 3, 1),
+    ("select", 3, 1),
+    ("box_extract", 3, 1),
+    ("box_len", 1, 2),
+    ("box_get", 1, 2),
+    ("asset_params_get", 1, 2),
+    ("app_params_get", 1, 2),
+    ("acct_params_get", 1, 2),
+    ("dup", 1, 2),
+    ("swap", 2, 2),
+    ("mulw", 2, 2),
+    ("addw", 2, 2),
+    ("expw", 2, 2),
+    ("app_global_get_ex", 2, 2),
+    ("asset_holding_get", 2, 2),
+    ("app_local_get_ex", 3, 2),
+    ("divmodw", 4, 4),
+];
+
+// How many values a line of compiled TEAL pops and pushes, `None` for labels, branches and
+// ops not in the table
+pub fn effect(line: &str) -> Option<(usize, usize)> {
+    let mut tokens = line.split_whitespace();
+    let op = tokens.next()?;
+    if let Some(&(_, pops, pushes)) = FIXED.iter().find(|(name, _, _)| *name == op) {
+        return Some((pops, pushes));
+    }
+    // ops whose effect follows from their first immediate
+    let n = tokens.next()?.parse::<usize>().ok()?;
+    Some(match op {
+        "dig" => (n + 1, n + 2),
+        "uncover" => (n + 1, n + 1),
+        "dupn" => (1, n + 1),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::effect;
+
+    #[test]
+    fn test_effect() {
+        assert_eq!(effect("int 1"), Some((0, 1)));
+        assert_eq!(effect("byte \"a b\""), Some((0, 1)));
+        assert_eq!(effect("assert // not enough"), Some((1, 0)));
+        assert_eq!(effect("replace2 2"), Some((2, 1)));
+        assert_eq!(effect("dig 2"), Some((3, 4)));
+        assert_eq!(effect("uncover 3"), Some((4, 4)));
+        assert_eq!(effect("mulw"), Some((2, 2)));
+        assert_eq!(effect("bz endif1"), None);
+        assert_eq!(effect("loop1:"), None);
+        assert_eq!(effect("dig"), None);
+        assert_eq!(effect("cover 1"), None);
+    }
+}
@@ -1,5 +1,7 @@
 use std::fmt::Display;
 
+use crate::abi::AbiType;
+
 use super::{type_error::TypeError, type_primitive::TypePrimitive, type_var::TypeVar};
 
 #[derive(Debug, PartialEq, Clone)]
@@ -7,6 +9,12 @@ pub enum TypeEnum {
     Simple(TypePrimitive),
     Arrow(Box<TypeEnum>, Box<TypeEnum>),
     Var(TypeVar),
+    // several values left on the stack at once, last one on top
+    Tuple(Vec<TypeEnum>),
+    // a value followed by a flag telling whether it exists
+    Option(Box<TypeEnum>),
+    // the ARC-4 encoding of a value of this type
+    Arc4(AbiType),
 }
 
 impl TypeEnum {
@@ -23,6 +31,15 @@ impl TypeEnum {
                     .chain(b.used_tvars().into_iter().filter(|e| !used.contains(e)))
                     .collect::<Vec<usize>>()
             }
+            TypeEnum::Tuple(items) => items.iter().fold(vec![], |mut used, item| {
+                for tv in item.used_tvars() {
+                    if !used.contains(&tv) {
+                        used.push(tv);
+                    }
+                }
+                used
+            }),
+            TypeEnum::Option(a) => a.used_tvars(),
             _ => {
                 vec![]
             }
@@ -59,6 +76,11 @@ impl TypeEnum {
             (TypeEnum::Arrow(ref mut a1, ref mut a2), TypeEnum::Arrow(ref mut b1, ref mut b2)) => {
                 a1.unify(b1).and(a2.unify(b2))
             }
+            (TypeEnum::Tuple(a), TypeEnum::Tuple(b)) if a.len() == b.len() => a
+                .iter_mut()
+                .zip(b.iter_mut())
+                .try_for_each(|(a, b)| a.unify(b)),
+            (TypeEnum::Option(ref mut a), TypeEnum::Option(ref mut b)) => a.unify(b),
             (a, b) => Err(TypeError::IrreconcilableTypes(a.clone(), b.clone())),
         }
     }
@@ -74,6 +96,8 @@ impl TypeEnum {
                     }
             }
             TypeEnum::Arrow(a, b) => a.contains(other) || b.contains(other),
+            TypeEnum::Tuple(items) => items.iter().any(|item| item.contains(other)),
+            TypeEnum::Option(a) => a.contains(other),
             _ => false,
         }
     }
@@ -92,6 +116,16 @@ impl TypeEnum {
                     "'{}",
                     (tvars.iter().position(|x| x == &v.id).unwrap() as u8 + ('a' as u8)) as char
                 ),
+                TypeEnum::Tuple(items) => format!(
+                    "({})",
+                    items
+                        .iter()
+                        .map(|item| item.stringify_with_tvars(tvars))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                TypeEnum::Option(a) => format!("option<{}>", a.stringify_with_tvars(tvars)),
+                TypeEnum::Arc4(t) => format!("arc4<{}>", t),
             }
         )
     }
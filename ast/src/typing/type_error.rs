@@ -1,6 +1,6 @@
 use thiserror::Error;
 
-use crate::expression::var::Var;
+use crate::{abi::AbiType, expression::var::Var};
 
 use super::{type_enum::TypeEnum, type_primitive::TypePrimitive, type_var::TypeVar};
 
@@ -18,4 +18,30 @@ pub enum TypeError {
     NonFunctionApplication(TypeEnum),
     #[error("Unbound identifier: {0:?}")]
     UnboundIdentifier(Var),
+    #[error("Transaction field {0} cannot be set by an inner transaction")]
+    UnsettableField(String),
+    #[error("Transaction field {0} is set more than once")]
+    DuplicateField(String),
+    #[error("Inner transactions must set at least one field")]
+    EmptyInnerTxn,
+    #[error("Box {0} is not declared in the box schema")]
+    UnboundBox(String),
+    #[error("Event {0} is not declared")]
+    UnboundEvent(String),
+    #[error("Field {1} of event {0} has type {2}, which has no ARC-4 encoding")]
+    UnsupportedEventField(String, String, TypePrimitive),
+    #[error("Template variable {0} is not declared")]
+    UnboundTemplate(String),
+    #[error("No value for template variable {0}")]
+    MissingTemplateValue(String),
+    #[error("Template variable {0} takes a {1} value")]
+    TemplateTypeMismatch(String, TypePrimitive),
+    #[error("{0} is only available to applications")]
+    ApplicationOnly(String),
+    #[error("{0} is only available to logic signatures")]
+    SignatureOnly(String),
+    #[error("Cannot {0} values of ARC-4 type {1}")]
+    UnsupportedAbiOperation(String, AbiType),
+    #[error("ARC-4 type {0} has no element {1}")]
+    AbiIndexOutOfRange(AbiType, usize),
 }
@@ -5,6 +5,8 @@ pub enum TypePrimitive {
     Void,
     UInt64,
     Byteslice,
+    // unsigned big-endian integer in a byteslice of at most 64 bytes
+    BigUInt,
     Halt,
 }
 
@@ -17,6 +19,7 @@ impl Display for TypePrimitive {
                 TypePrimitive::Void => "<void>",
                 TypePrimitive::UInt64 => "int",
                 TypePrimitive::Byteslice => "bytes",
+                TypePrimitive::BigUInt => "biguint",
                 TypePrimitive::Halt => "<halt>",
             }
         )
@@ -2,89 +2,431 @@ extern crate pest;
 #[macro_use]
 extern crate pest_derive;
 
-use core::slice;
-use std::{collections::HashMap, str::FromStr, vec};
+use std::{collections::HashMap, str::FromStr, sync::OnceLock};
 
-use parse_error::ParseError;
 use pest::{
     iterators::{Pair, Pairs},
+    pratt_parser::{Assoc, Op, PrattParser},
     Parser,
 };
 use rusteal_ast::{
+    abi::AbiType,
+    assembly::opcode::TXN_FIELDS,
+    box_schema::{BoxDef, BoxSchema},
     contract::Contract,
+    event::Event,
     expression::{
-        apply::Apply, binary::Binary, cond::Cond, primitive::Primitive, seq::Seq, txn::Txn,
-        var::Var, Expression,
+        apply::Apply,
+        arc4::Arc4,
+        arg::Arg,
+        assert::Assert,
+        binary::Binary,
+        bind::Bind,
+        box_storage::{BoxOp, BoxStorage},
+        bytes::Bytes,
+        cond::Cond,
+        constant::{OnComplete, TxnType},
+        crypto::{Crypto, Curve},
+        function::{Call, Function},
+        global::Global,
+        gtxn::Gtxn,
+        if_else::If,
+        itxn::{InnerTxn, Itxn},
+        log::Log,
+        loops::Loop,
+        primitive::Primitive,
+        query::Query,
+        ret::Ret,
+        router::{Method, Router},
+        seq::Seq,
+        template::Template,
+        txn::Txn,
+        unary::Unary,
+        unwrap::Unwrap,
+        var::{LVal, RVal, Var},
+        Expr,
     },
+    logic_sig::LogicSig,
     program::Program,
     struct_def::StructDef,
     typing::TypePrimitive,
     MAX_TEAL_VERSION,
 };
-use thiserror::Error;
 
 mod parse_error;
 
+pub use parse_error::ParseError;
+
+// the largest box the AVM allows, in bytes
+const MAX_BOX_SIZE: u64 = 32768;
+
 #[derive(Parser)]
 #[grammar = "grammar.pest"]
 struct RustealParser;
 
-fn parse_identifier(pair: Pair<Rule>) -> Result<&str, ParseError> {
+// lowest precedence first
+fn pratt_parser() -> &'static PrattParser<Rule> {
+    static PRATT_PARSER: OnceLock<PrattParser<Rule>> = OnceLock::new();
+    PRATT_PARSER.get_or_init(|| {
+        PrattParser::new()
+            .op(Op::infix(Rule::or, Assoc::Left))
+            .op(Op::infix(Rule::and, Assoc::Left))
+            .op(Op::infix(Rule::eq, Assoc::Left) | Op::infix(Rule::ne, Assoc::Left))
+            .op(Op::infix(Rule::lt, Assoc::Left)
+                | Op::infix(Rule::gt, Assoc::Left)
+                | Op::infix(Rule::le, Assoc::Left)
+                | Op::infix(Rule::ge, Assoc::Left))
+            .op(Op::infix(Rule::bit_or, Assoc::Left))
+            .op(Op::infix(Rule::bit_xor, Assoc::Left))
+            .op(Op::infix(Rule::bit_and, Assoc::Left))
+            .op(Op::infix(Rule::shl, Assoc::Left) | Op::infix(Rule::shr, Assoc::Left))
+            .op(Op::infix(Rule::add, Assoc::Left) | Op::infix(Rule::sub, Assoc::Left))
+            .op(Op::infix(Rule::mul, Assoc::Left)
+                | Op::infix(Rule::div, Assoc::Left)
+                | Op::infix(Rule::rem, Assoc::Left))
+            .op(Op::prefix(Rule::not) | Op::prefix(Rule::bit_not))
+            .op(Op::postfix(Rule::slice))
+    })
+}
+
+// applies the arguments in order, so the first one is applied first
+fn apply(f: Expr, args: impl IntoIterator<Item = Expr>) -> Expr {
+    args.into_iter()
+        .fold(f, |f, arg| Expr::Apply(Box::new(Apply(f, arg))))
+}
+
+fn parse_identifier(pair: Pair<'_, Rule>) -> Result<&str, ParseError<'_>> {
     match pair.as_rule() {
         Rule::identifier => Ok(pair.as_str()),
         _ => unreachable!(),
     }
 }
 
-fn parse_function_def(pair: Pair<Rule>) -> Result<Box<dyn Expression>, ParseError> {
-    todo!()
+fn parse_optionally_typed_field(
+    pair: Pair<'_, Rule>,
+) -> Result<(&str, Option<TypePrimitive>), ParseError<'_>> {
+    match pair.as_rule() {
+        Rule::optionally_typed_field => {
+            let mut i = pair.into_inner();
+            let identifier = parse_identifier(i.next().unwrap())?;
+            let datatype = i.next().map(parse_datatype).transpose()?;
+            Ok((identifier, datatype))
+        }
+        _ => unreachable!(),
+    }
 }
 
-fn parse_cond_arm(
-    pair: Pair<Rule>,
-) -> Result<(Box<dyn Expression>, Box<dyn Expression>), ParseError> {
-    todo!()
+// a function without a return type returns nothing, its scope is the rest of the block
+fn parse_function_def(pair: Pair<'_, Rule>) -> Result<Statement, ParseError<'_>> {
+    match pair.as_rule() {
+        Rule::function_def => {
+            let mut name = "";
+            let mut params = vec![];
+            let mut returns = TypePrimitive::Void;
+            let mut def = Expr::Primitive(Primitive::Void);
+            for p in pair.into_inner() {
+                match p.as_rule() {
+                    Rule::identifier => name = parse_identifier(p)?,
+                    Rule::optionally_typed_field => {
+                        let (param, datatype) = parse_optionally_typed_field(p)?;
+                        if params.iter().any(|(declared, _)| declared == param) {
+                            return Err(ParseError::DuplicateParameter(name, param));
+                        }
+                        params.push((param.to_string(), datatype));
+                    }
+                    Rule::datatype => returns = parse_datatype(p)?,
+                    Rule::block => def = parse_block(p)?,
+                    _ => unreachable!(),
+                }
+            }
+            Ok(Statement::Function(Function {
+                name: name.to_string(),
+                params,
+                returns,
+                def,
+                body: Expr::Primitive(Primitive::Void),
+            }))
+        }
+        _ => unreachable!(),
+    }
 }
 
-fn fold_cond(
-    mut i: vec::IntoIter<(Box<dyn Expression>, Box<dyn Expression>)>,
-) -> Option<Box<Cond>> {
-    let next = i.next();
-    match next {
-        Some((test, expr)) => Some(Box::new(Cond(test, expr, fold_cond(i)))),
-        _ => None,
+fn parse_cond_arm(pair: Pair<'_, Rule>) -> Result<(Expr, Expr), ParseError<'_>> {
+    match pair.as_rule() {
+        Rule::cond_arm => {
+            let mut i = pair.into_inner();
+            let test = parse_expression(i.next().unwrap())?;
+            let body = parse_expression(i.next().unwrap())?;
+            Ok((test, body))
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn parse_else_branch(pair: Pair<'_, Rule>) -> Result<Expr, ParseError<'_>> {
+    match pair.as_rule() {
+        Rule::else_branch => parse_expression(pair.into_inner().next().unwrap()),
+        _ => unreachable!(),
+    }
+}
+
+fn fold_cond(mut arms: Vec<(Expr, Expr)>, otherwise: Option<Expr>) -> Option<Box<Cond>> {
+    // the else branch is an arm that always matches
+    if let Some(otherwise) = otherwise {
+        arms.push((Expr::Primitive(Primitive::UInt64(1)), otherwise));
     }
+    arms.into_iter().rev().fold(None, |tail, (test, body)| {
+        Some(Box::new(Cond(test, body, tail)))
+    })
 }
 
-fn parse_cond_expression(pair: Pair<Rule>) -> Result<Box<dyn Expression>, ParseError> {
+fn parse_cond_expression(pair: Pair<'_, Rule>) -> Result<Expr, ParseError<'_>> {
     match pair.as_rule() {
         Rule::cond_expression => {
-            let arms = pair
-                .into_inner()
-                .map(|p| parse_cond_arm(p))
-                .collect::<Result<Vec<_>, ParseError>>()?;
+            let mut arms = vec![];
+            let mut otherwise = None;
+            for p in pair.into_inner() {
+                match p.as_rule() {
+                    Rule::cond_arm => arms.push(parse_cond_arm(p)?),
+                    Rule::else_branch => otherwise = Some(parse_else_branch(p)?),
+                    _ => unreachable!(),
+                }
+            }
+            if arms.is_empty() {
+                return Err(ParseError::EmptyCondExpression);
+            }
 
-            Ok(
-                fold_cond(arms.into_iter()).ok_or(ParseError::EmptyCondExpression)?
-                    as Box<dyn Expression>,
-            )
+            Ok(Expr::Cond(fold_cond(arms, otherwise).unwrap()))
         }
         _ => unreachable!(),
     }
 }
 
-fn parse_literal_expression(pair: Pair<Rule>) -> Result<Primitive, ParseError> {
+fn parse_if_expression(pair: Pair<'_, Rule>) -> Result<Expr, ParseError<'_>> {
+    match pair.as_rule() {
+        Rule::if_expression => {
+            let mut i = pair.into_inner();
+            let test = parse_expression(i.next().unwrap())?;
+            let then = parse_expression(i.next().unwrap())?;
+            let otherwise = match i.next() {
+                Some(p) => parse_else_branch(p)?,
+                None => Expr::Primitive(Primitive::Void),
+            };
+            Ok(apply(Expr::If(Box::new(If(then, otherwise))), [test]))
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn parse_loop_bound(pair: Pair<'_, Rule>) -> Result<u64, ParseError<'_>> {
+    let bound = pair.into_inner().next().unwrap().as_str();
+    bound.parse().map_err(|_| ParseError::InvalidLiteral(bound))
+}
+
+// the optional bound and the body, which close both kinds of loop
+fn parse_loop_tail(mut i: Pairs<'_, Rule>) -> Result<(Option<u64>, Expr), ParseError<'_>> {
+    let mut next = i.next().unwrap();
+    let bound = match next.as_rule() {
+        Rule::loop_bound => {
+            let bound = parse_loop_bound(next)?;
+            next = i.next().unwrap();
+            Some(bound)
+        }
+        _ => None,
+    };
+    Ok((bound, parse_expression(next)?))
+}
+
+fn parse_while_expression(pair: Pair<'_, Rule>) -> Result<Expr, ParseError<'_>> {
+    match pair.as_rule() {
+        Rule::while_expression => {
+            let mut i = pair.into_inner();
+            let test = parse_expression(i.next().unwrap())?;
+            let (bound, body) = parse_loop_tail(i)?;
+            Ok(Expr::Loop(Box::new(Loop::While { test, body, bound })))
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn parse_for_expression(pair: Pair<'_, Rule>) -> Result<Expr, ParseError<'_>> {
+    match pair.as_rule() {
+        Rule::for_expression => {
+            let mut i = pair.into_inner();
+            let identifier = parse_identifier(i.next().unwrap())?.to_string();
+            let start = parse_expression(i.next().unwrap())?;
+            let end = parse_expression(i.next().unwrap())?;
+            let (bound, body) = parse_loop_tail(i)?;
+            Ok(Expr::Loop(Box::new(Loop::For {
+                identifier,
+                start,
+                end,
+                body,
+                bound,
+            })))
+        }
+        _ => unreachable!(),
+    }
+}
+
+// field names are written in snake case, e.g. `xfer_asset` for XferAsset
+fn parse_txn_field(name: &str) -> Option<Txn> {
+    let normalized = name.replace('_', "");
+    TXN_FIELDS
+        .iter()
+        .find(|field| field.eq_ignore_ascii_case(&normalized))
+        .and_then(|field| Txn::from_str(field).ok())
+}
+
+fn parse_itxn_field(pair: Pair<'_, Rule>) -> Result<(Txn, Expr), ParseError<'_>> {
+    match pair.as_rule() {
+        Rule::itxn_field => {
+            let mut i = pair.into_inner();
+            let name = parse_identifier(i.next().unwrap())?;
+            let value = i.next().unwrap();
+            // `type: pay` sets the TypeEnum field to a named transaction type
+            if name == "type" {
+                let value = match TxnType::from_str(value.as_str()) {
+                    Ok(txn_type) => Expr::TxnType(txn_type),
+                    Err(_) => parse_expression(value)?,
+                };
+                return Ok((Txn::TypeEnum, value));
+            }
+            let field = parse_txn_field(name).ok_or(ParseError::UnknownTransactionField(name))?;
+            Ok((field, parse_expression(value)?))
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn parse_itxn_expression(pair: Pair<'_, Rule>) -> Result<Expr, ParseError<'_>> {
+    match pair.as_rule() {
+        Rule::itxn_expression => Ok(Expr::InnerTxn(Box::new(InnerTxn(
+            pair.into_inner()
+                .map(|fields| fields.into_inner().map(parse_itxn_field).collect())
+                .collect::<Result<_, _>>()?,
+        )))),
+        _ => unreachable!(),
+    }
+}
+
+fn parse_emit_expression(pair: Pair<'_, Rule>) -> Result<Expr, ParseError<'_>> {
+    match pair.as_rule() {
+        Rule::emit_expression => {
+            let mut i = pair.into_inner();
+            let name = parse_identifier(i.next().unwrap())?;
+            let args = i.map(parse_expression).collect::<Result<Vec<_>, _>>()?;
+            Ok(apply(Expr::Log(Log::Emit(name.to_string())), args))
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn parse_arc4_expression(pair: Pair<'_, Rule>) -> Result<Expr, ParseError<'_>> {
+    match pair.as_rule() {
+        Rule::arc4_expression => {
+            let mut i = pair.into_inner();
+            let t = parse_abi_type(i.next().unwrap())?;
+            let name = parse_identifier(i.next().unwrap())?;
+            let mut args = i.map(parse_expression).collect::<Result<Vec<_>, _>>()?;
+            let arity = match name {
+                "encode" | "decode" | "from_bytes" | "to_bytes" | "length" => 1,
+                "element" | "field" => 2,
+                // a dynamic array is packed from any number of elements
+                "pack" => match &t {
+                    AbiType::DynamicArray(_) => args.len(),
+                    _ => t.elements().map_or(0, |elements| elements.len()),
+                },
+                _ => return Err(ParseError::UnknownFunction(name)),
+            };
+            if args.len() != arity {
+                return Err(ParseError::WrongArgumentCount(name, arity, args.len()));
+            }
+            let f = match name {
+                "encode" => Arc4::Encode(t),
+                "decode" => Arc4::Decode(t),
+                "from_bytes" => Arc4::FromBytes(t),
+                "to_bytes" => Arc4::ToBytes(t),
+                "length" => Arc4::Length(t),
+                "element" => Arc4::Element(t),
+                // the index of a field is known at compile time
+                "field" => match args.pop() {
+                    Some(Expr::Primitive(Primitive::UInt64(index))) => {
+                        Arc4::Field(t, index as usize)
+                    }
+                    _ => return Err(ParseError::InvalidFieldIndex(name)),
+                },
+                _ => match t {
+                    AbiType::DynamicArray(element) => Arc4::PackArray(*element, arity),
+                    t => Arc4::Pack(t),
+                },
+            };
+            Ok(apply(Expr::Arc4(f), args))
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn parse_return_expression(pair: Pair<'_, Rule>) -> Result<Expr, ParseError<'_>> {
+    match pair.as_rule() {
+        Rule::return_expression => {
+            let value = parse_expression(pair.into_inner().next().unwrap())?;
+            Ok(Expr::Ret(match value {
+                Expr::Primitive(Primitive::UInt64(0)) => Ret::Reject,
+                Expr::Primitive(Primitive::UInt64(1)) => Ret::Approve,
+                value => Ret::Value(Box::new(value)),
+            }))
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn unescape(s: &str) -> Option<Vec<u8>> {
+    let mut out = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        out.push(match chars.next()? {
+            'b' => '\x08',
+            'f' => '\x0c',
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            'u' => {
+                let code: String = chars.by_ref().take(4).collect();
+                char::from_u32(u32::from_str_radix(&code, 16).ok()?)?
+            }
+            c => c,
+        });
+    }
+    Some(out.into_bytes())
+}
+
+fn parse_literal_expression(pair: Pair<'_, Rule>) -> Result<Primitive, ParseError<'_>> {
     match pair.as_rule() {
         Rule::literal_expression => {
             let lit = pair.into_inner().next().unwrap();
             match lit.as_rule() {
-                Rule::uint64 => Ok(Primitive::UInt64(lit.as_str().parse().unwrap())),
+                Rule::uint64 => Ok(Primitive::UInt64(
+                    lit.as_str()
+                        .parse()
+                        .map_err(|_| ParseError::InvalidLiteral(lit.as_str()))?,
+                )),
                 Rule::boolean => Ok(Primitive::UInt64(if lit.as_str() == "false" {
                     0
                 } else {
                     1
                 })),
-                Rule::bytes => Ok(Primitive::Byteslice(lit.as_str().as_bytes().to_vec())),
+                Rule::bytes => {
+                    let quoted = lit.as_str();
+                    Ok(Primitive::Byteslice(
+                        unescape(&quoted[1..quoted.len() - 1])
+                            .ok_or(ParseError::InvalidLiteral(quoted))?,
+                    ))
+                }
                 _ => unreachable!(),
             }
         }
@@ -92,146 +434,586 @@ fn parse_literal_expression(pair: Pair<Rule>) -> Result<Primitive, ParseError> {
     }
 }
 
-fn parse_qualified_identifier(pair: Pair<Rule>) -> Result<Vec<&str>, ParseError> {
+enum Segment<'a> {
+    Field(&'a str),
+    Index(Expr),
+    Relative(i64),
+}
+
+fn parse_qualified_identifier(pair: Pair<'_, Rule>) -> Result<Vec<Segment<'_>>, ParseError<'_>> {
     match pair.as_rule() {
         Rule::qualified_identifier => pair
             .into_inner()
-            .map(|i| parse_identifier(i))
-            .collect::<Result<Vec<&str>, ParseError>>(),
+            .map(|p| match p.as_rule() {
+                Rule::identifier => parse_identifier(p).map(Segment::Field),
+                Rule::index => parse_expression(p.into_inner().next().unwrap()).map(Segment::Index),
+                Rule::relative_index => {
+                    let offset = p.into_inner().next().unwrap().as_str();
+                    offset
+                        .trim_start_matches('+')
+                        .parse()
+                        .map(Segment::Relative)
+                        .map_err(|_| ParseError::InvalidLiteral(offset))
+                }
+                _ => unreachable!(),
+            })
+            .collect(),
         _ => unreachable!(),
     }
 }
 
-fn parse_binary_operation(
-    lhs: Box<dyn Expression>,
-    pair: Pair<Rule>,
-) -> Result<Box<dyn Expression>, ParseError> {
+fn box_storage(name: &str, op: BoxOp) -> Expr {
+    Expr::BoxStorage(BoxStorage {
+        name: name.to_string(),
+        op,
+    })
+}
+
+fn parse_rval(pair: Pair<'_, Rule>) -> Result<Expr, ParseError<'_>> {
+    let as_str = pair.as_str();
+    let segments = parse_qualified_identifier(pair)?;
+    match &segments[..] {
+        [Segment::Field(i)] => Ok(match OnComplete::from_str(i) {
+            Ok(on_complete) => Expr::OnComplete(on_complete),
+            Err(_) => Expr::RVal(RVal(Var::Bind(i.to_string()))),
+        }),
+        [Segment::Field("Txn"), Segment::Field(s)] => Txn::from_str(s)
+            .map(Expr::Txn)
+            .map_err(|_| ParseError::UnknownQualifiedIdentifier(as_str)),
+        [Segment::Field("Txn"), Segment::Field(s), Segment::Index(i)] => Txn::from_str(s)
+            .map(|txn| apply(Expr::Txn(txn), [i.clone()]))
+            .map_err(|_| ParseError::UnknownQualifiedIdentifier(as_str)),
+        [Segment::Field("Gtxn"), group_index, Segment::Field(s), index @ ..] => {
+            let txn =
+                Txn::from_str(s).map_err(|_| ParseError::UnknownQualifiedIdentifier(as_str))?;
+            let gtxn = match group_index {
+                Segment::Index(i) => apply(Expr::Gtxn(Gtxn(txn)), [i.clone()]),
+                Segment::Relative(offset) => Gtxn::relative(*offset, txn),
+                Segment::Field(_) => return Err(ParseError::UnknownQualifiedIdentifier(as_str)),
+            };
+            match index {
+                [] => Ok(gtxn),
+                [Segment::Index(i)] => Ok(apply(gtxn, [i.clone()])),
+                _ => Err(ParseError::UnknownQualifiedIdentifier(as_str)),
+            }
+        }
+        [Segment::Field("Itxn"), Segment::Field(s), index @ ..] => {
+            let itxn = Txn::from_str(s)
+                .map(|txn| Expr::Itxn(Itxn(txn)))
+                .map_err(|_| ParseError::UnknownQualifiedIdentifier(as_str))?;
+            match index {
+                [] => Ok(itxn),
+                [Segment::Index(i)] => Ok(apply(itxn, [i.clone()])),
+                _ => Err(ParseError::UnknownQualifiedIdentifier(as_str)),
+            }
+        }
+        [Segment::Field("Global"), Segment::Field(s)] => Global::from_str(s)
+            .map(Expr::Global)
+            .map_err(|_| ParseError::UnknownQualifiedIdentifier(as_str)),
+        [Segment::Field("global"), Segment::Field(s)] => {
+            Ok(Expr::RVal(RVal(Var::Global(s.to_string()))))
+        }
+        [Segment::Field("box"), Segment::Field(s)] => Ok(box_storage(s, BoxOp::Get)),
+        [Segment::Field("tmpl"), Segment::Field(s)] => Ok(Expr::Template(Template(s.to_string()))),
+        [Segment::Field("local"), Segment::Index(who), Segment::Field(s)] => Ok(apply(
+            Expr::RVal(RVal(Var::Local(s.to_string()))),
+            [who.clone()],
+        )),
+        _ => Err(ParseError::UnknownQualifiedIdentifier(as_str)),
+    }
+}
+
+fn parse_assignment(pair: Pair<'_, Rule>) -> Result<Expr, ParseError<'_>> {
     match pair.as_rule() {
-        Rule::binary_operation => {
+        Rule::assignment => {
             let mut i = pair.into_inner();
-            let operator = i.next().unwrap();
-            let rhs = parse_expression(i.next().unwrap())?;
-            match operator.as_str() {
-                ";" => Ok(Box::new(Seq(lhs, Some(rhs)))),
-                ">" => Ok(Box::new(Apply(
-                    Box::new(Apply(Box::new(Binary::GreaterThan), rhs)),
-                    lhs,
-                ))),
-                _ => todo!(),
+            let target = i.next().unwrap();
+            let as_str = target.as_str();
+            let value = parse_expression(i.next().unwrap())?;
+            let segments = parse_qualified_identifier(target)?;
+            match &segments[..] {
+                [Segment::Field(i)] => {
+                    Ok(apply(Expr::LVal(LVal(Var::Bind(i.to_string()))), [value]))
+                }
+                [Segment::Field("global"), Segment::Field(s)] => {
+                    Ok(apply(Expr::LVal(LVal(Var::Global(s.to_string()))), [value]))
+                }
+                [Segment::Field("box"), Segment::Field(s)] => {
+                    Ok(apply(box_storage(s, BoxOp::Put), [value]))
+                }
+                [Segment::Field("local"), Segment::Index(who), Segment::Field(s)] => Ok(apply(
+                    Expr::LVal(LVal(Var::Local(s.to_string()))),
+                    [who.clone(), value],
+                )),
+                _ => Err(ParseError::UnknownQualifiedIdentifier(as_str)),
             }
         }
         _ => unreachable!(),
     }
 }
 
-fn parse_expression(pair1: Pair<Rule>) -> Result<Box<dyn Expression>, ParseError> {
-    match pair1.as_rule() {
-        Rule::expression => {
-            let mut i = pair1.into_inner();
-            let pair = i.next().unwrap();
-
-            let as_str = pair.as_str();
-            let rule = pair.as_rule();
-            let expr = match rule {
-                // parenthesized/bracketed expressions result in nesting
-                Rule::expression => parse_expression(i.next().unwrap()),
-                Rule::literal_expression => parse_literal_expression(i.next().unwrap())
-                    .map(|p| Box::new(p) as Box<dyn Expression>),
-                Rule::cond_expression => parse_cond_expression(i.next().unwrap()),
-                Rule::qualified_identifier => {
-                    let i_vec = parse_qualified_identifier(i.next().unwrap())?;
-                    match &i_vec[..] {
-                        [i] => Ok(Box::new(Var(i.to_string())) as Box<dyn Expression>),
-                        ["Txn", s] => Txn::from_str(s)
-                            .map(|v| Box::new(v) as Box<dyn Expression>)
-                            .map_err(|_| ParseError::UnknownQualifiedIdentifier(as_str)),
-                        _ => Err(ParseError::UnknownQualifiedIdentifier(as_str)),
-                    }
+// builtins called like functions, with their arity
+fn builtin(name: &str) -> Option<(Expr, usize)> {
+    // box operations are methods on the box, e.g. `box.owner.extract(0, 8)`
+    if let Some((name, op)) = name.strip_prefix("box.").and_then(|s| s.split_once('.')) {
+        let (op, arity) = match op {
+            "create" => (BoxOp::Create, 0),
+            "delete" => (BoxOp::Delete, 0),
+            "len" => (BoxOp::Len, 0),
+            "extract" => (BoxOp::Extract, 2),
+            "replace" => (BoxOp::Replace, 2),
+            _ => return None,
+        };
+        return Some((box_storage(name, op), arity));
+    }
+    // state queries name their field, e.g. `AssetHolding.AssetBalance(account, asset)`
+    if let Some((namespace, field)) = name.split_once('.') {
+        let query = match namespace {
+            "AssetHolding" => Query::AssetHolding(field.parse().ok()?),
+            "AssetParams" => Query::AssetParams(field.parse().ok()?),
+            "AppParams" => Query::AppParams(field.parse().ok()?),
+            "AcctParams" => Query::AcctParams(field.parse().ok()?),
+            _ => return None,
+        };
+        let arity = if namespace == "AssetHolding" { 2 } else { 1 };
+        return Some((Expr::Query(query), arity));
+    }
+    Some(match name {
+        "log" => (Expr::Log(Log::Raw), 1),
+        "arg" => (Expr::Arg(Arg), 1),
+        "balance" => (Expr::Query(Query::Balance), 1),
+        "min_balance" => (Expr::Query(Query::MinBalance), 1),
+        "unwrap" => (Expr::Unwrap(Unwrap::Assert), 1),
+        "unwrap_or" => (Expr::Unwrap(Unwrap::Or), 2),
+        "concat" => (Expr::Bytes(Bytes::Concat), 2),
+        "len" => (Expr::Bytes(Bytes::Len), 1),
+        "substring" => (Expr::Bytes(Bytes::Substring), 3),
+        "extract" => (Expr::Bytes(Bytes::Extract), 3),
+        "extract_uint16" => (Expr::Bytes(Bytes::ExtractUInt16), 2),
+        "extract_uint32" => (Expr::Bytes(Bytes::ExtractUInt32), 2),
+        "extract_uint64" => (Expr::Bytes(Bytes::ExtractUInt64), 2),
+        "replace" => (Expr::Bytes(Bytes::Replace), 3),
+        "getbyte" => (Expr::Bytes(Bytes::GetByte), 2),
+        "setbyte" => (Expr::Bytes(Bytes::SetByte), 3),
+        "getbit" => (Expr::Bytes(Bytes::GetBit), 2),
+        "setbit" => (Expr::Bytes(Bytes::SetBit), 3),
+        "sha256" => (Expr::Crypto(Crypto::Sha256), 1),
+        "keccak256" => (Expr::Crypto(Crypto::Keccak256), 1),
+        "sha512_256" => (Expr::Crypto(Crypto::Sha512_256), 1),
+        "sha3_256" => (Expr::Crypto(Crypto::Sha3_256), 1),
+        "ed25519verify" => (Expr::Crypto(Crypto::Ed25519Verify), 3),
+        "ed25519verify_bare" => (Expr::Crypto(Crypto::Ed25519VerifyBare), 3),
+        "ecdsa_verify" => (Expr::Crypto(Crypto::EcdsaVerify(Curve::Secp256k1)), 5),
+        "ecdsa_verify_secp256r1" => (Expr::Crypto(Crypto::EcdsaVerify(Curve::Secp256r1)), 5),
+        "ecdsa_pk_recover" => (Expr::Crypto(Crypto::EcdsaPkRecover), 4),
+        _ => return None,
+    })
+}
+
+fn parse_apply_expression(pair: Pair<'_, Rule>) -> Result<Expr, ParseError<'_>> {
+    match pair.as_rule() {
+        Rule::apply_expression => {
+            let mut i = pair.into_inner();
+            let name = i.next().unwrap().as_str();
+            let mut args = i.map(parse_expression).collect::<Result<Vec<_>, _>>()?;
+            // the message of `assert(condition, "message")` is kept as a literal
+            if name == "assert" {
+                let message = match &args[..] {
+                    [_] => None,
+                    [_, Expr::Primitive(Primitive::Byteslice(message))] => Some(
+                        String::from_utf8(message.clone())
+                            .map_err(|_| ParseError::InvalidAssertMessage(name))?,
+                    ),
+                    [_, _] => return Err(ParseError::InvalidAssertMessage(name)),
+                    _ => return Err(ParseError::WrongArgumentCount(name, 2, args.len())),
+                };
+                args.truncate(1);
+                return Ok(apply(Expr::Assert(Assert(message)), args));
+            }
+            let Some((f, arity)) = builtin(name) else {
+                // any other name is a user function, which type checking resolves
+                if name.contains('.') {
+                    return Err(ParseError::UnknownFunction(name));
                 }
-                _ => unreachable!(),
+                return Ok(apply(Expr::Call(Call(name.to_string())), args));
             };
+            if args.len() != arity {
+                return Err(ParseError::WrongArgumentCount(name, arity, args.len()));
+            }
+            Ok(apply(f, args))
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn parse_slice(bytes: Expr, pair: Pair<'_, Rule>) -> Result<Expr, ParseError<'_>> {
+    let mut start = None;
+    let mut end = None;
+    for p in pair.into_inner() {
+        let bound = parse_expression(p.clone().into_inner().next().unwrap())?;
+        match p.as_rule() {
+            Rule::slice_start => start = Some(bound),
+            Rule::slice_end => end = Some(bound),
+            _ => unreachable!(),
+        }
+    }
+    let start = start.unwrap_or(Expr::Primitive(Primitive::UInt64(0)));
+    Ok(match end {
+        Some(end) => apply(Expr::Bytes(Bytes::Substring), [bytes, start, end]),
+        None => apply(Expr::Bytes(Bytes::SliceFrom), [bytes, start]),
+    })
+}
+
+fn parse_primary(pair: Pair<'_, Rule>) -> Result<Expr, ParseError<'_>> {
+    match pair.as_rule() {
+        Rule::expression => parse_expression(pair),
+        Rule::block => parse_block(pair),
+        Rule::literal_expression => parse_literal_expression(pair).map(Expr::Primitive),
+        Rule::if_expression => parse_if_expression(pair),
+        Rule::cond_expression => parse_cond_expression(pair),
+        Rule::while_expression => parse_while_expression(pair),
+        Rule::for_expression => parse_for_expression(pair),
+        Rule::itxn_expression => parse_itxn_expression(pair),
+        Rule::emit_expression => parse_emit_expression(pair),
+        Rule::return_expression => parse_return_expression(pair),
+        Rule::arc4_expression => parse_arc4_expression(pair),
+        Rule::apply_expression => parse_apply_expression(pair),
+        Rule::qualified_identifier => parse_rval(pair),
+        _ => unreachable!(),
+    }
+}
+
+fn parse_expression(pair: Pair<'_, Rule>) -> Result<Expr, ParseError<'_>> {
+    match pair.as_rule() {
+        Rule::expression => pratt_parser()
+            .map_primary(parse_primary)
+            .map_prefix(|op, rhs| {
+                let unary = match op.as_rule() {
+                    Rule::not => Unary::Not,
+                    Rule::bit_not => Unary::BitNot,
+                    _ => unreachable!(),
+                };
+                Ok(apply(Expr::Unary(unary), [rhs?]))
+            })
+            .map_postfix(|lhs, op| parse_slice(lhs?, op))
+            .map_infix(|lhs, op, rhs| {
+                let binary = match op.as_rule() {
+                    Rule::or => Binary::Or,
+                    Rule::and => Binary::And,
+                    Rule::eq => Binary::Equals,
+                    Rule::ne => Binary::NotEquals,
+                    Rule::lt => Binary::LessThan,
+                    Rule::gt => Binary::GreaterThan,
+                    Rule::le => Binary::LessThanEquals,
+                    Rule::ge => Binary::GreaterThanEquals,
+                    Rule::bit_or => Binary::BitOr,
+                    Rule::bit_xor => Binary::BitXor,
+                    Rule::bit_and => Binary::BitAnd,
+                    Rule::shl => Binary::ShiftLeft,
+                    Rule::shr => Binary::ShiftRight,
+                    Rule::add => Binary::Add,
+                    Rule::sub => Binary::Sub,
+                    Rule::mul => Binary::Mul,
+                    Rule::div => Binary::Div,
+                    Rule::rem => Binary::Mod,
+                    _ => unreachable!(),
+                };
+                // binary operations take the right hand side first, see `binop!`
+                Ok(apply(Expr::Binary(binary), [rhs?, lhs?]))
+            })
+            .parse(pair.into_inner()),
+        _ => unreachable!(),
+    }
+}
+
+enum Statement {
+    Let(String, Expr),
+    Function(Function),
+    Expr(Expr),
+}
 
-            let bin_pair = i.next();
-            if let Some(bin_pair) = bin_pair {
-                match bin_pair {
-                    // Rule::binary_operation => {}
-                    _ => todo!(),
+fn parse_statement(pair: Pair<'_, Rule>) -> Result<Statement, ParseError<'_>> {
+    match pair.as_rule() {
+        Rule::statement => {
+            let p = pair.into_inner().next().unwrap();
+            match p.as_rule() {
+                Rule::let_statement => {
+                    let mut i = p.into_inner();
+                    let identifier = parse_identifier(i.next().unwrap())?;
+                    let value = parse_expression(i.next().unwrap())?;
+                    Ok(Statement::Let(identifier.to_string(), value))
                 }
+                Rule::assignment => parse_assignment(p).map(Statement::Expr),
+                Rule::expression => parse_expression(p).map(Statement::Expr),
+                _ => unreachable!(),
             }
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn parse_abi_type(pair: Pair<'_, Rule>) -> Result<AbiType, ParseError<'_>> {
+    let as_str = pair.as_str();
+    as_str
+        .parse()
+        .map_err(|_| ParseError::UnknownAbiType(as_str))
+}
 
-            expr
+fn parse_on_completions(pair: Pair<'_, Rule>) -> Result<Vec<OnComplete>, ParseError<'_>> {
+    match pair.as_rule() {
+        Rule::on_completions => pair
+            .into_inner()
+            .map(|p| {
+                let name = parse_identifier(p)?;
+                OnComplete::from_str(name).map_err(|_| ParseError::UnknownOnCompletion(name))
+            })
+            .collect(),
+        _ => unreachable!(),
+    }
+}
+
+fn parse_method_def(pair: Pair<'_, Rule>) -> Result<Method, ParseError<'_>> {
+    match pair.as_rule() {
+        Rule::method_def => {
+            let mut method = Method {
+                name: String::new(),
+                args: vec![],
+                returns: None,
+                actions: vec![OnComplete::NoOp],
+                body: Expr::Primitive(Primitive::Void),
+            };
+            for p in pair.into_inner() {
+                match p.as_rule() {
+                    Rule::identifier => method.name = parse_identifier(p)?.to_string(),
+                    Rule::abi_arg => {
+                        let mut i = p.into_inner();
+                        let identifier = parse_identifier(i.next().unwrap())?;
+                        let abi_type = parse_abi_type(i.next().unwrap())?;
+                        method.args.push((identifier.to_string(), abi_type));
+                    }
+                    Rule::abi_type => method.returns = Some(parse_abi_type(p)?),
+                    Rule::on_completions => method.actions = parse_on_completions(p)?,
+                    Rule::block => method.body = parse_block(p)?,
+                    _ => unreachable!(),
+                }
+            }
+            Ok(method)
         }
         _ => unreachable!(),
     }
 }
 
-fn parse_prog(pair: Pair<Rule>) -> Result<(&str, Program), ParseError> {
+// `let`s and functions scope over the rest of their block, other statements are sequenced
+fn parse_block(pair: Pair<'_, Rule>) -> Result<Expr, ParseError<'_>> {
+    match pair.as_rule() {
+        Rule::block => parse_block_items(pair.into_inner()),
+        _ => unreachable!(),
+    }
+}
+
+fn parse_block_items<'a>(
+    pairs: impl Iterator<Item = Pair<'a, Rule>>,
+) -> Result<Expr, ParseError<'a>> {
+    let mut statements = vec![];
+    let mut tail = None;
+    for p in pairs {
+        match p.as_rule() {
+            Rule::function_def => statements.push(parse_function_def(p)?),
+            Rule::method_def => {
+                let name = p.into_inner().next().unwrap().as_str();
+                return Err(ParseError::NestedMethod(name));
+            }
+            Rule::bare_def => return Err(ParseError::NestedBare),
+            Rule::statement => statements.push(parse_statement(p)?),
+            Rule::expression => tail = Some(parse_expression(p)?),
+            _ => unreachable!(),
+        }
+    }
+
+    let tail = statements.into_iter().rev().fold(tail, |tail, statement| {
+        Some(match statement {
+            Statement::Let(identifier, value) => Expr::Bind(Box::new(Bind::Let {
+                identifier,
+                value,
+                body: tail.unwrap_or(Expr::Primitive(Primitive::Void)),
+            })),
+            Statement::Function(function) => Expr::Function(Box::new(Function {
+                body: tail.unwrap_or(Expr::Primitive(Primitive::Void)),
+                ..function
+            })),
+            Statement::Expr(head) => Expr::Seq(Box::new(Seq(head, tail))),
+        })
+    });
+    Ok(tail.unwrap_or(Expr::Primitive(Primitive::Void)))
+}
+
+fn parse_prog(pair: Pair<'_, Rule>) -> Result<(&str, Program), ParseError<'_>> {
     match pair.as_rule() {
         Rule::prog => {
             let mut i = pair.into_inner();
             let identifier = parse_identifier(i.next().unwrap())?;
-            let expression = parse_expression(i.next().unwrap())?;
-            Ok((
-                identifier,
-                Program {
+            // methods are routed to by selector, the rest of the prog handles bare calls
+            let mut methods = vec![];
+            let mut bare_actions = None;
+            let mut rest = vec![];
+            for p in i.next().unwrap().into_inner() {
+                match p.as_rule() {
+                    Rule::method_def => methods.push(parse_method_def(p)?),
+                    Rule::bare_def if bare_actions.is_some() => {
+                        return Err(ParseError::DuplicateBare)
+                    }
+                    Rule::bare_def => {
+                        bare_actions = Some(parse_on_completions(p.into_inner().next().unwrap())?)
+                    }
+                    _ => rest.push(p),
+                }
+            }
+            let body = parse_block_items(rest.into_iter())?;
+            let body = match (body, methods.is_empty() && bare_actions.is_none()) {
+                (body, true) => body,
+                (bare, false) => Expr::Router(Box::new(Router {
+                    methods,
+                    bare: match bare {
+                        Expr::Primitive(Primitive::Void) => None,
+                        bare => Some(bare),
+                    },
+                    bare_actions: bare_actions.unwrap_or(vec![OnComplete::NoOp]),
+                })),
+            };
+            let program = match body {
+                Expr::Primitive(Primitive::Void) => Program::default(),
+                body => Program {
                     version: MAX_TEAL_VERSION,
-                    body: expression,
+                    body,
+                    budget: None,
                 },
-            ))
+            };
+            Ok((identifier, program))
         }
         _ => unreachable!(),
     }
 }
 
-fn parse_datatype(pair: Pair<Rule>) -> Result<TypePrimitive, ParseError> {
+fn parse_datatype(pair: Pair<'_, Rule>) -> Result<TypePrimitive, ParseError<'_>> {
     match (pair.as_rule(), pair.as_str()) {
         (Rule::datatype, "uint64") => Ok(TypePrimitive::UInt64),
         (Rule::datatype, "bytes") => Ok(TypePrimitive::Byteslice),
+        (Rule::datatype, "biguint") => Ok(TypePrimitive::BigUInt),
         _ => unreachable!(),
     }
 }
 
-fn parse_typed_field(pair: Pair<Rule>) -> Result<(&str, TypePrimitive), ParseError> {
+type TypedField<'a> = (&'a str, TypePrimitive, Option<u64>);
+
+fn parse_typed_field(pair: Pair<'_, Rule>) -> Result<TypedField<'_>, ParseError<'_>> {
     match pair.as_rule() {
         Rule::typed_field => {
             let mut i = pair.into_inner();
             let identifier = parse_identifier(i.next().unwrap())?;
             let datatype = parse_datatype(i.next().unwrap())?;
-            Ok((identifier, datatype))
+            let size = match i.next() {
+                Some(p) => Some(
+                    p.into_inner()
+                        .as_str()
+                        .parse()
+                        .map_err(|_| ParseError::InvalidBoxSize(identifier))?,
+                ),
+                None => None,
+            };
+            Ok((identifier, datatype, size))
         }
         _ => unreachable!(),
     }
 }
 
-fn parse_struct_def(pair: Pair<Rule>) -> Result<StructDef, ParseError> {
+fn parse_struct_def(pair: Pair<'_, Rule>) -> Result<StructDef<'_>, ParseError<'_>> {
     match pair.as_rule() {
         Rule::struct_def => Ok(StructDef {
             fields: pair
                 .into_inner()
-                .map(|p| parse_typed_field(p))
+                .map(|p| match parse_typed_field(p)? {
+                    (identifier, _, Some(_)) => Err(ParseError::UnexpectedBoxSize(identifier)),
+                    (identifier, datatype, None) => Ok((identifier, datatype)),
+                })
                 .collect::<Result<HashMap<&str, TypePrimitive>, ParseError>>()?,
         }),
         _ => unreachable!(),
     }
 }
 
-fn parse_schema(pair: Pair<Rule>) -> Result<(&str, StructDef), ParseError> {
+fn parse_box_schema(pair: Pair<'_, Rule>) -> Result<BoxSchema<'_>, ParseError<'_>> {
+    match pair.as_rule() {
+        Rule::struct_def => Ok(BoxSchema {
+            boxes: pair
+                .into_inner()
+                .map(|p| {
+                    let (identifier, value_type, size) = parse_typed_field(p)?;
+                    // uint64 values are stored big-endian in 8 bytes
+                    let size = match (&value_type, size) {
+                        (TypePrimitive::UInt64, None | Some(8)) => 8,
+                        (TypePrimitive::UInt64, Some(_)) => {
+                            return Err(ParseError::InvalidBoxSize(identifier))
+                        }
+                        (_, None) => return Err(ParseError::MissingBoxSize(identifier)),
+                        (_, Some(size)) if !(1..=MAX_BOX_SIZE).contains(&size) => {
+                            return Err(ParseError::InvalidBoxSize(identifier))
+                        }
+                        (_, Some(size)) => size,
+                    };
+                    Ok((identifier, BoxDef { value_type, size }))
+                })
+                .collect::<Result<HashMap<&str, BoxDef>, ParseError>>()?,
+        }),
+        _ => unreachable!(),
+    }
+}
+
+fn parse_event_def(pair: Pair<'_, Rule>) -> Result<Event, ParseError<'_>> {
+    match pair.as_rule() {
+        Rule::event_def => {
+            let mut i = pair.into_inner();
+            let name = parse_identifier(i.next().unwrap())?;
+            let fields = i
+                .map(|p| match parse_typed_field(p)? {
+                    (identifier, _, Some(_)) => Err(ParseError::UnexpectedBoxSize(identifier)),
+                    (identifier, datatype, None) => Ok((identifier.to_string(), datatype)),
+                })
+                .collect::<Result<_, _>>()?;
+            Event::new(name, fields).map_err(|_| ParseError::InvalidEventField(name))
+        }
+        _ => unreachable!(),
+    }
+}
+
+enum Schema<'a> {
+    State(StructDef<'a>),
+    Box(BoxSchema<'a>),
+}
+
+fn parse_schema(pair: Pair<'_, Rule>) -> Result<(&str, Schema<'_>), ParseError<'_>> {
     match pair.as_rule() {
         Rule::schema => {
             let mut i = pair.into_inner();
             let name = parse_identifier(i.next().unwrap())?;
-            let struct_def = parse_struct_def(i.next().unwrap())?;
-            Ok((name, struct_def))
+            let struct_def = i.next().unwrap();
+            let schema = match name {
+                "box" => Schema::Box(parse_box_schema(struct_def)?),
+                _ => Schema::State(parse_struct_def(struct_def)?),
+            };
+            Ok((name, schema))
         }
         _ => unreachable!(),
     }
 }
 
-fn parse_contract(pairs: Pairs<Rule>) -> Result<Contract, ParseError> {
+fn parse_contract(pairs: Pairs<'_, Rule>) -> Result<Contract<'_>, ParseError<'_>> {
     let mut txn_approval: Option<Program> = None;
     let mut txn_clear: Option<Program> = None;
     let mut schema_global: Option<StructDef> = None;
     let mut schema_local: Option<StructDef> = None;
+    let mut schema_box: Option<BoxSchema> = None;
+    let mut events: Vec<Event> = vec![];
+    let mut templates: Vec<(String, TypePrimitive)> = vec![];
 
     for pair in pairs {
         match pair.as_rule() {
@@ -249,25 +1031,65 @@ fn parse_contract(pairs: Pairs<Rule>) -> Result<Contract, ParseError> {
             }
             Rule::schema => {
                 let (name, schema) = parse_schema(pair)?;
-                let o = match name {
-                    "global" => &mut schema_global,
-                    "local" => &mut schema_local,
+                let duplicate = match (name, schema) {
+                    ("global", Schema::State(s)) => schema_global.replace(s).is_some(),
+                    ("local", Schema::State(s)) => schema_local.replace(s).is_some(),
+                    ("box", Schema::Box(s)) => schema_box.replace(s).is_some(),
                     _ => return Err(ParseError::InvalidSchemaName(name)),
                 };
-                match o {
-                    Some(_) => return Err(ParseError::DuplicateSchemaName(name)),
-                    None => *o = Some(schema),
+                if duplicate {
+                    return Err(ParseError::DuplicateSchemaName(name));
+                }
+            }
+            Rule::event_def => {
+                let name = parse_identifier(pair.clone().into_inner().next().unwrap())?;
+                if events.iter().any(|event| event.name == name) {
+                    return Err(ParseError::DuplicateEventName(name));
                 }
+                events.push(parse_event_def(pair)?);
             }
+            Rule::tmpl_def => {
+                let mut i = pair.into_inner();
+                let name = parse_identifier(i.next().unwrap())?;
+                if templates.iter().any(|(declared, _)| declared == name) {
+                    return Err(ParseError::DuplicateTemplateName(name));
+                }
+                templates.push((name.to_string(), parse_datatype(i.next().unwrap())?));
+            }
+            Rule::EOI => {}
             _ => unreachable!(),
         }
     }
 
     Ok(Contract {
-        txn_approval: txn_approval.unwrap_or_else(|| Program::default()),
-        txn_clear: txn_clear.unwrap_or_else(|| Program::default()),
-        schema_global: schema_global.unwrap_or_else(|| StructDef::default()),
-        schema_local: schema_local.unwrap_or_else(|| StructDef::default()),
+        txn_approval: txn_approval.unwrap_or_default(),
+        txn_clear: txn_clear.unwrap_or_default(),
+        schema_global: schema_global.unwrap_or_default(),
+        schema_local: schema_local.unwrap_or_default(),
+        schema_box: schema_box.unwrap_or_default(),
+        events,
+        templates,
+    })
+}
+
+pub fn parse(source: &str) -> Result<Contract<'_>, ParseError<'_>> {
+    let contract = RustealParser::parse(Rule::contract, source)
+        .map_err(|e| ParseError::Syntax(Box::new(e)))?
+        .next()
+        .unwrap();
+    parse_contract(contract.into_inner())
+}
+
+// a logic signature, a single block compiled in signature mode
+pub fn parse_logic_sig(source: &str) -> Result<LogicSig, ParseError<'_>> {
+    let logic_sig = RustealParser::parse(Rule::logic_sig, source)
+        .map_err(|e| ParseError::Syntax(Box::new(e)))?
+        .next()
+        .unwrap();
+    let block = logic_sig.into_inner().next().unwrap();
+    Ok(LogicSig {
+        version: MAX_TEAL_VERSION,
+        body: parse_block_items(block.into_inner())?,
     })
 }
 
@@ -276,27 +1098,661 @@ mod tests {
     use std::fs;
 
     use pest::Parser;
+    use rusteal_ast::{
+        abi::AbiType,
+        apply, binop,
+        compilation_error::CompilationError,
+        context::TypeContext,
+        effects::{effects, report},
+        expression::{
+            apply::Apply,
+            arc4::Arc4,
+            binary::Binary,
+            bytes::Bytes,
+            constant::OnComplete,
+            global::Global,
+            gtxn::Gtxn,
+            primitive::Primitive,
+            txn::Txn,
+            var::{RVal, Var},
+            Expr, Expression,
+        },
+        int,
+        typing::{TypeError, TypePrimitive},
+    };
 
-    use crate::Rule;
-    use crate::RustealParser;
+    use crate::{parse, parse_expression, parse_logic_sig, ParseError, Rule, RustealParser};
+
+    fn expression(source: &str) -> Expr {
+        let pair = RustealParser::parse(Rule::expression, source)
+            .expect("successful parse")
+            .next()
+            .unwrap();
+        parse_expression(pair).unwrap()
+    }
 
     #[test]
     fn test() {
+        let unparsed_file = fs::read_to_string("examples/2.rteal").expect("could not open file");
+        let contract = parse(&unparsed_file).unwrap();
+        assert_eq!(contract.schema_global.fields.len(), 2);
+        contract.compile().unwrap();
+    }
+
+    #[test]
+    fn test_function_def() {
         let unparsed_file = fs::read_to_string("examples/1.rteal").expect("could not open file");
-        let file = RustealParser::parse(Rule::contract, &unparsed_file)
-            .expect("successful parse")
+        let contract = parse(&unparsed_file).unwrap();
+        let Expr::Function(function) = &contract.txn_approval.body else {
+            panic!("expected a function, got {:?}", contract.txn_approval.body);
+        };
+        assert_eq!(function.name, "do_thing");
+        assert_eq!(
+            function.params,
+            vec![
+                ("a".to_string(), Some(TypePrimitive::UInt64)),
+                ("b".to_string(), Some(TypePrimitive::UInt64)),
+            ]
+        );
+        assert_eq!(function.returns, TypePrimitive::UInt64);
+
+        assert!(matches!(
+            parse("prog approval { fn f(a, a) { a } 1 }"),
+            Err(ParseError::DuplicateParameter("f", "a"))
+        ));
+        assert!(matches!(
+            parse("prog approval { Foo.bar(1) }"),
+            Err(ParseError::UnknownFunction("Foo.bar"))
+        ));
+    }
+
+    #[test]
+    fn test_recursion() {
+        let contract = parse(
+            "prog approval {
+                fn fib(n: uint64): uint64 {
+                    if (n < 2) { n } else { fib(n - 1) + fib(n - 2) }
+                }
+                fn log_twice(message) {
+                    log(message);
+                    log(message);
+                }
+                log_twice(\"fib\");
+                fib(10) == 55
+            }",
+        )
+        .unwrap();
+        let compiled = contract.compile().unwrap();
+        assert!(compiled.approval.teal.contains("proto 1 1"));
+        assert!(compiled.approval.teal.contains("proto 1 0"));
+        assert!(compiled.approval.teal.contains("callsub fn_fib_"));
+
+        assert!(matches!(
+            parse("prog approval { missing(1) }").unwrap().compile(),
+            Err(CompilationError::TypeCheck(TypeError::UnboundIdentifier(Var::Bind(name))))
+                if name == "missing"
+        ));
+    }
+
+    #[test]
+    fn test_effects() {
+        let contract = parse(
+            "schema global { counter: uint64 }
+            schema box { data: bytes[8] }
+            prog approval {
+                fn bump() {
+                    global.counter = global.counter + 1;
+                }
+                cond {
+                    Txn.NumAppArgs == 0 => 1,
+                    Txn.NumAppArgs == 1 => { bump(); 1 },
+                } else { box.data = \"12345678\"; log(\"stored\"); 1 }
+            }",
+        )
+        .unwrap();
+        let body = &contract.txn_approval.body;
+        assert_eq!(
+            effects(body).to_string(),
+            "reads global.counter, writes global.counter, writes box.data, logs, may fail"
+        );
+        let entries = report(body)
+            .into_iter()
+            .map(|(label, effects)| format!("{label}: {effects}"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            vec![
+                "fn bump: reads global.counter, writes global.counter, may fail",
+                "cond 1 arm 1: pure",
+                "cond 1 arm 2: reads global.counter, writes global.counter, may fail",
+                "cond 1 arm 3: writes box.data, logs, may fail",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_precedence() {
+        assert_eq!(
+            expression("1 + 2 * 3 == 7 && !0"),
+            binop!(
+                (binop!((binop!((int!(1)) + (binop!((int!(2)) * (int!(3)))))) == (int!(7))))
+                    && (apply!(@fn Expr::Unary(rusteal_ast::expression::unary::Unary::Not); @arg int!(0)))
+            )
+        );
+        assert_eq!(
+            expression("10 - 4 - 3"),
+            binop!((binop!((int!(10)) - (int!(4)))) - (int!(3)))
+        );
+    }
+
+    #[test]
+    fn test_slice() {
+        let b = || Expr::Primitive(Primitive::from("hello"));
+        assert_eq!(
+            expression("\"hello\"[1:3]"),
+            apply!(@fn Expr::Bytes(Bytes::Substring); @arg b(); @arg int!(1); @arg int!(3))
+        );
+        assert_eq!(
+            expression("\"hello\"[:3]"),
+            apply!(@fn Expr::Bytes(Bytes::Substring); @arg b(); @arg int!(0); @arg int!(3))
+        );
+        assert_eq!(
+            expression("\"hello\"[2:]"),
+            apply!(@fn Expr::Bytes(Bytes::SliceFrom); @arg b(); @arg int!(2))
+        );
+        let e = expression("len(concat(\"hello\"[1:3], \"\\n\"))");
+        e.resolve(&TypeContext::default()).unwrap();
+        assert_eq!(
+            e.compile_raw().unwrap(),
+            "byte \"hello\"\nsubstring 1 3\nbyte \"\\n\"\nconcat\nlen"
+        );
+    }
+
+    #[test]
+    fn test_txn() {
+        let e = expression("Txn.ApplicationArgs[0] == \"create\"");
+        e.resolve(&TypeContext::default()).unwrap();
+        assert_eq!(
+            e.compile_raw().unwrap(),
+            "txna ApplicationArgs 0\nbyte \"create\"\n=="
+        );
+        let e = expression("Txn.Accounts[Txn.NumAccounts - 1]");
+        assert_eq!(
+            e.compile_raw().unwrap(),
+            "txn NumAccounts\nint 1\n-\ntxnas Accounts"
+        );
+    }
+
+    #[test]
+    fn test_gtxn() {
+        assert_eq!(
+            expression("Gtxn[0].Amount").compile_raw().unwrap(),
+            "gtxn 0 Amount"
+        );
+        assert_eq!(
+            expression("Gtxn[1].ApplicationArgs[2]")
+                .compile_raw()
+                .unwrap(),
+            "gtxna 1 ApplicationArgs 2"
+        );
+        assert_eq!(
+            expression("Gtxn[-1].Amount"),
+            Gtxn::relative(-1, Txn::Amount)
+        );
+        assert_eq!(
+            expression("Gtxn[+1].Sender"),
+            Gtxn::relative(1, Txn::Sender)
+        );
+        assert_eq!(
+            expression("Gtxn[Txn.GroupIndex - 1].Amount")
+                .compile_raw()
+                .unwrap(),
+            "txn GroupIndex\nint 1\n-\ngtxns Amount"
+        );
+        assert!(matches!(
+            expression("Gtxn[16].Amount").compile_raw(),
+            Err(CompilationError::GroupIndexOutOfRange(16))
+        ));
+    }
+
+    #[test]
+    fn test_itxn() {
+        let e = expression(
+            "itxn { type: pay, receiver: Txn.Sender, amount: 1000 } \
+             { type: axfer, xfer_asset: 10, asset_receiver: Txn.Sender, asset_amount: 5 }",
+        );
+        e.resolve(&TypeContext::default()).unwrap();
+        assert_eq!(
+            e.compile_raw().unwrap(),
+            "itxn_begin\nint pay\nitxn_field TypeEnum\ntxn Sender\nitxn_field Receiver\n\
+             int 1000\nitxn_field Amount\nitxn_next\nint axfer\nitxn_field TypeEnum\n\
+             int 10\nitxn_field XferAsset\ntxn Sender\nitxn_field AssetReceiver\n\
+             int 5\nitxn_field AssetAmount\nitxn_submit"
+        );
+        let e = expression("itxn { type: pay, amount: \"all\" }");
+        assert!(e.resolve(&TypeContext::default()).is_err());
+        let e = expression("itxn { type: pay } {}");
+        assert!(matches!(
+            e.resolve(&TypeContext::default()),
+            Err(TypeError::EmptyInnerTxn)
+        ));
+        let pair = RustealParser::parse(Rule::expression, "itxn { amout: 1 }")
+            .unwrap()
+            .next()
+            .unwrap();
+        assert!(matches!(
+            parse_expression(pair),
+            Err(ParseError::UnknownTransactionField("amout"))
+        ));
+        assert_eq!(
+            expression("Itxn.Logs[0]").compile_raw().unwrap(),
+            "itxna Logs 0"
+        );
+    }
+
+    #[test]
+    fn test_queries() {
+        let e = expression("unwrap_or(AssetHolding.AssetBalance(Txn.Sender, 10), 0) + balance(0)");
+        e.resolve(&TypeContext::default()).unwrap();
+        assert!(e
+            .compile_raw()
+            .unwrap()
+            .starts_with("txn Sender\nint 10\nasset_holding_get AssetBalance\nbnz"));
+        let e = expression("unwrap(AssetParams.AssetCreator(10)) == Txn.Sender");
+        e.resolve(&TypeContext::default()).unwrap();
+        assert_eq!(
+            e.compile_raw().unwrap(),
+            "int 10\nasset_params_get AssetCreator\nassert\ntxn Sender\n=="
+        );
+        // options cannot be used without unwrapping them
+        let e = expression("AppParams.AppCreator(1) == Txn.Sender");
+        assert!(e.resolve(&TypeContext::default()).is_err());
+    }
+
+    #[test]
+    fn test_global() {
+        assert_eq!(
+            expression("Global.GroupSize"),
+            Expr::Global(Global::GroupSize)
+        );
+        assert_eq!(
+            expression("global.GroupSize"),
+            Expr::RVal(RVal(Var::Global("GroupSize".to_string())))
+        );
+        let pair = RustealParser::parse(Rule::expression, "Global.Nope")
+            .unwrap()
+            .next()
+            .unwrap();
+        assert!(matches!(
+            parse_expression(pair),
+            Err(ParseError::UnknownQualifiedIdentifier("Global.Nope"))
+        ));
+    }
+
+    #[test]
+    fn test_builtins() {
+        let e = expression("sha256(\"secret\") == keccak256(\"secret\")");
+        e.resolve(&TypeContext::default()).unwrap();
+        assert_eq!(
+            e.compile_raw().unwrap(),
+            "byte \"secret\"\nsha256\nbyte \"secret\"\nkeccak256\n=="
+        );
+        let pair = RustealParser::parse(Rule::expression, "sha256(\"a\", \"b\")")
+            .unwrap()
+            .next()
+            .unwrap();
+        assert!(matches!(
+            parse_expression(pair),
+            Err(ParseError::WrongArgumentCount("sha256", 1, 2))
+        ));
+    }
+
+    #[test]
+    fn test_boxes() {
+        let contract = parse(
+            "schema box { owner: bytes[32], counter: uint64 }
+            prog approval {
+                if (box.counter.create()) {
+                    box.owner = Txn.Sender;
+                };
+                box.counter = box.counter + 1;
+                box.owner.extract(0, 8) != \"\"
+            }",
+        )
+        .unwrap();
+        assert_eq!(contract.schema_box.get("counter").unwrap().size, 8);
+        let compiled = contract.compile().unwrap();
+        assert!(compiled
+            .approval
+            .teal
+            .contains("byte \"owner\"\nint 0\nint 8\nbox_extract"));
+        // one path creates both boxes, the other only touches them
+        assert_eq!(compiled.box_usage.len(), 2);
+
+        assert!(matches!(
+            parse("schema box { owner: bytes }"),
+            Err(ParseError::MissingBoxSize("owner"))
+        ));
+        assert!(matches!(
+            parse("schema global { owner: bytes[32] }"),
+            Err(ParseError::UnexpectedBoxSize("owner"))
+        ));
+        assert!(matches!(
+            parse("schema box { counter: uint64[4] }"),
+            Err(ParseError::InvalidBoxSize("counter"))
+        ));
+    }
+
+    #[test]
+    fn test_events() {
+        let contract = parse(
+            "event Swapped(a: uint64, b: uint64)
+            prog approval {
+                log(\"start\");
+                emit Swapped(1, Txn.Fee);
+                1
+            }",
+        )
+        .unwrap();
+        let compiled = contract.compile().unwrap();
+        assert_eq!(compiled.events[0].signature(), "Swapped(uint64,uint64)");
+        assert!(compiled
+            .approval
+            .teal
+            .contains("byte 0x1ccbd925\nint 1\nitob\ntxn Fee\nitob\nconcat\nconcat\nlog"));
+
+        assert!(matches!(
+            parse("event A(a: uint64) event A(b: bytes)"),
+            Err(ParseError::DuplicateEventName("A"))
+        ));
+    }
+
+    #[test]
+    fn test_loops() {
+        let contract = parse(
+            "prog approval {
+                let total = 0;
+                for i in 0..Txn.NumAppArgs bound 16 {
+                    total = total + len(Txn.ApplicationArgs[i]);
+                };
+                let n = 0;
+                while (n < 3) {
+                    n = n + 1;
+                };
+                total > n
+            }",
+        )
+        .unwrap();
+        contract
+            .txn_approval
+            .body
+            .resolve(&TypeContext::default())
+            .unwrap();
+        let compiled = contract.compile().unwrap();
+        assert!(compiled.approval.teal.contains("// bound 16"));
+        // the while loop has no bound
+        assert_eq!(compiled.approval.cost, None);
+    }
+
+    #[test]
+    fn test_assert() {
+        let contract = parse(
+            "prog approval {
+                assert(Txn.Fee >= 1000, \"fee too low\");
+                assert(Txn.NumAppArgs);
+                1
+            }",
+        )
+        .unwrap();
+        let compiled = contract.compile().unwrap();
+        assert_eq!(
+            compiled.approval.messages.values().collect::<Vec<_>>(),
+            vec!["fee too low"]
+        );
+
+        let pair = RustealParser::parse(Rule::expression, "assert(1, Txn.Note)")
+            .unwrap()
             .next()
             .unwrap();
+        assert!(matches!(
+            parse_expression(pair),
+            Err(ParseError::InvalidAssertMessage("assert"))
+        ));
+    }
 
-        let pairs = file.into_inner();
+    #[test]
+    fn test_methods() {
+        let contract = parse(
+            "prog approval {
+                method add(a: uint64, b: uint64) -> uint64 {
+                    a + b
+                }
+                method hello(name: string) -> string {
+                    concat(\"hello \", name)
+                }
+                Txn.ApplicationID == 0
+            }",
+        )
+        .unwrap();
+        let router = match &contract.txn_approval.body {
+            Expr::Router(router) => router,
+            _ => panic!("expected a router"),
+        };
+        assert_eq!(router.methods[1].signature(), "hello(string)string");
+        assert!(router.bare.is_some());
+        contract
+            .txn_approval
+            .body
+            .resolve(&TypeContext::default())
+            .unwrap();
+        let compiled = contract.compile().unwrap();
+        assert!(compiled.approval.teal.contains("match"));
 
-        // for pair in pairs {
-        //     println!("{:?}", pair);
-        //     match pair.as_rule() {
+        // compound arguments stay encoded
+        let contract = parse(
+            "prog approval {
+                method echo(p: (uint64,address,byte[]), flags: bool[2]) -> (uint64,address,byte[]) {
+                    p
+                }
+            }",
+        )
+        .unwrap();
+        let router = match &contract.txn_approval.body {
+            Expr::Router(router) => router,
+            _ => panic!("expected a router"),
+        };
+        assert_eq!(
+            router.methods[0].signature(),
+            "echo((uint64,address,byte[]),bool[2])(uint64,address,byte[])"
+        );
+        contract.compile().unwrap();
+        assert!(matches!(
+            parse("prog approval { method f(a: uint7) { } }"),
+            Err(ParseError::UnknownAbiType("uint7"))
+        ));
+        assert!(matches!(
+            parse("prog approval { method f(a: int) { } }"),
+            Err(ParseError::Syntax(_))
+        ));
+        assert!(matches!(
+            parse("prog approval { if (1) { method f() { } } else { }; 1 }"),
+            Err(ParseError::NestedMethod("f"))
+        ));
+    }
+
+    #[test]
+    fn test_arc4() {
+        let contract = parse(
+            "prog approval {
+                method total(p: (uint64,address,byte[]), xs: uint64[]) -> uint64 {
+                    let first = arc4<(uint64,address,byte[])>.field(p, 0);
+                    let second = arc4<uint64[]>.element(xs, 1);
+                    arc4<uint64>.decode(first) + arc4<uint64>.decode(second) + arc4<uint64[]>.length(xs)
+                }
+                method pair(a: uint64, b: byte[]) -> (uint64,byte[]) {
+                    arc4<(uint64,byte[])>.pack(arc4<uint64>.encode(a), arc4<byte[]>.encode(b))
+                }
+                method many() -> uint64[] {
+                    arc4<uint64[]>.pack(arc4<uint64>.encode(1), arc4<uint64>.encode(2))
+                }
+            }",
+        )
+        .unwrap();
+        contract.compile().unwrap();
+
+        let t = |s: &str| s.parse::<AbiType>().unwrap();
+        let p = || Expr::RVal(RVal(Var::Bind("p".to_string())));
+        assert_eq!(
+            expression("arc4<(uint64,address,byte[])>.field(p, 2)"),
+            apply!(@fn Expr::Arc4(Arc4::Field(t("(uint64,address,byte[])"), 2)); @arg p())
+        );
+        assert_eq!(
+            expression("arc4<byte[]>.pack(p, p, p)"),
+            apply!(
+                @fn Expr::Arc4(Arc4::PackArray(AbiType::Byte, 3));
+                @arg p();
+                @arg p();
+                @arg p()
+            )
+        );
+        assert!(matches!(
+            parse("prog approval { arc4<(uint64,bool)>.field(p, 1 + 1) }"),
+            Err(ParseError::InvalidFieldIndex("field"))
+        ));
+        assert!(matches!(
+            parse("prog approval { arc4<(uint64,bool)>.pack(p) }"),
+            Err(ParseError::WrongArgumentCount("pack", 2, 1))
+        ));
+        assert!(matches!(
+            parse("prog approval { arc4<uint64[]>.sum(p) }"),
+            Err(ParseError::UnknownFunction("sum"))
+        ));
+        assert!(matches!(
+            parse("prog approval { arc4<uint7>.length(p) }"),
+            Err(ParseError::UnknownAbiType("uint7"))
+        ));
+
+        // the index is checked against the type
+        let contract = parse(
+            "prog approval {
+                method f(p: (uint64,bool)) -> bool {
+                    arc4<(uint64,bool)>.field(p, 2)
+                }
+            }",
+        )
+        .unwrap();
+        assert!(contract.compile().is_err());
+    }
 
-        //     }
-        // }
+    #[test]
+    fn test_on_completions() {
+        let contract = parse(
+            "prog approval {
+                method add(a: uint64, b: uint64) -> uint64 { a + b }
+                method retire() on(NoOp, DeleteApplication) { }
+                bare on(OptIn);
+                1
+            }",
+        )
+        .unwrap();
+        let router = match &contract.txn_approval.body {
+            Expr::Router(router) => router,
+            _ => panic!("expected a router"),
+        };
+        assert_eq!(router.methods[0].actions, vec![OnComplete::NoOp]);
+        assert_eq!(
+            router.methods[1].actions,
+            vec![OnComplete::NoOp, OnComplete::DeleteApplication]
+        );
+        assert_eq!(router.bare_actions, vec![OnComplete::OptIn]);
+        let compiled = contract.compile().unwrap();
+        assert!(compiled
+            .approval
+            .teal
+            .contains("txn OnCompletion\nint DeleteApplication\n==\n||\nassert"));
+
+        // bare actions alone still route, so the guard applies
+        let contract = parse("prog approval { bare on(OptIn); 1 }").unwrap();
+        assert!(
+            matches!(&contract.txn_approval.body, Expr::Router(router) if router.methods.is_empty())
+        );
+
+        assert!(matches!(
+            parse("prog approval { method f() on(Destroy) { } }"),
+            Err(ParseError::UnknownOnCompletion("Destroy"))
+        ));
+        assert!(matches!(
+            parse("prog approval { bare on(OptIn); bare on(NoOp); 1 }"),
+            Err(ParseError::DuplicateBare)
+        ));
+        assert!(matches!(
+            parse("prog approval { if (1) { bare on(OptIn); 1 } else { 0 } }"),
+            Err(ParseError::NestedBare)
+        ));
+    }
+
+    #[test]
+    fn test_logic_sig() {
+        let logic_sig = parse_logic_sig(
+            "logicsig {
+                let secret = arg(0);
+                sha256(secret) == sha256(\"open sesame\") && Txn.Fee <= 1000
+            }",
+        )
+        .unwrap();
+        let compiled = logic_sig.compile().unwrap();
+        assert!(compiled.program.teal.contains("arg 0"));
+        assert_eq!(compiled.address.len(), 58);
+
+        assert!(matches!(
+            parse_logic_sig("logicsig { global.counter == 1 }")
+                .unwrap()
+                .type_check(),
+            Err(TypeError::ApplicationOnly(_))
+        ));
+        assert!(matches!(
+            parse_logic_sig("logicsig { log(\"hi\"); 1 }")
+                .unwrap()
+                .type_check(),
+            Err(TypeError::ApplicationOnly(_))
+        ));
+        assert!(matches!(
+            parse("prog approval { arg(0) == \"x\" }")
+                .unwrap()
+                .txn_approval
+                .type_check(),
+            Err(TypeError::SignatureOnly(_))
+        ));
+    }
+
+    #[test]
+    fn test_templates() {
+        let contract = parse(
+            "tmpl FEE: uint64
+            tmpl CREATOR: bytes
+            prog approval {
+                Txn.Fee <= tmpl.FEE && Txn.Sender == tmpl.CREATOR
+            }",
+        )
+        .unwrap();
+        assert_eq!(
+            contract.templates,
+            vec![
+                ("FEE".to_string(), TypePrimitive::UInt64),
+                ("CREATOR".to_string(), TypePrimitive::Byteslice),
+            ]
+        );
+        let compiled = contract.compile().unwrap();
+        assert!(compiled.approval.teal.contains("int TMPL_FEE"));
+        assert!(compiled.approval.teal.contains("byte TMPL_CREATOR"));
 
-        // println!("{:?}", pairs);
+        assert!(matches!(
+            parse("tmpl FEE: uint64 tmpl FEE: bytes"),
+            Err(ParseError::DuplicateTemplateName("FEE"))
+        ));
+        assert!(matches!(
+            parse("prog approval { tmpl.FEE }").unwrap().compile(),
+            Err(CompilationError::UnknownTemplate(name)) if name == "FEE"
+        ));
     }
 }
@@ -0,0 +1,53 @@
+use std::{env, fs, process};
+
+use parser::parse;
+use rusteal_ast::effects::{effects, report};
+
+const USAGE: &str = "usage: rusteal [--emit=teal|effects] <file>";
+
+enum Emit {
+    Teal,
+    // the effects of each program, function, method and cond arm
+    Effects,
+}
+
+fn fail(message: impl std::fmt::Display) -> ! {
+    eprintln!("{message}");
+    process::exit(1)
+}
+
+fn main() {
+    let mut emit = Emit::Teal;
+    let mut path = None;
+    for arg in env::args().skip(1) {
+        match arg.strip_prefix("--emit=") {
+            Some("teal") => emit = Emit::Teal,
+            Some("effects") => emit = Emit::Effects,
+            Some(other) => fail(format!("unknown emit kind {other}\n{USAGE}")),
+            None if path.is_none() => path = Some(arg),
+            None => fail(USAGE),
+        }
+    }
+    let path = path.unwrap_or_else(|| fail(USAGE));
+    let source = fs::read_to_string(&path).unwrap_or_else(|e| fail(format!("{path}: {e}")));
+    let contract = parse(&source).unwrap_or_else(|e| fail(e));
+
+    match emit {
+        Emit::Teal => {
+            let compiled = contract.compile().unwrap_or_else(|e| fail(e));
+            println!("// approval\n{}", compiled.approval.teal);
+            println!("// clear\n{}", compiled.clear.teal);
+        }
+        Emit::Effects => {
+            for (name, program) in [
+                ("approval", &contract.txn_approval),
+                ("clear", &contract.txn_clear),
+            ] {
+                println!("{name}: {}", effects(&program.body));
+                for (label, effects) in report(&program.body) {
+                    println!("  {label}: {effects}");
+                }
+            }
+        }
+    }
+}
@@ -1,7 +1,11 @@
 use thiserror::Error;
 
+use crate::Rule;
+
 #[derive(Error, Debug)]
 pub enum ParseError<'a> {
+    #[error("{0}")]
+    Syntax(Box<pest::error::Error<Rule>>),
     #[error("Invalid program name {0}")]
     InvalidProgramName(&'a str),
     #[error("Duplicate program name {0}")]
@@ -10,8 +14,44 @@ pub enum ParseError<'a> {
     InvalidSchemaName(&'a str),
     #[error("Duplicate schema name {0}")]
     DuplicateSchemaName(&'a str),
+    #[error("Only boxes have a size, {0} is not a box")]
+    UnexpectedBoxSize(&'a str),
+    #[error("Box {0} must declare its size")]
+    MissingBoxSize(&'a str),
+    #[error("Invalid size for box {0}")]
+    InvalidBoxSize(&'a str),
+    #[error("Duplicate event name {0}")]
+    DuplicateEventName(&'a str),
+    #[error("Event {0} has a field without an ARC-4 encoding")]
+    InvalidEventField(&'a str),
+    #[error("Duplicate template variable {0}")]
+    DuplicateTemplateName(&'a str),
+    #[error("The message of {0} must be a string literal")]
+    InvalidAssertMessage(&'a str),
+    #[error("Unknown ABI type {0}")]
+    UnknownAbiType(&'a str),
+    #[error("Methods can only be declared at the top of a prog: {0}")]
+    NestedMethod(&'a str),
+    #[error("Bare actions can only be declared at the top of a prog")]
+    NestedBare,
+    #[error("Bare actions are declared twice")]
+    DuplicateBare,
+    #[error("Unknown on-completion action {0}")]
+    UnknownOnCompletion(&'a str),
     #[error("Cond expression must have at least one arm")]
     EmptyCondExpression,
     #[error("Unknown qualified identifier {0}")]
     UnknownQualifiedIdentifier(&'a str),
+    #[error("Invalid literal {0}")]
+    InvalidLiteral(&'a str),
+    #[error("Unknown transaction field {0}")]
+    UnknownTransactionField(&'a str),
+    #[error("Unknown function {0}")]
+    UnknownFunction(&'a str),
+    #[error("The index of {0} must be an integer literal")]
+    InvalidFieldIndex(&'a str),
+    #[error("{0} expects {1} argument(s), got {2}")]
+    WrongArgumentCount(&'a str, usize, usize),
+    #[error("Function {0} has two parameters named {1}")]
+    DuplicateParameter(&'a str, &'a str),
 }